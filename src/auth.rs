@@ -0,0 +1,71 @@
+use crate::Error;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::Request;
+use std::collections::HashMap;
+
+/// Authentication headers sent to each domain.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Credentials {
+    domains: HashMap<String, HeaderMap>,
+}
+
+impl Credentials {
+    /// Adds the `name: value` header to the requests sent to `domain`.
+    pub(crate) fn insert(&mut self, domain: &str, name: &str, value: &str) -> Result<(), Error> {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(http::Error::from)?;
+        let mut value = HeaderValue::from_str(value).map_err(http::Error::from)?;
+        value.set_sensitive(true);
+        self.domains
+            .entry(domain.to_lowercase())
+            .or_default()
+            .insert(name, value);
+        Ok(())
+    }
+
+    /// Adds the authentication headers of the request host to `request`.
+    pub(crate) fn apply(&self, request: &mut Request<()>) {
+        let headers = request
+            .uri()
+            .host()
+            .and_then(|host| self.domains.get(&host.to_lowercase()));
+        if let Some(headers) = headers {
+            for (name, value) in headers {
+                request.headers_mut().insert(name, value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::AUTHORIZATION;
+
+    #[test]
+    fn test_credentials_per_domain() {
+        let mut credentials = Credentials::default();
+        credentials
+            .insert("Federation.example.org", "authorization", "Bearer token")
+            .unwrap();
+        credentials
+            .insert("federation.example.org", "x-api-key", "key")
+            .unwrap();
+        assert!(credentials
+            .insert("example.org", "x-api-key", "new\nline")
+            .is_err());
+
+        let mut request = Request::get("https://federation.example.org/federation")
+            .body(())
+            .unwrap();
+        credentials.apply(&mut request);
+        assert_eq!("Bearer token", request.headers()[AUTHORIZATION]);
+        assert_eq!("key", request.headers()["x-api-key"]);
+        assert!(request.headers()[AUTHORIZATION].is_sensitive());
+
+        let mut request = Request::get("https://other.example.org/federation")
+            .body(())
+            .unwrap();
+        credentials.apply(&mut request);
+        assert!(request.headers().is_empty());
+    }
+}
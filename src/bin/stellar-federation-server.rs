@@ -0,0 +1,35 @@
+//! Federation server configured with a TOML file and environment
+//! variables, see `stellar_federation::server::ServerConfig`.
+//!
+//! Usage: `stellar-federation-server [config.toml]`. Without a file,
+//! the configuration is read from the environment variables only.
+use std::process;
+use stellar_federation::server::ServerConfig;
+
+fn main() {
+    let config = match std::env::args_os().nth(1) {
+        Some(path) => ServerConfig::open(path),
+        None => ServerConfig::from_env(),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("stellar-federation-server: {}", err);
+            process::exit(2);
+        }
+    };
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => {
+            eprintln!("stellar-federation-server: {}", err);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = runtime.block_on(config.serve()) {
+        eprintln!("stellar-federation-server: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,300 @@
+//! Blocking federation api.
+//!
+//! The functions in this module block the current thread until the
+//! resolution completes, managing an async runtime internally. They
+//! must not be called from within an async runtime.
+//!
+//! ```rust
+//! use stellar_federation::blocking::resolve_stellar_address;
+//!
+//! # fn run() -> std::result::Result<(), stellar_federation::Error> {
+//! let address = resolve_stellar_address("with-text-memo*ceccon.me")?;
+//! println!("Address = {:?}", address);
+//! # Ok(())
+//! # }
+//! ```
+use crate::{
+    BatchEvent, BatchOptions, Error, FederationClientBuilder, FederationResponse, ResolveOptions,
+    StellarToml,
+};
+use std::collections::HashMap;
+use stellar_base::PublicKey;
+use tokio::runtime::Runtime;
+use url::Url;
+
+/// Blocking federation client.
+///
+/// Wraps an async [`crate::FederationClient`] together with the runtime
+/// used to drive it, so that connections are reused across calls.
+#[derive(Debug)]
+pub struct FederationClient {
+    inner: crate::FederationClient,
+    runtime: Runtime,
+}
+
+impl FederationClient {
+    /// Creates a new blocking federation client with the default configuration.
+    pub fn new() -> Result<FederationClient, Error> {
+        FederationClient::from_builder(crate::FederationClient::builder())
+    }
+
+    /// Creates a new blocking federation client configured with `builder`.
+    pub fn from_builder(builder: FederationClientBuilder) -> Result<FederationClient, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let inner = builder.build()?;
+        Ok(FederationClient { inner, runtime })
+    }
+
+    /// Resolves a Stellar address, automatically discovering the federation server to use.
+    pub fn resolve_address(&self, address: &str) -> Result<FederationResponse, Error> {
+        self.runtime.block_on(self.inner.resolve_address(address))
+    }
+
+    /// Resolves a Stellar address, automatically discovering the federation server to use.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub fn resolve_address_with_options(
+        &self,
+        address: &str,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        self.runtime
+            .block_on(self.inner.resolve_address_with_options(address, options))
+    }
+
+    /// Resolves `addresses` concurrently, running at most
+    /// `max_concurrency` lookups at a time.
+    ///
+    /// The results are returned in the order of `addresses`.
+    pub fn resolve_addresses(
+        &self,
+        addresses: &[&str],
+        max_concurrency: usize,
+    ) -> Vec<Result<FederationResponse, Error>> {
+        self.runtime
+            .block_on(self.inner.resolve_addresses(addresses, max_concurrency))
+    }
+
+    /// Resolves `addresses` like [`FederationClient::resolve_addresses`],
+    /// according to the `options`, calling `progress` with a
+    /// [`BatchEvent`] as soon as each lookup completes.
+    pub fn resolve_addresses_with_progress<P>(
+        &self,
+        addresses: &[&str],
+        options: &BatchOptions,
+        progress: P,
+    ) -> Vec<Result<FederationResponse, Error>>
+    where
+        P: FnMut(BatchEvent<'_>) + Send,
+    {
+        self.runtime.block_on(
+            self.inner
+                .resolve_addresses_with_progress(addresses, options, progress),
+        )
+    }
+
+    /// Resolves `addresses` concurrently, returning the result of each
+    /// address, see [`crate::FederationClient::resolve_many`].
+    pub fn resolve_many(
+        &self,
+        addresses: &[&str],
+        options: &BatchOptions,
+    ) -> HashMap<String, Result<FederationResponse, Error>> {
+        self.runtime
+            .block_on(self.inner.resolve_many(addresses, options))
+    }
+
+    /// Resolves a Stellar address using the specified federation server.
+    pub fn resolve_address_from_server(
+        &self,
+        address: &str,
+        server: &Url,
+    ) -> Result<FederationResponse, Error> {
+        self.runtime
+            .block_on(self.inner.resolve_address_from_server(address, server))
+    }
+
+    /// Resolves a Stellar address using the specified federation server.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub fn resolve_address_from_server_with_options(
+        &self,
+        address: &str,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        self.runtime.block_on(
+            self.inner
+                .resolve_address_from_server_with_options(address, server, options),
+        )
+    }
+
+    /// Resolves the `account_id` using the specified federation server.
+    pub fn resolve_account_id(
+        &self,
+        account_id: &PublicKey,
+        server: &Url,
+    ) -> Result<FederationResponse, Error> {
+        self.runtime
+            .block_on(self.inner.resolve_account_id(account_id, server))
+    }
+
+    /// Resolves the `account_id` using the specified federation server.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub fn resolve_account_id_with_options(
+        &self,
+        account_id: &PublicKey,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        self.runtime.block_on(
+            self.inner
+                .resolve_account_id_with_options(account_id, server, options),
+        )
+    }
+
+    /// Resolves the `account_ids` using the specified federation server
+    /// concurrently, running at most `max_concurrency` lookups at a time.
+    ///
+    /// The results are returned in the order of `account_ids`.
+    pub fn resolve_account_ids(
+        &self,
+        account_ids: &[PublicKey],
+        server: &Url,
+        max_concurrency: usize,
+    ) -> Vec<Result<FederationResponse, Error>> {
+        self.runtime.block_on(
+            self.inner
+                .resolve_account_ids(account_ids, server, max_concurrency),
+        )
+    }
+
+    /// Resolves the `account_ids` like
+    /// [`FederationClient::resolve_account_ids`], according to the
+    /// `options`, calling `progress` with a [`BatchEvent`] as soon as
+    /// each lookup completes.
+    pub fn resolve_account_ids_with_progress<P>(
+        &self,
+        account_ids: &[PublicKey],
+        server: &Url,
+        options: &BatchOptions,
+        progress: P,
+    ) -> Vec<Result<FederationResponse, Error>>
+    where
+        P: FnMut(BatchEvent<'_>) + Send,
+    {
+        self.runtime
+            .block_on(self.inner.resolve_account_ids_with_progress(
+                account_ids,
+                server,
+                options,
+                progress,
+            ))
+    }
+
+    /// Resolves the `tx_id` using the specified federation server.
+    pub fn resolve_transaction_id(
+        &self,
+        tx_id: &str,
+        server: &Url,
+    ) -> Result<FederationResponse, Error> {
+        self.runtime
+            .block_on(self.inner.resolve_transaction_id(tx_id, server))
+    }
+
+    /// Resolves the `tx_id` using the specified federation server.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub fn resolve_transaction_id_with_options(
+        &self,
+        tx_id: &str,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        self.runtime.block_on(
+            self.inner
+                .resolve_transaction_id_with_options(tx_id, server, options),
+        )
+    }
+
+    /// Resolves to the information to send a payment to a different network or institution.
+    pub fn resolve_forward<'a, K>(
+        &self,
+        forward_parameters: K,
+        server: &Url,
+    ) -> Result<FederationResponse, Error>
+    where
+        K: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        self.runtime
+            .block_on(self.inner.resolve_forward(forward_parameters, server))
+    }
+
+    /// Resolves to the information to send a payment to a different network or institution.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub fn resolve_forward_with_options<'a, K>(
+        &self,
+        forward_parameters: K,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error>
+    where
+        K: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        self.runtime
+            .block_on(
+                self.inner
+                    .resolve_forward_with_options(forward_parameters, server, options),
+            )
+    }
+
+    /// Fetches and parses the `stellar.toml` file of `domain`.
+    pub fn resolve_stellar_toml(&self, domain: &str) -> Result<StellarToml, Error> {
+        self.runtime
+            .block_on(self.inner.resolve_stellar_toml(domain))
+    }
+}
+
+/// Resolves a Stellar address, automatically discovering the federation server to use.
+pub fn resolve_stellar_address(address: &str) -> Result<FederationResponse, Error> {
+    FederationClient::new()?.resolve_address(address)
+}
+
+/// Resolves a Stellar address using the specified federation server.
+pub fn resolve_stellar_address_from_server(
+    address: &str,
+    server: &Url,
+) -> Result<FederationResponse, Error> {
+    FederationClient::new()?.resolve_address_from_server(address, server)
+}
+
+/// Resolves the `account_id` using the specified federation server.
+pub fn resolve_stellar_account_id(
+    account_id: &PublicKey,
+    server: &Url,
+) -> Result<FederationResponse, Error> {
+    FederationClient::new()?.resolve_account_id(account_id, server)
+}
+
+/// Resolves the `tx_id` using the specified federation server.
+pub fn resolve_stellar_transaction_id(
+    tx_id: &str,
+    server: &Url,
+) -> Result<FederationResponse, Error> {
+    FederationClient::new()?.resolve_transaction_id(tx_id, server)
+}
+
+/// Resolves to the information to send a payment to a different network or institution.
+pub fn resolve_stellar_forward<'a, K>(
+    forward_parameters: K,
+    server: &Url,
+) -> Result<FederationResponse, Error>
+where
+    K: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    FederationClient::new()?.resolve_forward(forward_parameters, server)
+}
@@ -0,0 +1,482 @@
+use crate::clock::{Clock, SystemClock};
+use crate::FederationResponse;
+use hyper::body::Bytes;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use url::Url;
+
+/// Number of entries above which expired entries are purged on insert.
+const PURGE_THRESHOLD: usize = 1024;
+
+/// Future returned by the [`FederationCache`] methods.
+pub type CacheFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Cache used by [`FederationClient`](crate::FederationClient) to store
+/// `stellar.toml` files and federation responses.
+///
+/// Values are the raw response bodies, keys are hierarchical:
+/// `stellar.toml/<domain>` for `stellar.toml` files and
+/// `federation/<q>/<type>/<server>` for federation responses, where `<q>`
+/// is form url encoded. This makes it possible to invalidate all the
+/// responses to a query with a key prefix. When the client honors the
+/// HTTP caching headers, the validators of a response are stored under
+/// its key prefixed with `validators/`, and failed lookups are stored
+/// under their key prefixed with `negative/`. Entries that can be served
+/// once expired have a copy stored under their key prefixed with `stale/`.
+///
+/// The default implementation is [`MemoryCache`]. Implement this trait
+/// to store the entries somewhere else, for example in a distributed
+/// cache. Cache failures should not fail resolutions, so implementations
+/// are expected to treat errors as cache misses.
+pub trait FederationCache: fmt::Debug + Send + Sync {
+    /// Returns the value of `key`, unless it's missing or expired.
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Bytes>>;
+
+    /// Stores `value` for `key`, expiring after `ttl`.
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Duration) -> CacheFuture<'a, ()>;
+
+    /// Removes the entries whose key starts with `prefix`.
+    fn invalidate<'a>(&'a self, prefix: &'a str) -> CacheFuture<'a, ()>;
+
+    /// Returns the entries that are not expired, for inspection.
+    ///
+    /// The default implementation returns no entries.
+    fn entries(&self) -> CacheFuture<'_, Vec<CacheEntry>> {
+        Box::pin(async { Vec::new() })
+    }
+
+    /// Returns the number of entries removed because they expired.
+    ///
+    /// The default implementation returns 0.
+    fn evictions(&self) -> u64 {
+        0
+    }
+}
+
+/// Entry stored in a [`FederationCache`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// Key of the entry.
+    pub key: String,
+    /// Value of the entry.
+    pub value: Bytes,
+    /// Time at which the entry expires.
+    pub expires_at: SystemTime,
+}
+
+/// Cache usage counters of a [`FederationClient`](crate::FederationClient).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    /// Number of lookups answered by the cache.
+    pub hits: u64,
+    /// Number of lookups not found in the cache.
+    pub misses: u64,
+    /// Number of entries removed from the cache because they expired.
+    pub evictions: u64,
+}
+
+/// Hit and miss counters of a client cache.
+#[derive(Debug, Default)]
+pub(crate) struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// Lookup result stored in the cache of a
+/// [`FederationClient`](crate::FederationClient).
+// Listing the cache is not a hot path, the responses are not boxed.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum CachedLookup {
+    /// Federation server of a domain, from its `stellar.toml` file.
+    FederationServer {
+        /// Domain of the `stellar.toml` file.
+        domain: String,
+        /// Federation server, `None` if the file doesn't have one.
+        server: Option<Url>,
+        /// Time at which the entry expires.
+        expires_at: SystemTime,
+    },
+    /// Response of a federation server.
+    Response {
+        /// Query, for example a Stellar address or an account id.
+        q: String,
+        /// Type of the query, for example `name` or `id`.
+        kind: String,
+        /// Federation server.
+        server: Url,
+        /// Response to the query.
+        response: FederationResponse,
+        /// Time at which the entry expires.
+        expires_at: SystemTime,
+    },
+}
+
+impl CachedLookup {
+    /// Returns the lookup result stored in `entry`, if it is one.
+    pub(crate) fn from_entry(entry: &CacheEntry) -> Option<CachedLookup> {
+        let expires_at = entry.expires_at;
+        if let Some(domain) = entry.key.strip_prefix("stellar.toml/") {
+            let toml: crate::StellarToml = toml::from_slice(&entry.value).ok()?;
+            let server = match toml.federation_server {
+                Some(server) => Some(server.to_string().parse().ok()?),
+                None => None,
+            };
+            return Some(CachedLookup::FederationServer {
+                domain: domain.to_string(),
+                server,
+                expires_at,
+            });
+        }
+        let mut parts = entry.key.strip_prefix("federation/")?.splitn(3, '/');
+        let q = parts.next()?;
+        let q = url::form_urlencoded::parse(format!("q={}", q).as_bytes())
+            .next()
+            .map(|(_, q)| q.into_owned())?;
+        let kind = parts.next()?.to_string();
+        let server = parts.next()?.parse().ok()?;
+        let response = serde_json::from_slice(&entry.value).ok()?;
+        Some(CachedLookup::Response {
+            q,
+            kind,
+            server,
+            response,
+            expires_at,
+        })
+    }
+}
+
+/// In-memory [`FederationCache`].
+#[derive(Debug)]
+pub struct MemoryCache {
+    entries: TtlCache<String, Bytes>,
+    clock: Arc<dyn Clock>,
+}
+
+impl MemoryCache {
+    /// Creates an empty cache.
+    pub fn new() -> MemoryCache {
+        MemoryCache::with_clock(SystemClock)
+    }
+
+    /// Creates an empty cache expiring entries according to `clock`.
+    pub fn with_clock<C>(clock: C) -> MemoryCache
+    where
+        C: Clock + 'static,
+    {
+        MemoryCache::with_shared_clock(Arc::new(clock))
+    }
+
+    pub(crate) fn with_shared_clock(clock: Arc<dyn Clock>) -> MemoryCache {
+        MemoryCache {
+            entries: TtlCache::new(),
+            clock,
+        }
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> MemoryCache {
+        MemoryCache::new()
+    }
+}
+
+impl FederationCache for MemoryCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Bytes>> {
+        let value = self.entries.get(&key.to_string(), self.clock.now());
+        Box::pin(async move { value })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Duration) -> CacheFuture<'a, ()> {
+        self.entries
+            .insert(key.to_string(), value, ttl, self.clock.now());
+        Box::pin(async {})
+    }
+
+    fn invalidate<'a>(&'a self, prefix: &'a str) -> CacheFuture<'a, ()> {
+        self.entries.remove_where(|key| key.starts_with(prefix));
+        Box::pin(async {})
+    }
+
+    fn entries(&self) -> CacheFuture<'_, Vec<CacheEntry>> {
+        let (now, system_now) = (self.clock.now(), SystemTime::now());
+        let entries = self
+            .entries
+            .entries(now)
+            .into_iter()
+            .map(|(key, value, expires_at)| CacheEntry {
+                key,
+                value,
+                expires_at: system_now + expires_at.duration_since(now),
+            })
+            .collect();
+        Box::pin(async move { entries })
+    }
+
+    fn evictions(&self) -> u64 {
+        self.entries.evictions()
+    }
+}
+
+/// Returns the cache key of the `stellar.toml` file of `domain`.
+pub(crate) fn stellar_toml_key(domain: &str) -> String {
+    format!("stellar.toml/{}", domain)
+}
+
+/// Returns the cache key prefix of the responses to queries for `q`.
+pub(crate) fn query_key_prefix(q: &str) -> String {
+    let q: String = url::form_urlencoded::byte_serialize(q.as_bytes()).collect();
+    format!("federation/{}/", q)
+}
+
+/// Returns the cache key of the validators of the entry with `key`.
+pub(crate) fn validators_key(key: &str) -> String {
+    format!("validators/{}", key)
+}
+
+/// Returns the cache key of the stale copy of the entry with `key`.
+pub(crate) fn stale_key(key: &str) -> String {
+    format!("stale/{}", key)
+}
+
+/// Returns the cache key of the response to the federation request `url`.
+///
+/// The server is keyed with its own query parameters, only the `type`
+/// and `q` parameters of the request are stripped.
+pub(crate) fn response_key(url: &url::Url) -> String {
+    let mut server = url.clone();
+    let mut kind = String::new();
+    let mut q = None;
+    let mut params = Vec::new();
+    for (name, value) in url.query_pairs() {
+        match name.as_ref() {
+            "type" => kind = value.into_owned(),
+            "q" => q = Some(value.into_owned()),
+            _ => params.push((name.into_owned(), value.into_owned())),
+        }
+    }
+    if params.is_empty() {
+        server.set_query(None);
+    } else {
+        server.query_pairs_mut().clear().extend_pairs(params);
+    }
+    // Forward requests have no `q` parameter, use the whole query instead.
+    let q = q.unwrap_or_else(|| url.query().unwrap_or_default().to_string());
+    format!("{}{}/{}", query_key_prefix(&q), kind, server)
+}
+
+/// In-memory cache whose entries expire after a ttl.
+#[derive(Debug)]
+pub(crate) struct TtlCache<K, V> {
+    entries: Mutex<HashMap<K, Entry<V>>>,
+    evictions: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Eq + Hash,
+    V: Clone,
+{
+    pub(crate) fn new() -> TtlCache<K, V> {
+        TtlCache {
+            entries: Mutex::new(HashMap::new()),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the value of `key`, if cached and not expired at `now`.
+    pub(crate) fn get(&self, key: &K, now: Instant) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > now => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Caches `value` for `key` until `ttl` after `now`.
+    pub(crate) fn insert(&self, key: K, value: V, ttl: Duration, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if entries.len() >= PURGE_THRESHOLD {
+            let len = entries.len();
+            entries.retain(|_, entry| entry.expires_at > now);
+            self.evictions
+                .fetch_add((len - entries.len()) as u64, Ordering::Relaxed);
+        }
+        entries.insert(
+            key,
+            Entry {
+                value,
+                expires_at: now + ttl,
+            },
+        );
+    }
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Clone,
+    V: Clone,
+{
+    /// Returns the entries not expired at `now`, with their expiration time.
+    pub(crate) fn entries(&self, now: Instant) -> Vec<(K, V, Instant)> {
+        let entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries
+            .iter()
+            .filter(|(_, entry)| entry.expires_at > now)
+            .map(|(key, entry)| (key.clone(), entry.value.clone(), entry.expires_at))
+            .collect()
+    }
+}
+
+impl<K, V> TtlCache<K, V> {
+    /// Returns the number of entries removed because they expired.
+    pub(crate) fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    /// Removes the entries whose key matches `f`.
+    pub(crate) fn remove_where<F>(&self, f: F)
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        entries.retain(|key, _| !f(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = TtlCache::new();
+        let now = Instant::now();
+        cache.insert("example.org", 1, Duration::from_secs(10), now);
+        assert_eq!(Some(1), cache.get(&"example.org", now));
+        assert_eq!(None, cache.get(&"other.org", now));
+        assert_eq!(
+            None,
+            cache.get(&"example.org", now + Duration::from_secs(10))
+        );
+        assert_eq!(1, cache.evictions());
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_clock() {
+        let clock = crate::ManualClock::new();
+        let cache = MemoryCache::with_clock(clock.clone());
+        cache
+            .set("key", Bytes::from("value"), Duration::from_secs(10))
+            .await;
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(Some(Bytes::from("value")), cache.get("key").await);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(None, cache.get("key").await);
+    }
+
+    #[test]
+    fn test_remove_where() {
+        let cache = TtlCache::new();
+        let now = Instant::now();
+        let ttl = Duration::from_secs(10);
+        cache.insert("a.example.org", 1, ttl, now);
+        cache.insert("b.example.org", 2, ttl, now);
+        cache.remove_where(|key| key.starts_with("a."));
+        assert_eq!(None, cache.get(&"a.example.org", now));
+        assert_eq!(Some(2), cache.get(&"b.example.org", now));
+    }
+
+    #[test]
+    fn test_response_key() {
+        let url: url::Url = "https://example.org/federation?type=name&q=a%2Fb*example.org"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            "federation/a%2Fb*example.org/name/https://example.org/federation",
+            response_key(&url)
+        );
+        assert!(response_key(&url).starts_with(&query_key_prefix("a/b*example.org")));
+    }
+
+    #[test]
+    fn test_response_key_server_query() {
+        let tenant_a: url::Url = "https://fed.example/q?tenant=a&type=name&q=alice*example.org"
+            .parse()
+            .unwrap();
+        let tenant_b: url::Url = "https://fed.example/q?tenant=b&type=name&q=alice*example.org"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            "federation/alice*example.org/name/https://fed.example/q?tenant=a",
+            response_key(&tenant_a)
+        );
+        assert_ne!(response_key(&tenant_a), response_key(&tenant_b));
+    }
+
+    #[test]
+    fn test_cached_lookup_from_entry() {
+        let entry = CacheEntry {
+            key: "federation/a%2Fb*example.org/name/https://example.org/federation".to_string(),
+            value: Bytes::from_static(
+                br#"{"stellar_address":"a/b*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#,
+            ),
+            expires_at: SystemTime::now(),
+        };
+        match CachedLookup::from_entry(&entry) {
+            Some(CachedLookup::Response {
+                q, kind, server, ..
+            }) => {
+                assert_eq!("a/b*example.org", q);
+                assert_eq!("name", kind);
+                assert_eq!("https://example.org/federation", server.as_str());
+            }
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+
+        let entry = CacheEntry {
+            key: "stellar.toml/example.org".to_string(),
+            value: Bytes::from_static(b"VERSION=\"2.0.0\""),
+            expires_at: SystemTime::now(),
+        };
+        assert!(matches!(
+            CachedLookup::from_entry(&entry),
+            Some(CachedLookup::FederationServer { server: None, .. })
+        ));
+    }
+}
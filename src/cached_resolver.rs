@@ -0,0 +1,228 @@
+use crate::cache::{query_key_prefix, response_key, FederationCache, MemoryCache};
+use crate::resolver::{ResolveFuture, Resolver};
+use crate::{
+    normalize_stellar_address, response_json, stellar_account_id_request_url,
+    stellar_forward_request_url, stellar_transaction_id_request_url,
+};
+use hyper::body::Bytes;
+use std::sync::Arc;
+use std::time::Duration;
+use stellar_base::PublicKey;
+use url::Url;
+
+/// [`Resolver`] caching the successful lookups of another resolver in
+/// a [`FederationCache`].
+///
+/// Responses are stored with the same keys as the response cache of
+/// [`FederationClient`](crate::FederationClient), except that address
+/// lookups don't depend on the federation server and are stored under
+/// `federation/<q>/name/`. Failed lookups are not cached.
+///
+/// ```rust
+/// use stellar_federation::{CachedResolver, ChainedResolver, FederationClient, StaticResolver};
+/// use std::time::Duration;
+///
+/// let resolver = CachedResolver::new(
+///     ChainedResolver::new()
+///         .resolver(StaticResolver::new())
+///         .resolver(FederationClient::new()),
+///     Duration::from_secs(300),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct CachedResolver<R> {
+    inner: R,
+    cache: Arc<dyn FederationCache>,
+    ttl: Duration,
+}
+
+impl<R: Resolver> CachedResolver<R> {
+    /// Creates a resolver caching the lookups of `inner` for `ttl` in a
+    /// [`MemoryCache`].
+    pub fn new(inner: R, ttl: Duration) -> CachedResolver<R> {
+        CachedResolver {
+            inner,
+            cache: Arc::new(MemoryCache::new()),
+            ttl,
+        }
+    }
+
+    /// Sets the cache storing the lookups.
+    pub fn cache<C>(mut self, cache: C) -> CachedResolver<R>
+    where
+        C: FederationCache + 'static,
+    {
+        self.cache = Arc::new(cache);
+        self
+    }
+
+    /// Returns the wrapped resolver.
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Removes the cached lookups of `q`, for example a Stellar address
+    /// or an account id.
+    ///
+    /// Addresses are normalized like the lookups, so invalidating
+    /// `alice*Example.ORG` removes the lookup of `alice*example.org`.
+    pub async fn invalidate(&self, q: &str) {
+        let q = normalize_stellar_address(q).unwrap_or_else(|| q.to_string());
+        self.cache.invalidate(&query_key_prefix(&q)).await;
+    }
+
+    fn cached<'a, F>(&'a self, key: String, lookup: F) -> ResolveFuture<'a>
+    where
+        F: FnOnce() -> ResolveFuture<'a> + Send + 'a,
+    {
+        Box::pin(async move {
+            let cached = self.cache.get(&key).await;
+            if let Some(response) = cached.and_then(|body| serde_json::from_slice(&body).ok()) {
+                return Ok(response);
+            }
+            let response = lookup().await?;
+            let body = Bytes::from(response_json(&response).to_string());
+            self.cache.set(&key, body, self.ttl).await;
+            Ok(response)
+        })
+    }
+}
+
+impl<R: Resolver> Resolver for CachedResolver<R> {
+    fn resolve_address<'a>(&'a self, address: &'a str) -> ResolveFuture<'a> {
+        match normalize_stellar_address(address) {
+            Some(normalized) => {
+                let key = format!("{}name/", query_key_prefix(&normalized));
+                self.cached(key, move || self.inner.resolve_address(address))
+            }
+            None => self.inner.resolve_address(address),
+        }
+    }
+
+    fn resolve_account_id<'a>(
+        &'a self,
+        account_id: &'a PublicKey,
+        server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        let key = response_key(&stellar_account_id_request_url(account_id, server));
+        self.cached(key, move || {
+            self.inner.resolve_account_id(account_id, server)
+        })
+    }
+
+    fn resolve_transaction_id<'a>(&'a self, tx_id: &'a str, server: &'a Url) -> ResolveFuture<'a> {
+        let key = response_key(&stellar_transaction_id_request_url(tx_id, server));
+        self.cached(key, move || {
+            self.inner.resolve_transaction_id(tx_id, server)
+        })
+    }
+
+    fn resolve_forward<'a>(
+        &'a self,
+        forward_parameters: &'a [(&'a str, &'a str)],
+        server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        let url = stellar_forward_request_url(forward_parameters.iter().copied(), server);
+        self.cached(response_key(&url), move || {
+            self.inner.resolve_forward(forward_parameters, server)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, StaticResolver};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use stellar_base::Memo;
+
+    #[derive(Debug)]
+    struct CountingResolver {
+        inner: StaticResolver,
+        lookups: AtomicUsize,
+    }
+
+    impl Resolver for CountingResolver {
+        fn resolve_address<'a>(&'a self, address: &'a str) -> ResolveFuture<'a> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            self.inner.resolve_address(address)
+        }
+
+        fn resolve_account_id<'a>(
+            &'a self,
+            account_id: &'a PublicKey,
+            server: &'a Url,
+        ) -> ResolveFuture<'a> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            self.inner.resolve_account_id(account_id, server)
+        }
+
+        fn resolve_transaction_id<'a>(
+            &'a self,
+            tx_id: &'a str,
+            server: &'a Url,
+        ) -> ResolveFuture<'a> {
+            self.inner.resolve_transaction_id(tx_id, server)
+        }
+
+        fn resolve_forward<'a>(
+            &'a self,
+            forward_parameters: &'a [(&'a str, &'a str)],
+            server: &'a Url,
+        ) -> ResolveFuture<'a> {
+            self.inner.resolve_forward(forward_parameters, server)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caches_lookups() {
+        let inner = StaticResolver::from_toml(
+            r#"
+            ["payouts*example.org"]
+            account_id = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+            memo_type = "text"
+            memo = "kiosk"
+            "#,
+        )
+        .unwrap();
+        let resolver = CachedResolver::new(
+            CountingResolver {
+                inner,
+                lookups: AtomicUsize::new(0),
+            },
+            Duration::from_secs(60),
+        );
+        for _ in 0..2 {
+            let response = resolver
+                .resolve_address("payouts*example.org")
+                .await
+                .unwrap();
+            assert_eq!(Some(Memo::new_text("kiosk").unwrap()), response.memo);
+        }
+        assert_eq!(1, resolver.inner().lookups.load(Ordering::SeqCst));
+
+        // Failures are not cached.
+        for _ in 0..2 {
+            assert!(matches!(
+                resolver.resolve_address("other*example.org").await,
+                Err(Error::NotFound)
+            ));
+        }
+        assert_eq!(3, resolver.inner().lookups.load(Ordering::SeqCst));
+
+        resolver.invalidate("payouts*example.org").await;
+        resolver
+            .resolve_address("payouts*example.org")
+            .await
+            .unwrap();
+        assert_eq!(4, resolver.inner().lookups.load(Ordering::SeqCst));
+
+        // Invalidation matches the normalized address.
+        resolver.invalidate("payouts*Example.ORG").await;
+        resolver
+            .resolve_address("payouts*example.org")
+            .await
+            .unwrap();
+        assert_eq!(5, resolver.inner().lookups.load(Ordering::SeqCst));
+    }
+}
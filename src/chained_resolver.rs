@@ -0,0 +1,230 @@
+use crate::resolver::{ResolveFuture, Resolver};
+use crate::Error;
+use std::sync::Arc;
+use stellar_base::PublicKey;
+use url::Url;
+
+/// [`Resolver`] trying a list of resolvers in order, for example local
+/// overrides first, then the network.
+///
+/// A lookup falls through to the next resolver when it fails with an
+/// error the chain is configured to skip, otherwise its result is
+/// returned. When every resolver is skipped, the error of the last one
+/// is returned.
+///
+/// By default only missing records fall through: [`Error::NotFound`],
+/// and client error responses such as `404 Not Found`.
+///
+/// ```rust
+/// use stellar_federation::{ChainedResolver, FederationClient, StaticResolver};
+///
+/// # fn run() -> Result<(), stellar_federation::Error> {
+/// let overrides = StaticResolver::open("overrides.toml")?;
+/// let resolver = ChainedResolver::new()
+///     .resolver(overrides)
+///     .resolver(FederationClient::new());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChainedResolver {
+    resolvers: Vec<Arc<dyn Resolver>>,
+    fall_through_not_found: bool,
+    fall_through_unavailable: bool,
+}
+
+impl ChainedResolver {
+    /// Creates an empty chain, whose lookups fail with [`Error::NotFound`].
+    pub fn new() -> ChainedResolver {
+        ChainedResolver {
+            resolvers: Vec::new(),
+            fall_through_not_found: true,
+            fall_through_unavailable: false,
+        }
+    }
+
+    /// Appends `resolver` to the chain.
+    pub fn resolver<R>(mut self, resolver: R) -> ChainedResolver
+    where
+        R: Resolver + 'static,
+    {
+        self.resolvers.push(Arc::new(resolver));
+        self
+    }
+
+    /// Sets whether missing records fall through to the next resolver.
+    pub fn fall_through_not_found(mut self, fall_through: bool) -> ChainedResolver {
+        self.fall_through_not_found = fall_through;
+        self
+    }
+
+    /// Sets whether unavailable resolvers fall through to the next one:
+    /// timeouts, transport errors, server error responses, failures to
+    /// fetch the `stellar.toml` file, and offline clients.
+    pub fn fall_through_unavailable(mut self, fall_through: bool) -> ChainedResolver {
+        self.fall_through_unavailable = fall_through;
+        self
+    }
+
+    /// Returns whether a lookup failing with `error` falls through to
+    /// the next resolver.
+    fn falls_through(&self, error: &Error) -> bool {
+        match error {
+            Error::NotFound => self.fall_through_not_found,
+            Error::ErrorResponse { status, .. } if status.is_client_error() => {
+                self.fall_through_not_found
+            }
+            Error::ErrorResponse { .. }
+            | Error::Timeout
+            | Error::Offline
+            | Error::HyperError(_)
+            | Error::IoError(_)
+            | Error::TomlResolveError(_)
+            | Error::MissingFederationServer
+            | Error::CachedFailure(_) => self.fall_through_unavailable,
+            Error::Coalesced(error) => self.falls_through(error),
+            _ => false,
+        }
+    }
+
+    fn resolve<'a, F>(&'a self, lookup: F) -> ResolveFuture<'a>
+    where
+        F: Fn(&'a dyn Resolver) -> ResolveFuture<'a> + Send + 'a,
+    {
+        Box::pin(async move {
+            let mut result = Err(Error::NotFound);
+            for resolver in &self.resolvers {
+                result = lookup(resolver.as_ref()).await;
+                match &result {
+                    Err(error) if self.falls_through(error) => continue,
+                    _ => return result,
+                }
+            }
+            result
+        })
+    }
+}
+
+impl Default for ChainedResolver {
+    fn default() -> ChainedResolver {
+        ChainedResolver::new()
+    }
+}
+
+impl Resolver for ChainedResolver {
+    fn resolve_address<'a>(&'a self, address: &'a str) -> ResolveFuture<'a> {
+        self.resolve(move |resolver| resolver.resolve_address(address))
+    }
+
+    fn resolve_account_id<'a>(
+        &'a self,
+        account_id: &'a PublicKey,
+        server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        self.resolve(move |resolver| resolver.resolve_account_id(account_id, server))
+    }
+
+    fn resolve_transaction_id<'a>(&'a self, tx_id: &'a str, server: &'a Url) -> ResolveFuture<'a> {
+        self.resolve(move |resolver| resolver.resolve_transaction_id(tx_id, server))
+    }
+
+    fn resolve_forward<'a>(
+        &'a self,
+        forward_parameters: &'a [(&'a str, &'a str)],
+        server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        self.resolve(move |resolver| resolver.resolve_forward(forward_parameters, server))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StaticResolver;
+
+    #[derive(Debug)]
+    struct TimeoutResolver;
+
+    impl Resolver for TimeoutResolver {
+        fn resolve_address<'a>(&'a self, _address: &'a str) -> ResolveFuture<'a> {
+            Box::pin(async { Err(Error::Timeout) })
+        }
+
+        fn resolve_account_id<'a>(
+            &'a self,
+            _account_id: &'a PublicKey,
+            _server: &'a Url,
+        ) -> ResolveFuture<'a> {
+            Box::pin(async { Err(Error::Timeout) })
+        }
+
+        fn resolve_transaction_id<'a>(
+            &'a self,
+            _tx_id: &'a str,
+            _server: &'a Url,
+        ) -> ResolveFuture<'a> {
+            Box::pin(async { Err(Error::Timeout) })
+        }
+
+        fn resolve_forward<'a>(
+            &'a self,
+            _forward_parameters: &'a [(&'a str, &'a str)],
+            _server: &'a Url,
+        ) -> ResolveFuture<'a> {
+            Box::pin(async { Err(Error::Timeout) })
+        }
+    }
+
+    fn overrides() -> StaticResolver {
+        StaticResolver::from_toml(
+            r#"
+            ["payouts*example.org"]
+            account_id = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_falls_through_not_found() {
+        let resolver = ChainedResolver::new()
+            .resolver(StaticResolver::new())
+            .resolver(overrides());
+        assert!(resolver
+            .resolve_address("payouts*example.org")
+            .await
+            .is_ok());
+        assert!(matches!(
+            resolver.resolve_address("other*example.org").await,
+            Err(Error::NotFound)
+        ));
+
+        let resolver = resolver.fall_through_not_found(false);
+        assert!(matches!(
+            resolver.resolve_address("payouts*example.org").await,
+            Err(Error::NotFound)
+        ));
+        assert!(matches!(
+            ChainedResolver::new()
+                .resolve_address("payouts*example.org")
+                .await,
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_falls_through_unavailable() {
+        let resolver = ChainedResolver::new()
+            .resolver(TimeoutResolver)
+            .resolver(overrides());
+        assert!(matches!(
+            resolver.resolve_address("payouts*example.org").await,
+            Err(Error::Timeout)
+        ));
+        let resolver = resolver.fall_through_unavailable(true);
+        assert!(resolver
+            .resolve_address("payouts*example.org")
+            .await
+            .is_ok());
+    }
+}
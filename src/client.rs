@@ -0,0 +1,2151 @@
+use crate::auth::Credentials;
+use crate::cache::{
+    query_key_prefix, response_key, stale_key, stellar_toml_key, validators_key, CacheCounters,
+    CacheStats, CachedLookup, FederationCache, MemoryCache,
+};
+use crate::clock::{Clock, SystemClock};
+use crate::connector::new_https_connector;
+use crate::discovery::AnchorEndpoints;
+use crate::dns::{Dns, DnsResolver};
+use crate::executor::{Executor, TokioExecutor};
+#[cfg(feature = "horizon")]
+use crate::horizon::{
+    account_url, read_home_domain, ReverseLookup, DEFAULT_HORIZON_URL, MAX_HORIZON_RESPONSE_SIZE,
+};
+use crate::http_cache::{freshness, Freshness, StoredResponse, VALIDATORS_TTL};
+use crate::middleware::Middleware;
+use crate::negative_cache::{negative_key, Failure};
+use crate::options::{BatchOptions, CachePolicy, ResolveOptions};
+use crate::pinning::{CertificatePin, CertificatePins};
+use crate::progress::{run_batch, BatchEvent};
+use crate::proxy::{Proxy, ProxyConnector};
+use crate::rate_limit::{ConcurrencyLimits, RateLimit, RateLimiter};
+use crate::redirect::RedirectPolicy;
+use crate::retry::RetryPolicy;
+#[cfg(feature = "sep10")]
+use crate::sep10::{
+    ChallengeSigner, WebAuthAccount, WebAuthEndpoint, WebAuthEndpoints, WebAuthSessions,
+    WebAuthToken,
+};
+use crate::signing::{sign_request, RequestSigner};
+use crate::singleflight::{share, shared_source, unshare, SharedResult, Singleflight};
+use crate::stellar_toml::stellar_toml_uri;
+use crate::timer::{timeout, Timer, TokioTimer};
+use crate::tls::{Certificate, Identity, TlsConfig, TlsVersion};
+use crate::transport::{HyperTransport, Transport};
+use crate::{
+    domain_to_ascii, error_from_response, normalize_stellar_address, split_stellar_address,
+    stellar_account_id_request_url, stellar_address_request_url, stellar_forward_request_url,
+    stellar_muxed_account_request_url, stellar_transaction_id_request_url, DetailedResponse, Error,
+    FederationResponse, StellarToml, StellarTomlError,
+};
+use futures_core::Stream;
+use futures_util::future;
+use futures_util::stream::{self, StreamExt};
+use hyper::body::Bytes;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderMap, AUTHORIZATION};
+use hyper::{Body, Client, Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use stellar_base::crypto::{MuxedAccount, MuxedEd25519PublicKey};
+use stellar_base::PublicKey;
+use tokio::sync::Semaphore;
+use url::Url;
+
+/// Stream returned by [`FederationClient::resolve_address_stream`],
+/// yielding each address with the result of its lookup.
+pub type ResolveStream<'a> =
+    Pin<Box<dyn Stream<Item = (String, Result<FederationResponse, Error>)> + Send + 'a>>;
+
+/// Default maximum size of federation responses, in bytes.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+/// Maximum size of `stellar.toml` files, in bytes, as specified by SEP-0001.
+pub const MAX_STELLAR_TOML_SIZE: usize = 100 * 1024;
+
+/// Default maximum number of concurrent lookups of batch operations.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 10;
+
+/// Federation client.
+///
+/// The client owns a connection pool that is reused across requests,
+/// making it the preferred way to resolve many addresses. Cloning the
+/// client is cheap and clones share the same pool.
+///
+/// ```rust
+/// use stellar_federation::FederationClient;
+///
+/// # async fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let client = FederationClient::new();
+/// let address = client.resolve_address("with-text-memo*ceccon.me").await?;
+/// println!("Address = {:?}", address);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FederationClient {
+    transport: Arc<dyn Transport>,
+    timer: Arc<dyn Timer>,
+    clock: Arc<dyn Clock>,
+    executor: Arc<dyn Executor>,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    credentials: Arc<Credentials>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    #[cfg(feature = "sep10")]
+    web_auth: Arc<WebAuthSessions>,
+    rate_limiter: Arc<RateLimiter>,
+    concurrency_limits: Arc<ConcurrencyLimits>,
+    retry_policy: Option<RetryPolicy>,
+    redirect_policy: RedirectPolicy,
+    max_response_size: usize,
+    allow_http: bool,
+    muxed_id_lookups: bool,
+    verify_responses: bool,
+    #[cfg(feature = "horizon")]
+    horizon_url: Url,
+    cache: Arc<dyn FederationCache>,
+    stellar_toml_cache_ttl: Option<Duration>,
+    response_cache_ttl: Option<Duration>,
+    http_caching: bool,
+    negative_cache_ttl: Option<Duration>,
+    max_staleness: Option<Duration>,
+    inflight: Option<Arc<Singleflight<SharedResult>>>,
+    counters: Arc<CacheCounters>,
+    offline: Arc<AtomicBool>,
+}
+
+/// Builder for [`FederationClient`].
+#[derive(Debug, Clone, Default)]
+pub struct FederationClientBuilder {
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    pool_idle_timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    dns_resolver: Option<Arc<dyn DnsResolver>>,
+    retry_policy: Option<RetryPolicy>,
+    redirect_policy: RedirectPolicy,
+    max_response_size: Option<usize>,
+    allow_http: bool,
+    muxed_id_lookups: bool,
+    verify_responses: Option<bool>,
+    #[cfg(feature = "horizon")]
+    horizon_url: Option<Url>,
+    stellar_toml_cache_ttl: Option<Duration>,
+    response_cache_ttl: Option<Duration>,
+    http_caching: bool,
+    negative_cache_ttl: Option<Duration>,
+    max_staleness: Option<Duration>,
+    coalesce_requests: bool,
+    offline: bool,
+    cache: Option<Arc<dyn FederationCache>>,
+    proxies: Vec<Proxy>,
+    transport: Option<Arc<dyn Transport>>,
+    timer: Option<Arc<dyn Timer>>,
+    clock: Option<Arc<dyn Clock>>,
+    executor: Option<Arc<dyn Executor>>,
+    certificate_pins: CertificatePins,
+    tls: TlsConfig,
+    middlewares: Vec<Arc<dyn Middleware>>,
+    credentials: Vec<(String, String, String)>,
+    signer: Option<Arc<dyn RequestSigner>>,
+    #[cfg(feature = "sep10")]
+    web_auth: Vec<(String, WebAuthAccount)>,
+    rate_limit: Option<RateLimit>,
+    domain_rate_limits: HashMap<String, RateLimit>,
+    max_concurrency_per_domain: Option<usize>,
+    domain_max_concurrency: HashMap<String, usize>,
+}
+
+impl FederationClient {
+    /// Creates a new federation client with the default configuration.
+    ///
+    /// The default client has no timeouts, does not retry failed
+    /// requests, and does not follow redirects.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the tls backend cannot be initialized. Use
+    /// [`FederationClient::builder`] to handle the error instead.
+    pub fn new() -> FederationClient {
+        FederationClient::builder()
+            .build()
+            .expect("failed to initialize the federation client")
+    }
+
+    /// Creates a new [`FederationClientBuilder`].
+    pub fn builder() -> FederationClientBuilder {
+        FederationClientBuilder::default()
+    }
+
+    /// Resolves a Stellar address, automatically discovering the federation server to use.
+    ///
+    /// Internationalized domain names are converted to their ASCII form,
+    /// for example `maria*bücher.example` is resolved as
+    /// `maria*xn--bcher-kva.example`.
+    pub async fn resolve_address(&self, address: &str) -> Result<FederationResponse, Error> {
+        self.resolve_address_with_options(address, &ResolveOptions::default())
+            .await
+    }
+
+    /// Resolves a Stellar address, automatically discovering the federation server to use.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub async fn resolve_address_with_options(
+        &self,
+        address: &str,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        let detailed = self
+            .resolve_address_detailed_with_options(address, options)
+            .await?;
+        Ok(detailed.response)
+    }
+
+    /// Resolves a Stellar address like [`resolve_address`], returning
+    /// the `stellar.toml` file of its domain and the federation server
+    /// together with the response.
+    ///
+    /// [`resolve_address`]: FederationClient::resolve_address
+    pub async fn resolve_address_detailed(&self, address: &str) -> Result<DetailedResponse, Error> {
+        self.resolve_address_detailed_with_options(address, &ResolveOptions::default())
+            .await
+    }
+
+    /// Resolves a Stellar address like [`resolve_address`], returning
+    /// the `stellar.toml` file of its domain and the federation server
+    /// together with the response.
+    ///
+    /// The `options` override the client configuration for this call.
+    ///
+    /// [`resolve_address`]: FederationClient::resolve_address
+    pub async fn resolve_address_detailed_with_options(
+        &self,
+        address: &str,
+        options: &ResolveOptions,
+    ) -> Result<DetailedResponse, Error> {
+        self.with_timeout(options, async {
+            let address = self
+                .normalize_address(address)
+                .ok_or(Error::InvalidStellarAddress)?;
+            let (_, domain) =
+                split_stellar_address(&address).ok_or(Error::InvalidStellarAddress)?;
+            let stellar_toml = self.fetch_stellar_toml(domain, options).await?;
+            let federation_server = federation_server_url(&stellar_toml)?;
+            let url = stellar_address_request_url(&address, &federation_server);
+            let response = self.resolve_url(&url, options).await?;
+            Ok(DetailedResponse {
+                response,
+                stellar_toml,
+                federation_server,
+            })
+        })
+        .await
+    }
+
+    /// Resolves `addresses` concurrently, running at most
+    /// `max_concurrency` lookups at a time.
+    ///
+    /// The results are returned in the order of `addresses`. The lookups
+    /// share the connection pool and the cache of the client.
+    ///
+    /// The `stellar.toml` file of each domain is fetched once, before
+    /// resolving the addresses of that domain. When it can't be fetched
+    /// for a domain with several addresses, each of them gets an
+    /// [`Error::Coalesced`] error.
+    pub async fn resolve_addresses(
+        &self,
+        addresses: &[&str],
+        max_concurrency: usize,
+    ) -> Vec<Result<FederationResponse, Error>> {
+        let options = BatchOptions::new().max_concurrency(max_concurrency);
+        self.resolve_addresses_with_progress(addresses, &options, |_| {})
+            .await
+    }
+
+    /// Resolves `addresses` like [`resolve_addresses`], according to the
+    /// `options`, calling `progress` with a [`BatchEvent`] as soon as
+    /// each lookup completes.
+    ///
+    /// [`resolve_addresses`]: FederationClient::resolve_addresses
+    ///
+    /// ```rust
+    /// use stellar_federation::{BatchOptions, FederationClient};
+    ///
+    /// # async fn run() {
+    /// let client = FederationClient::new();
+    /// let addresses = ["alice*example.org", "bob*example.org"];
+    /// let options = BatchOptions::new().max_concurrency(10);
+    /// client
+    ///     .resolve_addresses_with_progress(&addresses, &options, |event| {
+    ///         println!("{}/{} done", event.progress.completed, event.progress.total());
+    ///     })
+    ///     .await;
+    /// # }
+    /// ```
+    pub async fn resolve_addresses_with_progress<P>(
+        &self,
+        addresses: &[&str],
+        options: &BatchOptions,
+        progress: P,
+    ) -> Vec<Result<FederationResponse, Error>>
+    where
+        P: FnMut(BatchEvent<'_>) + Send,
+    {
+        self.resolve_batch(addresses, options, progress).await
+    }
+
+    /// Resolves `addresses` concurrently, returning the result of each
+    /// address, so that the failure of some lookups doesn't discard the
+    /// successful ones.
+    ///
+    /// The lookups are run as with [`resolve_addresses`], according to
+    /// the `options`. When they fail fast, the lookups not started yet
+    /// after a failure fail with [`Error::Aborted`].
+    ///
+    /// [`resolve_addresses`]: FederationClient::resolve_addresses
+    ///
+    /// ```rust
+    /// use stellar_federation::{BatchOptions, FederationClient};
+    ///
+    /// # async fn run() {
+    /// let client = FederationClient::new();
+    /// let addresses = ["alice*example.org", "bob*example.org"];
+    /// let results = client
+    ///     .resolve_many(&addresses, &BatchOptions::new().max_concurrency(4))
+    ///     .await;
+    /// for (address, result) in &results {
+    ///     println!("{} = {:?}", address, result);
+    /// }
+    /// # }
+    /// ```
+    pub async fn resolve_many(
+        &self,
+        addresses: &[&str],
+        options: &BatchOptions,
+    ) -> HashMap<String, Result<FederationResponse, Error>> {
+        let results = self.resolve_batch(addresses, options, |_| {}).await;
+        addresses
+            .iter()
+            .map(|address| address.to_string())
+            .zip(results)
+            .collect()
+    }
+
+    async fn resolve_batch<P>(
+        &self,
+        addresses: &[&str],
+        batch_options: &BatchOptions,
+        progress: P,
+    ) -> Vec<Result<FederationResponse, Error>>
+    where
+        P: FnMut(BatchEvent<'_>) + Send,
+    {
+        let max_concurrency = batch_options.max_concurrency_value();
+        let fail_fast = batch_options.fail_fast_value();
+        let options = batch_options.resolve_options_value();
+        let aborted = AtomicBool::new(false);
+        let mut groups = HashMap::new();
+        let mut domains: Vec<String> = Vec::new();
+        let mut group_sizes: Vec<usize> = Vec::new();
+        let lookups: Vec<Option<(String, usize, usize)>> = addresses
+            .iter()
+            .map(|address| {
+                let address = self.normalize_address(address)?;
+                let (_, domain) = split_stellar_address(&address)?;
+                let group = *groups.entry(domain.to_string()).or_insert_with(|| {
+                    domains.push(domain.to_string());
+                    group_sizes.push(0);
+                    domains.len() - 1
+                });
+                let rank = group_sizes[group];
+                group_sizes[group] += 1;
+                Some((address, group, rank))
+            })
+            .collect();
+        let semaphores: Vec<Option<Semaphore>> = domains
+            .iter()
+            .map(|domain| self.concurrency_limits.get(domain).map(Semaphore::new))
+            .collect();
+
+        let futures = domains
+            .iter()
+            .map(|domain| self.with_timeout(options, self.federation_server(domain, options)));
+        let servers: Vec<Result<Url, Arc<Error>>> = stream::iter(futures)
+            .buffered(max_concurrency.max(1))
+            .map(|server| server.map_err(Arc::new))
+            .collect()
+            .await;
+        let targets: Vec<_> = lookups
+            .into_iter()
+            .map(|lookup| {
+                let (address, group, rank) = lookup?;
+                let url = servers[group]
+                    .as_ref()
+                    .map(|server| stellar_address_request_url(&address, server))
+                    .map_err(Arc::clone);
+                Some((url, group, rank))
+            })
+            .collect();
+        // Release the shared errors, so that a domain with a single
+        // address gets back the original error.
+        drop(servers);
+
+        let mut futures: Vec<_> = targets
+            .into_iter()
+            .enumerate()
+            .map(|(index, target)| {
+                let (url, group, rank) = match target {
+                    None => (Err(Error::InvalidStellarAddress), None, 0),
+                    Some((Ok(url), group, rank)) => (Ok(url), Some(group), rank),
+                    Some((Err(error), group, rank)) if group_sizes[group] == 1 => {
+                        let error = Arc::try_unwrap(error).unwrap_or_else(Error::Coalesced);
+                        (Err(error), Some(group), rank)
+                    }
+                    Some((Err(error), group, rank)) => {
+                        (Err(Error::Coalesced(error)), Some(group), rank)
+                    }
+                };
+                let semaphore = group.and_then(|group| semaphores[group].as_ref());
+                let aborted = &aborted;
+                let future = async move {
+                    let result = async {
+                        let url = url?;
+                        let _permit = match semaphore {
+                            Some(semaphore) => semaphore.acquire().await.ok(),
+                            None => None,
+                        };
+                        if aborted.load(Ordering::Relaxed) {
+                            return Err(Error::Aborted);
+                        }
+                        self.with_timeout(options, self.resolve_url(&url, options))
+                            .await
+                    }
+                    .await;
+                    if fail_fast && result.is_err() {
+                        aborted.store(true, Ordering::Relaxed);
+                    }
+                    result
+                };
+                (rank, index, future)
+            })
+            .collect();
+        // Start the lookups of the different domains in turn, so that
+        // the lookups waiting for the limit of a domain don't hold up
+        // the lookups of the other domains.
+        futures.sort_by_key(|(rank, _, _)| *rank);
+        let futures = futures
+            .into_iter()
+            .map(|(_, index, future)| (index, future))
+            .collect();
+        run_batch(futures, max_concurrency, progress).await
+    }
+
+    /// Resolves the Stellar addresses of the `addresses` stream
+    /// concurrently, running at most `max_concurrency` lookups at a time.
+    ///
+    /// The returned stream yields each address with its result as soon
+    /// as the lookup completes, so the results are not in the order of
+    /// `addresses`. Use [`futures::stream::iter`] to resolve the
+    /// addresses of an iterator.
+    ///
+    /// [`futures::stream::iter`]: https://docs.rs/futures/0.3/futures/stream/fn.iter.html
+    ///
+    /// ```rust
+    /// use futures_util::stream::{self, StreamExt};
+    /// use stellar_federation::FederationClient;
+    ///
+    /// # async fn run() {
+    /// let client = FederationClient::new();
+    /// let addresses = stream::iter(vec!["alice*example.org", "bob*example.org"]);
+    /// let mut results = client.resolve_address_stream(addresses, 10);
+    /// while let Some((address, result)) = results.next().await {
+    ///     println!("{} = {:?}", address, result);
+    /// }
+    /// # }
+    /// ```
+    pub fn resolve_address_stream<'a, S>(
+        &'a self,
+        addresses: S,
+        max_concurrency: usize,
+    ) -> ResolveStream<'a>
+    where
+        S: Stream + Send + 'a,
+        S::Item: Into<String>,
+    {
+        let lookups = addresses.map(move |address| {
+            let address = address.into();
+            async move {
+                let result = self.resolve_address(&address).await;
+                (address, result)
+            }
+        });
+        Box::pin(lookups.buffer_unordered(max_concurrency.max(1)))
+    }
+
+    /// Resolves a Stellar address using the specified federation server.
+    pub async fn resolve_address_from_server(
+        &self,
+        address: &str,
+        server: &Url,
+    ) -> Result<FederationResponse, Error> {
+        self.resolve_address_from_server_with_options(address, server, &ResolveOptions::default())
+            .await
+    }
+
+    /// Resolves a Stellar address using the specified federation server.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub async fn resolve_address_from_server_with_options(
+        &self,
+        address: &str,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        let address = self
+            .normalize_address(address)
+            .unwrap_or_else(|| address.to_string());
+        let url = stellar_address_request_url(&address, server);
+        self.with_timeout(options, self.resolve_url(&url, options))
+            .await
+    }
+
+    /// Resolves a Stellar address using the federation server declared
+    /// in `stellar_toml`, the `stellar.toml` file of its domain, already
+    /// fetched by the application.
+    ///
+    /// Fails with [`Error::MissingFederationServer`] if the file declares
+    /// no federation server. Use [`resolve_address_from_server`] when
+    /// only the federation server is known.
+    ///
+    /// [`resolve_address_from_server`]: FederationClient::resolve_address_from_server
+    pub async fn resolve_address_with_toml(
+        &self,
+        address: &str,
+        stellar_toml: &StellarToml,
+    ) -> Result<FederationResponse, Error> {
+        self.resolve_address_with_toml_and_options(
+            address,
+            stellar_toml,
+            &ResolveOptions::default(),
+        )
+        .await
+    }
+
+    /// Resolves a Stellar address using the federation server declared
+    /// in `stellar_toml`, the `stellar.toml` file of its domain.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub async fn resolve_address_with_toml_and_options(
+        &self,
+        address: &str,
+        stellar_toml: &StellarToml,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        let server = federation_server_url(stellar_toml)?;
+        self.resolve_address_from_server_with_options(address, &server, options)
+            .await
+    }
+
+    /// Resolves the `account_id` using the specified federation server.
+    pub async fn resolve_account_id(
+        &self,
+        account_id: &PublicKey,
+        server: &Url,
+    ) -> Result<FederationResponse, Error> {
+        self.resolve_account_id_with_options(account_id, server, &ResolveOptions::default())
+            .await
+    }
+
+    /// Resolves the `account_id` using the specified federation server.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub async fn resolve_account_id_with_options(
+        &self,
+        account_id: &PublicKey,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        let url = stellar_account_id_request_url(account_id, server);
+        self.with_timeout(options, self.resolve_url(&url, options))
+            .await
+    }
+
+    /// Resolves the muxed `account` using the specified federation
+    /// server.
+    ///
+    /// The lookup is sent with the base account id of muxed accounts,
+    /// unless muxed id lookups are enabled with
+    /// [`FederationClientBuilder::muxed_id_lookups`].
+    pub async fn resolve_muxed_account(
+        &self,
+        account: &MuxedAccount,
+        server: &Url,
+    ) -> Result<FederationResponse, Error> {
+        self.resolve_muxed_account_with_options(account, server, &ResolveOptions::default())
+            .await
+    }
+
+    /// Resolves the muxed `account` using the specified federation
+    /// server.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub async fn resolve_muxed_account_with_options(
+        &self,
+        account: &MuxedAccount,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        let url = match account {
+            MuxedAccount::MuxedEd25519(muxed) if !self.muxed_id_lookups => {
+                stellar_account_id_request_url(muxed.public_key(), server)
+            }
+            account => stellar_muxed_account_request_url(account, server),
+        };
+        self.with_timeout(options, self.resolve_url(&url, options))
+            .await
+    }
+
+    /// Finds the Stellar address of `account_id` without knowing its
+    /// federation server.
+    ///
+    /// The Horizon server of the client is queried for the home domain
+    /// of the account, then the account id is looked up with the
+    /// federation server declared in the `stellar.toml` file of the home
+    /// domain. Fails with [`Error::MissingHomeDomain`] if the account has
+    /// no home domain, and with [`Error::NotFound`] if Horizon doesn't
+    /// know the account.
+    ///
+    /// The home domain is set by the owner of the account, so the
+    /// address is only as trustworthy as the account itself.
+    #[cfg(feature = "horizon")]
+    pub async fn reverse_lookup(&self, account_id: &PublicKey) -> Result<ReverseLookup, Error> {
+        self.reverse_lookup_with_options(account_id, &ResolveOptions::default())
+            .await
+    }
+
+    /// Finds the Stellar address of `account_id` from its home domain,
+    /// like [`reverse_lookup`](FederationClient::reverse_lookup).
+    ///
+    /// The `options` override the client configuration for this call.
+    #[cfg(feature = "horizon")]
+    pub async fn reverse_lookup_with_options(
+        &self,
+        account_id: &PublicKey,
+        options: &ResolveOptions,
+    ) -> Result<ReverseLookup, Error> {
+        self.with_timeout(options, async {
+            let uri = account_url(&self.horizon_url, account_id)
+                .to_string()
+                .parse()?;
+            let response = self
+                .get(uri, MAX_HORIZON_RESPONSE_SIZE, &HeaderMap::new(), options)
+                .await?;
+            if !response.status().is_success() {
+                return Err(error_from_response(response.status(), response.body()));
+            }
+            let home_domain = read_home_domain(response.body())?;
+            let home_domain = domain_to_ascii(&home_domain).unwrap_or(home_domain);
+            let stellar_toml = self.fetch_stellar_toml(&home_domain, options).await?;
+            let federation_server = federation_server_url(&stellar_toml)?;
+            let url = stellar_account_id_request_url(account_id, &federation_server);
+            let response = self.resolve_url(&url, options).await?;
+            Ok(ReverseLookup {
+                horizon_url: self.horizon_url.clone(),
+                home_domain,
+                stellar_toml,
+                federation_server,
+                response,
+            })
+        })
+        .await
+    }
+
+    /// Resolves the `account_ids` using the specified federation server
+    /// concurrently, running at most `max_concurrency` lookups at a time.
+    ///
+    /// The results are returned in the order of `account_ids`.
+    pub async fn resolve_account_ids(
+        &self,
+        account_ids: &[PublicKey],
+        server: &Url,
+        max_concurrency: usize,
+    ) -> Vec<Result<FederationResponse, Error>> {
+        let options = BatchOptions::new().max_concurrency(max_concurrency);
+        self.resolve_account_ids_with_progress(account_ids, server, &options, |_| {})
+            .await
+    }
+
+    /// Resolves the `account_ids` like [`resolve_account_ids`], according
+    /// to the `options`, calling `progress` with a [`BatchEvent`] as soon
+    /// as each lookup completes.
+    ///
+    /// [`resolve_account_ids`]: FederationClient::resolve_account_ids
+    pub async fn resolve_account_ids_with_progress<P>(
+        &self,
+        account_ids: &[PublicKey],
+        server: &Url,
+        options: &BatchOptions,
+        progress: P,
+    ) -> Vec<Result<FederationResponse, Error>>
+    where
+        P: FnMut(BatchEvent<'_>) + Send,
+    {
+        let max_concurrency = options.max_concurrency_value();
+        let max_concurrency = match server.host_str() {
+            Some(host) => self
+                .concurrency_limits
+                .get(host)
+                .map_or(max_concurrency, |limit| limit.min(max_concurrency)),
+            None => max_concurrency,
+        };
+        let fail_fast = options.fail_fast_value();
+        let resolve_options = options.resolve_options_value();
+        let aborted = &AtomicBool::new(false);
+        let futures = account_ids
+            .iter()
+            .map(|account_id| async move {
+                if aborted.load(Ordering::Relaxed) {
+                    return Err(Error::Aborted);
+                }
+                let result = self
+                    .resolve_account_id_with_options(account_id, server, resolve_options)
+                    .await;
+                if fail_fast && result.is_err() {
+                    aborted.store(true, Ordering::Relaxed);
+                }
+                result
+            })
+            .enumerate()
+            .collect();
+        run_batch(futures, max_concurrency, progress).await
+    }
+
+    /// Resolves the `tx_id` using the specified federation server.
+    pub async fn resolve_transaction_id(
+        &self,
+        tx_id: &str,
+        server: &Url,
+    ) -> Result<FederationResponse, Error> {
+        self.resolve_transaction_id_with_options(tx_id, server, &ResolveOptions::default())
+            .await
+    }
+
+    /// Resolves the `tx_id` using the specified federation server.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub async fn resolve_transaction_id_with_options(
+        &self,
+        tx_id: &str,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        let url = stellar_transaction_id_request_url(tx_id, server);
+        self.with_timeout(options, self.resolve_url(&url, options))
+            .await
+    }
+
+    /// Resolves to the information to send a payment to a different network or institution.
+    ///
+    /// See [`resolve_stellar_forward`](crate::resolve_stellar_forward).
+    pub async fn resolve_forward<'a, K>(
+        &self,
+        forward_parameters: K,
+        server: &Url,
+    ) -> Result<FederationResponse, Error>
+    where
+        K: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        self.resolve_forward_with_options(forward_parameters, server, &ResolveOptions::default())
+            .await
+    }
+
+    /// Resolves to the information to send a payment to a different network or institution.
+    ///
+    /// The `options` override the client configuration for this call.
+    pub async fn resolve_forward_with_options<'a, K>(
+        &self,
+        forward_parameters: K,
+        server: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error>
+    where
+        K: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let url = stellar_forward_request_url(forward_parameters, server);
+        self.with_timeout(options, self.resolve_url(&url, options))
+            .await
+    }
+
+    /// Fetches and parses the `stellar.toml` file of `domain`.
+    pub async fn resolve_stellar_toml(&self, domain: &str) -> Result<StellarToml, Error> {
+        self.fetch_stellar_toml(domain, &ResolveOptions::default())
+            .await
+    }
+
+    /// Requests a SEP-0010 web authentication token of `account` from
+    /// the `WEB_AUTH_ENDPOINT` of the `stellar.toml` file of `domain`.
+    ///
+    /// The challenge returned by the endpoint is verified, then signed
+    /// with `signer` and posted back in exchange for the token. Fails
+    /// with [`Error::WebAuthError`] if the `stellar.toml` file has no
+    /// web authentication endpoint or signing key, if the challenge is
+    /// invalid, or if the endpoint rejects the signed challenge.
+    ///
+    /// Use [`FederationClientBuilder::web_auth`] to authenticate lookups
+    /// automatically instead.
+    #[cfg(feature = "sep10")]
+    pub async fn web_auth_token(
+        &self,
+        domain: &str,
+        account: &PublicKey,
+        signer: &dyn ChallengeSigner,
+    ) -> Result<WebAuthToken, Error> {
+        let domain = domain_to_ascii(domain).unwrap_or_else(|| domain.to_string());
+        let options = ResolveOptions::default();
+        let toml = self.fetch_stellar_toml(&domain, &options).await?;
+        let endpoint = WebAuthEndpoint::from_toml(&domain, &toml)?;
+        self.request_web_auth_token(&endpoint, account, signer, &options)
+            .await
+    }
+
+    #[cfg(feature = "sep10")]
+    async fn request_web_auth_token(
+        &self,
+        endpoint: &WebAuthEndpoint,
+        account: &PublicKey,
+        signer: &dyn ChallengeSigner,
+        options: &ResolveOptions,
+    ) -> Result<WebAuthToken, Error> {
+        let uri = endpoint.challenge_url(account).to_string().parse()?;
+        let response = self
+            .get(uri, self.max_response_size, &HeaderMap::new(), options)
+            .await?;
+        let mut challenge = endpoint.read_challenge(
+            response.status(),
+            response.body(),
+            account,
+            self.clock.system_time(),
+        )?;
+        signer.sign(&mut challenge, endpoint.network())?;
+
+        let request = Request::post(endpoint.url().to_string())
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(())?;
+        let request = self.prepare(request).await?;
+        let body = endpoint.token_request(&challenge)?;
+        let response = self
+            .transport
+            .send_with_body(request.map(|_| body), self.max_response_size)
+            .await?;
+        let response = self.finish(response).await?;
+        endpoint.read_token(response.status(), response.body())
+    }
+
+    /// Requests a web authentication token for the host of `url`, if it's
+    /// the federation server of a domain with a web authentication
+    /// account and it has no valid token.
+    ///
+    /// The federation server hosts of the domains are resolved by the
+    /// first authenticated lookup, and kept for the lifetime of the
+    /// client. Fails if the `stellar.toml` file of a domain can't be
+    /// fetched, or doesn't declare a web authentication endpoint.
+    #[cfg(feature = "sep10")]
+    async fn authenticate(&self, url: &Url, options: &ResolveOptions) -> Result<(), Error> {
+        let host = match url.host_str() {
+            Some(host) => host.to_lowercase(),
+            None => return Ok(()),
+        };
+        if self.web_auth.is_empty() || self.web_auth.has_token(&host, self.clock.system_time()) {
+            return Ok(());
+        }
+        let endpoints = match self.web_auth.endpoints() {
+            Some(endpoints) => endpoints,
+            None => {
+                let endpoints = Arc::new(self.resolve_web_auth_endpoints(options).await?);
+                self.web_auth.set_endpoints(endpoints.clone());
+                endpoints
+            }
+        };
+        let endpoint = match endpoints.get(&host) {
+            Some(endpoint) => endpoint,
+            None => return Ok(()),
+        };
+        let account = match self.web_auth.account(endpoint.home_domain()) {
+            Some(account) => account,
+            None => return Ok(()),
+        };
+        let token = self
+            .request_web_auth_token(endpoint, &account.account, account.signer.as_ref(), options)
+            .await?;
+        self.web_auth.insert_token(&host, token);
+        Ok(())
+    }
+
+    /// Returns the web authentication endpoints of the domains with an
+    /// account, by the host of their federation server.
+    #[cfg(feature = "sep10")]
+    async fn resolve_web_auth_endpoints(
+        &self,
+        options: &ResolveOptions,
+    ) -> Result<WebAuthEndpoints, Error> {
+        let mut endpoints = HashMap::new();
+        for (domain, _) in self.web_auth.accounts() {
+            let toml = self.fetch_stellar_toml(domain, options).await?;
+            let host = federation_server_url(&toml)?
+                .host_str()
+                .map(str::to_lowercase)
+                .ok_or(Error::MissingFederationServer)?;
+            let endpoint = WebAuthEndpoint::from_toml(domain, &toml)?;
+            endpoints.insert(host, Arc::new(endpoint));
+        }
+        Ok(endpoints)
+    }
+
+    /// Returns the endpoints of the services of `domain`, read from its
+    /// `stellar.toml` file.
+    ///
+    /// The file is fetched like for lookups, so it's served from the
+    /// cache when the client caches `stellar.toml` files.
+    pub async fn discover(&self, domain: &str) -> Result<AnchorEndpoints, Error> {
+        let domain = domain_to_ascii(domain).unwrap_or_else(|| domain.to_string());
+        let toml = self
+            .fetch_stellar_toml(&domain, &ResolveOptions::default())
+            .await?;
+        AnchorEndpoints::from_toml(&domain, &toml)
+    }
+
+    /// Fetches the `stellar.toml` files of `domains` concurrently,
+    /// returning the url of their federation servers in order.
+    ///
+    /// Use this method at startup to warm the cache with the domains
+    /// that are likely to be looked up. The files are cached only if
+    /// the client caches `stellar.toml` files, see
+    /// [`FederationClientBuilder::stellar_toml_cache_ttl`].
+    pub async fn prefetch_domains(&self, domains: &[&str]) -> Vec<Result<Url, Error>> {
+        let options = ResolveOptions::default();
+        let futures = domains
+            .iter()
+            .map(|domain| self.federation_server(domain, &options));
+        future::join_all(futures).await
+    }
+
+    /// Returns the url of the federation server of `domain`.
+    async fn federation_server(
+        &self,
+        domain: &str,
+        options: &ResolveOptions,
+    ) -> Result<Url, Error> {
+        let toml = self.fetch_stellar_toml(domain, options).await?;
+        federation_server_url(&toml)
+    }
+
+    async fn fetch_stellar_toml(
+        &self,
+        domain: &str,
+        options: &ResolveOptions,
+    ) -> Result<StellarToml, Error> {
+        let domain = domain_to_ascii(domain).unwrap_or_else(|| domain.to_string());
+        let key = stellar_toml_key(&domain);
+        if options.cache_policy_value() != CachePolicy::Default {
+            return self.download_stellar_toml(&domain, options).await;
+        }
+        let read_cache = self.stellar_toml_cache_ttl.is_some()
+            || self.http_caching
+            || self.negative_cache_ttl.is_some();
+        if read_cache {
+            let cached = self.cache.get(&key).await;
+            if let Some(toml) = cached.and_then(|body| toml::from_slice(&body).ok()) {
+                self.counters.hit();
+                return Ok(toml);
+            }
+        }
+        if let Some(toml) = self.stale(&key, |body| toml::from_slice(body).ok()).await {
+            self.refresh_stellar_toml(domain, options);
+            return Ok(toml);
+        }
+        if let Some(failure) = self.cached_failure(&key).await {
+            self.counters.hit();
+            return Err(failure.into_stellar_toml_error());
+        }
+        if read_cache {
+            self.counters.miss();
+        }
+        self.download_stellar_toml(&domain, options).await
+    }
+
+    /// Fetches the `stellar.toml` file of the ASCII `domain`, bypassing
+    /// the cache, then caches it unless the cache policy of `options` is
+    /// [`CachePolicy::NoCache`].
+    async fn download_stellar_toml(
+        &self,
+        domain: &str,
+        options: &ResolveOptions,
+    ) -> Result<StellarToml, Error> {
+        let key = stellar_toml_key(domain);
+        let use_cache = options.cache_policy_value() != CachePolicy::NoCache;
+        let uri = stellar_toml_uri(domain, self.allow_http)?;
+        let result = self
+            .get_coalesced(&key, uri, MAX_STELLAR_TOML_SIZE, options, use_cache)
+            .await
+            .map_err(|err| match err {
+                Error::HyperError(err) => StellarTomlError::from(err).into(),
+                err => err,
+            });
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                // Coalesced connection errors are not converted, look
+                // through them to cache the failure all the same.
+                if use_cache
+                    && matches!(
+                        shared_source(&err),
+                        Error::TomlResolveError(_) | Error::HyperError(_)
+                    )
+                {
+                    self.store_failure(&key, Failure::from_error(&err)).await;
+                }
+                return Err(err);
+            }
+        };
+
+        if response.status().is_success() {
+            let result: StellarToml = match toml::from_slice(response.body()) {
+                Ok(result) => result,
+                Err(err) => {
+                    let err = StellarTomlError::from(err).into();
+                    if use_cache {
+                        self.store_failure(&key, Failure::from_error(&err)).await;
+                    }
+                    return Err(err);
+                }
+            };
+            // Files without a federation server fail lookups, cache them
+            // like failures.
+            let ttl = match result.federation_server {
+                Some(_) => self.stellar_toml_cache_ttl,
+                None => self.stellar_toml_cache_ttl.or(self.negative_cache_ttl),
+            };
+            if use_cache {
+                self.store(&key, &response, ttl).await;
+            }
+            Ok(result)
+        } else if response.status().is_client_error() {
+            if use_cache {
+                self.store_failure(&key, Failure::from_response(&response))
+                    .await;
+            }
+            Err(StellarTomlError::ClientError(response.status()).into())
+        } else {
+            Err(StellarTomlError::ServerError(response.status()).into())
+        }
+    }
+
+    /// Returns `address` with its domain converted to ASCII.
+    ///
+    /// When http is allowed, the domain can include a port.
+    fn normalize_address(&self, address: &str) -> Option<String> {
+        if self.allow_http {
+            let (name, domain) = split_stellar_address(address)?;
+            if let Some((host, port)) = domain.rsplit_once(':') {
+                if port.parse::<u16>().is_ok() {
+                    return Some(format!("{}*{}:{}", name, domain_to_ascii(host)?, port));
+                }
+            }
+        }
+        normalize_stellar_address(address)
+    }
+
+    async fn resolve_url(
+        &self,
+        url: &Url,
+        options: &ResolveOptions,
+    ) -> Result<FederationResponse, Error> {
+        let key = response_key(url);
+        let cache_policy = options.cache_policy_value();
+        let cache_ttl = options.cache_ttl_or(self.response_cache_ttl);
+        let use_cache =
+            cache_policy != CachePolicy::NoCache && (cache_ttl.is_some() || self.http_caching);
+        let read_cache = cache_policy == CachePolicy::Default;
+        if use_cache && read_cache {
+            let cached = self.cache.get(&key).await;
+            if let Some(result) = cached.and_then(|body| serde_json::from_slice(&body).ok()) {
+                self.counters.hit();
+                return self.verify_response(url, result);
+            }
+            if let Some(result) = self
+                .stale(&key, |body| serde_json::from_slice(body).ok())
+                .await
+            {
+                self.refresh_url(url, options);
+                return self.verify_response(url, result);
+            }
+        }
+        if read_cache {
+            if let Some(failure) = self.cached_failure(&key).await {
+                self.counters.hit();
+                return Err(failure.into_error());
+            }
+            if use_cache || self.negative_cache_ttl.is_some() {
+                self.counters.miss();
+            }
+        }
+
+        #[cfg(feature = "sep10")]
+        self.authenticate(url, options).await?;
+        let uri: hyper::Uri = url.to_string().parse()?;
+        let response = self
+            .get_coalesced(&key, uri, self.max_response_size, options, use_cache)
+            .await?;
+
+        if response.status().is_success() {
+            let result: FederationResponse = serde_json::from_slice(response.body())?;
+            let result = self.verify_response(url, result)?;
+            if use_cache {
+                self.store(&key, &response, cache_ttl).await;
+            }
+            Ok(result)
+        } else {
+            if response.status() == StatusCode::NOT_FOUND && cache_policy != CachePolicy::NoCache {
+                self.store_failure(&key, Failure::from_response(&response))
+                    .await;
+            }
+            Err(error_from_response(response.status(), response.body()))
+        }
+    }
+
+    /// Returns the `response` to the lookup of `url` if it matches the
+    /// query, or if responses are not verified.
+    fn verify_response(
+        &self,
+        url: &Url,
+        response: FederationResponse,
+    ) -> Result<FederationResponse, Error> {
+        if self.verify_responses {
+            verify_response(url, &response)?;
+        }
+        Ok(response)
+    }
+
+    /// Removes the cached responses to queries for `q`, for example a
+    /// Stellar address or an account id, from all federation servers.
+    pub async fn invalidate(&self, q: &str) {
+        let q = self.normalize_address(q).unwrap_or_else(|| q.to_string());
+        let prefix = query_key_prefix(&q);
+        self.cache.invalidate(&prefix).await;
+        self.cache.invalidate(&validators_key(&prefix)).await;
+        self.cache.invalidate(&negative_key(&prefix)).await;
+        self.cache.invalidate(&stale_key(&prefix)).await;
+    }
+
+    /// Switches the client to offline mode, or back online.
+    ///
+    /// Offline clients never send requests: lookups are served from the
+    /// cache, including stale entries when they are allowed, and fail
+    /// with [`Error::Offline`] when they are not cached. The mode is
+    /// shared by the clones of the client.
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if the client is in offline mode.
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::Relaxed)
+    }
+
+    /// Returns the cache usage counters.
+    ///
+    /// The counters are shared by the clones of the client.
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits(),
+            misses: self.counters.misses(),
+            evictions: self.cache.evictions(),
+        }
+    }
+
+    /// Returns the federation servers and responses in the cache, with
+    /// their expiration time.
+    ///
+    /// Only caches that support inspection return entries, see
+    /// [`FederationCache::entries`].
+    pub async fn cache_entries(&self) -> Vec<CachedLookup> {
+        self.cache
+            .entries()
+            .await
+            .iter()
+            .filter_map(CachedLookup::from_entry)
+            .collect()
+    }
+
+    /// Removes all cached responses and `stellar.toml` files.
+    pub async fn clear_cache(&self) {
+        self.cache.invalidate("federation/").await;
+        self.cache.invalidate("stellar.toml/").await;
+        self.cache.invalidate("validators/").await;
+        self.cache.invalidate("negative/").await;
+        self.cache.invalidate("stale/").await;
+    }
+
+    /// Sends a `GET` request to `uri` for the lookup cached with `key`,
+    /// revalidating the cached response if `revalidate` is set.
+    ///
+    /// When requests are coalesced, concurrent calls for the same `key`
+    /// share a single request.
+    async fn get_coalesced(
+        &self,
+        key: &str,
+        uri: hyper::Uri,
+        limit: usize,
+        options: &ResolveOptions,
+        revalidate: bool,
+    ) -> Result<Response<Bytes>, Error> {
+        let get = || async move {
+            if revalidate {
+                self.get_cached(key, uri, limit, options).await
+            } else {
+                self.get(uri, limit, &HeaderMap::new(), options).await
+            }
+        };
+        match &self.inflight {
+            Some(inflight) => unshare(inflight.run(key, || async { share(get().await) }).await),
+            None => get().await,
+        }
+    }
+
+    /// Sends a `GET` request to `uri`, like [`get`](FederationClient::get).
+    ///
+    /// When the client honors the HTTP caching headers, the request is
+    /// conditional on the validators stored for the cache entry `key`,
+    /// and a `304 Not Modified` response is replaced by the stored one.
+    async fn get_cached(
+        &self,
+        key: &str,
+        uri: hyper::Uri,
+        limit: usize,
+        options: &ResolveOptions,
+    ) -> Result<Response<Bytes>, Error> {
+        let stored = if self.http_caching {
+            self.cache
+                .get(&validators_key(key))
+                .await
+                .and_then(|value| StoredResponse::decode(&value))
+        } else {
+            None
+        };
+        let headers = stored
+            .as_ref()
+            .map(StoredResponse::conditional_headers)
+            .unwrap_or_default();
+        let response = self.get(uri, limit, &headers, options).await?;
+        match stored {
+            Some(stored) if response.status() == StatusCode::NOT_MODIFIED => {
+                Ok(stored.into_response(response.into_parts().0))
+            }
+            _ => Ok(response),
+        }
+    }
+
+    /// Caches the successful `response` under `key` for `ttl`.
+    ///
+    /// When the client honors the HTTP caching headers, the
+    /// `Cache-Control` header of the response overrides `ttl` and the
+    /// response validators are stored to revalidate it later.
+    async fn store(&self, key: &str, response: &Response<Bytes>, ttl: Option<Duration>) {
+        let ttl = if self.http_caching {
+            match freshness(response.headers()) {
+                Freshness::NoStore => return,
+                Freshness::MaxAge(max_age) => Some(max_age),
+                Freshness::Unspecified => ttl,
+            }
+        } else {
+            ttl
+        };
+        if let Some(ttl) = ttl.filter(|ttl| *ttl > Duration::from_secs(0)) {
+            self.cache.set(key, response.body().clone(), ttl).await;
+        }
+        if let (Some(ttl), Some(max_staleness)) = (ttl, self.max_staleness) {
+            self.cache
+                .set(
+                    &stale_key(key),
+                    response.body().clone(),
+                    ttl + max_staleness,
+                )
+                .await;
+        }
+        if self.http_caching {
+            if let Some(stored) = StoredResponse::from_response(response) {
+                let ttl = ttl.unwrap_or_default().max(VALIDATORS_TTL);
+                self.cache
+                    .set(&validators_key(key), stored.encode(), ttl)
+                    .await;
+            }
+        }
+    }
+
+    /// Fetches the `stellar.toml` file of `domain` in the background,
+    /// refreshing its cache entry.
+    fn refresh_stellar_toml(&self, domain: String, options: &ResolveOptions) {
+        if self.is_offline() {
+            return;
+        }
+        let client = self.clone();
+        let options = options.clone();
+        self.executor.spawn(Box::pin(async move {
+            let _ = client.download_stellar_toml(&domain, &options).await;
+        }));
+    }
+
+    /// Fetches again the `stellar.toml` files every `interval` in the
+    /// background, so that changes to the federation server of cached
+    /// domains are picked up before the cache entries expire.
+    ///
+    /// Only the files in the cache are refreshed, which requires a
+    /// cache that supports inspection, see [`FederationCache::entries`].
+    /// The task runs until [`RefreshHandle::stop`] is called.
+    pub fn spawn_stellar_toml_refresh(&self, interval: Duration) -> RefreshHandle {
+        let handle = RefreshHandle {
+            stopped: Arc::new(AtomicBool::new(false)),
+        };
+        let stopped = handle.stopped.clone();
+        let client = self.clone();
+        self.executor.spawn(Box::pin(async move {
+            loop {
+                client.timer.sleep(interval).await;
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                client.refresh_cached_stellar_tomls().await;
+            }
+        }));
+        handle
+    }
+
+    /// Fetches again the `stellar.toml` files in the cache, concurrently.
+    async fn refresh_cached_stellar_tomls(&self) {
+        let options = ResolveOptions::default();
+        let domains: Vec<String> = self
+            .cache
+            .entries()
+            .await
+            .into_iter()
+            .filter_map(|entry| entry.key.strip_prefix("stellar.toml/").map(str::to_string))
+            .collect();
+        let futures = domains
+            .iter()
+            .map(|domain| self.download_stellar_toml(domain, &options));
+        future::join_all(futures).await;
+    }
+
+    /// Sends the federation request `url` in the background, refreshing
+    /// its cache entry.
+    fn refresh_url(&self, url: &Url, options: &ResolveOptions) {
+        if self.is_offline() {
+            return;
+        }
+        let client = self.clone();
+        let url = url.clone();
+        let options = options.clone().cache_policy(CachePolicy::Refresh);
+        self.executor.spawn(Box::pin(async move {
+            let _ = client.resolve_url(&url, &options).await;
+        }));
+    }
+
+    /// Returns the stale entry of the lookup cached with `key`, parsed
+    /// with `parse`, if stale entries are served.
+    ///
+    /// The entry is counted as a hit.
+    async fn stale<T, F>(&self, key: &str, parse: F) -> Option<T>
+    where
+        F: FnOnce(&[u8]) -> Option<T>,
+    {
+        self.max_staleness?;
+        let value = self.cache.get(&stale_key(key)).await?;
+        let result = parse(&value)?;
+        self.counters.hit();
+        Some(result)
+    }
+
+    /// Returns the cached failure of the lookup cached with `key`, if
+    /// failures are cached.
+    async fn cached_failure(&self, key: &str) -> Option<Failure> {
+        self.negative_cache_ttl?;
+        let value = self.cache.get(&negative_key(key)).await?;
+        Failure::decode(&value)
+    }
+
+    /// Caches the `failure` of the lookup cached with `key`, if failures
+    /// are cached.
+    async fn store_failure(&self, key: &str, failure: Failure) {
+        if let Some(ttl) = self.negative_cache_ttl {
+            self.cache
+                .set(&negative_key(key), failure.encode(), ttl)
+                .await;
+        }
+    }
+
+    /// Runs `future` within the time budget of `options`, if any.
+    async fn with_timeout<F, T>(&self, options: &ResolveOptions, future: F) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        match options.timeout_duration() {
+            Some(duration) => timeout(self.timer.as_ref(), duration, future).await,
+            None => future.await,
+        }
+    }
+
+    /// Sends a `GET` request to `uri` with the additional `headers` and
+    /// reads the whole response body, following redirects according to
+    /// the client redirect policy.
+    ///
+    /// Bodies larger than `limit` bytes are rejected.
+    async fn get(
+        &self,
+        uri: hyper::Uri,
+        limit: usize,
+        headers: &HeaderMap,
+        options: &ResolveOptions,
+    ) -> Result<Response<Bytes>, Error> {
+        let retry_policy = options.retry_policy_or(self.retry_policy.as_ref());
+        let mut uri = uri;
+        let mut redirects = 0;
+        loop {
+            let response = self
+                .get_with_retry(uri.clone(), limit, headers, retry_policy)
+                .await?;
+            match self.redirect_policy.next(&uri, &response, redirects)? {
+                Some(next) => {
+                    uri = next;
+                    redirects += 1;
+                }
+                None => return Ok(response),
+            }
+        }
+    }
+
+    /// Sends a `GET` request to `uri` and reads the whole response body,
+    /// retrying according to `retry_policy`.
+    async fn get_with_retry(
+        &self,
+        uri: hyper::Uri,
+        limit: usize,
+        headers: &HeaderMap,
+        retry_policy: Option<&RetryPolicy>,
+    ) -> Result<Response<Bytes>, Error> {
+        let mut attempt = 0;
+        loop {
+            let result = self.send(uri.clone(), limit, headers).await;
+            match retry_policy {
+                Some(policy) if policy.should_retry(attempt, &result) => {
+                    self.timer.sleep(policy.delay(attempt)).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    /// Sends a `GET` request to `uri` with the additional `headers`,
+    /// running the middlewares around it.
+    ///
+    /// The request is delayed as needed to respect the rate limit of
+    /// the destination domain.
+    async fn send(
+        &self,
+        uri: hyper::Uri,
+        limit: usize,
+        headers: &HeaderMap,
+    ) -> Result<Response<Bytes>, Error> {
+        let mut request = Request::get(uri).body(())?;
+        request.headers_mut().extend(headers.clone());
+        let request = self.prepare(request).await?;
+        let response = self.transport.send(request, limit).await?;
+        self.finish(response).await
+    }
+
+    /// Adds the credentials of the client to `request`, then runs the
+    /// request hooks of the middlewares and signs it.
+    ///
+    /// The request is delayed as needed to respect the rate limit of
+    /// the destination domain.
+    async fn prepare(&self, mut request: Request<()>) -> Result<Request<()>, Error> {
+        if self.is_offline() {
+            return Err(Error::Offline);
+        }
+        if let Some(host) = request.uri().host() {
+            let delay = self.rate_limiter.reserve(host, self.clock.now());
+            if delay > Duration::from_secs(0) {
+                self.timer.sleep(delay).await;
+            }
+        }
+        self.credentials.apply(&mut request);
+        #[cfg(feature = "sep10")]
+        self.web_auth.apply(&mut request, self.clock.system_time());
+        for middleware in &self.middlewares {
+            request = middleware.on_request(request);
+        }
+        if let Some(signer) = &self.signer {
+            sign_request(signer.as_ref(), &mut request, self.clock.system_time())?;
+        }
+        Ok(request)
+    }
+
+    /// Runs the response hooks of the middlewares on `response`.
+    async fn finish(&self, mut response: Response<Bytes>) -> Result<Response<Bytes>, Error> {
+        for middleware in self.middlewares.iter().rev() {
+            response = middleware.on_response(response).await?;
+        }
+        Ok(response)
+    }
+}
+
+/// Handle of the background task started by
+/// [`FederationClient::spawn_stellar_toml_refresh`].
+#[derive(Debug, Clone)]
+pub struct RefreshHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl RefreshHandle {
+    /// Stops the task, the refresh in progress, if any, completes.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+impl FederationClientBuilder {
+    /// Sets the timeout for establishing a connection to the server.
+    pub fn connect_timeout(mut self, timeout: Duration) -> FederationClientBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for each request, from sending the request
+    /// until the whole response body has been read.
+    pub fn timeout(mut self, timeout: Duration) -> FederationClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of idle connections kept in the pool for each host.
+    ///
+    /// Defaults to no limit.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> FederationClientBuilder {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Sets how long idle connections are kept in the pool.
+    ///
+    /// Defaults to 90 seconds.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> FederationClientBuilder {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables TCP keep-alive probes, sent after connections have been
+    /// idle for `interval`.
+    ///
+    /// TCP keep-alive is disabled by default.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> FederationClientBuilder {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Sets the resolver used to look up host names.
+    ///
+    /// Defaults to the system resolver.
+    pub fn dns_resolver<R>(mut self, resolver: R) -> FederationClientBuilder
+    where
+        R: DnsResolver + 'static,
+    {
+        self.dns_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Sets the policy used to retry failed requests.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> FederationClientBuilder {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Sets the policy used to follow redirects.
+    pub fn redirect_policy(mut self, policy: RedirectPolicy) -> FederationClientBuilder {
+        self.redirect_policy = policy;
+        self
+    }
+
+    /// Sets the maximum size of federation responses, in bytes.
+    ///
+    /// Defaults to [`DEFAULT_MAX_RESPONSE_SIZE`].
+    pub fn max_response_size(mut self, size: usize) -> FederationClientBuilder {
+        self.max_response_size = Some(size);
+        self
+    }
+
+    /// Caches `stellar.toml` files for `ttl`, so that resolving multiple
+    /// addresses of the same domain fetches the file once.
+    ///
+    /// The cache is shared by the clones of the client. By default
+    /// `stellar.toml` files are not cached.
+    pub fn stellar_toml_cache_ttl(mut self, ttl: Duration) -> FederationClientBuilder {
+        self.stellar_toml_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Caches federation responses for `ttl`.
+    ///
+    /// Responses are cached by federation server, query type and query,
+    /// and shared by the clones of the client. Use
+    /// [`ResolveOptions`] to change the ttl or bypass the cache for a
+    /// single call, and [`FederationClient::invalidate`] to remove
+    /// cached responses. By default responses are not cached.
+    pub fn response_cache_ttl(mut self, ttl: Duration) -> FederationClientBuilder {
+        self.response_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Honors the HTTP caching headers of `stellar.toml` files and
+    /// federation responses.
+    ///
+    /// The `max-age` of the `Cache-Control` header overrides the cache
+    /// ttl, and responses with `no-store` are not cached. Responses with
+    /// an `ETag` or `Last-Modified` header are revalidated with a
+    /// conditional request once expired, a `304 Not Modified` response
+    /// reusing the cached body. Disabled by default.
+    pub fn http_caching(mut self, enable: bool) -> FederationClientBuilder {
+        self.http_caching = enable;
+        self
+    }
+
+    /// Caches failed lookups for `ttl`.
+    ///
+    /// Failures to fetch a `stellar.toml` file, `stellar.toml` files
+    /// without a federation server, and `404 Not Found` federation
+    /// responses are cached, so that repeated lookups of a mistyped
+    /// address fail without sending requests. Failures that cannot be
+    /// cached as is, like connection errors, are returned as
+    /// [`Error::CachedFailure`]. Prefer a short ttl, by default failures
+    /// are not cached.
+    pub fn negative_cache_ttl(mut self, ttl: Duration) -> FederationClientBuilder {
+        self.negative_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Serves expired cache entries for up to `max_staleness` while they
+    /// are refreshed.
+    ///
+    /// A lookup of an expired `stellar.toml` file or response returns it
+    /// immediately and refreshes it in the background with the client
+    /// [`Executor`], so that the next lookups get the fresh entry.
+    /// Entries expired for longer than `max_staleness` are not served.
+    /// Disabled by default.
+    pub fn stale_while_revalidate(mut self, max_staleness: Duration) -> FederationClientBuilder {
+        self.max_staleness = Some(max_staleness);
+        self
+    }
+
+    /// Coalesces concurrent identical lookups.
+    ///
+    /// Lookups started while an identical lookup is in flight wait for
+    /// its response instead of sending their own request. The response
+    /// is shared by all the lookups, and so is the error of a failed
+    /// request. Every lookup gets the same error: errors wrapping an
+    /// error that can't be cloned, like connection errors, are returned
+    /// as [`Error::Coalesced`], the others as is. Disabled by default.
+    pub fn coalesce_requests(mut self, enable: bool) -> FederationClientBuilder {
+        self.coalesce_requests = enable;
+        self
+    }
+
+    /// Sends the id lookups of [`FederationClient::resolve_muxed_account`]
+    /// with the muxed account id starting with `M`, instead of its base
+    /// account id. Only enable it for federation servers supporting
+    /// muxed accounts, the [`server`](crate::server) module of this
+    /// crate looks them up by their base account id. Disabled by
+    /// default.
+    pub fn muxed_id_lookups(mut self, enable: bool) -> FederationClientBuilder {
+        self.muxed_id_lookups = enable;
+        self
+    }
+
+    /// Checks that federation responses match the query, failing with
+    /// [`Error::ResponseMismatch`] otherwise.
+    ///
+    /// The `stellar_address` of the responses to name lookups must be the
+    /// queried address, and the `account_id` of the responses to id
+    /// lookups the queried account, or its base account for muxed
+    /// accounts. Enabled by default.
+    pub fn verify_responses(mut self, enable: bool) -> FederationClientBuilder {
+        self.verify_responses = Some(enable);
+        self
+    }
+
+    /// Sets the Horizon server queried for the home domain of accounts
+    /// by [`FederationClient::reverse_lookup`], defaulting to
+    /// [`DEFAULT_HORIZON_URL`](crate::DEFAULT_HORIZON_URL).
+    #[cfg(feature = "horizon")]
+    pub fn horizon_url(mut self, url: Url) -> FederationClientBuilder {
+        self.horizon_url = Some(url);
+        self
+    }
+
+    /// Starts the client offline, see [`FederationClient::set_offline`].
+    pub fn offline(mut self, offline: bool) -> FederationClientBuilder {
+        self.offline = offline;
+        self
+    }
+
+    /// Sets the cache used to store `stellar.toml` files and federation
+    /// responses.
+    ///
+    /// Defaults to a [`MemoryCache`]. Caching is enabled separately with
+    /// [`stellar_toml_cache_ttl`](FederationClientBuilder::stellar_toml_cache_ttl)
+    /// and [`response_cache_ttl`](FederationClientBuilder::response_cache_ttl).
+    pub fn cache<C>(mut self, cache: C) -> FederationClientBuilder
+    where
+        C: FederationCache + 'static,
+    {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Fetches `stellar.toml` files over plain http.
+    ///
+    /// **This is insecure and must only be used for local development**,
+    /// for example to test against a federation server running on
+    /// `http://localhost:8000`. It also allows Stellar addresses to
+    /// include a port, as in `alice*localhost:8000`.
+    pub fn allow_http(mut self, allow: bool) -> FederationClientBuilder {
+        self.allow_http = allow;
+        self
+    }
+
+    /// Adds a proxy used to send requests.
+    ///
+    /// When multiple proxies are configured, the first one that
+    /// intercepts the request is used. Use [`Proxy::from_env`] to
+    /// honor the `HTTPS_PROXY` and `HTTP_PROXY` environment variables.
+    ///
+    /// ```rust
+    /// use stellar_federation::{FederationClient, Proxy};
+    ///
+    /// # fn run() -> std::result::Result<(), stellar_federation::Error> {
+    /// let mut builder = FederationClient::builder();
+    /// for proxy in Proxy::from_env()? {
+    ///     builder = builder.proxy(proxy);
+    /// }
+    /// let client = builder.build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn proxy(mut self, proxy: Proxy) -> FederationClientBuilder {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Adds a trusted root certificate.
+    ///
+    /// The certificate is trusted in addition to the platform trust store.
+    pub fn add_root_certificate(mut self, certificate: Certificate) -> FederationClientBuilder {
+        self.tls.root_certificates.push(certificate);
+        self
+    }
+
+    /// Sets the client identity presented to servers that require client
+    /// certificates.
+    ///
+    /// The identity is used for both the `stellar.toml` and the
+    /// federation requests.
+    pub fn identity(mut self, identity: Identity) -> FederationClientBuilder {
+        self.tls.identity = Some(identity);
+        self
+    }
+
+    /// Sets the minimum tls version accepted when connecting to servers.
+    ///
+    /// rustls only supports TLS 1.2 and 1.3, so with the `rustls`
+    /// feature TLS 1.2 is the minimum version regardless of this setting.
+    pub fn min_tls_version(mut self, version: TlsVersion) -> FederationClientBuilder {
+        self.tls.min_version = Some(version);
+        self
+    }
+
+    /// Restricts the tls cipher suites to `cipher_suites`, identified by
+    /// their IANA names, for example `TLS13_AES_256_GCM_SHA384` or
+    /// `TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384`.
+    ///
+    /// Only supported with the `rustls` feature, building the client
+    /// fails with native-tls. Unknown cipher suites also make building
+    /// the client fail.
+    pub fn tls_cipher_suites<I, S>(mut self, cipher_suites: I) -> FederationClientBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tls.cipher_suites = Some(cipher_suites.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Pins a certificate for `domain`.
+    ///
+    /// `sha256` is the SHA-256 hash of the DER encoded certificate.
+    /// Once a domain has pins, connections to it fail with
+    /// [`Error::CertificatePinMismatch`] unless the server presents a
+    /// certificate matching one of them. Pin more than one certificate
+    /// to allow for rotation.
+    ///
+    /// With native-tls only the leaf certificate is checked, with rustls
+    /// any certificate in the presented chain can match.
+    ///
+    /// Internationalized domains are converted to ASCII, like the
+    /// domains of Stellar addresses.
+    pub fn pin_certificate(self, domain: &str, sha256: [u8; 32]) -> FederationClientBuilder {
+        self.pin(domain, CertificatePin::Certificate(sha256))
+    }
+
+    /// Pins a public key for `domain`.
+    ///
+    /// `sha256` is the SHA-256 hash of the DER encoded
+    /// SubjectPublicKeyInfo of the certificate, as in HTTP public key
+    /// pinning. Unlike certificate pins, public key pins keep matching
+    /// when the certificate is renewed with the same key. Public key pins
+    /// and certificate pins of a domain can be mixed, a connection
+    /// succeeds if any of them matches.
+    pub fn pin_public_key(self, domain: &str, sha256: [u8; 32]) -> FederationClientBuilder {
+        self.pin(domain, CertificatePin::PublicKey(sha256))
+    }
+
+    fn pin(mut self, domain: &str, pin: CertificatePin) -> FederationClientBuilder {
+        let domain = domain_to_ascii(domain).unwrap_or_else(|| domain.to_lowercase());
+        self.certificate_pins.entry(domain).or_default().push(pin);
+        self
+    }
+
+    /// Sends `token` as a bearer token in the `Authorization` header of
+    /// the requests to `domain`.
+    ///
+    /// The token is only sent to hosts equal to `domain`, not to its
+    /// subdomains nor to other hosts after redirects.
+    pub fn bearer_auth(mut self, domain: &str, token: &str) -> FederationClientBuilder {
+        self.credentials.push((
+            domain.to_string(),
+            AUTHORIZATION.to_string(),
+            format!("Bearer {}", token),
+        ));
+        self
+    }
+
+    /// Sends `key` in the `header` header of the requests to `domain`,
+    /// for example `X-Api-Key`.
+    ///
+    /// Like [`bearer_auth`](FederationClientBuilder::bearer_auth), the key
+    /// is only sent to hosts equal to `domain`. Building the client fails
+    /// if `header` or `key` are not valid header names and values.
+    pub fn api_key(mut self, domain: &str, header: &str, key: &str) -> FederationClientBuilder {
+        self.credentials
+            .push((domain.to_string(), header.to_string(), key.to_string()));
+        self
+    }
+
+    /// Adds a middleware that runs around every request.
+    ///
+    /// Request hooks run in the order the middlewares are added,
+    /// response hooks in reverse order.
+    pub fn middleware<M>(mut self, middleware: M) -> FederationClientBuilder
+    where
+        M: Middleware + 'static,
+    {
+        self.middlewares.push(Arc::new(middleware));
+        self
+    }
+
+    /// Sets the signer used to sign every request.
+    pub fn request_signer<S>(mut self, signer: S) -> FederationClientBuilder
+    where
+        S: RequestSigner + 'static,
+    {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Authenticates the lookups sent to the federation server of
+    /// `domain` with a SEP-0010 web authentication token of `account`.
+    ///
+    /// The token is requested from the `WEB_AUTH_ENDPOINT` of the
+    /// `stellar.toml` file of `domain` before the first lookup sent to
+    /// its federation server, with the challenge signed by `signer`, and
+    /// renewed when it expires. Like
+    /// [`bearer_auth`](FederationClientBuilder::bearer_auth), the token
+    /// is only sent to the host of the federation server.
+    ///
+    /// The `stellar.toml` files of the domains are fetched once, by the
+    /// first lookup needing a token. Lookups fail if a file can't be
+    /// fetched or doesn't declare a web authentication endpoint, and
+    /// token expiration follows the [`Clock`] of the client.
+    #[cfg(feature = "sep10")]
+    pub fn web_auth<S>(
+        mut self,
+        domain: &str,
+        account: PublicKey,
+        signer: S,
+    ) -> FederationClientBuilder
+    where
+        S: ChallengeSigner + 'static,
+    {
+        let domain = domain_to_ascii(domain).unwrap_or_else(|| domain.to_string());
+        self.web_auth.push((
+            domain,
+            WebAuthAccount {
+                account,
+                signer: Arc::new(signer),
+            },
+        ));
+        self
+    }
+
+    /// Limits the requests sent to each domain.
+    ///
+    /// Every domain has its own token bucket, shared by the clones of
+    /// the client. Retries and redirects count against the limit.
+    pub fn rate_limit(mut self, limit: RateLimit) -> FederationClientBuilder {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Limits the requests sent to `domain`, overriding the limit set
+    /// with [`rate_limit`](FederationClientBuilder::rate_limit).
+    pub fn domain_rate_limit(mut self, domain: &str, limit: RateLimit) -> FederationClientBuilder {
+        self.domain_rate_limits.insert(domain.to_lowercase(), limit);
+        self
+    }
+
+    /// Limits the concurrent lookups of batch operations to each domain.
+    ///
+    /// The limit applies to the domain of the Stellar addresses resolved
+    /// with [`FederationClient::resolve_addresses`], and to the host of
+    /// the federation server of reverse lookups, on top of the overall
+    /// `max_concurrency` of the batch.
+    pub fn max_concurrency_per_domain(mut self, max: usize) -> FederationClientBuilder {
+        self.max_concurrency_per_domain = Some(max.max(1));
+        self
+    }
+
+    /// Limits the concurrent lookups of batch operations to `domain`,
+    /// overriding the limit set with
+    /// [`max_concurrency_per_domain`](FederationClientBuilder::max_concurrency_per_domain).
+    pub fn domain_max_concurrency(mut self, domain: &str, max: usize) -> FederationClientBuilder {
+        self.domain_max_concurrency
+            .insert(domain.to_lowercase(), max.max(1));
+        self
+    }
+
+    /// Sets the transport used to send http requests.
+    ///
+    /// The connect timeout, timeout, connection pool, TCP keep-alive,
+    /// dns resolver, proxy, and tls settings configure the default
+    /// transport and are ignored when a custom transport is used.
+    pub fn transport<T>(mut self, transport: T) -> FederationClientBuilder
+    where
+        T: Transport + 'static,
+    {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Sets the timer used to wait between retries.
+    ///
+    /// The default timer requires a tokio runtime. Use a custom
+    /// [`Transport`], [`Timer`] and [`Executor`] to run the client on a
+    /// different async runtime, for example async-std or smol.
+    pub fn timer<T>(mut self, timer: T) -> FederationClientBuilder
+    where
+        T: Timer + 'static,
+    {
+        self.timer = Some(Arc::new(timer));
+        self
+    }
+
+    /// Sets the clock used to expire cache entries and enforce rate
+    /// limits.
+    ///
+    /// The clock is used by the default cache, a custom [`MemoryCache`]
+    /// must be created with [`MemoryCache::with_clock`] to use it.
+    pub fn clock<C>(mut self, clock: C) -> FederationClientBuilder
+    where
+        C: Clock + 'static,
+    {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Sets the executor used to run background tasks.
+    ///
+    /// The default executor requires a tokio runtime.
+    pub fn executor<E>(mut self, executor: E) -> FederationClientBuilder
+    where
+        E: Executor + 'static,
+    {
+        self.executor = Some(Arc::new(executor));
+        self
+    }
+
+    /// Creates the [`FederationClient`].
+    ///
+    /// Fails if the tls configuration is invalid.
+    pub fn build(self) -> Result<FederationClient, Error> {
+        let mut credentials = Credentials::default();
+        for (domain, header, value) in &self.credentials {
+            credentials.insert(domain, header, value)?;
+        }
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                let mut http = HttpConnector::new_with_resolver(Dns::new(self.dns_resolver));
+                http.enforce_http(false);
+                http.set_connect_timeout(self.connect_timeout);
+                http.set_keepalive(self.tcp_keepalive);
+                let https = new_https_connector(
+                    ProxyConnector::new(http, self.proxies),
+                    &self.tls,
+                    self.certificate_pins,
+                )?;
+                let mut client = Client::builder();
+                if let Some(max) = self.pool_max_idle_per_host {
+                    client.pool_max_idle_per_host(max);
+                }
+                if let Some(timeout) = self.pool_idle_timeout {
+                    client.pool_idle_timeout(timeout);
+                }
+                let inner = client.build::<_, Body>(https);
+                Arc::new(HyperTransport::new(inner, self.timeout))
+            }
+        };
+        #[cfg(feature = "sep10")]
+        let web_auth = {
+            let mut web_auth = WebAuthSessions::default();
+            for (domain, account) in self.web_auth {
+                web_auth.insert_account(&domain, account);
+            }
+            Arc::new(web_auth)
+        };
+        let clock = self.clock.unwrap_or_else(|| Arc::new(SystemClock));
+        let cache = match self.cache {
+            Some(cache) => cache,
+            None => Arc::new(MemoryCache::with_shared_clock(clock.clone())),
+        };
+        Ok(FederationClient {
+            transport,
+            timer: self.timer.unwrap_or_else(|| Arc::new(TokioTimer)),
+            clock,
+            executor: self.executor.unwrap_or_else(|| Arc::new(TokioExecutor)),
+            middlewares: self.middlewares,
+            credentials: Arc::new(credentials),
+            signer: self.signer,
+            #[cfg(feature = "sep10")]
+            web_auth,
+            rate_limiter: Arc::new(RateLimiter::new(self.rate_limit, self.domain_rate_limits)),
+            concurrency_limits: Arc::new(ConcurrencyLimits::new(
+                self.max_concurrency_per_domain,
+                self.domain_max_concurrency,
+            )),
+            retry_policy: self.retry_policy,
+            redirect_policy: self.redirect_policy,
+            max_response_size: self.max_response_size.unwrap_or(DEFAULT_MAX_RESPONSE_SIZE),
+            allow_http: self.allow_http,
+            muxed_id_lookups: self.muxed_id_lookups,
+            verify_responses: self.verify_responses.unwrap_or(true),
+            #[cfg(feature = "horizon")]
+            horizon_url: match self.horizon_url {
+                Some(horizon_url) => horizon_url,
+                None => DEFAULT_HORIZON_URL.parse()?,
+            },
+            cache,
+            stellar_toml_cache_ttl: self.stellar_toml_cache_ttl,
+            response_cache_ttl: self.response_cache_ttl,
+            http_caching: self.http_caching,
+            negative_cache_ttl: self.negative_cache_ttl,
+            max_staleness: self.max_staleness,
+            counters: Arc::new(CacheCounters::default()),
+            inflight: if self.coalesce_requests {
+                Some(Arc::new(Singleflight::new()))
+            } else {
+                None
+            },
+            offline: Arc::new(AtomicBool::new(self.offline)),
+        })
+    }
+}
+
+impl Default for FederationClient {
+    fn default() -> FederationClient {
+        FederationClient::new()
+    }
+}
+
+/// Returns the url of the federation server declared in `toml`.
+fn federation_server_url(toml: &StellarToml) -> Result<Url, Error> {
+    match &toml.federation_server {
+        Some(federation_server) => Ok(federation_server.to_string().parse()?),
+        None => Err(Error::MissingFederationServer),
+    }
+}
+
+/// Checks that `response` matches the name or id lookup of `url`.
+///
+/// Addresses are compared with their domain converted to ASCII and
+/// ignoring case, transaction id and forward lookups are not checked.
+fn verify_response(url: &Url, response: &FederationResponse) -> Result<(), Error> {
+    let mut kind = None;
+    let mut q = None;
+    for (name, value) in url.query_pairs() {
+        match name.as_ref() {
+            "type" => kind = Some(value.into_owned()),
+            "q" => q = Some(value.into_owned()),
+            _ => {}
+        }
+    }
+    let q = match q {
+        Some(q) => q,
+        None => return Ok(()),
+    };
+    match kind.as_deref() {
+        Some("name") => {
+            let expected = normalize_stellar_address(&q).unwrap_or_else(|| q.clone());
+            let actual = normalize_stellar_address(&response.stellar_address)
+                .unwrap_or_else(|| response.stellar_address.clone());
+            if expected.eq_ignore_ascii_case(&actual) {
+                Ok(())
+            } else {
+                Err(Error::ResponseMismatch {
+                    expected: q,
+                    actual: response.stellar_address.clone(),
+                })
+            }
+        }
+        Some("id") => {
+            let expected = match PublicKey::from_account_id(&q) {
+                Ok(account_id) => account_id,
+                Err(_) => match MuxedEd25519PublicKey::from_account_id(&q) {
+                    Ok(muxed) => muxed.public_key().clone(),
+                    Err(_) => return Ok(()),
+                },
+            };
+            if response.account_id == expected {
+                Ok(())
+            } else {
+                Err(Error::ResponseMismatch {
+                    expected: q,
+                    actual: response.account_id.account_id(),
+                })
+            }
+        }
+        _ => Ok(()),
+    }
+}
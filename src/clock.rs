@@ -0,0 +1,93 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Source of the current time, used to expire cache entries and to
+/// enforce rate limits.
+///
+/// The default clock is [`SystemClock`]. Use [`ManualClock`] in tests to
+/// advance time deterministically, together with a custom
+/// [`Timer`](crate::Timer) for retry delays.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Returns the current wall-clock time, used to check the expiration
+    /// of web authentication tokens and to sign requests.
+    ///
+    /// Defaults to the system time.
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock returning the system monotonic time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when advanced.
+///
+/// Clones share the same time.
+///
+/// ```rust
+/// use stellar_federation::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// let system_start = clock.system_time();
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(Duration::from_secs(60), clock.now() - start);
+/// assert_eq!(
+///     Duration::from_secs(60),
+///     clock.system_time().duration_since(system_start).unwrap()
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+    start: Instant,
+    system_start: SystemTime,
+}
+
+impl ManualClock {
+    /// Creates a clock starting at the current time.
+    pub fn new() -> ManualClock {
+        let start = Instant::now();
+        ManualClock {
+            now: Arc::new(Mutex::new(start)),
+            start,
+            system_start: SystemTime::now(),
+        }
+    }
+
+    /// Moves the clock forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|err| err.into_inner());
+        *now += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the system time the clock was created at, moved forward
+    /// like [`ManualClock::now`].
+    fn system_time(&self) -> SystemTime {
+        self.system_start + (self.now() - self.start)
+    }
+}
@@ -0,0 +1,132 @@
+use crate::pinning::{CertificatePins, PinningConnector};
+use crate::proxy::ProxyConnector;
+use crate::tls::{IdentityKind, TlsConfig, TlsVersion};
+use crate::Error;
+
+/// The tls connector used by the federation client.
+#[cfg(feature = "native-tls")]
+pub(crate) type TlsConnector = hyper_tls::HttpsConnector<ProxyConnector>;
+
+/// The tls connector used by the federation client.
+#[cfg(feature = "rustls")]
+pub(crate) type TlsConnector = hyper_rustls::HttpsConnector<ProxyConnector>;
+
+/// The https connector used by the federation client.
+pub(crate) type HttpsConnector = PinningConnector;
+
+/// Creates a new https connector wrapping `http`.
+pub(crate) fn new_https_connector(
+    http: ProxyConnector,
+    tls: &TlsConfig,
+    pins: CertificatePins,
+) -> Result<HttpsConnector, Error> {
+    let tls = new_tls_connector(http, tls)?;
+    Ok(PinningConnector::new(tls, pins))
+}
+
+/// Creates a new tls connector backed by native-tls, wrapping `http`.
+#[cfg(feature = "native-tls")]
+fn new_tls_connector(http: ProxyConnector, config: &TlsConfig) -> Result<TlsConnector, Error> {
+    let mut builder = native_tls::TlsConnector::builder();
+    for certificate in &config.root_certificates {
+        let certificate = native_tls::Certificate::from_der(certificate.to_der())
+            .map_err(|_| Error::InvalidCertificate)?;
+        builder.add_root_certificate(certificate);
+    }
+    if let Some(version) = config.min_version {
+        builder.min_protocol_version(Some(match version {
+            TlsVersion::Tls10 => native_tls::Protocol::Tlsv10,
+            TlsVersion::Tls11 => native_tls::Protocol::Tlsv11,
+            TlsVersion::Tls12 => native_tls::Protocol::Tlsv12,
+            TlsVersion::Tls13 => native_tls::Protocol::Tlsv13,
+        }));
+    }
+    if let Some(identity) = &config.identity {
+        let identity = match &identity.kind {
+            IdentityKind::Pkcs12 { der, password } => {
+                native_tls::Identity::from_pkcs12(der, password)
+            }
+            IdentityKind::Pem {
+                certificate_chain,
+                key,
+                ..
+            } => native_tls::Identity::from_pkcs8(certificate_chain, key),
+        }
+        .map_err(|_| Error::InvalidIdentity)?;
+        builder.identity(identity);
+    }
+    if config.cipher_suites.is_some() {
+        return Err(Error::TlsError(
+            "cipher suites can only be configured with the rustls feature".into(),
+        ));
+    }
+    let tls = builder
+        .build()
+        .map_err(|err| Error::TlsError(Box::new(err)))?;
+    Ok(hyper_tls::HttpsConnector::from((http, tls.into())))
+}
+
+/// Creates a new tls connector backed by rustls, wrapping `http`.
+#[cfg(feature = "rustls")]
+fn new_tls_connector(http: ProxyConnector, config: &TlsConfig) -> Result<TlsConnector, Error> {
+    let mut roots = rustls::RootCertStore::empty();
+    let native_certs = rustls_native_certs::load_native_certs()?;
+    let native_certs: Vec<Vec<u8>> = native_certs.into_iter().map(|cert| cert.0).collect();
+    roots.add_parsable_certificates(&native_certs);
+    for certificate in &config.root_certificates {
+        roots
+            .add(&rustls::Certificate(certificate.to_der().to_vec()))
+            .map_err(|_| Error::InvalidCertificate)?;
+    }
+    let cipher_suites = match &config.cipher_suites {
+        None => rustls::DEFAULT_CIPHER_SUITES.to_vec(),
+        Some(names) => {
+            let mut suites = Vec::new();
+            for name in names {
+                let suite = rustls::ALL_CIPHER_SUITES
+                    .iter()
+                    .find(|suite| format!("{:?}", suite.suite()).eq_ignore_ascii_case(name))
+                    .ok_or_else(|| {
+                        Error::TlsError(format!("unsupported cipher suite {}", name).into())
+                    })?;
+                suites.push(*suite);
+            }
+            suites
+        }
+    };
+    let versions: &[&rustls::SupportedProtocolVersion] = match config.min_version {
+        Some(TlsVersion::Tls13) => &[&rustls::version::TLS13],
+        _ => rustls::DEFAULT_VERSIONS,
+    };
+    let tls = rustls::ClientConfig::builder()
+        .with_cipher_suites(&cipher_suites)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(versions)
+        .map_err(|err| Error::TlsError(Box::new(err)))?
+        .with_root_certificates(roots);
+    let tls = match config.identity.as_ref().map(|identity| &identity.kind) {
+        None => tls.with_no_client_auth(),
+        Some(IdentityKind::Pem {
+            certificates,
+            key_der,
+            ..
+        }) => {
+            let certificates = certificates
+                .iter()
+                .map(|certificate| rustls::Certificate(certificate.to_der().to_vec()))
+                .collect();
+            tls.with_client_auth_cert(certificates, rustls::PrivateKey(key_der.clone()))
+                .map_err(|_| Error::InvalidIdentity)?
+        }
+        Some(IdentityKind::Pkcs12 { .. }) => {
+            return Err(Error::TlsError(
+                "PKCS#12 identities can only be used with the native-tls feature".into(),
+            ))
+        }
+    };
+    Ok(hyper_rustls::HttpsConnectorBuilder::new()
+        .with_tls_config(tls)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(http))
+}
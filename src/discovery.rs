@@ -0,0 +1,109 @@
+use crate::{Error, StellarToml};
+use stellar_base::PublicKey;
+use url::Url;
+
+/// Endpoints of the services of a domain, as declared in its
+/// `stellar.toml` file.
+///
+/// Returned by [`FederationClient::discover`](crate::FederationClient::discover),
+/// so that the flows following a lookup, for example SEP-0010
+/// authentication or SEP-0012 KYC, don't fetch the file again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorEndpoints {
+    /// The domain, in its ASCII form.
+    pub domain: String,
+    /// The SEP-0002 federation server, `FEDERATION_SERVER`.
+    pub federation_server: Option<Url>,
+    /// The SEP-0010 web authentication endpoint, `WEB_AUTH_ENDPOINT`.
+    pub web_auth_endpoint: Option<Url>,
+    /// The SEP-0012 KYC server, `KYC_SERVER`.
+    pub kyc_server: Option<Url>,
+    /// The SEP-0006 transfer server, `TRANSFER_SERVER`.
+    pub transfer_server: Option<Url>,
+    /// The SEP-0024 transfer server, `TRANSFER_SERVER_SEP0024`.
+    pub transfer_server_sep0024: Option<Url>,
+    /// The key signing SEP-0010 challenges, `SIGNING_KEY`.
+    pub signing_key: Option<PublicKey>,
+    /// The passphrase of the network of the services,
+    /// `NETWORK_PASSPHRASE`.
+    pub network_passphrase: Option<String>,
+}
+
+impl AnchorEndpoints {
+    /// Returns the endpoints declared in `toml`, the `stellar.toml` file
+    /// of `domain`.
+    ///
+    /// Fails with [`Error::InvalidUrl`] if an endpoint is not an
+    /// absolute url.
+    pub fn from_toml(domain: &str, toml: &StellarToml) -> Result<AnchorEndpoints, Error> {
+        Ok(AnchorEndpoints {
+            domain: domain.to_string(),
+            federation_server: parse_url(&toml.federation_server)?,
+            web_auth_endpoint: parse_url(&toml.web_auth_endpoint)?,
+            kyc_server: parse_url(&toml.kyc_server)?,
+            transfer_server: parse_url(&toml.transfer_server)?,
+            transfer_server_sep0024: parse_url(&toml.transfer_server_sep0024)?,
+            signing_key: toml.signing_key.clone(),
+            network_passphrase: toml.network_passphrase.clone(),
+        })
+    }
+
+    /// Returns the federation server, failing with
+    /// [`Error::MissingFederationServer`] if the domain has none.
+    pub fn federation_server(&self) -> Result<&Url, Error> {
+        self.federation_server
+            .as_ref()
+            .ok_or(Error::MissingFederationServer)
+    }
+}
+
+fn parse_url(uri: &Option<hyper::Uri>) -> Result<Option<Url>, Error> {
+    match uri {
+        Some(uri) => Ok(Some(uri.to_string().parse()?)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let toml: StellarToml = toml::from_str(
+            r#"
+            NETWORK_PASSPHRASE="Public Global Stellar Network ; September 2015"
+            FEDERATION_SERVER="https://example.org/federation"
+            WEB_AUTH_ENDPOINT="https://example.org/auth"
+            KYC_SERVER="https://kyc.example.org"
+            SIGNING_KEY="GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+            "#,
+        )
+        .unwrap();
+        let endpoints = AnchorEndpoints::from_toml("example.org", &toml).unwrap();
+        assert_eq!(
+            "https://example.org/federation",
+            endpoints.federation_server().unwrap().as_str()
+        );
+        assert_eq!(
+            Some("https://example.org/auth"),
+            endpoints.web_auth_endpoint.as_ref().map(Url::as_str)
+        );
+        assert_eq!(
+            Some("https://kyc.example.org/"),
+            endpoints.kyc_server.as_ref().map(Url::as_str)
+        );
+        assert_eq!(None, endpoints.transfer_server);
+        assert_eq!(
+            "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP",
+            endpoints.signing_key.unwrap().account_id()
+        );
+
+        let toml: StellarToml = toml::from_str(r#"KYC_SERVER="https://kyc.example.org""#).unwrap();
+        let endpoints = AnchorEndpoints::from_toml("example.org", &toml).unwrap();
+        assert!(matches!(
+            endpoints.federation_server(),
+            Err(Error::MissingFederationServer)
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+use hyper::client::connect::dns::{GaiResolver, Name};
+use hyper::service::Service;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Future returned by [`DnsResolver::lookup`].
+pub type DnsFuture = Pin<Box<dyn Future<Output = Result<Vec<IpAddr>, io::Error>> + Send>>;
+
+/// Resolver used to look up the ip addresses of host names.
+///
+/// The default transport uses the system resolver. Implement this trait
+/// to resolve host names differently, for example to cache lookups, then
+/// configure the client with
+/// [`FederationClientBuilder::dns_resolver`](crate::FederationClientBuilder::dns_resolver).
+/// The resolver is used for both the `stellar.toml` discovery and the
+/// federation requests, as well as to resolve the proxy host.
+///
+/// [`DohResolver`](crate::DohResolver) resolves host names using
+/// DNS-over-HTTPS. With the `hickory-dns` feature enabled,
+/// `HickoryResolver` provides
+/// a resolver based on [hickory-dns](https://crates.io/crates/hickory-resolver).
+pub trait DnsResolver: fmt::Debug + Send + Sync {
+    /// Returns the ip addresses of `host`.
+    fn lookup(&self, host: &str) -> DnsFuture;
+}
+
+/// Resolver used by the http connector.
+#[derive(Debug, Clone)]
+pub(crate) enum Dns {
+    System(GaiResolver),
+    Custom(Arc<dyn DnsResolver>),
+}
+
+impl Dns {
+    pub(crate) fn new(resolver: Option<Arc<dyn DnsResolver>>) -> Dns {
+        match resolver {
+            Some(resolver) => Dns::Custom(resolver),
+            None => Dns::System(GaiResolver::new()),
+        }
+    }
+}
+
+impl Service<Name> for Dns {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, io::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        match self {
+            Dns::System(resolver) => resolver.poll_ready(cx),
+            Dns::Custom(_) => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        match self {
+            Dns::System(resolver) => {
+                let resolving = resolver.call(name);
+                Box::pin(async move { Ok(resolving.await?.collect::<Vec<_>>().into_iter()) })
+            }
+            Dns::Custom(resolver) => {
+                let resolving = resolver.lookup(name.as_str());
+                Box::pin(async move {
+                    // The port is set by the connector.
+                    let addrs = resolving
+                        .await?
+                        .into_iter()
+                        .map(|ip| SocketAddr::new(ip, 0))
+                        .collect::<Vec<_>>();
+                    Ok(addrs.into_iter())
+                })
+            }
+        }
+    }
+}
+
+/// Resolver based on hickory-dns.
+///
+/// Lookups are cached according to the records ttl.
+#[cfg(feature = "hickory-dns")]
+#[derive(Debug, Clone)]
+pub struct HickoryResolver {
+    inner: Arc<hickory_resolver::TokioAsyncResolver>,
+}
+
+#[cfg(feature = "hickory-dns")]
+impl HickoryResolver {
+    /// Creates a resolver configured with the system configuration,
+    /// for example `/etc/resolv.conf` on unix.
+    pub fn from_system_conf() -> Result<HickoryResolver, io::Error> {
+        let inner = hickory_resolver::TokioAsyncResolver::tokio_from_system_conf()
+            .map_err(io::Error::other)?;
+        Ok(HickoryResolver::from(inner))
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl From<hickory_resolver::TokioAsyncResolver> for HickoryResolver {
+    fn from(resolver: hickory_resolver::TokioAsyncResolver) -> HickoryResolver {
+        HickoryResolver {
+            inner: Arc::new(resolver),
+        }
+    }
+}
+
+#[cfg(feature = "hickory-dns")]
+impl DnsResolver for HickoryResolver {
+    fn lookup(&self, host: &str) -> DnsFuture {
+        let inner = self.inner.clone();
+        let host = host.to_string();
+        Box::pin(async move {
+            let lookup = inner.lookup_ip(host).await.map_err(io::Error::other)?;
+            Ok(lookup.iter().collect())
+        })
+    }
+}
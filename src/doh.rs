@@ -0,0 +1,259 @@
+use crate::connector::new_https_connector;
+use crate::dns::{Dns, DnsFuture, DnsResolver};
+use crate::proxy::ProxyConnector;
+use crate::tls::TlsConfig;
+use crate::transport::{HyperTransport, Transport};
+use crate::Error;
+use hyper::client::HttpConnector;
+use hyper::header::ACCEPT;
+use hyper::{Body, Client, Request};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const DNS_MESSAGE: &str = "application/dns-message";
+/// Maximum size of a DNS message.
+const MAX_MESSAGE_SIZE: usize = 65535;
+
+/// Resolver using DNS-over-HTTPS, as specified by
+/// [RFC 8484](https://tools.ietf.org/html/rfc8484).
+///
+/// Queries are sent to the configured endpoint over https, so that
+/// host names are not revealed to the network. Use an ip address in the
+/// endpoint url to avoid resolving the endpoint itself with the system
+/// resolver.
+///
+/// ```rust
+/// use stellar_federation::{DohResolver, FederationClient};
+///
+/// # fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let resolver = DohResolver::new("https://1.1.1.1/dns-query")?;
+/// let client = FederationClient::builder().dns_resolver(resolver).build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DohResolver {
+    endpoint: Url,
+    transport: Arc<HyperTransport>,
+    timeout: Option<Duration>,
+}
+
+impl DohResolver {
+    /// Creates a resolver sending queries to the DoH `endpoint`.
+    pub fn new(endpoint: &str) -> Result<DohResolver, Error> {
+        let endpoint: Url = endpoint.parse()?;
+        let mut http = HttpConnector::new_with_resolver(Dns::new(None));
+        http.enforce_http(false);
+        let https = new_https_connector(
+            ProxyConnector::new(http, Vec::new()),
+            &TlsConfig::default(),
+            Default::default(),
+        )?;
+        let inner = Client::builder().build::<_, Body>(https);
+        Ok(DohResolver {
+            endpoint,
+            transport: Arc::new(HyperTransport::new(inner, None)),
+            timeout: None,
+        })
+    }
+
+    /// Sets the timeout of each query.
+    pub fn timeout(mut self, timeout: Duration) -> DohResolver {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    async fn query(&self, host: &str, record_type: u16) -> Result<Vec<IpAddr>, io::Error> {
+        let message = encode_query(host, record_type)?;
+        let mut url = self.endpoint.clone();
+        url.query_pairs_mut().append_pair(
+            "dns",
+            &base64::encode_config(&message, base64::URL_SAFE_NO_PAD),
+        );
+        let request = Request::get(url.as_str())
+            .header(ACCEPT, DNS_MESSAGE)
+            .body(())
+            .map_err(other)?;
+        let sending = self.transport.send(request, MAX_MESSAGE_SIZE);
+        let response = match self.timeout {
+            None => sending.await,
+            Some(timeout) => tokio::time::timeout(timeout, sending)
+                .await
+                .unwrap_or(Err(Error::Timeout)),
+        }
+        .map_err(|err| other(err.to_string()))?;
+        if !response.status().is_success() {
+            return Err(other(format!(
+                "DoH query failed with status {}",
+                response.status()
+            )));
+        }
+        decode_response(response.body())
+    }
+}
+
+impl DnsResolver for DohResolver {
+    fn lookup(&self, host: &str) -> DnsFuture {
+        let resolver = self.clone();
+        let host = host.to_string();
+        Box::pin(async move {
+            let mut addrs = resolver.query(&host, TYPE_A).await?;
+            addrs.extend(resolver.query(&host, TYPE_AAAA).await?);
+            if addrs.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no addresses found for {}", host),
+                ));
+            }
+            Ok(addrs)
+        })
+    }
+}
+
+/// Encodes a recursive query for the `record_type` records of `host`.
+fn encode_query(host: &str, record_type: u16) -> Result<Vec<u8>, io::Error> {
+    // Id 0 as recommended by RFC 8484, recursion desired, one question.
+    let mut message = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(other(format!("invalid host name {}", host)));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&record_type.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+/// Decodes the A and AAAA records in the answer section of `message`.
+fn decode_response(message: &[u8]) -> Result<Vec<IpAddr>, io::Error> {
+    let mut reader = Reader { message, offset: 0 };
+    reader.take(3)?;
+    let rcode = reader.take(1)?[0] & 0x0f;
+    // NXDOMAIN is an empty answer, other errors are failures.
+    match rcode {
+        0 => {}
+        3 => return Ok(Vec::new()),
+        rcode => return Err(other(format!("DoH query failed with rcode {}", rcode))),
+    }
+    let questions = reader.u16()?;
+    let answers = reader.u16()?;
+    reader.take(4)?;
+    for _ in 0..questions {
+        reader.skip_name()?;
+        reader.take(4)?;
+    }
+    let mut addrs = Vec::new();
+    for _ in 0..answers {
+        reader.skip_name()?;
+        let record_type = reader.u16()?;
+        let class = reader.u16()?;
+        reader.take(4)?;
+        let length = reader.u16()? as usize;
+        let data = reader.take(length)?;
+        match (record_type, class, data.len()) {
+            (TYPE_A, CLASS_IN, 4) => {
+                addrs.push(Ipv4Addr::new(data[0], data[1], data[2], data[3]).into())
+            }
+            (TYPE_AAAA, CLASS_IN, 16) => {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(data);
+                addrs.push(Ipv6Addr::from(octets).into())
+            }
+            // CNAME and other records, the addresses follow.
+            _ => {}
+        }
+    }
+    Ok(addrs)
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], io::Error> {
+        let data = self
+            .message
+            .get(self.offset..self.offset + len)
+            .ok_or_else(|| other("truncated DoH response"))?;
+        self.offset += len;
+        Ok(data)
+    }
+
+    fn u16(&mut self) -> Result<u16, io::Error> {
+        let data = self.take(2)?;
+        Ok(u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    fn skip_name(&mut self) -> Result<(), io::Error> {
+        loop {
+            let len = self.take(1)?[0];
+            match len {
+                0 => return Ok(()),
+                // Compression pointer, the name ends here.
+                len if len & 0xc0 == 0xc0 => {
+                    self.take(1)?;
+                    return Ok(());
+                }
+                len => {
+                    self.take(len as usize)?;
+                }
+            }
+        }
+    }
+}
+
+fn other<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::other(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_query() {
+        let message = encode_query("example.org", TYPE_A).unwrap();
+        assert_eq!(
+            b"\x00\x00\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x07example\x03org\x00\x00\x01\x00\x01"
+                .to_vec(),
+            message
+        );
+        assert!(encode_query("example..org", TYPE_A).is_err());
+    }
+
+    #[test]
+    fn test_decode_response() {
+        let mut message = encode_query("example.org", TYPE_A).unwrap();
+        // Response flags and two answers: a CNAME and an A record.
+        message[2] = 0x81;
+        message[3] = 0x80;
+        message[7] = 2;
+        message.extend_from_slice(b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x00\x3c\x00\x02\xc0\x0c");
+        message
+            .extend_from_slice(b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x00\x3c\x00\x04\x5d\xb8\xd8\x22");
+        assert_eq!(
+            vec![IpAddr::from([93, 184, 216, 34])],
+            decode_response(&message).unwrap()
+        );
+
+        message[3] = 0x83;
+        assert!(decode_response(&message).unwrap().is_empty());
+        message[3] = 0x82;
+        assert!(decode_response(&message).is_err());
+        assert!(decode_response(&message[..20]).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+/// Future run in the background by an [`Executor`].
+pub type SpawnFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Executor used by [`FederationClient`](crate::FederationClient) to run
+/// background tasks, for example refreshing stale cache entries.
+///
+/// The default executor spawns tasks on the current tokio runtime.
+/// Implement this trait, together with [`Timer`](crate::Timer) and
+/// [`Transport`](crate::Transport), to run the client on a different
+/// async runtime.
+pub trait Executor: fmt::Debug + Send + Sync {
+    /// Runs `future` in the background.
+    fn spawn(&self, future: SpawnFuture);
+}
+
+/// Executor based on tokio.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TokioExecutor;
+
+impl Executor for TokioExecutor {
+    fn spawn(&self, future: SpawnFuture) {
+        tokio::spawn(future);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::cache::{CacheEntry, CacheFuture, FederationCache};
+use crate::Error;
+use hyper::body::Bytes;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 4] = b"SFC1";
+const CHECKSUM_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 8 + 4;
+
+/// [`FederationCache`] storing entries as files in a directory, so that
+/// they survive process restarts.
+///
+/// Each entry is stored in its own file, together with a checksum.
+/// Entries that can't be read back, for example because the process
+/// was killed while writing them, are treated as missing and removed.
+///
+/// The files are read and written with blocking calls, entries are
+/// small enough that this is not an issue in practice.
+#[derive(Debug, Clone)]
+pub struct FileCache {
+    dir: PathBuf,
+    evictions: Arc<AtomicU64>,
+}
+
+impl FileCache {
+    /// Creates a cache storing its entries in `dir`, creating the
+    /// directory if it doesn't exist.
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<FileCache, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(FileCache {
+            dir,
+            evictions: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let hash = stellar_base::crypto::hash(key.as_bytes());
+        let name: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join(name)
+    }
+
+    fn read(&self, key: &str) -> Option<Bytes> {
+        let path = self.path(key);
+        let contents = fs::read(&path).ok()?;
+        match decode(&contents) {
+            // A different key with the same hash, keep it.
+            Some((entry_key, _, _)) if entry_key != key.as_bytes() => None,
+            Some((_, value, expires_at)) if expires_at > SystemTime::now() => {
+                Some(Bytes::copy_from_slice(value))
+            }
+            Some(_) => {
+                let _ = fs::remove_file(&path);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    fn write(&self, key: &str, value: &[u8], ttl: Duration) -> io::Result<()> {
+        let expires_at = SystemTime::now() + ttl;
+        let contents = encode(key, value, expires_at);
+        let path = self.path(key);
+        // Write to a temporary file first, so that readers never see a
+        // partially written entry.
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &path)
+    }
+
+    fn read_entries(&self) -> io::Result<Vec<CacheEntry>> {
+        let now = SystemTime::now();
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some() {
+                continue;
+            }
+            let contents = match fs::read(&path) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            if let Some((key, value, expires_at)) = decode(&contents) {
+                if let (Ok(key), true) = (std::str::from_utf8(key), expires_at > now) {
+                    entries.push(CacheEntry {
+                        key: key.to_string(),
+                        value: Bytes::copy_from_slice(value),
+                        expires_at,
+                    });
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn remove_prefix(&self, prefix: &str) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some() {
+                continue;
+            }
+            // Files that aren't entries are left alone, they may not
+            // belong to the cache.
+            let matches = fs::read(&path)
+                .ok()
+                .and_then(|contents| {
+                    decode(&contents).map(|(key, _, _)| key.starts_with(prefix.as_bytes()))
+                })
+                .unwrap_or(false);
+            if matches {
+                let _ = fs::remove_file(&path);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FederationCache for FileCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Bytes>> {
+        let value = self.read(key);
+        Box::pin(async move { value })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Duration) -> CacheFuture<'a, ()> {
+        // Failing to cache an entry is not an error.
+        let _ = self.write(key, &value, ttl);
+        Box::pin(async {})
+    }
+
+    fn invalidate<'a>(&'a self, prefix: &'a str) -> CacheFuture<'a, ()> {
+        let _ = self.remove_prefix(prefix);
+        Box::pin(async {})
+    }
+
+    fn entries(&self) -> CacheFuture<'_, Vec<CacheEntry>> {
+        let entries = self.read_entries().unwrap_or_default();
+        Box::pin(async move { entries })
+    }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
+
+/// Encodes an entry as the magic bytes, the expiration time in seconds
+/// since the epoch, the key length, the key, the value, and the
+/// checksum of all the previous bytes.
+fn encode(key: &str, value: &[u8], expires_at: SystemTime) -> Vec<u8> {
+    let expires_at = expires_at
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let mut contents = Vec::with_capacity(HEADER_LEN + key.len() + value.len() + CHECKSUM_LEN);
+    contents.extend_from_slice(MAGIC);
+    contents.extend_from_slice(&expires_at.to_be_bytes());
+    contents.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    contents.extend_from_slice(key.as_bytes());
+    contents.extend_from_slice(value);
+    let checksum = stellar_base::crypto::hash(&contents);
+    contents.extend_from_slice(&checksum);
+    contents
+}
+
+/// Decodes an entry, returning its key, value and expiration time
+/// unless it's corrupted.
+fn decode(contents: &[u8]) -> Option<(&[u8], &[u8], SystemTime)> {
+    if contents.len() < HEADER_LEN + CHECKSUM_LEN {
+        return None;
+    }
+    let (data, checksum) = contents.split_at(contents.len() - CHECKSUM_LEN);
+    if stellar_base::crypto::hash(data)[..] != checksum[..] {
+        return None;
+    }
+    let key = entry_key(data)?;
+    let mut expires_at = [0; 8];
+    expires_at.copy_from_slice(&data[MAGIC.len()..MAGIC.len() + 8]);
+    let expires_at = UNIX_EPOCH + Duration::from_secs(u64::from_be_bytes(expires_at));
+    Some((key, &data[HEADER_LEN + key.len()..], expires_at))
+}
+
+/// Returns the key of an entry, without validating its checksum.
+fn entry_key(contents: &[u8]) -> Option<&[u8]> {
+    if contents.len() < HEADER_LEN || &contents[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    let mut key_len = [0; 4];
+    key_len.copy_from_slice(&contents[MAGIC.len() + 8..HEADER_LEN]);
+    let key_len = u32::from_be_bytes(key_len) as usize;
+    contents.get(HEADER_LEN..HEADER_LEN + key_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "stellar-federation-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let expires_at = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let contents = encode("key", b"value", expires_at);
+        assert_eq!(
+            Some((&b"key"[..], &b"value"[..], expires_at)),
+            decode(&contents)
+        );
+        assert_eq!(None, decode(&contents[..contents.len() - 1]));
+        let mut corrupted = contents.clone();
+        corrupted[HEADER_LEN] ^= 1;
+        assert_eq!(None, decode(&corrupted));
+
+        // The key length can't reach into the checksum.
+        let mut crafted = Vec::new();
+        crafted.extend_from_slice(MAGIC);
+        crafted.extend_from_slice(&0u64.to_be_bytes());
+        crafted.extend_from_slice(&10u32.to_be_bytes());
+        crafted.extend_from_slice(b"key");
+        let checksum = stellar_base::crypto::hash(&crafted);
+        crafted.extend_from_slice(&checksum);
+        assert_eq!(None, decode(&crafted));
+    }
+
+    #[tokio::test]
+    async fn test_entries_survive_restarts() {
+        let dir = temp_dir("restart");
+        let cache = FileCache::new(&dir).unwrap();
+        let ttl = Duration::from_secs(60);
+        cache.set("federation/a", Bytes::from("a"), ttl).await;
+        cache.set("stellar.toml/b", Bytes::from("b"), ttl).await;
+
+        let cache = FileCache::new(&dir).unwrap();
+        assert_eq!(Some(Bytes::from("a")), cache.get("federation/a").await);
+        assert_eq!(2, cache.entries().await.len());
+        let foreign = dir.join("README");
+        fs::write(&foreign, b"not an entry").unwrap();
+        cache.invalidate("federation/").await;
+        assert_eq!(None, cache.get("federation/a").await);
+        assert!(foreign.exists());
+        assert_eq!(Some(Bytes::from("b")), cache.get("stellar.toml/b").await);
+
+        // Corrupted entries are ignored and removed.
+        let path = cache.path("stellar.toml/b");
+        fs::write(&path, b"garbage").unwrap();
+        assert_eq!(None, cache.get("stellar.toml/b").await);
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
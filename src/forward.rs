@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+/// Parameters of a forward federation request.
+///
+/// The `forward_type` parameter is required. The other parameters depend
+/// on the institution receiving the payment, whose `stellar.toml` file
+/// should list them. Use the helpers for common forward types, and
+/// [`ForwardParams::param`] for the others:
+///
+/// ```rust
+/// use stellar_federation::ForwardParams;
+///
+/// let params = ForwardParams::bank_account("BOPBPHMM", "2382376");
+/// let params = ForwardParams::new("card").param("pan", "4111111111111111");
+/// ```
+///
+/// A reference to the parameters is passed to
+/// [`resolve_stellar_forward`](crate::resolve_stellar_forward) and
+/// [`FederationClient::resolve_forward`](crate::FederationClient::resolve_forward).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardParams {
+    forward_type: String,
+    params: BTreeMap<String, String>,
+}
+
+impl ForwardParams {
+    /// Creates the parameters of a `forward_type` request.
+    pub fn new(forward_type: &str) -> ForwardParams {
+        ForwardParams {
+            forward_type: forward_type.to_string(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Creates the parameters of a `bank_account` request, with the
+    /// `swift` code of the bank and the `acct` account number, as in
+    /// the example of SEP-0002.
+    pub fn bank_account(swift: &str, acct: &str) -> ForwardParams {
+        ForwardParams::new("bank_account")
+            .param("swift", swift)
+            .param("acct", acct)
+    }
+
+    /// Creates the parameters of a `mobile_money` request, with the
+    /// `mobile_money_provider` and `mobile_money_number` parameters,
+    /// named after the SEP-0009 fields.
+    pub fn mobile_money(provider: &str, number: &str) -> ForwardParams {
+        ForwardParams::new("mobile_money")
+            .param("mobile_money_provider", provider)
+            .param("mobile_money_number", number)
+    }
+
+    /// Sets the `name` parameter to `value`, replacing its previous
+    /// value. The `type` and `forward_type` parameters can't be set.
+    pub fn param(mut self, name: &str, value: &str) -> ForwardParams {
+        if name != "type" && name != "forward_type" {
+            self.params.insert(name.to_string(), value.to_string());
+        }
+        self
+    }
+
+    /// Returns the `forward_type` parameter.
+    pub fn forward_type(&self) -> &str {
+        &self.forward_type
+    }
+
+    /// Returns the value of the `name` parameter, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Returns the parameters of the request, starting with
+    /// `forward_type`.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        std::iter::once(("forward_type", self.forward_type.as_str())).chain(
+            self.params
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+    }
+}
+
+impl<'a> IntoIterator for &'a ForwardParams {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Box<dyn Iterator<Item = (&'a str, &'a str)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params() {
+        let params = ForwardParams::bank_account("BOPBPHMM", "2382376");
+        assert_eq!(
+            vec![
+                ("forward_type", "bank_account"),
+                ("acct", "2382376"),
+                ("swift", "BOPBPHMM"),
+            ],
+            params.iter().collect::<Vec<_>>()
+        );
+        let params = ForwardParams::new("card")
+            .param("pan", "1")
+            .param("pan", "2")
+            .param("forward_type", "bank_account")
+            .param("type", "name");
+        assert_eq!("card", params.forward_type());
+        assert_eq!(Some("2"), params.get("pan"));
+        assert_eq!(2, (&params).into_iter().count());
+        let params = ForwardParams::mobile_money("mpesa", "+254700000000");
+        assert_eq!(Some("+254700000000"), params.get("mobile_money_number"));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{Error, FederationResponse, StellarToml};
+use stellar_base::PublicKey;
+use url::Url;
+
+/// Default Horizon server, the public network instance run by the
+/// Stellar Development Foundation.
+pub const DEFAULT_HORIZON_URL: &str = "https://horizon.stellar.org";
+
+/// Maximum size of the Horizon account responses, in bytes.
+pub(crate) const MAX_HORIZON_RESPONSE_SIZE: usize = 1024 * 1024;
+
+/// Result of a reverse lookup, with each step that led from the account
+/// id to its Stellar address, see
+/// [`FederationClient::reverse_lookup`](crate::FederationClient::reverse_lookup).
+#[derive(Debug, Clone)]
+pub struct ReverseLookup {
+    /// The Horizon server queried for the account.
+    pub horizon_url: Url,
+    /// The home domain of the account, in its ASCII form.
+    pub home_domain: String,
+    /// The `stellar.toml` file of the home domain.
+    pub stellar_toml: StellarToml,
+    /// The federation server declared in the `stellar.toml` file.
+    pub federation_server: Url,
+    /// The response of the federation server to the id lookup.
+    pub response: FederationResponse,
+}
+
+#[derive(Deserialize)]
+struct HorizonAccount {
+    #[serde(default)]
+    home_domain: Option<String>,
+}
+
+/// Returns the url of the `account_id` resource of the `horizon` server.
+pub(crate) fn account_url(horizon: &Url, account_id: &PublicKey) -> Url {
+    let mut url = horizon.clone();
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments
+            .pop_if_empty()
+            .push("accounts")
+            .push(&account_id.account_id());
+    }
+    url
+}
+
+/// Returns the home domain in the `body` of a Horizon account response.
+///
+/// Fails with [`Error::MissingHomeDomain`] if the account has none.
+pub(crate) fn read_home_domain(body: &[u8]) -> Result<String, Error> {
+    let account: HorizonAccount = serde_json::from_slice(body)?;
+    match account.home_domain {
+        Some(home_domain) if !home_domain.is_empty() => Ok(home_domain),
+        _ => Err(Error::MissingHomeDomain),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    #[test]
+    fn test_account_url() {
+        let account_id = PublicKey::from_account_id(ACCOUNT_ID).unwrap();
+        let expected = format!("https://horizon.stellar.org/accounts/{}", ACCOUNT_ID);
+        let horizon: Url = DEFAULT_HORIZON_URL.parse().unwrap();
+        assert_eq!(expected, account_url(&horizon, &account_id).as_str());
+        let horizon: Url = "https://example.org/horizon/".parse().unwrap();
+        assert_eq!(
+            format!("https://example.org/horizon/accounts/{}", ACCOUNT_ID),
+            account_url(&horizon, &account_id).as_str()
+        );
+    }
+
+    #[test]
+    fn test_read_home_domain() {
+        let body = format!(
+            r#"{{"id":"{0}","account_id":"{0}","home_domain":"example.org"}}"#,
+            ACCOUNT_ID
+        );
+        assert_eq!("example.org", read_home_domain(body.as_bytes()).unwrap());
+        let body = format!(r#"{{"id":"{0}","account_id":"{0}"}}"#, ACCOUNT_ID);
+        assert!(matches!(
+            read_home_domain(body.as_bytes()),
+            Err(Error::MissingHomeDomain)
+        ));
+        assert!(matches!(
+            read_home_domain(br#"{"home_domain":""}"#),
+            Err(Error::MissingHomeDomain)
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+use hyper::body::Bytes;
+use hyper::header::{
+    HeaderMap, HeaderValue, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use hyper::http::response::Parts;
+use hyper::{Response, StatusCode};
+use std::time::Duration;
+
+/// How long the validators of a response are kept, at least.
+pub(crate) const VALIDATORS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Freshness of a response, according to its `Cache-Control` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Freshness {
+    /// The response must not be stored.
+    NoStore,
+    /// The response is fresh for the duration.
+    MaxAge(Duration),
+    /// The response doesn't specify its freshness.
+    Unspecified,
+}
+
+/// Returns the freshness of the response with `headers`.
+///
+/// `no-cache` responses can be stored, but must be revalidated before
+/// being used, so they are fresh for no time.
+pub(crate) fn freshness(headers: &HeaderMap) -> Freshness {
+    let mut freshness = Freshness::Unspecified;
+    let directives = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for directive in directives {
+        let directive = directive.trim().to_ascii_lowercase();
+        if directive == "no-store" {
+            return Freshness::NoStore;
+        } else if directive == "no-cache" {
+            freshness = Freshness::MaxAge(Duration::from_secs(0));
+        } else if let Some(seconds) = directive.strip_prefix("max-age=") {
+            if let Ok(seconds) = seconds.trim_matches('"').parse() {
+                if freshness == Freshness::Unspecified {
+                    freshness = Freshness::MaxAge(Duration::from_secs(seconds));
+                }
+            }
+        }
+    }
+    freshness
+}
+
+/// Response body stored with its validators, used to revalidate it with
+/// a conditional request.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StoredResponse {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    body: Bytes,
+}
+
+impl StoredResponse {
+    /// Returns the stored `response`, if it has any validator.
+    pub(crate) fn from_response(response: &Response<Bytes>) -> Option<StoredResponse> {
+        let etag = response.headers().get(ETAG).cloned();
+        let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(StoredResponse {
+            etag,
+            last_modified,
+            body: response.body().clone(),
+        })
+    }
+
+    /// Returns the headers making a request conditional on the validators.
+    pub(crate) fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = &self.etag {
+            headers.insert(IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = &self.last_modified {
+            headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+        headers
+    }
+
+    /// Returns the response to use in place of a `304 Not Modified`
+    /// response with `parts`.
+    pub(crate) fn into_response(self, mut parts: Parts) -> Response<Bytes> {
+        parts.status = StatusCode::OK;
+        if let Some(etag) = self.etag {
+            parts.headers.entry(ETAG).or_insert(etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            parts.headers.entry(LAST_MODIFIED).or_insert(last_modified);
+        }
+        Response::from_parts(parts, self.body)
+    }
+
+    /// Encodes the response as a cache value.
+    ///
+    /// Header values cannot contain new lines, the validators are stored
+    /// on the first two lines followed by the body.
+    pub(crate) fn encode(&self) -> Bytes {
+        let mut value = Vec::with_capacity(self.body.len() + 64);
+        for validator in &[&self.etag, &self.last_modified] {
+            if let Some(validator) = validator {
+                value.extend_from_slice(validator.as_bytes());
+            }
+            value.push(b'\n');
+        }
+        value.extend_from_slice(&self.body);
+        value.into()
+    }
+
+    /// Decodes a cache value created by [`StoredResponse::encode`].
+    pub(crate) fn decode(value: &Bytes) -> Option<StoredResponse> {
+        let etag_end = value.iter().position(|b| *b == b'\n')?;
+        let last_modified_end =
+            etag_end + 1 + value[etag_end + 1..].iter().position(|b| *b == b'\n')?;
+        let validator = |bytes: &[u8]| -> Option<Option<HeaderValue>> {
+            if bytes.is_empty() {
+                Some(None)
+            } else {
+                HeaderValue::from_bytes(bytes).ok().map(Some)
+            }
+        };
+        Some(StoredResponse {
+            etag: validator(&value[..etag_end])?,
+            last_modified: validator(&value[etag_end + 1..last_modified_end])?,
+            body: value.slice(last_modified_end + 1..),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freshness() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CACHE_CONTROL, HeaderValue::from_static(value));
+            headers
+        };
+        assert_eq!(Freshness::Unspecified, freshness(&HeaderMap::new()));
+        assert_eq!(
+            Freshness::MaxAge(Duration::from_secs(60)),
+            freshness(&headers("public, max-age=60"))
+        );
+        assert_eq!(
+            Freshness::MaxAge(Duration::from_secs(0)),
+            freshness(&headers("max-age=60, no-cache"))
+        );
+        assert_eq!(
+            Freshness::NoStore,
+            freshness(&headers("no-store, max-age=60"))
+        );
+    }
+
+    #[test]
+    fn test_stored_response_roundtrip() {
+        let response = Response::builder()
+            .header(ETAG, "\"v1\"")
+            .body(Bytes::from_static(b"{\n}"))
+            .unwrap();
+        let stored = StoredResponse::from_response(&response).unwrap();
+        assert_eq!(
+            Some(stored.clone()),
+            StoredResponse::decode(&stored.encode())
+        );
+        assert_eq!(
+            Some(&HeaderValue::from_static("\"v1\"")),
+            stored.conditional_headers().get(IF_NONE_MATCH)
+        );
+
+        let not_modified = Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .body(())
+            .unwrap();
+        let response = stored.into_response(not_modified.into_parts().0);
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(&Bytes::from_static(b"{\n}"), response.body());
+    }
+
+    #[test]
+    fn test_response_without_validators_is_not_stored() {
+        let response = Response::new(Bytes::from_static(b"{}"));
+        assert_eq!(None, StoredResponse::from_response(&response));
+    }
+}
@@ -18,54 +18,303 @@
 //! # Ok(())
 //! # }
 //! ```
+//!
+//! ## Features
+//!
+//! The crate uses `native-tls` by default. Disable the default features
+//! and enable the `rustls` feature to use
+//! [rustls](https://crates.io/crates/rustls) instead. The two features
+//! are mutually exclusive.
+//!
+//! Enable the `socks` feature to send requests through a SOCKS5 proxy,
+//! see [`Proxy`]. This also enables resolving `.onion` federation
+//! servers through Tor.
+//!
+//! Enable the `hickory-dns` feature to resolve host names with
+//! `HickoryResolver` instead of the system resolver, see
+//! [`DnsResolver`].
+//!
+//! Enable the `file-cache` feature to persist cached entries on disk
+//! with `FileCache`, or the `sqlite-cache` feature to store them in a
+//! SQLite database with `SqliteCache`, see [`FederationCache`].
+//!
+//! Enable the `blocking` feature to use the synchronous api in the
+//! [`blocking`](crate::blocking) module.
+//!
+//! Enable the `server` feature to run a federation server with the
+//! [`server`](crate::server) module.
+//!
+//! Enable the `sep10` feature to authenticate lookups with a SEP-0010
+//! web authentication token, see
+//! `FederationClientBuilder::web_auth`.
+//!
+//! Enable the `horizon` feature to find the Stellar address of an
+//! account id from the home domain of the account, see
+//! `FederationClient::reverse_lookup`.
+//!
+//! Enable the `qrcode` feature to render the SEP-0007 pay URI of a
+//! [`FederationResponse`] as a QR code, with
+//! `FederationResponse::to_pay_qr_svg` and
+//! `FederationResponse::to_pay_qr_png`.
+//!
+//! ## Custom Transports
+//!
+//! Http requests are sent using hyper by default, which requires a
+//! tokio runtime. A different http stack can be used by implementing
+//! the [`Transport`] trait. Together with a custom [`Timer`] and
+//! [`Executor`], this makes it possible to run [`FederationClient`] on
+//! other async runtimes, for example async-std or smol.
+//!
+//! There is no `wasm` feature yet, and the crate cannot target
+//! `wasm32-unknown-unknown`: `stellar-base` depends on libsodium, which
+//! does not build for that target. A fetch-based [`Transport`] alone is
+//! not enough to run the client in a browser.
+
+#[cfg(all(feature = "native-tls", feature = "rustls"))]
+compile_error!("features `native-tls` and `rustls` are mutually exclusive");
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("either the `native-tls` or the `rustls` feature must be enabled");
+
 #[macro_use]
 extern crate serde_derive;
 
-use hyper::Client;
-use hyper_tls::HttpsConnector;
+mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod cache;
+mod cached_resolver;
+mod chained_resolver;
+mod client;
+mod clock;
+mod connector;
+mod discovery;
+mod dns;
+mod doh;
+mod executor;
+#[cfg(feature = "file-cache")]
+mod file_cache;
+mod forward;
+#[cfg(feature = "horizon")]
+mod horizon;
+mod http_cache;
+mod middleware;
+mod negative_cache;
+mod options;
+mod pinning;
+mod progress;
+mod proxy;
+#[cfg(feature = "qrcode")]
+mod qr;
+mod rate_limit;
+mod redirect;
+mod request;
+mod resolver;
+mod retry;
+#[cfg(feature = "sep10")]
+mod sep10;
+mod sep31;
+mod sep7;
+#[cfg(feature = "server")]
+pub mod server;
+mod signing;
+mod singleflight;
+#[cfg(feature = "socks")]
+mod socks;
+#[cfg(feature = "sqlite-cache")]
+mod sqlite_cache;
+mod static_resolver;
+mod stellar_toml;
+mod timer;
+mod tls;
+mod transport;
+
+pub use crate::cache::{
+    CacheEntry, CacheFuture, CacheStats, CachedLookup, FederationCache, MemoryCache,
+};
+pub use crate::cached_resolver::CachedResolver;
+pub use crate::chained_resolver::ChainedResolver;
+pub use crate::client::{
+    FederationClient, FederationClientBuilder, RefreshHandle, ResolveStream,
+    DEFAULT_MAX_CONCURRENCY, DEFAULT_MAX_RESPONSE_SIZE, MAX_STELLAR_TOML_SIZE,
+};
+pub use crate::clock::{Clock, ManualClock, SystemClock};
+pub use crate::discovery::AnchorEndpoints;
+#[cfg(feature = "hickory-dns")]
+pub use crate::dns::HickoryResolver;
+pub use crate::dns::{DnsFuture, DnsResolver};
+pub use crate::doh::DohResolver;
+pub use crate::executor::{Executor, SpawnFuture};
+#[cfg(feature = "file-cache")]
+pub use crate::file_cache::FileCache;
+pub use crate::forward::ForwardParams;
+#[cfg(feature = "horizon")]
+pub use crate::horizon::{ReverseLookup, DEFAULT_HORIZON_URL};
+pub use crate::middleware::Middleware;
+pub use crate::options::{BatchOptions, CachePolicy, ResolveOptions};
+pub use crate::progress::{BatchEvent, BatchProgress};
+pub use crate::proxy::Proxy;
+pub use crate::rate_limit::RateLimit;
+pub use crate::redirect::RedirectPolicy;
+pub use crate::request::{parse_federation_request, FederationRequest, ForwardQuery};
+pub use crate::resolver::{ResolveFuture, Resolver};
+pub use crate::retry::RetryPolicy;
+#[cfg(feature = "sep10")]
+pub use crate::sep10::{ChallengeSigner, WebAuthToken};
+pub use crate::sep31::{FieldError, Sep31Field, Sep31Fields};
+pub use crate::signing::{RequestSigner, SigningRequest};
+#[cfg(feature = "sqlite-cache")]
+pub use crate::sqlite_cache::SqliteCache;
+pub use crate::static_resolver::StaticResolver;
+pub use crate::stellar_toml::{StellarToml, StellarTomlError, STELLAR_TOML_PATH};
+pub use crate::timer::{SleepFuture, Timer};
+pub use crate::tls::{Certificate, Identity, TlsVersion};
+pub use crate::transport::{Transport, TransportFuture};
+
 use serde::de::{Deserialize, Deserializer, Error as SerdeError};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::collections::HashMap;
+use stellar_base::crypto::{MuxedAccount, MuxedEd25519PublicKey};
 use stellar_base::{Memo, PublicKey};
 use url::Url;
 
 /// Stellar federation response.
+///
+/// It serializes to and deserializes from the JSON body of a federation
+/// response, as described in SEP-0002.
 #[derive(Debug, Clone)]
 pub struct FederationResponse {
     /// The Stellar address, for example `example*stellar.org`.
     pub stellar_address: String,
-    /// The Stellar account id.
+    /// The Stellar account id. The base account of [`muxed_account`]
+    /// when the server returned a muxed account.
+    ///
+    /// [`muxed_account`]: FederationResponse::muxed_account
     pub account_id: PublicKey,
+    /// The muxed account, if the server returned a muxed account id
+    /// starting with `M` instead of an account id starting with `G`.
+    pub muxed_account: Option<MuxedEd25519PublicKey>,
     /// An optional memo to include when sending payments to the address.
     pub memo: Option<Memo>,
+    /// The fields of the response besides the standard ones, for example
+    /// a `memo_required` flag or a display name added by the anchor.
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
-/// Resolves a Stellar address, automatically discovering the federation server to use.
-pub async fn resolve_stellar_address(address: &str) -> Result<FederationResponse, Error> {
-    let mut address_parts = address.split("*").into_iter();
-    match (
-        address_parts.next(),
-        address_parts.next(),
-        address_parts.next(),
-    ) {
-        (Some(_name), Some(domain), None) => {
-            let toml = stellar_toml::resolve(&domain).await?;
-            if let Some(federation_server) = toml.federation_server {
-                let url: Url = federation_server.to_string().parse()?;
-                resolve_stellar_address_from_server(&address, &url).await
-            } else {
-                Err(Error::MissingFederationServer)
-            }
+impl FederationResponse {
+    /// Returns the account to send payments to, the muxed account if
+    /// the server returned one, or the account id otherwise.
+    pub fn destination(&self) -> MuxedAccount {
+        match &self.muxed_account {
+            Some(muxed_account) => MuxedAccount::MuxedEd25519(muxed_account.clone()),
+            None => MuxedAccount::Ed25519(self.account_id.clone()),
         }
-        _ => Err(Error::InvalidStellarAddress),
     }
 }
 
+/// Federation response of a Stellar address, with the `stellar.toml`
+/// file of its domain and the federation server that answered, see
+/// [`resolve_stellar_address_detailed`].
+#[derive(Debug, Clone)]
+pub struct DetailedResponse {
+    /// The federation response.
+    pub response: FederationResponse,
+    /// The `stellar.toml` file of the domain of the address.
+    pub stellar_toml: StellarToml,
+    /// The federation server declared in the `stellar.toml` file.
+    pub federation_server: Url,
+}
+
+/// Resolves a Stellar address, automatically discovering the federation server to use.
+///
+/// This function creates a new [`FederationClient`] on every call, use
+/// the client directly when resolving multiple addresses.
+pub async fn resolve_stellar_address(address: &str) -> Result<FederationResponse, Error> {
+    FederationClient::new().resolve_address(address).await
+}
+
+/// Resolves a Stellar address like [`resolve_stellar_address`],
+/// returning the `stellar.toml` file of its domain and the federation
+/// server together with the response.
+///
+/// ```rust
+/// use stellar_federation::resolve_stellar_address_detailed;
+///
+/// # async fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let detailed = resolve_stellar_address_detailed("with-text-memo*ceccon.me").await?;
+/// println!("Address = {:?}", detailed.response);
+/// println!("Federation server = {}", detailed.federation_server);
+/// println!("Signing key = {:?}", detailed.stellar_toml.signing_key);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn resolve_stellar_address_detailed(address: &str) -> Result<DetailedResponse, Error> {
+    FederationClient::new()
+        .resolve_address_detailed(address)
+        .await
+}
+
+/// Resolves Stellar addresses concurrently, running at most
+/// `max_concurrency` lookups at a time.
+///
+/// The results are returned in the order of `addresses`.
+pub async fn resolve_stellar_addresses(
+    addresses: &[&str],
+    max_concurrency: usize,
+) -> Vec<Result<FederationResponse, Error>> {
+    FederationClient::new()
+        .resolve_addresses(addresses, max_concurrency)
+        .await
+}
+
 /// Resolves a Stellar address using the specified federation server.
 pub async fn resolve_stellar_address_from_server(
     address: &str,
     server: &Url,
 ) -> Result<FederationResponse, Error> {
-    let url = stellar_address_request_url(address, server);
-    resolve_url(&url).await
+    FederationClient::new()
+        .resolve_address_from_server(address, server)
+        .await
+}
+
+/// Returns the endpoints of the services of `domain`, read from its
+/// `stellar.toml` file.
+///
+/// ```rust
+/// use stellar_federation::discover;
+///
+/// # async fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let endpoints = discover("ceccon.me").await?;
+/// println!("Federation server = {:?}", endpoints.federation_server);
+/// println!("KYC server = {:?}", endpoints.kyc_server);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn discover(domain: &str) -> Result<AnchorEndpoints, Error> {
+    FederationClient::new().discover(domain).await
+}
+
+/// Resolves a Stellar address using the federation server declared in
+/// `stellar_toml`, the `stellar.toml` file of its domain, so that an
+/// application that already fetched the file doesn't fetch it again.
+///
+/// ```rust
+/// use stellar_federation::{resolve_stellar_address_with_toml, FederationClient};
+///
+/// # async fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let client = FederationClient::new();
+/// let stellar_toml = client.resolve_stellar_toml("ceccon.me").await?;
+/// let address = resolve_stellar_address_with_toml("with-text-memo*ceccon.me", &stellar_toml).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn resolve_stellar_address_with_toml(
+    address: &str,
+    stellar_toml: &StellarToml,
+) -> Result<FederationResponse, Error> {
+    FederationClient::new()
+        .resolve_address_with_toml(address, stellar_toml)
+        .await
 }
 
 /// Returns the url for a Stellar address federation request.
@@ -84,8 +333,23 @@ pub async fn resolve_stellar_account_id(
     account_id: &PublicKey,
     server: &Url,
 ) -> Result<FederationResponse, Error> {
-    let url = stellar_account_id_request_url(account_id, server);
-    resolve_url(&url).await
+    FederationClient::new()
+        .resolve_account_id(account_id, server)
+        .await
+}
+
+/// Resolves the `account_ids` using the specified federation server
+/// concurrently, running at most [`DEFAULT_MAX_CONCURRENCY`] lookups at
+/// a time.
+///
+/// The results are returned in the order of `account_ids`.
+pub async fn resolve_stellar_account_ids(
+    account_ids: &[PublicKey],
+    server: &Url,
+) -> Vec<Result<FederationResponse, Error>> {
+    FederationClient::new()
+        .resolve_account_ids(account_ids, server, DEFAULT_MAX_CONCURRENCY)
+        .await
 }
 
 /// Returns the url for a Stellar account id request.
@@ -99,13 +363,28 @@ pub fn stellar_account_id_request_url(public_key: &PublicKey, server: &Url) -> U
     url
 }
 
+/// Returns the url for a Stellar muxed account id request.
+///
+/// The `q` parameter is the muxed account id starting with `M`, which
+/// not all federation servers support.
+pub fn stellar_muxed_account_request_url(account: &MuxedAccount, server: &Url) -> Url {
+    let mut url = server.clone();
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("type", "id");
+        query.append_pair("q", &account.account_id());
+    }
+    url
+}
+
 /// Resolves the `tx_id` using the specified federation server.
 pub async fn resolve_stellar_transaction_id(
     tx_id: &str,
     server: &Url,
 ) -> Result<FederationResponse, Error> {
-    let url = stellar_transaction_id_request_url(tx_id, server);
-    resolve_url(&url).await
+    FederationClient::new()
+        .resolve_transaction_id(tx_id, server)
+        .await
 }
 
 /// Returns the url for a Stellar transaction id request.
@@ -124,7 +403,18 @@ pub fn stellar_transaction_id_request_url(tx_id: &str, server: &Url) -> Url {
 /// The `forward_parameters` parameters will vary depending on what
 /// institution is the destination of the payment. The `stellar.toml`
 /// file of the institution should specify which parameters to
-/// include.
+/// include. Build them with [`ForwardParams`]:
+///
+/// ```rust
+/// use stellar_federation::{resolve_stellar_forward, ForwardParams};
+///
+/// # async fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let server = "https://example.org/federation".parse()?;
+/// let params = ForwardParams::bank_account("BOPBPHMM", "2382376");
+/// let response = resolve_stellar_forward(&params, &server).await?;
+/// # Ok(())
+/// # }
+/// ```
 pub async fn resolve_stellar_forward<'a, K>(
     forward_parameters: K,
     server: &Url,
@@ -132,8 +422,9 @@ pub async fn resolve_stellar_forward<'a, K>(
 where
     K: IntoIterator<Item = (&'a str, &'a str)>,
 {
-    let url = stellar_forward_request_url(forward_parameters, server);
-    resolve_url(&url).await
+    FederationClient::new()
+        .resolve_forward(forward_parameters, server)
+        .await
 }
 
 /// Returns the url for a forward request.
@@ -152,23 +443,34 @@ where
     url
 }
 
-async fn resolve_url(url: &Url) -> Result<FederationResponse, Error> {
-    let https = HttpsConnector::new();
-    let client = Client::builder().build::<_, hyper::Body>(https);
-    let uri: hyper::Uri = url.to_string().parse()?;
-    let response = client.get(uri).await?;
-
-    if response.status().is_success() {
-        let bytes = hyper::body::to_bytes(response).await?;
-        let result: FederationResponse = serde_json::from_slice(&bytes)?;
-        Ok(result)
-    } else if response.status().is_client_error() {
-        Err(Error::ClientError(response))
-    } else {
-        Err(Error::ServerError(response))
+/// Splits a Stellar address into its name and domain parts.
+pub(crate) fn split_stellar_address(address: &str) -> Option<(&str, &str)> {
+    let mut address_parts = address.split('*');
+    match (
+        address_parts.next(),
+        address_parts.next(),
+        address_parts.next(),
+    ) {
+        (Some(name), Some(domain), None) => Some((name, domain)),
+        _ => None,
+    }
+}
+
+/// Converts `domain` to its ASCII form, applying the UTS #46 processing
+/// to internationalized domain names.
+pub(crate) fn domain_to_ascii(domain: &str) -> Option<String> {
+    match url::Host::parse(domain).ok()? {
+        url::Host::Domain(domain) => Some(domain),
+        host => Some(host.to_string()),
     }
 }
 
+/// Returns `address` with its domain converted to ASCII.
+pub(crate) fn normalize_stellar_address(address: &str) -> Option<String> {
+    let (name, domain) = split_stellar_address(address)?;
+    Some(format!("{}*{}", name, domain_to_ascii(domain)?))
+}
+
 /// Crate error type.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -178,18 +480,26 @@ pub enum Error {
     /// Federation server is missing.
     #[error("missing federation server")]
     MissingFederationServer,
-    /// The client sent a bad request.
-    #[error("client response error")]
-    ClientError(hyper::Response<hyper::Body>),
-    /// Server error response.
-    #[error("server response error")]
-    ServerError(hyper::Response<hyper::Body>),
+    /// The federation server answered with an error status other than
+    /// `404 Not Found` and `501 Not Implemented`, with the `detail` of
+    /// its JSON error body, if any.
+    #[error("federation server error response: {status}")]
+    ErrorResponse {
+        /// The status of the response.
+        status: hyper::StatusCode,
+        /// The `detail` of the error body, for example `"invalid
+        /// stellar address"`.
+        detail: Option<String>,
+    },
     /// Error resolving `stellar.toml` file.
     #[error("toml resolve error")]
-    TomlResolveError(#[from] stellar_toml::Error),
+    TomlResolveError(#[from] StellarTomlError),
     /// Error parsing json.
     #[error("json error")]
     JsonError(#[from] serde_json::error::Error),
+    /// Error parsing toml.
+    #[error("toml error")]
+    TomlError(#[from] toml::de::Error),
     /// Http error.
     #[error("hyper error")]
     HyperError(#[from] hyper::Error),
@@ -199,6 +509,115 @@ pub enum Error {
     /// Invalid uri format.
     #[error("invalid uri")]
     InvalidUri(#[from] http::uri::InvalidUri),
+    /// Invalid http request.
+    #[error("http error")]
+    HttpError(#[from] http::Error),
+    /// The request timed out.
+    #[error("request timed out")]
+    Timeout,
+    /// Invalid proxy configuration.
+    #[error("invalid proxy")]
+    InvalidProxy,
+    /// The server redirected too many times.
+    #[error("too many redirects")]
+    TooManyRedirects,
+    /// The redirect policy doesn't allow following the redirect.
+    #[error("redirect to {0} not allowed")]
+    RedirectNotAllowed(Url),
+    /// I/O error.
+    #[error("io error")]
+    IoError(#[from] std::io::Error),
+    /// Invalid certificate.
+    #[error("invalid certificate")]
+    InvalidCertificate,
+    /// Error signing a request.
+    #[error("request signing error")]
+    SigningError(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// Error opening a cache.
+    #[error("cache error")]
+    CacheError(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// The lookup failed recently and its failure is cached, see
+    /// [`FederationClientBuilder::negative_cache_ttl`].
+    #[error("cached lookup failure: {0}")]
+    CachedFailure(String),
+    /// A coalesced lookup failed with this error, which can't be
+    /// cloned, see [`FederationClientBuilder::coalesce_requests`].
+    #[error("coalesced request error")]
+    Coalesced(#[source] std::sync::Arc<Error>),
+    /// The lookup was not run because another lookup of the batch
+    /// failed, see [`BatchOptions::fail_fast`].
+    #[error("batch aborted")]
+    Aborted,
+    /// The resolver has no record for the lookup, or the federation
+    /// server answered with `404 Not Found`.
+    #[error("federation record not found")]
+    NotFound,
+    /// The client is offline and the lookup is not cached, see
+    /// [`FederationClient::set_offline`].
+    #[error("offline and not cached")]
+    Offline,
+    /// The federation server doesn't support the query type, it
+    /// answered with `501 Not Implemented`.
+    #[error("query type not implemented")]
+    NotImplemented,
+    /// Invalid client identity.
+    #[error("invalid client identity")]
+    InvalidIdentity,
+    /// Error configuring the tls backend.
+    #[error("tls error")]
+    TlsError(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// The certificates presented by the server don't match the pinned ones.
+    #[error("certificate pin mismatch for {0}")]
+    CertificatePinMismatch(String),
+    /// The federation request is invalid, or the federation server
+    /// rejected it, with the detail of the error.
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    /// Invalid IP network.
+    #[error("invalid ip network")]
+    InvalidIpNetwork,
+    /// Error querying a database or a directory.
+    #[error("database error")]
+    DatabaseError(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// Invalid CSV file.
+    #[error("csv error at line {line}: {message}")]
+    CsvError {
+        /// The line of the error.
+        line: usize,
+        /// The description of the error.
+        message: String,
+    },
+    /// The response body is larger than the limit.
+    #[error("response body larger than {0} bytes")]
+    ResponseTooLarge(usize),
+    /// There is already a federation record with the Stellar address.
+    #[error("federation record already exists")]
+    RecordExists,
+    /// Invalid server configuration, with the description of the error.
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+    /// The parameters of a forward request don't match the fields
+    /// required by the anchor, see [`Sep31Fields::validate`].
+    #[error("invalid fields: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    InvalidFields(Vec<FieldError>),
+    /// Failed to render a QR code.
+    #[error("qr code error")]
+    QrCodeError(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// SEP-0010 web authentication failed, with the reason.
+    #[error("web authentication error: {0}")]
+    WebAuthError(String),
+    /// The account has no home domain to look up its Stellar address.
+    #[error("missing home domain")]
+    MissingHomeDomain,
+    /// The federation server answered a lookup with another address or
+    /// account than the one queried.
+    #[error("response mismatch: expected {expected}, got {actual}")]
+    ResponseMismatch {
+        /// The queried address or account id.
+        expected: String,
+        /// The address or account id of the response.
+        actual: String,
+    },
 }
 
 #[derive(Deserialize, Debug)]
@@ -207,6 +626,8 @@ struct IntermediateFederationResponse {
     pub account_id: String,
     pub memo_type: Option<String>,
     pub memo: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 impl<'de> Deserialize<'de> for FederationResponse {
@@ -216,8 +637,16 @@ impl<'de> Deserialize<'de> for FederationResponse {
     {
         let intermediate = IntermediateFederationResponse::deserialize(deserializer)?;
 
-        let account_id = PublicKey::from_account_id(intermediate.account_id.trim())
-            .map_err(|_| SerdeError::custom("Malformed account_id"))?;
+        let account_id = intermediate.account_id.trim();
+        let (account_id, muxed_account) = if account_id.starts_with('M') {
+            let muxed_account = MuxedEd25519PublicKey::from_account_id(account_id)
+                .map_err(|_| SerdeError::custom("Malformed account_id"))?;
+            (muxed_account.public_key().clone(), Some(muxed_account))
+        } else {
+            let account_id = PublicKey::from_account_id(account_id)
+                .map_err(|_| SerdeError::custom("Malformed account_id"))?;
+            (account_id, None)
+        };
 
         let memo = match (intermediate.memo_type, intermediate.memo) {
             (None, None) => Ok(None),
@@ -240,25 +669,113 @@ impl<'de> Deserialize<'de> for FederationResponse {
                     Memo::new_hash(&hash).map_err(|_| SerdeError::custom("Malformed hash memo"))?;
                 Ok(Some(memo))
             }
+            (Some(ref t), Some(value)) if t == "return" => {
+                let hash = base64::decode(value)
+                    .map_err(|_| SerdeError::custom("Malformed base64 return memo"))?;
+                let memo = Memo::new_return(&hash)
+                    .map_err(|_| SerdeError::custom("Malformed return memo"))?;
+                Ok(Some(memo))
+            }
             _ => Err(SerdeError::custom("Invalid memo_type or memo")),
         }?;
 
         let response = FederationResponse {
             stellar_address: intermediate.stellar_address.clone(),
             account_id,
+            muxed_account,
             memo,
+            extra: intermediate.extra,
         };
         Ok(response)
     }
 }
 
+/// Fields of a federation response described in SEP-0002.
+const STANDARD_FIELDS: [&str; 4] = ["stellar_address", "account_id", "memo_type", "memo"];
+
+/// Serializes the response as the JSON body of a federation response,
+/// with the `memo_type` and `memo` strings and the extra fields.
+impl Serialize for FederationResponse {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let memo = match &self.memo {
+            None | Some(Memo::None) => None,
+            Some(Memo::Text(text)) => Some(("text", text.clone())),
+            Some(Memo::Id(id)) => Some(("id", id.to_string())),
+            Some(Memo::Hash(hash)) => Some(("hash", base64::encode(hash))),
+            Some(Memo::Return(hash)) => Some(("return", base64::encode(hash))),
+        };
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("stellar_address", &self.stellar_address)?;
+        map.serialize_entry("account_id", &self.destination().account_id())?;
+        if let Some((memo_type, memo)) = memo {
+            map.serialize_entry("memo_type", memo_type)?;
+            map.serialize_entry("memo", &memo)?;
+        }
+        for (name, value) in &self.extra {
+            if !STANDARD_FIELDS.contains(&name.as_str()) {
+                map.serialize_entry(name, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Returns the error of a federation server answering with the error
+/// `status` and `body`, a JSON object like `{"detail": "not found"}`.
+pub(crate) fn error_from_response(status: hyper::StatusCode, body: &[u8]) -> Error {
+    match status {
+        hyper::StatusCode::NOT_FOUND => Error::NotFound,
+        hyper::StatusCode::NOT_IMPLEMENTED => Error::NotImplemented,
+        status => {
+            let body: Option<serde_json::Value> = serde_json::from_slice(body).ok();
+            let detail = body
+                .as_ref()
+                .and_then(|body| body.get("detail"))
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string);
+            Error::ErrorResponse { status, detail }
+        }
+    }
+}
+
+/// Returns the JSON body of a federation response for `response`.
+pub(crate) fn response_json(response: &FederationResponse) -> serde_json::Value {
+    serde_json::to_value(response).expect("invalid federation response")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
     use stellar_base::PublicKey;
     use url::Url;
 
+    #[test]
+    fn test_split_stellar_address() {
+        assert_eq!(
+            Some(("test", "example.org")),
+            split_stellar_address("test*example.org")
+        );
+        assert_eq!(None, split_stellar_address("example.org"));
+        assert_eq!(None, split_stellar_address("test*example*org"));
+    }
+
+    #[test]
+    fn test_normalize_stellar_address() {
+        assert_eq!(
+            Some("maria*xn--bcher-kva.example".to_string()),
+            normalize_stellar_address("maria*Bücher.example")
+        );
+        assert_eq!(
+            Some("test*example.org".to_string()),
+            normalize_stellar_address("test*example.org")
+        );
+        assert_eq!(None, normalize_stellar_address("test*exa mple.org"));
+        assert_eq!(None, normalize_stellar_address("test"));
+    }
+
     #[test]
     fn test_stellar_address_request_url() {
         let server: Url = "https://example.org/federation".parse().unwrap();
@@ -285,6 +802,156 @@ mod tests {
         assert_eq!(Some(&"id".to_string()), query.get("type"));
     }
 
+    #[test]
+    fn test_muxed_account_request_url() {
+        let server: Url = "https://example.org/federation".parse().unwrap();
+        let account =
+            PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+                .unwrap()
+                .into_muxed_account(42);
+        let url = stellar_muxed_account_request_url(&account, &server);
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(Some(&account.account_id()), query.get("q"));
+        assert!(query["q"].starts_with('M'));
+        assert_eq!(Some(&"id".to_string()), query.get("type"));
+    }
+
+    #[test]
+    fn test_deserialize_muxed_account() {
+        let public_key =
+            PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+                .unwrap();
+        let muxed_account = public_key.to_muxed_account(42);
+        let body = serde_json::json!({
+            "stellar_address": "alice*example.org",
+            "account_id": muxed_account.account_id(),
+        });
+        let response: FederationResponse = serde_json::from_value(body.clone()).unwrap();
+        assert_eq!(public_key, response.account_id);
+        assert_eq!(muxed_account, response.destination());
+        assert_eq!(body, response_json(&response));
+
+        let body = serde_json::json!({
+            "stellar_address": "alice*example.org",
+            "account_id": public_key.account_id(),
+        });
+        let response: FederationResponse = serde_json::from_value(body).unwrap();
+        assert!(response.muxed_account.is_none());
+        assert_eq!(MuxedAccount::Ed25519(public_key), response.destination());
+
+        let body = serde_json::json!({
+            "stellar_address": "alice*example.org",
+            "account_id": "MAAAAAAAAAAAAAB",
+        });
+        assert!(serde_json::from_value::<FederationResponse>(body).is_err());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let public_key =
+            PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+                .unwrap();
+        let memos = [
+            (None, serde_json::json!({})),
+            (
+                Some(Memo::new_text("hello").unwrap()),
+                serde_json::json!({ "memo_type": "text", "memo": "hello" }),
+            ),
+            (
+                Some(Memo::new_id(42)),
+                serde_json::json!({ "memo_type": "id", "memo": "42" }),
+            ),
+            (
+                Some(Memo::new_hash(&[1; 32]).unwrap()),
+                serde_json::json!({
+                    "memo_type": "hash",
+                    "memo": "AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE="
+                }),
+            ),
+            (
+                Some(Memo::new_return(&[2; 32]).unwrap()),
+                serde_json::json!({
+                    "memo_type": "return",
+                    "memo": "AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI="
+                }),
+            ),
+        ];
+        for (memo, memo_json) in memos.iter() {
+            let response = FederationResponse {
+                stellar_address: "alice*example.org".to_string(),
+                account_id: public_key.clone(),
+                muxed_account: None,
+                memo: memo.clone(),
+                extra: HashMap::new(),
+            };
+            let mut expected = serde_json::json!({
+                "stellar_address": "alice*example.org",
+                "account_id": "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP",
+            });
+            for (name, value) in memo_json.as_object().unwrap() {
+                expected[name] = value.clone();
+            }
+            let json = serde_json::to_string(&response).unwrap();
+            assert_eq!(
+                expected,
+                serde_json::from_str::<serde_json::Value>(&json).unwrap()
+            );
+            let parsed: FederationResponse = serde_json::from_str(&json).unwrap();
+            assert_eq!(response.account_id, parsed.account_id);
+            assert_eq!(response.memo, parsed.memo);
+        }
+    }
+
+    #[test]
+    fn test_extra_fields() {
+        let body = serde_json::json!({
+            "stellar_address": "alice*example.org",
+            "account_id": "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP",
+            "memo_type": "id",
+            "memo": "42",
+            "memo_required": true,
+            "display_name": "Alice",
+        });
+        let response: FederationResponse = serde_json::from_value(body.clone()).unwrap();
+        assert_eq!(2, response.extra.len());
+        assert_eq!(
+            Some(&serde_json::json!(true)),
+            response.extra.get("memo_required")
+        );
+        assert_eq!(
+            Some(&serde_json::json!("Alice")),
+            response.extra.get("display_name")
+        );
+        assert_eq!(body, serde_json::to_value(&response).unwrap());
+    }
+
+    #[test]
+    fn test_error_from_response() {
+        use hyper::StatusCode;
+
+        let body = br#"{"detail": "not found"}"#;
+        assert!(matches!(
+            error_from_response(StatusCode::NOT_FOUND, body),
+            Error::NotFound
+        ));
+        assert!(matches!(
+            error_from_response(StatusCode::NOT_IMPLEMENTED, b""),
+            Error::NotImplemented
+        ));
+        assert!(matches!(
+            error_from_response(StatusCode::BAD_REQUEST, br#"{"detail": "invalid stellar address"}"#),
+            Error::ErrorResponse { status: StatusCode::BAD_REQUEST, detail: Some(detail) }
+                if detail == "invalid stellar address"
+        ));
+        assert!(matches!(
+            error_from_response(StatusCode::BAD_GATEWAY, b"<html>bad gateway</html>"),
+            Error::ErrorResponse {
+                status: StatusCode::BAD_GATEWAY,
+                detail: None
+            }
+        ));
+    }
+
     #[test]
     fn test_transaction_id_request_url() {
         let server: Url = "https://example.org/federation".parse().unwrap();
@@ -322,4 +989,15 @@ mod tests {
         assert_eq!(Some(&"BOPBPHMM".to_string()), query.get("swift"));
         assert_eq!(Some(&"2382376".to_string()), query.get("acct"));
     }
+
+    #[test]
+    fn test_forward_params_request_url() {
+        let server: Url = "https://example.org/federation".parse().unwrap();
+        let params = ForwardParams::bank_account("BOPBPHMM", "2382376");
+        let url = stellar_forward_request_url(&params, &server);
+        assert_eq!(
+            Some("type=forward&forward_type=bank_account&acct=2382376&swift=BOPBPHMM"),
+            url.query()
+        );
+    }
 }
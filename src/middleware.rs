@@ -0,0 +1,48 @@
+use crate::transport::TransportFuture;
+use hyper::body::Bytes;
+use hyper::{Request, Response};
+use std::fmt;
+
+/// Hooks that run around every request sent by
+/// [`FederationClient`](crate::FederationClient), including the
+/// `stellar.toml` requests.
+///
+/// Middlewares can add headers, rewrite urls, log traffic, or reject
+/// responses. The hooks run once per attempt, so retried and redirected
+/// requests go through them again.
+///
+/// ```rust
+/// use stellar_federation::{FederationClient, Middleware};
+/// use hyper::header::{HeaderValue, AUTHORIZATION};
+/// use hyper::Request;
+///
+/// #[derive(Debug)]
+/// struct Authorization(HeaderValue);
+///
+/// impl Middleware for Authorization {
+///     fn on_request(&self, mut request: Request<()>) -> Request<()> {
+///         request.headers_mut().insert(AUTHORIZATION, self.0.clone());
+///         request
+///     }
+/// }
+///
+/// # fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let client = FederationClient::builder()
+///     .middleware(Authorization(HeaderValue::from_static("Bearer token")))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Middleware: fmt::Debug + Send + Sync {
+    /// Called before the request is sent.
+    fn on_request(&self, request: Request<()>) -> Request<()> {
+        request
+    }
+
+    /// Called after a response is received.
+    ///
+    /// Return an error to fail the request.
+    fn on_response(&self, response: Response<Bytes>) -> TransportFuture<'_> {
+        Box::pin(async move { Ok(response) })
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{error_from_response, Error, StellarTomlError};
+use hyper::body::Bytes;
+use hyper::{Response, StatusCode};
+
+/// Returns the cache key of the failure of the lookup cached with `key`.
+pub(crate) fn negative_key(key: &str) -> String {
+    format!("negative/{}", key)
+}
+
+/// Failed lookup stored in the cache.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Failure {
+    /// The server responded with an error status.
+    Response(StatusCode, Bytes),
+    /// The lookup failed with an error that cannot be stored, only its
+    /// message is kept.
+    Error(String),
+}
+
+impl Failure {
+    /// Returns the failure of the error `response`.
+    pub(crate) fn from_response(response: &Response<Bytes>) -> Failure {
+        Failure::Response(response.status(), response.body().clone())
+    }
+
+    /// Returns the failure of `err`, with the messages of its sources.
+    pub(crate) fn from_error(err: &Error) -> Failure {
+        let mut message = err.to_string();
+        let mut source = std::error::Error::source(err);
+        while let Some(err) = source {
+            message.push_str(": ");
+            message.push_str(&err.to_string());
+            source = err.source();
+        }
+        Failure::Error(message)
+    }
+
+    /// Returns the error of a federation request that failed.
+    pub(crate) fn into_error(self) -> Error {
+        match self {
+            Failure::Response(status, body) => error_from_response(status, &body),
+            Failure::Error(message) => Error::CachedFailure(message),
+        }
+    }
+
+    /// Returns the error of a `stellar.toml` request that failed.
+    pub(crate) fn into_stellar_toml_error(self) -> Error {
+        match self {
+            Failure::Response(status, _) if status.is_client_error() => {
+                StellarTomlError::ClientError(status).into()
+            }
+            Failure::Response(status, _) => StellarTomlError::ServerError(status).into(),
+            Failure::Error(message) => Error::CachedFailure(message),
+        }
+    }
+
+    /// Encodes the failure as a cache value.
+    pub(crate) fn encode(&self) -> Bytes {
+        let (kind, content) = match self {
+            Failure::Response(status, body) => (status.as_str().to_string(), body.as_ref()),
+            Failure::Error(message) => ("error".to_string(), message.as_bytes()),
+        };
+        let mut value = Vec::with_capacity(kind.len() + 1 + content.len());
+        value.extend_from_slice(kind.as_bytes());
+        value.push(b'\n');
+        value.extend_from_slice(content);
+        value.into()
+    }
+
+    /// Decodes a cache value created by [`Failure::encode`].
+    pub(crate) fn decode(value: &Bytes) -> Option<Failure> {
+        let end = value.iter().position(|b| *b == b'\n')?;
+        let content = value.slice(end + 1..);
+        match &value[..end] {
+            b"error" => String::from_utf8(content.to_vec()).ok().map(Failure::Error),
+            status => StatusCode::from_bytes(status)
+                .ok()
+                .map(|status| Failure::Response(status, content)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_roundtrip() {
+        let failures = vec![
+            Failure::Response(StatusCode::NOT_FOUND, Bytes::from_static(b"not\nfound")),
+            Failure::from_error(&Error::MissingFederationServer),
+        ];
+        for failure in failures {
+            assert_eq!(Some(failure.clone()), Failure::decode(&failure.encode()));
+        }
+    }
+
+    #[test]
+    fn test_into_error() {
+        let failure = Failure::Response(StatusCode::NOT_FOUND, Bytes::new());
+        assert!(matches!(failure.clone().into_error(), Error::NotFound));
+        assert!(matches!(
+            failure.into_stellar_toml_error(),
+            Error::TomlResolveError(StellarTomlError::ClientError(StatusCode::NOT_FOUND))
+        ));
+    }
+}
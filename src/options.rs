@@ -0,0 +1,189 @@
+use crate::retry::RetryPolicy;
+use crate::DEFAULT_MAX_CONCURRENCY;
+use std::time::Duration;
+
+/// How a resolution uses the cache.
+///
+/// The policy applies to the federation response, the `stellar.toml`
+/// file of the domain, and their cached failures. Only relevant when the
+/// client caches them, see
+/// [`FederationClientBuilder::response_cache_ttl`](crate::FederationClientBuilder::response_cache_ttl).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CachePolicy {
+    /// Return the cached response if any, otherwise cache the new response.
+    #[default]
+    Default,
+    /// Ignore the cached response, then cache the new response.
+    Refresh,
+    /// Neither read nor write the cache.
+    NoCache,
+}
+
+/// Options overriding the client configuration for a single resolution.
+///
+/// ```rust
+/// use stellar_federation::{FederationClient, ResolveOptions};
+/// use std::time::Duration;
+///
+/// # async fn run() -> std::result::Result<(), stellar_federation::Error> {
+/// let client = FederationClient::new();
+/// let options = ResolveOptions::new()
+///     .timeout(Duration::from_secs(2))
+///     .no_retry();
+/// let address = client
+///     .resolve_address_with_options("with-text-memo*ceccon.me", &options)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ResolveOptions {
+    timeout: Option<Duration>,
+    retry_policy: Option<Option<RetryPolicy>>,
+    cache_policy: CachePolicy,
+    cache_ttl: Option<Duration>,
+}
+
+impl ResolveOptions {
+    /// Creates options that use the client configuration.
+    pub fn new() -> ResolveOptions {
+        ResolveOptions::default()
+    }
+
+    /// Sets the time budget of the whole resolution, including
+    /// `stellar.toml` discovery, retries, and redirects.
+    ///
+    /// The resolution fails with [`Error::Timeout`](crate::Error::Timeout)
+    /// once the budget is exhausted.
+    pub fn timeout(mut self, timeout: Duration) -> ResolveOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the policy used to retry failed requests, instead of the
+    /// client retry policy.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> ResolveOptions {
+        self.retry_policy = Some(Some(policy));
+        self
+    }
+
+    /// Disables retries, even if the client has a retry policy.
+    pub fn no_retry(mut self) -> ResolveOptions {
+        self.retry_policy = Some(None);
+        self
+    }
+
+    /// Sets how the resolution uses the response cache.
+    pub fn cache_policy(mut self, policy: CachePolicy) -> ResolveOptions {
+        self.cache_policy = policy;
+        self
+    }
+
+    /// Sets how long the response is cached, instead of the client
+    /// response cache ttl.
+    pub fn cache_ttl(mut self, ttl: Duration) -> ResolveOptions {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    pub(crate) fn timeout_duration(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn cache_policy_value(&self) -> CachePolicy {
+        self.cache_policy
+    }
+
+    pub(crate) fn cache_ttl_or(&self, default: Option<Duration>) -> Option<Duration> {
+        self.cache_ttl.or(default)
+    }
+
+    /// Returns the retry policy to use, falling back to `default`.
+    pub(crate) fn retry_policy_or<'a>(
+        &'a self,
+        default: Option<&'a RetryPolicy>,
+    ) -> Option<&'a RetryPolicy> {
+        match &self.retry_policy {
+            Some(policy) => policy.as_ref(),
+            None => default,
+        }
+    }
+}
+
+/// Options of a batch resolution with
+/// [`FederationClient::resolve_many`](crate::FederationClient::resolve_many).
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    max_concurrency: usize,
+    fail_fast: bool,
+    resolve_options: ResolveOptions,
+}
+
+impl Default for BatchOptions {
+    fn default() -> BatchOptions {
+        BatchOptions {
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            fail_fast: false,
+            resolve_options: ResolveOptions::default(),
+        }
+    }
+}
+
+impl BatchOptions {
+    /// Creates options running at most [`DEFAULT_MAX_CONCURRENCY`]
+    /// lookups at a time and collecting all the results.
+    pub fn new() -> BatchOptions {
+        BatchOptions::default()
+    }
+
+    /// Sets the maximum number of lookups running at a time.
+    pub fn max_concurrency(mut self, max: usize) -> BatchOptions {
+        self.max_concurrency = max.max(1);
+        self
+    }
+
+    /// Stops starting new lookups as soon as one fails.
+    ///
+    /// The lookups that are not started fail with
+    /// [`Error::Aborted`](crate::Error::Aborted), the lookups already
+    /// running complete normally.
+    pub fn fail_fast(mut self, fail_fast: bool) -> BatchOptions {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Sets the options overriding the client configuration for each
+    /// lookup, the timeout applying to each lookup separately.
+    pub fn resolve_options(mut self, options: ResolveOptions) -> BatchOptions {
+        self.resolve_options = options;
+        self
+    }
+
+    pub(crate) fn max_concurrency_value(&self) -> usize {
+        self.max_concurrency
+    }
+
+    pub(crate) fn fail_fast_value(&self) -> bool {
+        self.fail_fast
+    }
+
+    pub(crate) fn resolve_options_value(&self) -> &ResolveOptions {
+        &self.resolve_options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_override() {
+        let default = RetryPolicy::new(3);
+        let options = ResolveOptions::new();
+        assert!(options.retry_policy_or(Some(&default)).is_some());
+        let options = options.no_retry();
+        assert!(options.retry_policy_or(Some(&default)).is_none());
+        let options = options.retry_policy(RetryPolicy::new(5));
+        assert!(options.retry_policy_or(None).is_some());
+    }
+}
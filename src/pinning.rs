@@ -0,0 +1,259 @@
+use crate::connector::TlsConnector;
+use crate::proxy::BoxError;
+use hyper::service::Service;
+use hyper::Uri;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+type Stream = <TlsConnector as Service<Uri>>::Response;
+
+/// Pins, by domain.
+pub(crate) type CertificatePins = HashMap<String, Vec<CertificatePin>>;
+
+/// A pinned SHA-256 hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CertificatePin {
+    /// Hash of the DER encoded certificate.
+    Certificate([u8; 32]),
+    /// Hash of the DER encoded SubjectPublicKeyInfo of the certificate.
+    PublicKey([u8; 32]),
+}
+
+impl CertificatePin {
+    /// Returns `true` if the DER encoded `certificate` matches the pin.
+    fn matches(&self, certificate: &[u8]) -> bool {
+        match self {
+            CertificatePin::Certificate(pin) => {
+                pin[..] == stellar_base::crypto::hash(certificate)[..]
+            }
+            CertificatePin::PublicKey(pin) => subject_public_key_info(certificate)
+                .is_some_and(|spki| pin[..] == stellar_base::crypto::hash(spki)[..]),
+        }
+    }
+}
+
+/// Connector that verifies the certificates presented by pinned domains.
+#[derive(Debug, Clone)]
+pub(crate) struct PinningConnector {
+    inner: TlsConnector,
+    pins: Arc<CertificatePins>,
+}
+
+/// Error returned when the certificates presented by a domain don't
+/// match its pins.
+#[derive(Debug)]
+pub(crate) struct CertificatePinMismatch(pub(crate) String);
+
+impl fmt::Display for CertificatePinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "certificate pin mismatch for {}", self.0)
+    }
+}
+
+impl std::error::Error for CertificatePinMismatch {}
+
+impl PinningConnector {
+    pub(crate) fn new(inner: TlsConnector, pins: CertificatePins) -> PinningConnector {
+        PinningConnector {
+            inner,
+            pins: Arc::new(pins),
+        }
+    }
+}
+
+impl Service<Uri> for PinningConnector {
+    type Response = Stream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Stream, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let host = dst.host().unwrap_or_default().to_lowercase();
+        let pins = self.pins.get(&host).cloned();
+        let connecting = self.inner.call(dst);
+        Box::pin(async move {
+            let stream = connecting.await?;
+            if let Some(pins) = pins {
+                if !pins_match(&pins, &peer_certificates(&stream)) {
+                    return Err(CertificatePinMismatch(host).into());
+                }
+            }
+            Ok(stream)
+        })
+    }
+}
+
+/// Returns `true` if any of the DER encoded `certificates` matches the `pins`.
+fn pins_match(pins: &[CertificatePin], certificates: &[Vec<u8>]) -> bool {
+    certificates
+        .iter()
+        .any(|certificate| pins.iter().any(|pin| pin.matches(certificate)))
+}
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_VERSION: u8 = 0xa0;
+
+/// A DER element, with its header in `encoded` but not in `contents`.
+struct DerElement<'a> {
+    tag: u8,
+    encoded: &'a [u8],
+    contents: &'a [u8],
+}
+
+/// Splits the DER element at the start of `input` from the rest of it.
+fn der_element(input: &[u8]) -> Option<(DerElement<'_>, &[u8])> {
+    let tag = *input.first()?;
+    let first = *input.get(1)?;
+    let (length, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let bytes = input.get(2..2 + count)?;
+        let length = bytes
+            .iter()
+            .fold(0, |length, byte| length << 8 | *byte as usize);
+        (length, 2 + count)
+    };
+    let encoded = input.get(..header.checked_add(length)?)?;
+    let element = DerElement {
+        tag,
+        encoded,
+        contents: &encoded[header..],
+    };
+    Some((element, &input[encoded.len()..]))
+}
+
+/// Returns the DER encoded SubjectPublicKeyInfo of the DER encoded X.509
+/// `certificate`.
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (certificate, _) = der_element(certificate)?;
+    let (tbs, _) = der_element(certificate.contents)?;
+    if certificate.tag != DER_SEQUENCE || tbs.tag != DER_SEQUENCE {
+        return None;
+    }
+    // The optional version, then the serial number, signature algorithm,
+    // issuer, validity and subject come before the key.
+    let mut fields = tbs.contents;
+    let (first, _) = der_element(fields)?;
+    let skipped = if first.tag == DER_VERSION { 6 } else { 5 };
+    for _ in 0..skipped {
+        fields = der_element(fields)?.1;
+    }
+    let (spki, _) = der_element(fields)?;
+    (spki.tag == DER_SEQUENCE).then_some(spki.encoded)
+}
+
+/// Returns the DER encoded certificates presented by the server.
+///
+/// native-tls only exposes the leaf certificate.
+#[cfg(feature = "native-tls")]
+fn peer_certificates(stream: &Stream) -> Vec<Vec<u8>> {
+    match stream {
+        hyper_tls::MaybeHttpsStream::Http(_) => Vec::new(),
+        hyper_tls::MaybeHttpsStream::Https(tls) => tls
+            .get_ref()
+            .peer_certificate()
+            .ok()
+            .flatten()
+            .and_then(|certificate| certificate.to_der().ok())
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Returns the DER encoded certificates presented by the server.
+#[cfg(feature = "rustls")]
+fn peer_certificates(stream: &Stream) -> Vec<Vec<u8>> {
+    match stream {
+        hyper_rustls::MaybeHttpsStream::Http(_) => Vec::new(),
+        hyper_rustls::MaybeHttpsStream::Https(tls) => tls
+            .get_ref()
+            .1
+            .peer_certificates()
+            .map(|certificates| {
+                certificates
+                    .iter()
+                    .map(|certificate| certificate.0.clone())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    /// Returns the DER encoding of an element with `tag` and `contents`.
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![tag];
+        if contents.len() < 0x80 {
+            encoded.push(contents.len() as u8);
+        } else {
+            encoded.extend_from_slice(&[0x82, (contents.len() >> 8) as u8, contents.len() as u8]);
+        }
+        encoded.extend_from_slice(contents);
+        encoded
+    }
+
+    /// Returns a certificate with the `spki` key, with a version if
+    /// `versioned`.
+    fn certificate(spki: &[u8], versioned: bool) -> Vec<u8> {
+        let mut tbs = Vec::new();
+        if versioned {
+            tbs.extend(der(DER_VERSION, &der(0x02, &[2])));
+        }
+        tbs.extend(der(0x02, &[1]));
+        tbs.extend(der(DER_SEQUENCE, &der(0x06, &[0x2a, 0x86, 0x48])));
+        tbs.extend(der(DER_SEQUENCE, &[0; 200]));
+        tbs.extend(der(DER_SEQUENCE, &[]));
+        tbs.extend(der(DER_SEQUENCE, &[1; 20]));
+        tbs.extend_from_slice(spki);
+        tbs.extend(der(0xa3, &[]));
+        let mut certificate = der(DER_SEQUENCE, &tbs);
+        certificate.extend(der(DER_SEQUENCE, &[]));
+        certificate.extend(der(0x03, &[0; 64]));
+        der(DER_SEQUENCE, &certificate)
+    }
+
+    fn sha256(bytes: &[u8]) -> [u8; 32] {
+        stellar_base::crypto::hash(bytes).try_into().unwrap()
+    }
+
+    #[test]
+    fn test_pins_match() {
+        let certificate = b"certificate".to_vec();
+        let pin = CertificatePin::Certificate(sha256(&certificate));
+        assert!(pins_match(&[pin], &[b"other".to_vec(), certificate]));
+        assert!(!pins_match(&[pin], &[b"other".to_vec()]));
+        assert!(!pins_match(&[pin], &[]));
+    }
+
+    #[test]
+    fn test_public_key_pins_match() {
+        let spki = der(DER_SEQUENCE, &der(0x03, &[7; 300]));
+        let pin = CertificatePin::PublicKey(sha256(&spki));
+        for versioned in [true, false] {
+            let certificate = certificate(&spki, versioned);
+            assert_eq!(Some(&spki[..]), subject_public_key_info(&certificate));
+            assert!(pins_match(&[pin], &[certificate]));
+        }
+
+        let other = der(DER_SEQUENCE, &der(0x03, &[8; 300]));
+        assert!(!pins_match(&[pin], &[certificate(&other, true)]));
+        assert!(subject_public_key_info(b"certificate").is_none());
+        assert!(subject_public_key_info(&spki[..spki.len() - 1]).is_none());
+        assert!(!pins_match(&[pin], &[spki]));
+    }
+}
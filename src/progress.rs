@@ -0,0 +1,151 @@
+use crate::{Error, FederationResponse};
+use futures_util::stream::{self, StreamExt};
+use std::future::Future;
+
+/// Counts of a batch operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BatchProgress {
+    /// Number of completed lookups, including the failed ones.
+    pub completed: usize,
+    /// Number of failed lookups.
+    pub failed: usize,
+    /// Number of lookups not completed yet.
+    pub remaining: usize,
+}
+
+impl BatchProgress {
+    pub(crate) fn new(total: usize) -> BatchProgress {
+        BatchProgress {
+            completed: 0,
+            failed: 0,
+            remaining: total,
+        }
+    }
+
+    /// Returns the total number of lookups of the batch.
+    pub fn total(&self) -> usize {
+        self.completed + self.remaining
+    }
+
+    pub(crate) fn record(&mut self, failed: bool) {
+        self.completed += 1;
+        self.remaining -= 1;
+        if failed {
+            self.failed += 1;
+        }
+    }
+}
+
+/// Event reported when a lookup of a batch operation completes.
+#[derive(Debug)]
+pub struct BatchEvent<'a> {
+    /// Index of the lookup in the batch.
+    pub index: usize,
+    /// Result of the lookup.
+    pub result: &'a Result<FederationResponse, Error>,
+    /// Progress of the batch, including this lookup.
+    pub progress: BatchProgress,
+}
+
+/// Returns a completion callback reporting [`BatchEvent`]s to `on_event`.
+pub(crate) fn report<P>(
+    total: usize,
+    mut on_event: P,
+) -> impl FnMut(usize, &Result<FederationResponse, Error>)
+where
+    P: FnMut(BatchEvent<'_>),
+{
+    let mut progress = BatchProgress::new(total);
+    move |index, result| {
+        progress.record(result.is_err());
+        on_event(BatchEvent {
+            index,
+            result,
+            progress,
+        });
+    }
+}
+
+/// Runs the `lookups`, each with the index of its address in the batch,
+/// at most `max_concurrency` at a time, reporting [`BatchEvent`]s to
+/// `on_event` as soon as they complete.
+///
+/// The lookups are started in order, and their results are returned in
+/// the order of their indexes.
+pub(crate) async fn run_batch<F, P>(
+    lookups: Vec<(usize, F)>,
+    max_concurrency: usize,
+    on_event: P,
+) -> Vec<Result<FederationResponse, Error>>
+where
+    F: Future<Output = Result<FederationResponse, Error>>,
+    P: FnMut(BatchEvent<'_>),
+{
+    let mut on_complete = report(lookups.len(), on_event);
+    let mut results: Vec<Option<_>> = lookups.iter().map(|_| None).collect();
+    let mut completed = stream::iter(
+        lookups
+            .into_iter()
+            .map(|(index, lookup)| async move { (index, lookup.await) }),
+    )
+    .buffer_unordered(max_concurrency.max(1));
+    while let Some((index, result)) = completed.next().await {
+        on_complete(index, &result);
+        results[index] = Some(result);
+    }
+    results.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut events = Vec::new();
+        let mut on_complete = report(2, |event| events.push((event.index, event.progress)));
+        on_complete(1, &Err(Error::InvalidStellarAddress));
+        on_complete(0, &Err(Error::MissingFederationServer));
+        drop(on_complete);
+        assert_eq!(
+            vec![
+                (
+                    1,
+                    BatchProgress {
+                        completed: 1,
+                        failed: 1,
+                        remaining: 1
+                    }
+                ),
+                (
+                    0,
+                    BatchProgress {
+                        completed: 2,
+                        failed: 2,
+                        remaining: 0
+                    }
+                ),
+            ],
+            events
+        );
+        assert_eq!(2, events[1].1.total());
+    }
+
+    #[tokio::test]
+    async fn test_run_batch() {
+        let lookups = [(0, 30), (1, 10), (2, 20)]
+            .iter()
+            .map(|&(index, millis)| {
+                let lookup = async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+                    Err(Error::Timeout)
+                };
+                (index, lookup)
+            })
+            .collect();
+        let mut completed = Vec::new();
+        let results = run_batch(lookups, 3, |event| completed.push(event.index)).await;
+        assert_eq!(3, results.len());
+        assert_eq!(vec![1, 2, 0], completed);
+    }
+}
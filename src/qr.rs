@@ -0,0 +1,115 @@
+use crate::{Error, FederationResponse};
+use qrcode::render::svg;
+use qrcode::{Color, QrCode};
+use stellar_base::amount::Amount;
+use stellar_base::Asset;
+
+/// Size of a module of the PNG images, in pixels.
+const MODULE_SIZE: usize = 8;
+
+/// Width of the quiet zone around the code, in modules.
+const QUIET_ZONE: usize = 4;
+
+impl FederationResponse {
+    /// Returns the [SEP-0007 pay URI](FederationResponse::to_pay_uri) of
+    /// the response rendered as a QR code, in an SVG image.
+    pub fn to_pay_qr_svg(
+        &self,
+        amount: Option<&Amount>,
+        asset: Option<&Asset>,
+    ) -> Result<String, Error> {
+        let code = pay_qr_code(self, amount, asset)?;
+        Ok(code
+            .render::<svg::Color<'_>>()
+            .module_dimensions(MODULE_SIZE as u32, MODULE_SIZE as u32)
+            .build())
+    }
+
+    /// Returns the [SEP-0007 pay URI](FederationResponse::to_pay_uri) of
+    /// the response rendered as a QR code, in a grayscale PNG image.
+    pub fn to_pay_qr_png(
+        &self,
+        amount: Option<&Amount>,
+        asset: Option<&Asset>,
+    ) -> Result<Vec<u8>, Error> {
+        let code = pay_qr_code(self, amount, asset)?;
+        let width = code.width();
+        let colors = code.to_colors();
+        let size = (width + 2 * QUIET_ZONE) * MODULE_SIZE;
+        let mut pixels = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                let module = (y / MODULE_SIZE)
+                    .checked_sub(QUIET_ZONE)
+                    .zip((x / MODULE_SIZE).checked_sub(QUIET_ZONE))
+                    .filter(|(row, column)| *row < width && *column < width);
+                let dark = match module {
+                    Some((row, column)) => colors[row * width + column] == Color::Dark,
+                    None => false,
+                };
+                pixels.push(if dark { 0 } else { 255 });
+            }
+        }
+
+        let mut image = Vec::new();
+        let mut encoder = png::Encoder::new(&mut image, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(qr_code_error)?;
+        writer.write_image_data(&pixels).map_err(qr_code_error)?;
+        writer.finish().map_err(qr_code_error)?;
+        Ok(image)
+    }
+}
+
+fn pay_qr_code(
+    response: &FederationResponse,
+    amount: Option<&Amount>,
+    asset: Option<&Asset>,
+) -> Result<QrCode, Error> {
+    QrCode::new(response.to_pay_uri(amount, asset)).map_err(qr_code_error)
+}
+
+fn qr_code_error<E>(err: E) -> Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    Error::QrCodeError(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::{Memo, PublicKey};
+
+    fn response() -> FederationResponse {
+        FederationResponse {
+            stellar_address: "alice*example.org".to_string(),
+            account_id: PublicKey::from_account_id(
+                "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP",
+            )
+            .unwrap(),
+            muxed_account: None,
+            memo: Some(Memo::new_id(42)),
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_to_pay_qr_svg() {
+        let svg = response().to_pay_qr_svg(None, None).unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("<svg"));
+    }
+
+    #[test]
+    fn test_to_pay_qr_png() {
+        let png = response().to_pay_qr_png(None, None).unwrap();
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        let decoder = png::Decoder::new(png.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.width, info.height);
+        assert_eq!(0, info.width as usize % MODULE_SIZE);
+    }
+}
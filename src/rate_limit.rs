@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Rate limit of the requests sent to a domain.
+///
+/// Requests are limited with a token bucket: up to `burst` requests
+/// are sent immediately, then requests are delayed so that no more
+/// than `requests_per_second` are sent on average. Concurrent and
+/// sequential requests share the same bucket.
+///
+/// The federation server also uses rate limits to limit the requests
+/// of each client, rejecting the requests over the limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub(crate) requests_per_second: f64,
+    pub(crate) burst: u32,
+}
+
+impl RateLimit {
+    /// Creates a rate limit of `requests_per_second`, with a burst of one request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_per_second` is not positive.
+    pub fn new(requests_per_second: f64) -> RateLimit {
+        assert!(
+            requests_per_second > 0.0,
+            "requests_per_second must be positive"
+        );
+        RateLimit {
+            requests_per_second,
+            burst: 1,
+        }
+    }
+
+    /// Sets the number of requests that can be sent at once.
+    pub fn burst(mut self, burst: u32) -> RateLimit {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+/// Rate limiter keeping a token bucket per domain.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    default: Option<RateLimit>,
+    domains: HashMap<String, RateLimit>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(
+        default: Option<RateLimit>,
+        domains: HashMap<String, RateLimit>,
+    ) -> RateLimiter {
+        RateLimiter {
+            default,
+            domains,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserves a request to `domain`, returning how long to wait before
+    /// sending it.
+    pub(crate) fn reserve(&self, domain: &str, now: Instant) -> Duration {
+        let domain = domain.to_lowercase();
+        let limit = match self.domains.get(&domain).or(self.default.as_ref()) {
+            Some(limit) => *limit,
+            None => return Duration::from_secs(0),
+        };
+        let burst = f64::from(limit.burst);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        let bucket = buckets.entry(domain).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * limit.requests_per_second).min(burst);
+        bucket.updated_at = now;
+        // Tokens go negative while requests are waiting, so that later
+        // requests wait behind earlier ones.
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / limit.requests_per_second)
+        }
+    }
+}
+
+/// Maximum number of concurrent batch lookups per domain.
+#[derive(Debug, Default)]
+pub(crate) struct ConcurrencyLimits {
+    default: Option<usize>,
+    domains: HashMap<String, usize>,
+}
+
+impl ConcurrencyLimits {
+    pub(crate) fn new(
+        default: Option<usize>,
+        domains: HashMap<String, usize>,
+    ) -> ConcurrencyLimits {
+        ConcurrencyLimits { default, domains }
+    }
+
+    /// Returns the maximum number of concurrent lookups to `domain`.
+    pub(crate) fn get(&self, domain: &str) -> Option<usize> {
+        self.domains
+            .get(&domain.to_lowercase())
+            .or(self.default.as_ref())
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrency_limits() {
+        let mut domains = HashMap::new();
+        domains.insert("small.example".to_string(), 2);
+        let limits = ConcurrencyLimits::new(Some(8), domains);
+        assert_eq!(Some(2), limits.get("Small.example"));
+        assert_eq!(Some(8), limits.get("example.org"));
+        assert_eq!(None, ConcurrencyLimits::default().get("example.org"));
+    }
+
+    #[test]
+    fn test_burst_then_rate() {
+        let limiter = RateLimiter::new(Some(RateLimit::new(2.0).burst(2)), HashMap::new());
+        let now = Instant::now();
+        assert_eq!(Duration::from_secs(0), limiter.reserve("example.org", now));
+        assert_eq!(Duration::from_secs(0), limiter.reserve("example.org", now));
+        assert_eq!(
+            Duration::from_millis(500),
+            limiter.reserve("example.org", now)
+        );
+        assert_eq!(
+            Duration::from_millis(1000),
+            limiter.reserve("example.org", now)
+        );
+        // Other domains have their own bucket.
+        assert_eq!(Duration::from_secs(0), limiter.reserve("other.org", now));
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            Duration::from_secs(0),
+            limiter.reserve("EXAMPLE.org", later)
+        );
+    }
+
+    #[test]
+    fn test_domain_override() {
+        let mut domains = HashMap::new();
+        domains.insert("slow.org".to_string(), RateLimit::new(1.0));
+        let limiter = RateLimiter::new(None, domains);
+        let now = Instant::now();
+        assert_eq!(Duration::from_secs(0), limiter.reserve("fast.org", now));
+        assert_eq!(Duration::from_secs(0), limiter.reserve("fast.org", now));
+        assert_eq!(Duration::from_secs(0), limiter.reserve("slow.org", now));
+        assert_eq!(Duration::from_secs(1), limiter.reserve("slow.org", now));
+    }
+}
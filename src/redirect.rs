@@ -0,0 +1,157 @@
+use crate::Error;
+use hyper::body::Bytes;
+use hyper::header::LOCATION;
+use hyper::{Response, StatusCode, Uri};
+use url::Url;
+
+/// Policy used to follow redirect responses.
+///
+/// By default redirects are not followed and a redirect response is
+/// treated as a server error.
+#[derive(Debug, Clone)]
+pub struct RedirectPolicy {
+    max_redirects: usize,
+    https_only: bool,
+    same_origin_only: bool,
+}
+
+impl RedirectPolicy {
+    /// Creates a policy that doesn't follow redirects.
+    pub fn none() -> RedirectPolicy {
+        RedirectPolicy::limited(0)
+    }
+
+    /// Creates a policy that follows at most `max_redirects` redirects.
+    ///
+    /// By default only redirects to https urls with the same origin
+    /// as the original request are followed.
+    pub fn limited(max_redirects: usize) -> RedirectPolicy {
+        RedirectPolicy {
+            max_redirects,
+            https_only: true,
+            same_origin_only: true,
+        }
+    }
+
+    /// Sets whether only redirects to https urls are followed.
+    pub fn https_only(mut self, https_only: bool) -> RedirectPolicy {
+        self.https_only = https_only;
+        self
+    }
+
+    /// Sets whether only redirects to the same origin (scheme, host,
+    /// and port) are followed.
+    pub fn same_origin_only(mut self, same_origin_only: bool) -> RedirectPolicy {
+        self.same_origin_only = same_origin_only;
+        self
+    }
+
+    /// Returns the uri to follow after receiving `response` to a request
+    /// to `uri`, or `None` if the response should be returned as is.
+    ///
+    /// `redirects` is the number of redirects already followed.
+    pub(crate) fn next(
+        &self,
+        uri: &Uri,
+        response: &Response<Bytes>,
+        redirects: usize,
+    ) -> Result<Option<Uri>, Error> {
+        if self.max_redirects == 0 || !is_redirect(response.status()) {
+            return Ok(None);
+        }
+        let location = match response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+        {
+            None => return Ok(None),
+            Some(location) => location,
+        };
+        if redirects >= self.max_redirects {
+            return Err(Error::TooManyRedirects);
+        }
+
+        let current: Url = uri.to_string().parse()?;
+        let next = current.join(location)?;
+        if self.https_only && next.scheme() != "https" {
+            return Err(Error::RedirectNotAllowed(next));
+        }
+        if self.same_origin_only && next.origin() != current.origin() {
+            return Err(Error::RedirectNotAllowed(next));
+        }
+        Ok(Some(next.to_string().parse()?))
+    }
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> RedirectPolicy {
+        RedirectPolicy::none()
+    }
+}
+
+fn is_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redirect(location: &str) -> Response<Bytes> {
+        Response::builder()
+            .status(StatusCode::FOUND)
+            .header(LOCATION, location)
+            .body(Bytes::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_redirect_none() {
+        let uri: Uri = "https://example.org/federation".parse().unwrap();
+        let policy = RedirectPolicy::none();
+        let next = policy.next(&uri, &redirect("/other"), 0).unwrap();
+        assert_eq!(None, next);
+    }
+
+    #[test]
+    fn test_redirect_same_origin() {
+        let uri: Uri = "https://example.org/federation?q=a".parse().unwrap();
+        let policy = RedirectPolicy::limited(2);
+        let next = policy
+            .next(&uri, &redirect("/v2/federation?q=a"), 0)
+            .unwrap();
+        assert_eq!(
+            Some("https://example.org/v2/federation?q=a".parse().unwrap()),
+            next
+        );
+        assert!(matches!(
+            policy.next(&uri, &redirect("https://other.org/federation"), 0),
+            Err(Error::RedirectNotAllowed(_))
+        ));
+        assert!(matches!(
+            policy.next(&uri, &redirect("/federation"), 2),
+            Err(Error::TooManyRedirects)
+        ));
+    }
+
+    #[test]
+    fn test_redirect_https_only() {
+        let uri: Uri = "https://example.org/federation".parse().unwrap();
+        let policy = RedirectPolicy::limited(1).same_origin_only(false);
+        assert!(policy
+            .next(&uri, &redirect("https://other.org/federation"), 0)
+            .unwrap()
+            .is_some());
+        assert!(matches!(
+            policy.next(&uri, &redirect("http://other.org/federation"), 0),
+            Err(Error::RedirectNotAllowed(_))
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+use crate::{split_stellar_address, Error};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use stellar_base::crypto::MuxedEd25519PublicKey;
+use stellar_base::PublicKey;
+use url::Url;
+
+/// Maximum length of the parameters of a federation request, in bytes,
+/// well above the length of the longest Stellar address.
+pub(crate) const MAX_PARAM_LENGTH: usize = 512;
+
+/// Federation request received by a federation server.
+///
+/// Parse it from the url of the request with
+/// [`parse_federation_request`], or from its query string with
+/// [`str::parse`]:
+///
+/// ```rust
+/// use stellar_federation::FederationRequest;
+///
+/// let request: FederationRequest = "type=name&q=alice*example.org".parse()?;
+/// assert_eq!(FederationRequest::Name("alice*example.org".to_string()), request);
+/// # Ok::<(), stellar_federation::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FederationRequest {
+    /// `type=name`, with the Stellar address.
+    Name(String),
+    /// `type=id`, with the account id.
+    ///
+    /// Muxed account ids starting with `M` are replaced by their base
+    /// account id.
+    Id(PublicKey),
+    /// `type=txid`, with the transaction id.
+    TxId(String),
+    /// `type=forward`, with its parameters.
+    Forward(ForwardQuery),
+}
+
+/// Parameters of a forward federation request.
+///
+/// Forward requests carry institution specific parameters, identified
+/// by the `forward_type` parameter, for example the `swift` and `acct`
+/// parameters of a `bank_account` forward request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardQuery {
+    forward_type: String,
+    params: BTreeMap<String, String>,
+}
+
+impl ForwardQuery {
+    /// Creates a query of `forward_type` with the `params` parameters.
+    pub fn new(forward_type: &str, params: BTreeMap<String, String>) -> ForwardQuery {
+        ForwardQuery {
+            forward_type: forward_type.to_string(),
+            params,
+        }
+    }
+
+    /// Returns the `forward_type` parameter.
+    pub fn forward_type(&self) -> &str {
+        &self.forward_type
+    }
+
+    /// Returns the parameters other than `type` and `forward_type`.
+    pub fn params(&self) -> &BTreeMap<String, String> {
+        &self.params
+    }
+
+    /// Returns the value of the `name` parameter, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+}
+
+/// Parses the federation request of `url`, for federation servers not
+/// using the [`server`](crate::server) module.
+///
+/// The requests with missing, empty, duplicate or unknown parameters
+/// are rejected with [`Error::InvalidRequest`], as well as invalid
+/// Stellar addresses and account ids. Id requests of muxed accounts,
+/// sent by clients with
+/// [`FederationClientBuilder::muxed_id_lookups`](crate::FederationClientBuilder::muxed_id_lookups),
+/// look up their base account id. Forward requests accept any
+/// parameter besides `type` and `forward_type`.
+pub fn parse_federation_request(url: &Url) -> Result<FederationRequest, Error> {
+    url.query().unwrap_or_default().parse()
+}
+
+impl FromStr for FederationRequest {
+    type Err = Error;
+
+    /// Parses the query string of a federation request, for example
+    /// `type=name&q=alice*example.org`.
+    fn from_str(query: &str) -> Result<FederationRequest, Error> {
+        FederationRequest::parse(query).map_err(Error::InvalidRequest)
+    }
+}
+
+impl FederationRequest {
+    /// Returns the `q` parameter of the request, if any.
+    pub fn q(&self) -> Option<String> {
+        match self {
+            FederationRequest::Name(address) => Some(address.clone()),
+            FederationRequest::Id(account_id) => Some(account_id.account_id()),
+            FederationRequest::TxId(tx_id) => Some(tx_id.clone()),
+            FederationRequest::Forward(_) => None,
+        }
+    }
+
+    /// Returns the query type of the request, the value of its `type`
+    /// parameter.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FederationRequest::Name(_) => "name",
+            FederationRequest::Id(_) => "id",
+            FederationRequest::TxId(_) => "txid",
+            FederationRequest::Forward(_) => "forward",
+        }
+    }
+
+    /// Parses the query string of a federation request, returning the
+    /// reason why it is invalid otherwise.
+    ///
+    /// Values longer than [`MAX_PARAM_LENGTH`] are rejected too.
+    pub(crate) fn parse(query: &str) -> Result<FederationRequest, String> {
+        let mut params = BTreeMap::new();
+        for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            if value.len() > MAX_PARAM_LENGTH {
+                return Err(format!("{} parameter too long", name));
+            }
+            // The parameters are passed to the handler as a map, don't
+            // silently drop some of them.
+            if params.contains_key(name.as_ref()) {
+                return Err(format!("duplicate {} parameter", name));
+            }
+            params.insert(name.into_owned(), value.into_owned());
+        }
+        let kind = params
+            .remove("type")
+            .ok_or_else(|| "missing type parameter".to_string())?;
+        if kind == "forward" {
+            let forward_type = params
+                .remove("forward_type")
+                .filter(|forward_type| !forward_type.is_empty())
+                .ok_or_else(|| "missing forward_type parameter".to_string())?;
+            return Ok(FederationRequest::Forward(ForwardQuery {
+                forward_type,
+                params,
+            }));
+        }
+        if !matches!(kind.as_str(), "name" | "id" | "txid") {
+            return Err("invalid type parameter".to_string());
+        }
+        let q = params
+            .remove("q")
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| "missing q parameter".to_string())?;
+        if let Some(name) = params.keys().next() {
+            return Err(format!("unknown {} parameter", name));
+        }
+        match kind.as_str() {
+            "name" => {
+                split_stellar_address(&q).ok_or_else(|| "invalid stellar address".to_string())?;
+                Ok(FederationRequest::Name(q))
+            }
+            "id" if q.starts_with('M') => MuxedEd25519PublicKey::from_account_id(&q)
+                .map(|muxed| FederationRequest::Id(muxed.public_key().clone()))
+                .map_err(|_| "invalid account id".to_string()),
+            "id" => PublicKey::from_account_id(&q)
+                .map(FederationRequest::Id)
+                .map_err(|_| "invalid account id".to_string()),
+            _ => Ok(FederationRequest::TxId(q)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert!(matches!(
+            FederationRequest::parse("type=name&q=alice*example.org"),
+            Ok(FederationRequest::Name(q)) if q == "alice*example.org"
+        ));
+        assert!(matches!(
+            FederationRequest::parse(
+                "type=id&q=GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+            ),
+            Ok(FederationRequest::Id(_))
+        ));
+        assert!(matches!(
+            FederationRequest::parse("type=txid&q=abc"),
+            Ok(FederationRequest::TxId(q)) if q == "abc"
+        ));
+        assert!(matches!(
+            FederationRequest::parse(
+                "type=forward&forward_type=bank_account&swift=BOPBPHMM&acct=2382376"
+            ),
+            Ok(FederationRequest::Forward(query))
+                if query.forward_type() == "bank_account"
+                    && query.get("swift") == Some("BOPBPHMM")
+                    && query.params().len() == 2
+        ));
+        assert!(matches!(
+            FederationRequest::parse("type=forward&forward_type=card&name=J%C3%BCrgen+M%C3%BCller&memo="),
+            Ok(FederationRequest::Forward(query))
+                if query.get("name") == Some("Jürgen Müller") && query.get("memo") == Some("")
+        ));
+        assert_eq!(
+            Some("missing forward_type parameter"),
+            FederationRequest::parse("type=forward&swift=BOPBPHMM")
+                .err()
+                .as_deref()
+        );
+        assert_eq!(
+            Some("missing forward_type parameter"),
+            FederationRequest::parse("type=forward&forward_type=")
+                .err()
+                .as_deref()
+        );
+        assert_eq!(
+            Some("duplicate acct parameter"),
+            FederationRequest::parse("type=forward&forward_type=card&acct=1&acct=2")
+                .err()
+                .as_deref()
+        );
+        assert_eq!(
+            Some("missing type parameter"),
+            FederationRequest::parse("q=alice*example.org")
+                .err()
+                .as_deref()
+        );
+        assert_eq!(
+            Some("missing q parameter"),
+            FederationRequest::parse("type=name").err().as_deref()
+        );
+        assert_eq!(
+            Some("invalid stellar address"),
+            FederationRequest::parse("type=name&q=alice")
+                .err()
+                .as_deref()
+        );
+        assert_eq!(
+            Some("invalid account id"),
+            FederationRequest::parse("type=id&q=alice").err().as_deref()
+        );
+        let public_key =
+            PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+                .unwrap();
+        assert_eq!(
+            Ok(FederationRequest::Id(public_key.clone())),
+            FederationRequest::parse(&format!(
+                "type=id&q={}",
+                public_key.to_muxed_account(42).account_id()
+            ))
+        );
+        assert_eq!(
+            Some("invalid type parameter"),
+            FederationRequest::parse("type=other&q=alice")
+                .err()
+                .as_deref()
+        );
+    }
+
+    #[test]
+    fn test_parse_strict() {
+        let error = |query: &str| FederationRequest::parse(query).err();
+        assert_eq!(
+            Some("duplicate q parameter".to_string()),
+            error("type=name&q=alice*example.org&q=bob*example.org")
+        );
+        assert_eq!(
+            Some("duplicate type parameter".to_string()),
+            error("type=id&type=name&q=alice*example.org")
+        );
+        assert_eq!(
+            Some("duplicate forward_type parameter".to_string()),
+            error("type=forward&forward_type=card&forward_type=bank_account")
+        );
+        assert_eq!(
+            Some("unknown memo parameter".to_string()),
+            error("type=name&q=alice*example.org&memo=1")
+        );
+        assert_eq!(
+            Some("missing q parameter".to_string()),
+            error("type=txid&q=")
+        );
+        let long = format!("{}*example.org", "a".repeat(MAX_PARAM_LENGTH));
+        assert_eq!(
+            Some("q parameter too long".to_string()),
+            error(&format!("type=name&q={}", long))
+        );
+        assert_eq!(
+            Some("acct parameter too long".to_string()),
+            error(&format!("type=forward&forward_type=card&acct={}", long))
+        );
+        let longest = format!("{}*example.org", "a".repeat(MAX_PARAM_LENGTH - 12));
+        assert!(FederationRequest::parse(&format!("type=name&q={}", longest)).is_ok());
+    }
+
+    #[test]
+    fn test_parse_federation_request() {
+        let url: Url = "https://example.org/federation?type=txid&q=abc"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            FederationRequest::TxId("abc".to_string()),
+            parse_federation_request(&url).unwrap()
+        );
+        let url: Url = "https://example.org/federation".parse().unwrap();
+        assert!(matches!(
+            parse_federation_request(&url),
+            Err(Error::InvalidRequest(detail)) if detail == "missing type parameter"
+        ));
+    }
+}
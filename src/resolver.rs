@@ -0,0 +1,87 @@
+use crate::{Error, FederationClient, FederationResponse};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use stellar_base::PublicKey;
+use url::Url;
+
+/// Future returned by the [`Resolver`] methods.
+pub type ResolveFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<FederationResponse, Error>> + Send + 'a>>;
+
+/// Source of federation responses.
+///
+/// [`FederationClient`] implements this trait by sending federation
+/// requests. Write code against this trait to swap the network for
+/// another source, for example a static table in tests, or to compose
+/// several sources.
+///
+/// ```rust
+/// use stellar_federation::{Error, FederationClient, Resolver};
+///
+/// async fn account_id(resolver: &dyn Resolver, address: &str) -> Result<String, Error> {
+///     let response = resolver.resolve_address(address).await?;
+///     Ok(response.account_id.account_id())
+/// }
+///
+/// # async fn run() -> Result<(), Error> {
+/// let client = FederationClient::new();
+/// println!("{}", account_id(&client, "with-text-memo*ceccon.me").await?);
+/// # Ok(())
+/// # }
+/// ```
+pub trait Resolver: fmt::Debug + Send + Sync {
+    /// Resolves a Stellar address.
+    fn resolve_address<'a>(&'a self, address: &'a str) -> ResolveFuture<'a>;
+
+    /// Resolves the `account_id` using the specified federation server.
+    fn resolve_account_id<'a>(
+        &'a self,
+        account_id: &'a PublicKey,
+        server: &'a Url,
+    ) -> ResolveFuture<'a>;
+
+    /// Resolves the `tx_id` using the specified federation server.
+    fn resolve_transaction_id<'a>(&'a self, tx_id: &'a str, server: &'a Url) -> ResolveFuture<'a>;
+
+    /// Resolves the `forward_parameters` using the specified federation server.
+    fn resolve_forward<'a>(
+        &'a self,
+        forward_parameters: &'a [(&'a str, &'a str)],
+        server: &'a Url,
+    ) -> ResolveFuture<'a>;
+}
+
+impl Resolver for FederationClient {
+    fn resolve_address<'a>(&'a self, address: &'a str) -> ResolveFuture<'a> {
+        Box::pin(FederationClient::resolve_address(self, address))
+    }
+
+    fn resolve_account_id<'a>(
+        &'a self,
+        account_id: &'a PublicKey,
+        server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        Box::pin(FederationClient::resolve_account_id(
+            self, account_id, server,
+        ))
+    }
+
+    fn resolve_transaction_id<'a>(&'a self, tx_id: &'a str, server: &'a Url) -> ResolveFuture<'a> {
+        Box::pin(FederationClient::resolve_transaction_id(
+            self, tx_id, server,
+        ))
+    }
+
+    fn resolve_forward<'a>(
+        &'a self,
+        forward_parameters: &'a [(&'a str, &'a str)],
+        server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        Box::pin(FederationClient::resolve_forward(
+            self,
+            forward_parameters.iter().copied(),
+            server,
+        ))
+    }
+}
@@ -0,0 +1,170 @@
+use crate::Error;
+use hyper::body::Bytes;
+use hyper::{Response, StatusCode};
+use rand::Rng;
+use std::time::Duration;
+
+/// Policy used to retry failed requests.
+///
+/// Requests are retried with an exponential backoff: the n-th retry
+/// waits `base_delay * 2^n`, capped at `max_delay`. When jitter is
+/// enabled the actual delay is picked uniformly between zero and the
+/// computed delay.
+///
+/// The policy is applied to both the `stellar.toml` and the federation
+/// requests.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    retry_statuses: Vec<StatusCode>,
+    retry_timeouts: bool,
+    retry_transport_errors: bool,
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy making at most `max_attempts` attempts,
+    /// including the first one.
+    ///
+    /// By default the policy retries timeouts, transport errors, and the
+    /// `429`, `502`, `503`, and `504` status codes, starting with a delay
+    /// of 100 milliseconds capped at 10 seconds, with jitter.
+    pub fn new(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            retry_statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_timeouts: true,
+            retry_transport_errors: true,
+        }
+    }
+
+    /// Sets the delay before the first retry.
+    pub fn base_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Sets the maximum delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> RetryPolicy {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Enables or disables jitter.
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the response status codes that are retried.
+    pub fn retry_statuses<I>(mut self, statuses: I) -> RetryPolicy
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.retry_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Sets whether requests that timed out are retried.
+    pub fn retry_timeouts(mut self, retry: bool) -> RetryPolicy {
+        self.retry_timeouts = retry;
+        self
+    }
+
+    /// Sets whether transport errors, for example connection failures,
+    /// are retried.
+    pub fn retry_transport_errors(mut self, retry: bool) -> RetryPolicy {
+        self.retry_transport_errors = retry;
+        self
+    }
+
+    /// Returns `true` if the request should be retried after `attempt`
+    /// (starting from zero) ended with `result`.
+    pub(crate) fn should_retry(
+        &self,
+        attempt: u32,
+        result: &Result<Response<Bytes>, Error>,
+    ) -> bool {
+        if attempt + 1 >= self.max_attempts {
+            return false;
+        }
+        match result {
+            Ok(response) => self.retry_statuses.contains(&response.status()),
+            Err(Error::Timeout) => self.retry_timeouts,
+            Err(Error::HyperError(_)) => self.retry_transport_errors,
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the delay to wait before retrying after `attempt`.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        if self.jitter {
+            let millis = delay.as_millis() as u64;
+            Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+        } else {
+            delay
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: StatusCode) -> Response<Bytes> {
+        let mut response = Response::new(Bytes::new());
+        *response.status_mut() = status;
+        response
+    }
+
+    #[test]
+    fn test_delay_exponential_backoff() {
+        let policy = RetryPolicy::new(10)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1))
+            .jitter(false);
+        assert_eq!(Duration::from_millis(100), policy.delay(0));
+        assert_eq!(Duration::from_millis(200), policy.delay(1));
+        assert_eq!(Duration::from_millis(800), policy.delay(3));
+        assert_eq!(Duration::from_secs(1), policy.delay(4));
+        assert_eq!(Duration::from_secs(1), policy.delay(40));
+    }
+
+    #[test]
+    fn test_delay_with_jitter() {
+        let policy = RetryPolicy::new(10).base_delay(Duration::from_millis(100));
+        for attempt in 0..5 {
+            assert!(policy.delay(attempt) <= Duration::from_millis(100 * 2u64.pow(attempt)));
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::new(3);
+        assert!(policy.should_retry(0, &Ok(response(StatusCode::SERVICE_UNAVAILABLE))));
+        assert!(policy.should_retry(1, &Err(Error::Timeout)));
+        assert!(!policy.should_retry(2, &Err(Error::Timeout)));
+        assert!(!policy.should_retry(0, &Ok(response(StatusCode::NOT_FOUND))));
+        assert!(!policy.should_retry(0, &Ok(response(StatusCode::OK))));
+        assert!(!policy.should_retry(0, &Err(Error::InvalidStellarAddress)));
+
+        let policy = RetryPolicy::new(3).retry_timeouts(false);
+        assert!(!policy.should_retry(0, &Err(Error::Timeout)));
+    }
+}
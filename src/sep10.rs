@@ -0,0 +1,618 @@
+use crate::{Error, StellarToml};
+use hyper::body::Bytes;
+use hyper::header::{HeaderValue, AUTHORIZATION};
+use hyper::{Request, StatusCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use stellar_base::transaction::TransactionEnvelope;
+use stellar_base::xdr::{XDRDeserialize, XDRSerialize};
+use stellar_base::{KeyPair, Network, PublicKey};
+use url::Url;
+
+/// Time before the expiration of a token when it's renewed.
+const EXPIRATION_MARGIN: Duration = Duration::from_secs(60);
+
+/// Signs the SEP-0010 challenge transactions of a client account.
+///
+/// The signer is called with the challenge after it has been verified,
+/// and must add the signatures of the client account, for example with
+/// a key held in a hardware wallet. [`KeyPair`] signs the challenge with
+/// its own key:
+///
+/// ```rust
+/// use stellar_base::KeyPair;
+/// use stellar_federation::FederationClient;
+///
+/// # fn run(key: KeyPair) -> Result<(), stellar_federation::Error> {
+/// let account = key.public_key().clone();
+/// let client = FederationClient::builder()
+///     .web_auth("example.org", account, key)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait ChallengeSigner: fmt::Debug + Send + Sync {
+    /// Signs the `challenge` transaction of `network`.
+    fn sign(&self, challenge: &mut TransactionEnvelope, network: &Network) -> Result<(), Error>;
+}
+
+impl ChallengeSigner for KeyPair {
+    fn sign(&self, challenge: &mut TransactionEnvelope, network: &Network) -> Result<(), Error> {
+        challenge
+            .sign(self, network)
+            .map_err(|err| web_auth_error(format!("failed to sign challenge: {}", err)))
+    }
+}
+
+/// JSON Web Token returned by a SEP-0010 web authentication endpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebAuthToken {
+    token: String,
+    expires_at: Option<SystemTime>,
+}
+
+impl WebAuthToken {
+    /// Creates a token, reading its expiration time from the `exp`
+    /// claim of the JWT, if any.
+    pub fn new(token: &str) -> WebAuthToken {
+        WebAuthToken {
+            token: token.to_string(),
+            expires_at: jwt_expiration(token),
+        }
+    }
+
+    /// Returns the encoded token, to send as a bearer token.
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Returns the expiration time of the token, if it has one.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    /// Returns `true` if the token expires within a minute of `now`.
+    pub(crate) fn is_expired(&self, now: SystemTime) -> bool {
+        match self.expires_at {
+            Some(expires_at) => now + EXPIRATION_MARGIN >= expires_at,
+            None => false,
+        }
+    }
+}
+
+/// Returns the `exp` claim of the JWT `token`.
+fn jwt_expiration(token: &str) -> Option<SystemTime> {
+    let payload = token.split('.').nth(1)?;
+    let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    let exp = claims.get("exp")?.as_u64()?;
+    Some(UNIX_EPOCH + Duration::from_secs(exp))
+}
+
+/// Client account authenticated with the web authentication endpoint
+/// of a domain.
+#[derive(Debug, Clone)]
+pub(crate) struct WebAuthAccount {
+    pub(crate) account: PublicKey,
+    pub(crate) signer: Arc<dyn ChallengeSigner>,
+}
+
+/// Web authentication endpoints of federation server hosts, by host.
+pub(crate) type WebAuthEndpoints = HashMap<String, Arc<WebAuthEndpoint>>;
+
+/// Web authentication accounts of each domain, the endpoints of their
+/// federation server hosts, and the tokens sent to each host.
+#[derive(Debug, Default)]
+pub(crate) struct WebAuthSessions {
+    accounts: HashMap<String, WebAuthAccount>,
+    endpoints: Mutex<Option<Arc<WebAuthEndpoints>>>,
+    tokens: Mutex<HashMap<String, WebAuthToken>>,
+}
+
+impl WebAuthSessions {
+    /// Authenticates the lookups sent to the federation server of
+    /// `domain` as `account`.
+    pub(crate) fn insert_account(&mut self, domain: &str, account: WebAuthAccount) {
+        self.accounts.insert(domain.to_lowercase(), account);
+    }
+
+    /// Returns `true` if no domain has an account.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Returns the domains with an account, and their account.
+    pub(crate) fn accounts(&self) -> impl Iterator<Item = (&str, &WebAuthAccount)> {
+        self.accounts
+            .iter()
+            .map(|(domain, account)| (domain.as_str(), account))
+    }
+
+    /// Returns the account of the ASCII `domain`.
+    pub(crate) fn account(&self, domain: &str) -> Option<&WebAuthAccount> {
+        self.accounts.get(domain)
+    }
+
+    /// Returns the endpoints of the federation server hosts of the
+    /// domains, if they have been resolved.
+    pub(crate) fn endpoints(&self) -> Option<Arc<WebAuthEndpoints>> {
+        self.endpoints
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Remembers the `endpoints` of the federation server hosts of the
+    /// domains.
+    pub(crate) fn set_endpoints(&self, endpoints: Arc<WebAuthEndpoints>) {
+        *self.endpoints.lock().unwrap_or_else(|err| err.into_inner()) = Some(endpoints);
+    }
+
+    /// Returns `true` if `host` has a token valid at `now`.
+    pub(crate) fn has_token(&self, host: &str, now: SystemTime) -> bool {
+        let tokens = self.tokens.lock().unwrap_or_else(|err| err.into_inner());
+        tokens
+            .get(&host.to_lowercase())
+            .is_some_and(|token| !token.is_expired(now))
+    }
+
+    /// Sends `token` to `host` from now on.
+    pub(crate) fn insert_token(&self, host: &str, token: WebAuthToken) {
+        let mut tokens = self.tokens.lock().unwrap_or_else(|err| err.into_inner());
+        tokens.insert(host.to_lowercase(), token);
+    }
+
+    /// Adds the token of the request host to `request`, if it's valid at
+    /// `now`.
+    pub(crate) fn apply(&self, request: &mut Request<()>, now: SystemTime) {
+        let host = match request.uri().host() {
+            Some(host) => host.to_lowercase(),
+            None => return,
+        };
+        let tokens = self.tokens.lock().unwrap_or_else(|err| err.into_inner());
+        let token = tokens.get(&host).filter(|token| !token.is_expired(now));
+        let value =
+            token.and_then(|token| HeaderValue::from_str(&format!("Bearer {}", token.token)).ok());
+        if let Some(mut value) = value {
+            value.set_sensitive(true);
+            request.headers_mut().insert(AUTHORIZATION, value);
+        }
+    }
+}
+
+/// SEP-0010 web authentication endpoint of a domain.
+#[derive(Debug)]
+pub(crate) struct WebAuthEndpoint {
+    url: Url,
+    home_domain: String,
+    signing_key: PublicKey,
+    network: Network,
+}
+
+#[derive(Deserialize)]
+struct ChallengeResponse {
+    transaction: String,
+    network_passphrase: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TokenRequest<'a> {
+    transaction: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl WebAuthEndpoint {
+    /// Returns the endpoint declared in the `stellar.toml` file of the
+    /// ASCII `home_domain`.
+    ///
+    /// The network defaults to the public network.
+    pub(crate) fn from_toml(
+        home_domain: &str,
+        toml: &StellarToml,
+    ) -> Result<WebAuthEndpoint, Error> {
+        let url = toml
+            .web_auth_endpoint
+            .as_ref()
+            .ok_or_else(|| web_auth_error("missing WEB_AUTH_ENDPOINT"))?
+            .to_string()
+            .parse()?;
+        let signing_key = toml
+            .signing_key
+            .clone()
+            .ok_or_else(|| web_auth_error("missing SIGNING_KEY"))?;
+        let network = match &toml.network_passphrase {
+            Some(passphrase) => Network::new(passphrase.clone()),
+            None => Network::new_public(),
+        };
+        Ok(WebAuthEndpoint {
+            url,
+            home_domain: home_domain.to_string(),
+            signing_key,
+            network,
+        })
+    }
+
+    /// Returns the ASCII home domain of the endpoint.
+    pub(crate) fn home_domain(&self) -> &str {
+        &self.home_domain
+    }
+
+    /// Returns the url of the endpoint.
+    pub(crate) fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Returns the network of the challenges.
+    pub(crate) fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// Returns the url of the challenge of `account`.
+    pub(crate) fn challenge_url(&self, account: &PublicKey) -> Url {
+        let mut url = self.url.clone();
+        url.query_pairs_mut()
+            .append_pair("account", &account.account_id())
+            .append_pair("home_domain", &self.home_domain);
+        url
+    }
+
+    /// Parses the challenge of `account` in the `body` of the response
+    /// to the challenge request, and verifies it at `now`.
+    pub(crate) fn read_challenge(
+        &self,
+        status: StatusCode,
+        body: &[u8],
+        account: &PublicKey,
+        now: SystemTime,
+    ) -> Result<TransactionEnvelope, Error> {
+        if !status.is_success() {
+            return Err(response_error("challenge", status, body));
+        }
+        let response: ChallengeResponse = serde_json::from_slice(body)?;
+        if let Some(passphrase) = &response.network_passphrase {
+            if passphrase != self.network.passphrase() {
+                return Err(web_auth_error(format!(
+                    "challenge for network {:?}",
+                    passphrase
+                )));
+            }
+        }
+        let challenge = TransactionEnvelope::from_xdr_base64(&response.transaction)
+            .map_err(|err| web_auth_error(format!("invalid challenge: {}", err)))?;
+        self.verify_challenge(&challenge, account, now)?;
+        Ok(challenge)
+    }
+
+    /// Checks that `challenge` is a valid challenge of `account`, signed
+    /// by the server, as described in SEP-0010.
+    fn verify_challenge(
+        &self,
+        challenge: &TransactionEnvelope,
+        account: &PublicKey,
+        now: SystemTime,
+    ) -> Result<(), Error> {
+        let tx = challenge
+            .as_transaction()
+            .ok_or_else(|| invalid_challenge("fee bump transaction"))?;
+        if tx.source_account().account_id() != self.signing_key.account_id() {
+            return Err(invalid_challenge("source account is not the signing key"));
+        }
+        if *tx.sequence() != 0 {
+            return Err(invalid_challenge("sequence number is not zero"));
+        }
+
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        let time_bounds = tx
+            .time_bounds()
+            .as_ref()
+            .ok_or_else(|| invalid_challenge("missing time bounds"))?;
+        let too_early = time_bounds
+            .lower()
+            .is_some_and(|lower| now < lower.timestamp());
+        let too_late = time_bounds
+            .upper()
+            .is_some_and(|upper| now > upper.timestamp());
+        if too_early || too_late {
+            return Err(invalid_challenge("expired"));
+        }
+
+        let mut operations = tx.operations().iter();
+        let first = operations
+            .next()
+            .and_then(|operation| operation.as_manage_data().map(|data| (operation, data)));
+        match first {
+            Some((operation, data))
+                if operation
+                    .source_account()
+                    .as_ref()
+                    .map(|source| source.account_id())
+                    == Some(account.account_id())
+                    && data.data_name() == format!("{} auth", self.home_domain) => {}
+            _ => return Err(invalid_challenge("invalid home domain operation")),
+        }
+        for operation in operations {
+            let data = operation
+                .as_manage_data()
+                .ok_or_else(|| invalid_challenge("operation is not manage data"))?;
+            if data.data_name() == "client_domain" {
+                continue;
+            }
+            if operation
+                .source_account()
+                .as_ref()
+                .map(|source| source.account_id())
+                != Some(self.signing_key.account_id())
+            {
+                return Err(invalid_challenge("operation source is not the signing key"));
+            }
+            if data.data_name() == "web_auth_domain" {
+                let value = data.data_value().as_ref().map(|value| value.as_bytes());
+                if value != Some(self.web_auth_domain().as_bytes()) {
+                    return Err(invalid_challenge("invalid web_auth_domain"));
+                }
+            }
+        }
+
+        let hash = tx
+            .hash(&self.network)
+            .map_err(|err| web_auth_error(format!("invalid challenge: {}", err)))?;
+        let signed = tx
+            .signatures()
+            .iter()
+            .any(|signature| signature.signature().verify(&self.signing_key, &hash));
+        if !signed {
+            return Err(invalid_challenge("not signed by the signing key"));
+        }
+        Ok(())
+    }
+
+    /// Returns the host of the endpoint, with its port if it's not the
+    /// default one.
+    fn web_auth_domain(&self) -> String {
+        let host = self.url.host_str().unwrap_or_default();
+        match self.url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        }
+    }
+
+    /// Returns the JSON body of the request posting the signed
+    /// `challenge`.
+    pub(crate) fn token_request(&self, challenge: &TransactionEnvelope) -> Result<Bytes, Error> {
+        let transaction = challenge
+            .xdr_base64()
+            .map_err(|err| web_auth_error(format!("invalid challenge: {}", err)))?;
+        let body = serde_json::to_vec(&TokenRequest {
+            transaction: &transaction,
+        })?;
+        Ok(body.into())
+    }
+
+    /// Parses the token in the `body` of the response to the token
+    /// request.
+    pub(crate) fn read_token(
+        &self,
+        status: StatusCode,
+        body: &[u8],
+    ) -> Result<WebAuthToken, Error> {
+        if !status.is_success() {
+            return Err(response_error("token", status, body));
+        }
+        let response: TokenResponse = serde_json::from_slice(body)?;
+        Ok(WebAuthToken::new(&response.token))
+    }
+}
+
+/// Returns the error of a failed `request`, with the `error` field of
+/// its JSON body, if any.
+fn response_error(request: &str, status: StatusCode, body: &[u8]) -> Error {
+    match serde_json::from_slice::<ErrorResponse>(body) {
+        Ok(response) => web_auth_error(format!(
+            "{} request failed with {}: {}",
+            request, status, response.error
+        )),
+        Err(_) => web_auth_error(format!("{} request failed with {}", request, status)),
+    }
+}
+
+fn invalid_challenge(reason: &str) -> Error {
+    web_auth_error(format!("invalid challenge: {}", reason))
+}
+
+fn web_auth_error<S: Into<String>>(reason: S) -> Error {
+    Error::WebAuthError(reason.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::account::DataValue;
+    use stellar_base::amount::Stroops;
+    use stellar_base::time_bounds::TimeBounds;
+    use stellar_base::xdr;
+    use stellar_base::{Operation, Transaction};
+
+    const NOW: u64 = 1_600_000_000;
+
+    fn endpoint(server: &KeyPair) -> WebAuthEndpoint {
+        WebAuthEndpoint {
+            url: "https://auth.example.org/auth".parse().unwrap(),
+            home_domain: "example.org".to_string(),
+            signing_key: server.public_key().clone(),
+            network: Network::new_test(),
+        }
+    }
+
+    fn challenge(server: &KeyPair, account: &PublicKey, home_domain: &str) -> TransactionEnvelope {
+        let time_bounds = TimeBounds::from_xdr(&xdr::TimeBounds {
+            min_time: xdr::TimePoint {
+                value: xdr::Uint64 { value: NOW - 10 },
+            },
+            max_time: xdr::TimePoint {
+                value: xdr::Uint64 { value: NOW + 300 },
+            },
+        })
+        .unwrap();
+        let auth = Operation::new_manage_data()
+            .with_source_account(account.clone())
+            .with_data_name(format!("{} auth", home_domain))
+            .with_data_value(Some(DataValue::from_slice(&[b'n'; 64]).unwrap()))
+            .build()
+            .unwrap();
+        let web_auth_domain = Operation::new_manage_data()
+            .with_source_account(server.public_key().clone())
+            .with_data_name("web_auth_domain".to_string())
+            .with_data_value(Some(DataValue::from_slice(b"auth.example.org").unwrap()))
+            .build()
+            .unwrap();
+        let mut tx = Transaction::builder(server.public_key().clone(), 0, Stroops::new(100))
+            .with_time_bounds(time_bounds)
+            .add_operation(auth)
+            .add_operation(web_auth_domain)
+            .into_transaction()
+            .unwrap();
+        tx.sign(server, &Network::new_test()).unwrap();
+        tx.into_envelope()
+    }
+
+    fn verify(
+        endpoint: &WebAuthEndpoint,
+        challenge: &TransactionEnvelope,
+        account: &PublicKey,
+    ) -> Result<(), Error> {
+        endpoint.verify_challenge(challenge, account, UNIX_EPOCH + Duration::from_secs(NOW))
+    }
+
+    #[test]
+    fn test_verify_challenge() {
+        let server = KeyPair::random().unwrap();
+        let client = KeyPair::random().unwrap();
+        let account = client.public_key();
+        let endpoint = endpoint(&server);
+        verify(
+            &endpoint,
+            &challenge(&server, account, "example.org"),
+            account,
+        )
+        .unwrap();
+
+        let other = KeyPair::random().unwrap();
+        let challenges = [
+            challenge(&server, account, "other.example.org"),
+            challenge(&server, other.public_key(), "example.org"),
+            challenge(&other, account, "example.org"),
+        ];
+        for challenge in challenges.iter() {
+            assert!(matches!(
+                verify(&endpoint, challenge, account),
+                Err(Error::WebAuthError(_))
+            ));
+        }
+
+        let mut unsigned = challenge(&server, account, "example.org");
+        unsigned
+            .as_transaction_mut()
+            .unwrap()
+            .signatures_mut()
+            .clear();
+        assert!(verify(&endpoint, &unsigned, account).is_err());
+        let expired = endpoint.verify_challenge(
+            &challenge(&server, account, "example.org"),
+            account,
+            UNIX_EPOCH + Duration::from_secs(NOW + 600),
+        );
+        assert!(expired.is_err());
+    }
+
+    #[test]
+    fn test_read_challenge() {
+        let server = KeyPair::random().unwrap();
+        let client = KeyPair::random().unwrap();
+        let endpoint = endpoint(&server);
+        let xdr = challenge(&server, client.public_key(), "example.org")
+            .xdr_base64()
+            .unwrap();
+        let now = UNIX_EPOCH + Duration::from_secs(NOW);
+        let body = serde_json::json!({
+            "transaction": xdr,
+            "network_passphrase": "Public Global Stellar Network ; September 2015"
+        });
+        let result = endpoint.read_challenge(
+            StatusCode::OK,
+            body.to_string().as_bytes(),
+            client.public_key(),
+            now,
+        );
+        assert!(matches!(result, Err(Error::WebAuthError(_))));
+
+        let body = serde_json::json!({ "transaction": xdr });
+        let mut challenge = endpoint
+            .read_challenge(
+                StatusCode::OK,
+                body.to_string().as_bytes(),
+                client.public_key(),
+                now,
+            )
+            .unwrap();
+        ChallengeSigner::sign(&client, &mut challenge, endpoint.network()).unwrap();
+        assert_eq!(2, challenge.as_transaction().unwrap().signatures().len());
+
+        let result = endpoint.read_challenge(
+            StatusCode::BAD_REQUEST,
+            br#"{"error":"invalid account"}"#,
+            client.public_key(),
+            now,
+        );
+        assert_eq!(
+            "web authentication error: challenge request failed with 400 Bad Request: invalid account",
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_token_expiration() {
+        // {"alg":"HS256","typ":"JWT"}.{"sub":"G...","exp":1600000000}
+        let token = WebAuthToken::new(
+            "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.eyJzdWIiOiJHLi4uIiwiZXhwIjoxNjAwMDAwMDAwfQ.c2ln",
+        );
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+            token.expires_at()
+        );
+        assert!(!token.is_expired(UNIX_EPOCH + Duration::from_secs(1_599_999_000)));
+        assert!(token.is_expired(UNIX_EPOCH + Duration::from_secs(1_599_999_950)));
+        assert_eq!(None, WebAuthToken::new("opaque").expires_at());
+    }
+
+    #[test]
+    fn test_sessions() {
+        let sessions = WebAuthSessions::default();
+        let now = UNIX_EPOCH + Duration::from_secs(NOW);
+        sessions.insert_token("Federation.example.org", WebAuthToken::new("token"));
+        assert!(sessions.has_token("federation.example.org", now));
+        let mut request = Request::get("https://federation.example.org/federation")
+            .body(())
+            .unwrap();
+        sessions.apply(&mut request, now);
+        assert_eq!("Bearer token", request.headers()[AUTHORIZATION]);
+        let mut request = Request::get("https://example.org/federation")
+            .body(())
+            .unwrap();
+        sessions.apply(&mut request, now);
+        assert!(request.headers().is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{Error, ForwardParams};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Transaction fields a SEP-0031 receiving anchor requires for an
+/// asset, to validate the parameters of a forward request before it's
+/// sent.
+///
+/// The fields are read from the response of the `/info` endpoint of the
+/// anchor:
+///
+/// ```rust
+/// use stellar_federation::Sep31Fields;
+///
+/// # fn run() -> Result<(), stellar_federation::Error> {
+/// let info = serde_json::json!({
+///     "receive": {
+///         "USDC": {
+///             "fields": {
+///                 "transaction": {
+///                     "receiver_routing_number": { "description": "routing number" },
+///                     "receiver_account_number": { "description": "account number" },
+///                     "account_type": { "description": "type of account", "choices": ["checking", "savings"] }
+///                 }
+///             }
+///         }
+///     }
+/// });
+/// let fields = Sep31Fields::from_info(&info, "USDC")?;
+/// let params = fields.forward_params(
+///     "bank_account",
+///     &[
+///         ("receiver_routing_number", "121000358"),
+///         ("receiver_account_number", "2382376"),
+///         ("account_type", "savings"),
+///     ],
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sep31Fields {
+    fields: BTreeMap<String, Sep31Field>,
+}
+
+/// Transaction field declared by a SEP-0031 anchor.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Sep31Field {
+    /// Description of the field.
+    #[serde(default)]
+    pub description: String,
+    /// Whether the field can be omitted.
+    #[serde(default)]
+    pub optional: bool,
+    /// The values the field can take, any value if `None`.
+    #[serde(default)]
+    pub choices: Option<Vec<String>>,
+}
+
+/// Invalid field of a forward request, see [`Sep31Fields::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    /// The required field is missing or empty.
+    Missing(String),
+    /// The value of the field is not one of its choices.
+    InvalidChoice {
+        /// The name of the field.
+        name: String,
+        /// The value of the field.
+        value: String,
+        /// The values the field can take.
+        choices: Vec<String>,
+    },
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::Missing(name) => write!(f, "missing {}", name),
+            FieldError::InvalidChoice {
+                name,
+                value,
+                choices,
+            } => write!(
+                f,
+                "invalid {} {:?}, expected one of {}",
+                name,
+                value,
+                choices.join(", ")
+            ),
+        }
+    }
+}
+
+impl Sep31Fields {
+    /// Returns the transaction fields of `asset_code` in `info`, the
+    /// response of the `/info` endpoint of the anchor.
+    ///
+    /// Fails with [`Error::InvalidRequest`] if the anchor doesn't
+    /// receive the asset.
+    pub fn from_info(info: &serde_json::Value, asset_code: &str) -> Result<Sep31Fields, Error> {
+        let asset = info
+            .get("receive")
+            .and_then(|receive| receive.get(asset_code))
+            .ok_or_else(|| Error::InvalidRequest(format!("asset {} not received", asset_code)))?;
+        let fields = match asset
+            .get("fields")
+            .and_then(|fields| fields.get("transaction"))
+        {
+            Some(fields) => serde_json::from_value(fields.clone())?,
+            None => BTreeMap::new(),
+        };
+        Ok(Sep31Fields { fields })
+    }
+
+    /// Returns the field `name`, if the anchor declares it.
+    pub fn get(&self, name: &str) -> Option<&Sep31Field> {
+        self.fields.get(name)
+    }
+
+    /// Returns the names and the declarations of the fields.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Sep31Field)> {
+        self.fields
+            .iter()
+            .map(|(name, field)| (name.as_str(), field))
+    }
+
+    /// Checks that `params` has all the required fields, and that the
+    /// fields with choices have one of them.
+    ///
+    /// Fails with [`Error::InvalidFields`] listing all the invalid
+    /// fields.
+    pub fn validate(&self, params: &ForwardParams) -> Result<(), Error> {
+        let mut errors = Vec::new();
+        for (name, field) in &self.fields {
+            match params.get(name).filter(|value| !value.is_empty()) {
+                None if !field.optional => errors.push(FieldError::Missing(name.clone())),
+                None => {}
+                Some(value) => match &field.choices {
+                    Some(choices) if !choices.iter().any(|choice| choice == value) => {
+                        errors.push(FieldError::InvalidChoice {
+                            name: name.clone(),
+                            value: value.to_string(),
+                            choices: choices.clone(),
+                        })
+                    }
+                    _ => {}
+                },
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidFields(errors))
+        }
+    }
+
+    /// Returns the parameters of a `forward_type` request with the
+    /// `fields` of the transaction, after validating them.
+    ///
+    /// Required fields named `type` or `forward_type` can't be sent in a
+    /// forward request, and are reported as missing.
+    pub fn forward_params<'a, K>(
+        &self,
+        forward_type: &str,
+        fields: K,
+    ) -> Result<ForwardParams, Error>
+    where
+        K: IntoIterator<Item = &'a (&'a str, &'a str)>,
+    {
+        let params = fields
+            .into_iter()
+            .fold(ForwardParams::new(forward_type), |params, (name, value)| {
+                params.param(name, value)
+            });
+        self.validate(&params)?;
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> Sep31Fields {
+        let info = serde_json::json!({
+            "receive": {
+                "USDC": {
+                    "enabled": true,
+                    "fields": {
+                        "transaction": {
+                            "receiver_routing_number": { "description": "routing number" },
+                            "receiver_account_number": { "description": "account number" },
+                            "account_type": { "description": "type", "choices": ["checking", "savings"] },
+                            "reference": { "description": "reference", "optional": true }
+                        }
+                    }
+                }
+            }
+        });
+        Sep31Fields::from_info(&info, "USDC").unwrap()
+    }
+
+    #[test]
+    fn test_from_info() {
+        let fields = fields();
+        assert_eq!(4, fields.iter().count());
+        assert!(fields.get("reference").unwrap().optional);
+        assert!(matches!(
+            Sep31Fields::from_info(&serde_json::json!({ "receive": {} }), "USDC"),
+            Err(Error::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_forward_params() {
+        let fields = fields();
+        let params = fields
+            .forward_params(
+                "bank_account",
+                &[
+                    ("receiver_routing_number", "121000358"),
+                    ("receiver_account_number", "2382376"),
+                    ("account_type", "savings"),
+                ],
+            )
+            .unwrap();
+        assert_eq!("bank_account", params.forward_type());
+        assert_eq!(Some("savings"), params.get("account_type"));
+
+        let result = fields.forward_params(
+            "bank_account",
+            &[
+                ("receiver_routing_number", "121000358"),
+                ("account_type", "brokerage"),
+            ],
+        );
+        let errors = match result {
+            Err(Error::InvalidFields(errors)) => errors,
+            _ => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(
+            vec![
+                FieldError::InvalidChoice {
+                    name: "account_type".to_string(),
+                    value: "brokerage".to_string(),
+                    choices: vec!["checking".to_string(), "savings".to_string()],
+                },
+                FieldError::Missing("receiver_account_number".to_string()),
+            ],
+            errors
+        );
+        assert_eq!(
+            "invalid account_type \"brokerage\", expected one of checking, savings",
+            errors[0].to_string()
+        );
+    }
+}
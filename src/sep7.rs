@@ -0,0 +1,102 @@
+use crate::FederationResponse;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use stellar_base::amount::Amount;
+use stellar_base::{Asset, Memo};
+
+/// Characters percent-encoded in the values of a SEP-0007 URI, all but
+/// the unreserved characters of RFC 3986.
+const VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+impl FederationResponse {
+    /// Returns a SEP-0007 `web+stellar:pay` URI to pay the resolved
+    /// account, with the memo of the response.
+    ///
+    /// The `amount` and the `asset` are left for the wallet to choose
+    /// when `None`, the asset defaults to lumens.
+    ///
+    /// ```rust
+    /// # use stellar_federation::FederationResponse;
+    /// # fn run(response: FederationResponse) {
+    /// // For example `web+stellar:pay?destination=GBUF…&memo=42&memo_type=MEMO_ID`.
+    /// let uri = response.to_pay_uri(None, None);
+    /// # }
+    /// ```
+    pub fn to_pay_uri(&self, amount: Option<&Amount>, asset: Option<&Asset>) -> String {
+        let mut params = vec![("destination", self.destination().account_id())];
+        if let Some(amount) = amount {
+            params.push(("amount", amount.to_string()));
+        }
+        if let Some(Asset::Credit(asset)) = asset {
+            params.push(("asset_code", asset.code().to_string()));
+            params.push(("asset_issuer", asset.issuer().account_id()));
+        }
+        let memo = match &self.memo {
+            None | Some(Memo::None) => None,
+            Some(Memo::Text(text)) => Some((text.clone(), "MEMO_TEXT")),
+            Some(Memo::Id(id)) => Some((id.to_string(), "MEMO_ID")),
+            Some(Memo::Hash(hash)) => Some((base64::encode(hash), "MEMO_HASH")),
+            Some(Memo::Return(hash)) => Some((base64::encode(hash), "MEMO_RETURN")),
+        };
+        if let Some((memo, memo_type)) = memo {
+            params.push(("memo", memo));
+            params.push(("memo_type", memo_type.to_string()));
+        }
+        let query: Vec<String> = params
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, utf8_percent_encode(value, VALUE)))
+            .collect();
+        format!("web+stellar:pay?{}", query.join("&"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::asset::CreditAsset;
+    use stellar_base::PublicKey;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    fn response(memo: Option<Memo>) -> FederationResponse {
+        FederationResponse {
+            stellar_address: "alice*example.org".to_string(),
+            account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+            muxed_account: None,
+            memo,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_to_pay_uri() {
+        assert_eq!(
+            format!("web+stellar:pay?destination={}", ACCOUNT_ID),
+            response(None).to_pay_uri(None, None)
+        );
+
+        let amount: Amount = "120.5".parse().unwrap();
+        let issuer = PublicKey::from_account_id(ACCOUNT_ID).unwrap();
+        let asset = Asset::Credit(CreditAsset::new("USDC".to_string(), issuer).unwrap());
+        let text = response(Some(Memo::new_text("pay me & you").unwrap()));
+        assert_eq!(
+            format!(
+                "web+stellar:pay?destination={0}&amount=120.5000000&asset_code=USDC&asset_issuer={0}\
+                 &memo=pay%20me%20%26%20you&memo_type=MEMO_TEXT",
+                ACCOUNT_ID
+            ),
+            text.to_pay_uri(Some(&amount), Some(&asset))
+        );
+
+        let uri = response(Some(Memo::new_id(42))).to_pay_uri(None, Some(&Asset::Native));
+        assert!(uri.ends_with("&memo=42&memo_type=MEMO_ID"));
+        let uri = response(Some(Memo::new_hash(&[0xfb; 32]).unwrap())).to_pay_uri(None, None);
+        assert!(uri.contains("&memo=%2B%2Fv7%2B%2Fv7"));
+        assert!(uri.ends_with("%2B%2Fs%3D&memo_type=MEMO_HASH"));
+        let uri = response(Some(Memo::new_return(&[1; 32]).unwrap())).to_pay_uri(None, None);
+        assert!(uri.ends_with("&memo_type=MEMO_RETURN"));
+    }
+}
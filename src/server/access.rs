@@ -0,0 +1,229 @@
+use crate::{Error, RateLimit};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Number of clients tracked by the rate limiter before the clients
+/// with a full bucket are forgotten.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Network of IP addresses, in CIDR notation, for example `10.0.0.0/8`
+/// or `2001:db8::/32`. A single address is a network of one address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    /// Returns `true` if `addr` is in the network.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                prefix_matches(u32::from(network), u32::from(addr), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                prefix_matches(u128::from(network), u128::from(addr), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches<T>(network: T, addr: T, prefix_len: u8) -> bool
+where
+    T: Copy + Eq + std::ops::BitXor<Output = T> + std::ops::Shr<u32, Output = T> + From<u8>,
+{
+    let bits = (std::mem::size_of::<T>() * 8) as u32;
+    let prefix_len = u32::from(prefix_len);
+    prefix_len == 0 || (network ^ addr) >> (bits - prefix_len) == T::from(0)
+}
+
+impl FromStr for IpNetwork {
+    type Err = Error;
+
+    fn from_str(network: &str) -> Result<IpNetwork, Error> {
+        let (addr, prefix_len) = match network.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (network, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| Error::InvalidIpNetwork)?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| Error::InvalidIpNetwork)?,
+            None => max_prefix_len,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(Error::InvalidIpNetwork);
+        }
+        Ok(IpNetwork {
+            addr: addr.to_canonical(),
+            prefix_len,
+        })
+    }
+}
+
+/// Outcome of [`AccessControl::check`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Access {
+    Allowed,
+    Denied,
+    /// The client exceeded the rate limit and can retry after the
+    /// duration.
+    Limited(Duration),
+}
+
+/// Access control of the clients of the server, by IP address.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AccessControl {
+    allow: Vec<IpNetwork>,
+    deny: Vec<IpNetwork>,
+    limiter: Option<Arc<ClientRateLimiter>>,
+}
+
+impl AccessControl {
+    pub(crate) fn allow(&mut self, network: IpNetwork) {
+        self.allow.push(network);
+    }
+
+    pub(crate) fn deny(&mut self, network: IpNetwork) {
+        self.deny.push(network);
+    }
+
+    pub(crate) fn rate_limit(&mut self, limit: RateLimit) {
+        self.limiter = Some(Arc::new(ClientRateLimiter::new(limit)));
+    }
+
+    /// Returns whether the client at `addr` is denied access.
+    pub(crate) fn is_denied(&self, addr: IpAddr) -> bool {
+        self.deny.iter().any(|network| network.contains(addr))
+    }
+
+    /// Checks a request of the client at `addr`.
+    pub(crate) fn check(&self, addr: IpAddr, now: Instant) -> Access {
+        if self.is_denied(addr) {
+            return Access::Denied;
+        }
+        if self.allow.iter().any(|network| network.contains(addr)) {
+            return Access::Allowed;
+        }
+        match &self.limiter {
+            Some(limiter) => limiter.check(addr.to_canonical(), now),
+            None => Access::Allowed,
+        }
+    }
+}
+
+/// Rate limiter keeping a token bucket per client.
+///
+/// Unlike the client rate limiter, requests over the limit are
+/// rejected instead of delayed.
+#[derive(Debug)]
+struct ClientRateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * limit.requests_per_second)
+            .min(f64::from(limit.burst));
+        self.updated_at = now;
+    }
+}
+
+impl ClientRateLimiter {
+    fn new(limit: RateLimit) -> ClientRateLimiter {
+        ClientRateLimiter {
+            limit,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn check(&self, addr: IpAddr, now: Instant) -> Access {
+        let burst = f64::from(self.limit.burst);
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&addr) {
+            // Clients with a full bucket are in the same state as new
+            // clients.
+            buckets.retain(|_, bucket| {
+                bucket.refill(&self.limit, now);
+                bucket.tokens < burst
+            });
+        }
+        let bucket = buckets.entry(addr).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        bucket.refill(&self.limit, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Access::Allowed
+        } else {
+            let wait = (1.0 - bucket.tokens) / self.limit.requests_per_second;
+            Access::Limited(Duration::from_secs_f64(wait))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_network() {
+        let network: IpNetwork = "10.1.0.0/16".parse().unwrap();
+        assert!(network.contains("10.1.2.3".parse().unwrap()));
+        assert!(network.contains("::ffff:10.1.2.3".parse().unwrap()));
+        assert!(!network.contains("10.2.0.1".parse().unwrap()));
+        let network: IpNetwork = "2001:db8::/32".parse().unwrap();
+        assert!(network.contains("2001:db8::1".parse().unwrap()));
+        assert!(!network.contains("2001:db9::1".parse().unwrap()));
+        let network: IpNetwork = "192.0.2.1".parse().unwrap();
+        assert!(network.contains("192.0.2.1".parse().unwrap()));
+        assert!(!network.contains("192.0.2.2".parse().unwrap()));
+        let network: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(network.contains("192.0.2.2".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("10.0.0/8".parse::<IpNetwork>().is_err());
+        assert!("10.0.0.0/a".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_access_control() {
+        let mut access = AccessControl::default();
+        access.rate_limit(RateLimit::new(2.0).burst(2));
+        access.allow("10.0.0.0/8".parse().unwrap());
+        access.deny("10.0.0.1".parse().unwrap());
+        let now = Instant::now();
+        let client = "192.0.2.1".parse().unwrap();
+        assert_eq!(Access::Allowed, access.check(client, now));
+        assert_eq!(Access::Allowed, access.check(client, now));
+        assert_eq!(
+            Access::Limited(Duration::from_millis(500)),
+            access.check(client, now)
+        );
+        let later = now + Duration::from_millis(500);
+        assert_eq!(Access::Allowed, access.check(client, later));
+        // Other clients have their own bucket.
+        let other = "192.0.2.2".parse().unwrap();
+        assert_eq!(Access::Allowed, access.check(other, now));
+        // Allowed networks are not rate limited.
+        let allowed = "10.0.0.2".parse().unwrap();
+        for _ in 0..10 {
+            assert_eq!(Access::Allowed, access.check(allowed, now));
+        }
+        let denied = "10.0.0.1".parse().unwrap();
+        assert_eq!(Access::Denied, access.check(denied, now));
+    }
+}
@@ -0,0 +1,340 @@
+use super::tls::Handshake;
+use super::{FederationHandler, FederationService};
+use crate::Error;
+use futures_util::StreamExt;
+use rustls_acme::caches::DirCache;
+use rustls_acme::{is_tls_alpn_challenge, AcmeConfig};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::LazyConfigAcceptor;
+
+/// Automatic TLS certificates for the server, obtained and renewed
+/// with ACME, for example from Let's Encrypt.
+///
+/// The certificates are validated with the `tls-alpn-01` challenge, so
+/// the server must be reachable on port 443 of the domains. They are
+/// stored in a cache directory, so that they are not requested again
+/// on restarts, which would quickly hit the rate limits of Let's
+/// Encrypt.
+#[derive(Debug, Clone)]
+pub struct AcmeTls {
+    domains: Vec<String>,
+    contacts: Vec<String>,
+    cache_dir: PathBuf,
+    production: bool,
+}
+
+impl AcmeTls {
+    /// Creates a configuration obtaining a certificate for `domains`,
+    /// from the Let's Encrypt staging environment, and caching it in
+    /// `cache_dir`.
+    ///
+    /// Switch to the production environment with
+    /// [`AcmeTls::production`] once the setup works.
+    pub fn new<P: AsRef<Path>>(domains: &[&str], cache_dir: P) -> AcmeTls {
+        AcmeTls {
+            domains: domains.iter().map(|domain| domain.to_string()).collect(),
+            contacts: Vec::new(),
+            cache_dir: cache_dir.as_ref().to_path_buf(),
+            production: false,
+        }
+    }
+
+    /// Adds a contact email, used by the certificate authority to warn
+    /// about expiring certificates.
+    pub fn contact(mut self, email: &str) -> AcmeTls {
+        self.contacts.push(format!("mailto:{}", email));
+        self
+    }
+
+    /// Uses the Let's Encrypt production environment, issuing trusted
+    /// certificates, instead of the staging one.
+    pub fn production(mut self, production: bool) -> AcmeTls {
+        self.production = production;
+        self
+    }
+
+    /// Checks the domains and contacts, and creates the cache
+    /// directory.
+    fn prepare(&self) -> Result<(), Error> {
+        if self.domains.is_empty() {
+            return Err(Error::InvalidConfig("missing acme domain".to_string()));
+        }
+        if let Some(domain) = self.domains.iter().find(|domain| !is_domain(domain)) {
+            return Err(Error::InvalidConfig(format!(
+                "invalid acme domain {}",
+                domain
+            )));
+        }
+        let invalid_contact = self.contacts.iter().find(|contact| {
+            !contact
+                .strip_prefix("mailto:")
+                .and_then(|email| email.split_once('@'))
+                .is_some_and(|(user, domain)| {
+                    !user.is_empty()
+                        && !user.contains(|c: char| c.is_whitespace() || c.is_control())
+                        && is_domain(domain)
+                })
+        });
+        if let Some(contact) = invalid_contact {
+            return Err(Error::InvalidConfig(format!(
+                "invalid acme contact {}",
+                contact
+            )));
+        }
+        fs::create_dir_all(&self.cache_dir)?;
+        Ok(())
+    }
+}
+
+/// Returns whether `domain` is a fully qualified domain name, wildcards
+/// can't be validated with `tls-alpn-01`.
+fn is_domain(domain: &str) -> bool {
+    domain.len() <= 253
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        })
+}
+
+/// Rustls configurations of the ACME handshakes.
+#[derive(Clone)]
+pub(super) struct AcmeConfigs {
+    default: Arc<rustls::ServerConfig>,
+    challenge: Arc<rustls::ServerConfig>,
+}
+
+impl AcmeConfigs {
+    /// Completes the handshake of `stream`, answering the challenges of
+    /// the certificate authority.
+    pub(super) async fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> io::Result<Option<TlsStream<TcpStream>>> {
+        let start = LazyConfigAcceptor::new(Default::default(), stream).await?;
+        if is_tls_alpn_challenge(&start.client_hello()) {
+            let mut stream = start.into_stream(self.challenge.clone()).await?;
+            stream.shutdown().await?;
+            Ok(None)
+        } else {
+            start.into_stream(self.default.clone()).await.map(Some)
+        }
+    }
+}
+
+impl fmt::Debug for AcmeConfigs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcmeConfigs").finish()
+    }
+}
+
+impl<H: FederationHandler + 'static> FederationService<H> {
+    /// Serves federation requests over https on `addr`, with
+    /// certificates obtained and renewed automatically, until the
+    /// server fails.
+    ///
+    /// Fails with [`Error::InvalidConfig`] if a domain or contact of
+    /// `acme` is invalid, before binding `addr`. The cache directory is
+    /// created if it doesn't exist.
+    ///
+    /// ```rust,no_run
+    /// # use stellar_federation::server::{FederationHandler, FederationService};
+    /// use stellar_federation::server::AcmeTls;
+    ///
+    /// # async fn run<H: FederationHandler + 'static>(handler: H) -> Result<(), stellar_federation::Error> {
+    /// let acme = AcmeTls::new(&["federation.example.org"], "/var/lib/federation/acme")
+    ///     .contact("admin@example.org")
+    ///     .production(true);
+    /// FederationService::new(handler)
+    ///     .serve_acme(([0, 0, 0, 0], 443).into(), acme)
+    ///     .await
+    /// # }
+    /// ```
+    pub async fn serve_acme(self, addr: SocketAddr, acme: AcmeTls) -> Result<(), Error> {
+        self.serve_acme_with_shutdown(addr, acme, std::future::pending())
+            .await
+    }
+
+    /// Serves federation requests over https on `addr`, with
+    /// certificates obtained and renewed automatically, until `signal`
+    /// completes, see [`FederationService::serve_with_shutdown`].
+    pub async fn serve_acme_with_shutdown<S>(
+        self,
+        addr: SocketAddr,
+        acme: AcmeTls,
+        signal: S,
+    ) -> Result<(), Error>
+    where
+        S: Future<Output = ()> + Send + 'static,
+    {
+        acme.prepare()?;
+        let listener = TcpListener::bind(addr).await?;
+        let mut state = AcmeConfig::new(acme.domains)
+            .contact(acme.contacts)
+            .cache(DirCache::new(acme.cache_dir))
+            .directory_lets_encrypt(acme.production)
+            .state();
+        let mut default = rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(state.resolver());
+        default.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let configs = AcmeConfigs {
+            default: Arc::new(default),
+            challenge: state.challenge_rustls_config(),
+        };
+        // The state orders and renews the certificates as it is polled,
+        // retrying after errors.
+        let renewer = tokio::spawn(async move { while state.next().await.is_some() {} });
+        let result = self
+            .serve_handshake(listener, Handshake::Acme(configs), signal)
+            .await;
+        renewer.abort();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tls::server_config;
+    use super::super::tls::tests::{connect, CERTIFICATE, KEY};
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "stellar-federation-acme-{}-{}",
+            name,
+            std::process::id()
+        ))
+    }
+
+    async fn accept(
+        listener: &TcpListener,
+        configs: &AcmeConfigs,
+    ) -> io::Result<Option<TlsStream<TcpStream>>> {
+        let (stream, _) = listener.accept().await?;
+        configs.accept(stream).await
+    }
+
+    #[test]
+    fn test_builder() {
+        let acme = AcmeTls::new(&["example.org", "www.example.org"], "/var/lib/acme");
+        assert_eq!(vec!["example.org", "www.example.org"], acme.domains);
+        assert!(acme.contacts.is_empty());
+        assert_eq!(PathBuf::from("/var/lib/acme"), acme.cache_dir);
+        assert!(!acme.production);
+
+        let acme = acme
+            .contact("admin@example.org")
+            .contact("ops@example.org")
+            .production(true);
+        assert_eq!(
+            vec!["mailto:admin@example.org", "mailto:ops@example.org"],
+            acme.contacts
+        );
+        assert!(acme.production);
+    }
+
+    #[test]
+    fn test_prepare() {
+        let dir = temp_dir("prepare");
+        let _ = fs::remove_dir_all(&dir);
+        let cache_dir = dir.join("certificates");
+        let acme =
+            AcmeTls::new(&["federation.example.org"], &cache_dir).contact("admin@example.org");
+        acme.prepare().unwrap();
+        assert!(cache_dir.is_dir());
+
+        let invalid_domains: &[&[&str]] = &[
+            &[],
+            &["localhost"],
+            &["*.example.org"],
+            &["-example.org"],
+            &["example..org"],
+            &["exa mple.org"],
+            &["example.org", "https://example.org"],
+        ];
+        for domains in invalid_domains {
+            assert!(
+                matches!(
+                    AcmeTls::new(domains, &cache_dir).prepare(),
+                    Err(Error::InvalidConfig(_))
+                ),
+                "{:?}",
+                domains
+            );
+        }
+        for contact in &["admin", "@example.org", "admin@", "ad min@example.org"] {
+            assert!(
+                matches!(
+                    AcmeTls::new(&["example.org"], &cache_dir)
+                        .contact(contact)
+                        .prepare(),
+                    Err(Error::InvalidConfig(_))
+                ),
+                "{}",
+                contact
+            );
+        }
+
+        // The cache directory can't be created over a file.
+        let file = dir.join("file");
+        fs::write(&file, b"").unwrap();
+        assert!(matches!(
+            AcmeTls::new(&["example.org"], file.join("certificates")).prepare(),
+            Err(Error::IoError(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_accept() {
+        let default = server_config(CERTIFICATE.as_bytes(), KEY.as_bytes()).unwrap();
+        let mut challenge = server_config(CERTIFICATE.as_bytes(), KEY.as_bytes()).unwrap();
+        challenge.alpn_protocols = vec![b"acme-tls/1".to_vec()];
+        let configs = AcmeConfigs {
+            default: Arc::new(default),
+            challenge: Arc::new(challenge),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Other client hellos, with or without ALPN, get the default
+        // configuration and a stream carrying requests.
+        let (client, accepted) =
+            tokio::join!(connect(addr, &[b"http/1.1"]), accept(&listener, &configs));
+        assert_eq!(
+            Some(&b"http/1.1"[..]),
+            client.unwrap().get_ref().1.alpn_protocol()
+        );
+        let accepted = accepted.unwrap().unwrap();
+        assert_eq!(Some(&b"http/1.1"[..]), accepted.get_ref().1.alpn_protocol());
+
+        let (client, accepted) = tokio::join!(connect(addr, &[]), accept(&listener, &configs));
+        assert_eq!(None, client.unwrap().get_ref().1.alpn_protocol());
+        assert!(accepted.unwrap().is_some());
+
+        // Challenges are answered and the connection closed.
+        let (client, accepted) =
+            tokio::join!(connect(addr, &[b"acme-tls/1"]), accept(&listener, &configs));
+        assert_eq!(
+            Some(&b"acme-tls/1"[..]),
+            client.unwrap().get_ref().1.alpn_protocol()
+        );
+        assert!(accepted.unwrap().is_none());
+    }
+}
@@ -0,0 +1,484 @@
+use super::{error_response, json_response, shutdown, FederationHandler};
+use crate::{normalize_stellar_address, response_json, Error, FederationResponse};
+use hyper::body::{Buf, HttpBody};
+use hyper::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use hyper::service::make_service_fn;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use percent_encoding::percent_decode_str;
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_service::Service;
+
+/// Maximum size of the request bodies, in bytes.
+const MAX_BODY_LEN: usize = 16 * 1024;
+
+/// Future returned by the [`RecordStore`] methods.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, Error>> + Send + 'a>>;
+
+/// Source of records that can also be modified, managed with an
+/// [`AdminService`].
+///
+/// The Stellar addresses of the records are normalized, with their
+/// domain in lowercase ASCII form.
+pub trait RecordStore: FederationHandler {
+    /// Returns all the records, ordered by Stellar address.
+    fn list_records(&self) -> StoreFuture<'_, Vec<FederationResponse>>;
+
+    /// Adds `record`, failing with [`Error::RecordExists`] if there is
+    /// already a record with its Stellar address.
+    fn create_record(&self, record: FederationResponse) -> StoreFuture<'_, ()>;
+
+    /// Replaces the record of `address` with `record`, renaming it if
+    /// their Stellar addresses differ.
+    ///
+    /// Fails with [`Error::NotFound`] if there is no record of
+    /// `address`, and with [`Error::RecordExists`] if the record is
+    /// renamed to the address of another record.
+    fn update_record<'a>(
+        &'a self,
+        address: &'a str,
+        record: FederationResponse,
+    ) -> StoreFuture<'a, ()>;
+
+    /// Deletes the record of `address`, failing with [`Error::NotFound`]
+    /// if there is none.
+    fn delete_record<'a>(&'a self, address: &'a str) -> StoreFuture<'a, ()>;
+}
+
+/// Service managing the records of a [`RecordStore`], for example for a
+/// support team.
+///
+/// Requests must be authenticated with the token of the service, in an
+/// `Authorization: Bearer <token>` header, and are answered with JSON.
+/// The records have the fields of the federation responses:
+///
+/// - `GET /admin/records` lists the records.
+/// - `POST /admin/records` creates a record, answering
+///   `409 Conflict` if there is already a record with its address.
+/// - `GET /admin/records/<address>` returns a record.
+/// - `PUT /admin/records/<address>` replaces a record, renaming it if
+///   the address of the new record differs.
+/// - `DELETE /admin/records/<address>` deletes a record.
+///
+/// The service is usually served on a separate address, reachable only
+/// from the internal network, with [`AdminService::serve`]. It can also
+/// be mounted next to the [`FederationService`] by routing the paths
+/// under its prefix, `/admin` by default, to it.
+///
+/// [`FederationService`]: super::FederationService
+pub struct AdminService<S> {
+    store: Arc<S>,
+    token: Arc<str>,
+    prefix: Arc<str>,
+}
+
+impl<S> Clone for AdminService<S> {
+    fn clone(&self) -> Self {
+        AdminService {
+            store: self.store.clone(),
+            token: self.token.clone(),
+            prefix: self.prefix.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for AdminService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminService")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl<S: RecordStore + 'static> AdminService<S> {
+    /// Creates a service managing the records of `store`, for the
+    /// requests authenticated with `token`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `token` is empty.
+    pub fn new(store: S, token: &str) -> AdminService<S> {
+        assert!(!token.is_empty(), "empty admin token");
+        AdminService {
+            store: Arc::new(store),
+            token: token.into(),
+            prefix: "/admin".into(),
+        }
+    }
+
+    /// Serves the records under `prefix` instead of `/admin`, for
+    /// example `/internal/federation`.
+    pub fn prefix(mut self, prefix: &str) -> AdminService<S> {
+        self.prefix = prefix.trim_end_matches('/').into();
+        self
+    }
+
+    /// Returns the store of the service.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Answers the admin `request`.
+    pub async fn handle<B>(&self, request: Request<B>) -> Response<Body>
+    where
+        B: HttpBody,
+    {
+        let path = match request.uri().path().strip_prefix(&*self.prefix) {
+            Some(path) => path,
+            None => return error_response(StatusCode::NOT_FOUND, "not found"),
+        };
+        let address = match path.strip_prefix("/records") {
+            Some("") => None,
+            Some(address) => match address.strip_prefix('/') {
+                Some(address) => match percent_decode_str(address).decode_utf8() {
+                    Ok(address) => Some(address.into_owned()),
+                    Err(_) => return error_response(StatusCode::BAD_REQUEST, "invalid path"),
+                },
+                None => return error_response(StatusCode::NOT_FOUND, "not found"),
+            },
+            None => return error_response(StatusCode::NOT_FOUND, "not found"),
+        };
+        if !self.authorized(&request) {
+            let mut response = error_response(StatusCode::UNAUTHORIZED, "unauthorized");
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            return response;
+        }
+        let address = match address.map(|address| normalize_stellar_address(&address)) {
+            Some(None) => {
+                return error_response(StatusCode::BAD_REQUEST, "invalid stellar address")
+            }
+            Some(Some(address)) => Some(address),
+            None => None,
+        };
+        let method = request.method().clone();
+        let result = match (method, address) {
+            (Method::GET, None) => self.store.list_records().await.map(|records| {
+                let records: Vec<_> = records.iter().map(response_json).collect();
+                json_response(StatusCode::OK, &records.into())
+            }),
+            (Method::POST, None) => match read_record(request.into_body()).await {
+                Ok(record) => {
+                    let body = response_json(&record);
+                    self.store
+                        .create_record(record)
+                        .await
+                        .map(|()| json_response(StatusCode::CREATED, &body))
+                }
+                Err(response) => return response,
+            },
+            (Method::GET, Some(address)) => match self.store.lookup_name(&address).await {
+                Ok(Some(record)) => Ok(json_response(StatusCode::OK, &response_json(&record))),
+                Ok(None) => Err(Error::NotFound),
+                Err(err) => Err(err),
+            },
+            (Method::PUT, Some(address)) => match read_record(request.into_body()).await {
+                Ok(record) => {
+                    let body = response_json(&record);
+                    self.store
+                        .update_record(&address, record)
+                        .await
+                        .map(|()| json_response(StatusCode::OK, &body))
+                }
+                Err(response) => return response,
+            },
+            (Method::DELETE, Some(address)) => self.store.delete_record(&address).await.map(|()| {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::NO_CONTENT;
+                response
+            }),
+            _ => return error_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+        };
+        match result {
+            Ok(response) => response,
+            Err(Error::NotFound) => error_response(StatusCode::NOT_FOUND, "not found"),
+            Err(Error::RecordExists) => {
+                error_response(StatusCode::CONFLICT, "record already exists")
+            }
+            Err(Error::InvalidRequest(detail)) => error_response(StatusCode::BAD_REQUEST, &detail),
+            Err(Error::NotImplemented) => {
+                error_response(StatusCode::NOT_IMPLEMENTED, "not supported")
+            }
+            Err(_) => error_response(StatusCode::INTERNAL_SERVER_ERROR, "internal server error"),
+        }
+    }
+
+    fn authorized<B>(&self, request: &Request<B>) -> bool {
+        let token = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match token {
+            Some(token) => constant_time_eq(token.trim().as_bytes(), self.token.as_bytes()),
+            None => false,
+        }
+    }
+
+    /// Serves admin requests on `addr` until the server fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), Error> {
+        self.serve_with_shutdown(addr, std::future::pending()).await
+    }
+
+    /// Serves admin requests on `addr` until `signal` completes, like
+    /// [`FederationService::serve_with_shutdown`].
+    ///
+    /// [`FederationService::serve_with_shutdown`]: super::FederationService::serve_with_shutdown
+    pub async fn serve_with_shutdown<F>(self, addr: SocketAddr, signal: F) -> Result<(), Error>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (signal, deadline) = shutdown::grace_period(signal, shutdown::DEFAULT_GRACE_PERIOD);
+        let make_service = make_service_fn(move |_| {
+            let service = self.clone();
+            async move { Ok::<_, Infallible>(service) }
+        });
+        let server = Server::try_bind(&addr)?
+            .serve(make_service)
+            .with_graceful_shutdown(signal);
+        if let Some(result) = shutdown::run_until(server, deadline).await {
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl<S, B> Service<Request<B>> for AdminService<S>
+where
+    S: RecordStore + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+{
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let service = self.clone();
+        Box::pin(async move { Ok(service.handle(request).await) })
+    }
+}
+
+/// Reads the record of a request body, answering invalid records with
+/// `400 Bad Request`.
+async fn read_record<B: HttpBody>(body: B) -> Result<FederationResponse, Response<Body>> {
+    let mut body = Box::pin(body);
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let mut chunk =
+            chunk.map_err(|_| error_response(StatusCode::BAD_REQUEST, "invalid body"))?;
+        if bytes.len() + chunk.remaining() > MAX_BODY_LEN {
+            return Err(error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "body too large",
+            ));
+        }
+        while chunk.has_remaining() {
+            let len = chunk.chunk().len();
+            bytes.extend_from_slice(chunk.chunk());
+            chunk.advance(len);
+        }
+    }
+    let mut record: FederationResponse = serde_json::from_slice(&bytes)
+        .map_err(|err| error_response(StatusCode::BAD_REQUEST, &err.to_string()))?;
+    record.stellar_address = normalize_stellar_address(&record.stellar_address)
+        .ok_or_else(|| error_response(StatusCode::BAD_REQUEST, "invalid stellar address"))?;
+    Ok(record)
+}
+
+/// Compares `a` and `b` in a time independent of their contents.
+pub(super) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::HandlerFuture;
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    #[derive(Debug, Default)]
+    struct Memory(Mutex<BTreeMap<String, FederationResponse>>);
+
+    impl FederationHandler for Memory {
+        fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+            let record = self.0.lock().unwrap().get(address).cloned();
+            Box::pin(async move { Ok(record) })
+        }
+    }
+
+    impl RecordStore for Memory {
+        fn list_records(&self) -> StoreFuture<'_, Vec<FederationResponse>> {
+            let records = self.0.lock().unwrap().values().cloned().collect();
+            Box::pin(async move { Ok(records) })
+        }
+
+        fn create_record(&self, record: FederationResponse) -> StoreFuture<'_, ()> {
+            let mut records = self.0.lock().unwrap();
+            let result = match records.contains_key(&record.stellar_address) {
+                true => Err(Error::RecordExists),
+                false => {
+                    records.insert(record.stellar_address.clone(), record);
+                    Ok(())
+                }
+            };
+            Box::pin(async move { result })
+        }
+
+        fn update_record<'a>(
+            &'a self,
+            address: &'a str,
+            record: FederationResponse,
+        ) -> StoreFuture<'a, ()> {
+            let mut records = self.0.lock().unwrap();
+            let result = if !records.contains_key(address) {
+                Err(Error::NotFound)
+            } else if record.stellar_address != address
+                && records.contains_key(&record.stellar_address)
+            {
+                Err(Error::RecordExists)
+            } else {
+                records.remove(address);
+                records.insert(record.stellar_address.clone(), record);
+                Ok(())
+            };
+            Box::pin(async move { result })
+        }
+
+        fn delete_record<'a>(&'a self, address: &'a str) -> StoreFuture<'a, ()> {
+            let result = match self.0.lock().unwrap().remove(address) {
+                Some(_) => Ok(()),
+                None => Err(Error::NotFound),
+            };
+            Box::pin(async move { result })
+        }
+    }
+
+    async fn call(
+        service: &AdminService<Memory>,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, Option<serde_json::Value>) {
+        let request = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(AUTHORIZATION, "Bearer secret")
+            .body(Body::from(
+                body.map(|body| body.to_string()).unwrap_or_default(),
+            ))
+            .unwrap();
+        let response = service.handle(request).await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).ok())
+    }
+
+    fn record(name: &str, memo: &str) -> serde_json::Value {
+        serde_json::json!({
+            "stellar_address": name,
+            "account_id": ACCOUNT_ID,
+            "memo_type": "id",
+            "memo": memo,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_records() {
+        let service = AdminService::new(Memory::default(), "secret");
+        let alice = record("alice*example.org", "1");
+        let (status, body) = call(
+            &service,
+            Method::POST,
+            "/admin/records",
+            Some(record("alice*Example.ORG", "1")),
+        )
+        .await;
+        assert_eq!(StatusCode::CREATED, status);
+        assert_eq!(Some(alice.clone()), body);
+        let (status, _) = call(&service, Method::POST, "/admin/records", Some(alice)).await;
+        assert_eq!(StatusCode::CONFLICT, status);
+
+        let bob = record("bob*example.org", "2");
+        let (status, body) = call(
+            &service,
+            Method::PUT,
+            "/admin/records/alice%2Aexample.org",
+            Some(bob.clone()),
+        )
+        .await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(Some(bob.clone()), body);
+        let (status, body) = call(&service, Method::GET, "/admin/records", None).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(Some(serde_json::json!([bob])), body);
+        let (status, _) = call(
+            &service,
+            Method::GET,
+            "/admin/records/alice*example.org",
+            None,
+        )
+        .await;
+        assert_eq!(StatusCode::NOT_FOUND, status);
+
+        let (status, _) = call(
+            &service,
+            Method::DELETE,
+            "/admin/records/bob*example.org",
+            None,
+        )
+        .await;
+        assert_eq!(StatusCode::NO_CONTENT, status);
+        assert!(service.store().0.lock().unwrap().is_empty());
+
+        let invalid = [
+            (Method::POST, "/admin/records", Some(record("bob", "2"))),
+            (
+                Method::POST,
+                "/admin/records",
+                Some(serde_json::json!({ "stellar_address": "bob*example.org" })),
+            ),
+            (Method::GET, "/admin/records/bob", None),
+        ];
+        for (method, path, body) in invalid {
+            let (status, _) = call(&service, method, path, body).await;
+            assert_eq!(StatusCode::BAD_REQUEST, status);
+        }
+        let (status, _) = call(&service, Method::PATCH, "/admin/records", None).await;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, status);
+    }
+
+    #[tokio::test]
+    async fn test_authentication() {
+        let service = AdminService::new(Memory::default(), "secret").prefix("/internal/");
+        for authorization in [None, Some("Bearer wrong"), Some("Basic secret")] {
+            let mut request = Request::get("/internal/records");
+            if let Some(authorization) = authorization {
+                request = request.header(AUTHORIZATION, authorization);
+            }
+            let response = service.handle(request.body(Body::empty()).unwrap()).await;
+            assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+            assert_eq!("Bearer", response.headers()[WWW_AUTHENTICATE]);
+        }
+
+        let request = Request::get("/admin/records")
+            .header(AUTHORIZATION, "Bearer secret")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.handle(request).await;
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+}
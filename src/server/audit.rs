@@ -0,0 +1,291 @@
+use super::Outcome;
+use crate::signing::civil_from_days;
+use crate::Error;
+use hyper::StatusCode;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default size above which the audit log is rotated.
+const DEFAULT_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Default number of rotated audit logs kept.
+const DEFAULT_MAX_FILES: usize = 10;
+
+/// Append-only log of the lookups answered by a [`FederationService`],
+/// with one JSON object per line:
+///
+/// ```json
+/// {"timestamp":"2024-05-01T12:00:00.000Z","query_type":"name","q":"alice*example.org","tenant":null,"status":200,"result":"found","client_ip_hash":"5d41402abc4b2a76b9719d911017c592"}
+/// ```
+///
+/// Unlike the request logs, the log records the queried address, so
+/// that it can be audited who was looked up and when. The address of
+/// the client is hashed with the salt set with [`AuditLog::ip_salt`].
+/// In [privacy mode], the queried address is hashed too, in a
+/// `q_hash` field replacing `q`.
+/// Invalid requests, and requests denied before their query is parsed,
+/// are not recorded.
+///
+/// Once the log grows above [`AuditLog::max_size`], it's renamed with a
+/// `.1` suffix, the previously rotated logs are shifted to `.2`, `.3`
+/// and so on, and the oldest one above [`AuditLog::max_files`] is
+/// removed.
+///
+/// Write failures don't fail the requests.
+///
+/// [`FederationService`]: super::FederationService
+/// [privacy mode]: super::FederationService::privacy_mode
+pub struct AuditLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    salt: Vec<u8>,
+    file: Mutex<LogFile>,
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+impl AuditLog {
+    /// Opens the audit log at `path`, creating it if needed, and
+    /// appending to it otherwise.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<AuditLog, Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = LogFile::open(&path)?;
+        Ok(AuditLog {
+            path,
+            max_size: DEFAULT_MAX_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+            salt: Vec::new(),
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Sets the size in bytes above which the log is rotated. Defaults
+    /// to 100 MiB.
+    pub fn max_size(mut self, max_size: u64) -> AuditLog {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets the number of rotated logs kept. Defaults to 10.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_files` is 0.
+    pub fn max_files(mut self, max_files: usize) -> AuditLog {
+        assert!(max_files > 0, "max_files must be positive");
+        self.max_files = max_files;
+        self
+    }
+
+    /// Sets the salt hashed with the addresses of the clients, and with
+    /// the queried addresses in privacy mode, so that they can't be
+    /// recovered by hashing every address. The hashes are stable as long
+    /// as the salt doesn't change.
+    pub fn ip_salt(mut self, salt: &str) -> AuditLog {
+        self.salt = salt.as_bytes().to_vec();
+        self
+    }
+
+    /// Records a lookup answered with `status`, with a hash of its `q`
+    /// parameter in privacy mode.
+    pub(super) fn record(&self, outcome: &Outcome, status: StatusCode, privacy_mode: bool) {
+        let (query_type, q) = match (outcome.kind, &outcome.q) {
+            (Some(kind), Some(q)) => (kind, q),
+            _ => return,
+        };
+        let (q_key, q) = match privacy_mode {
+            true => ("q_hash", self.hash(q)),
+            false => ("q", q.clone()),
+        };
+        let mut entry = serde_json::json!({
+            "timestamp": timestamp(SystemTime::now()),
+            "query_type": query_type,
+            "tenant": outcome.tenant,
+            "status": status.as_u16(),
+            "result": result(status),
+            "client_ip_hash": outcome.client_ip.map(|ip| self.hash(&ip.to_string())),
+        });
+        entry[q_key] = q.into();
+        let mut line = entry.to_string();
+        line.push('\n');
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        // There is nothing to do about failures, the request is answered
+        // anyway.
+        let _ = self.write(&mut file, line.as_bytes());
+    }
+
+    fn write(&self, file: &mut LogFile, line: &[u8]) -> io::Result<()> {
+        if file.size > 0 && file.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+            *file = LogFile::open(&self.path)?;
+        }
+        file.file.write_all(line)?;
+        file.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts the rotated logs and renames the log with a `.1` suffix.
+    fn rotate(&self) -> io::Result<()> {
+        for index in (1..self.max_files).rev() {
+            match fs::rename(self.rotated(index), self.rotated(index + 1)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    /// Returns the salted hash of `value`.
+    fn hash(&self, value: &str) -> String {
+        let mut data = self.salt.clone();
+        data.extend_from_slice(value.as_bytes());
+        let hash = stellar_base::crypto::hash(&data);
+        hash[..16]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl LogFile {
+    fn open(path: &Path) -> io::Result<LogFile> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(LogFile { file, size })
+    }
+}
+
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("path", &self.path)
+            .field("max_size", &self.max_size)
+            .field("max_files", &self.max_files)
+            .finish()
+    }
+}
+
+/// Returns the result of a lookup answered with `status`.
+fn result(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::OK | StatusCode::NOT_MODIFIED => "found",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::BAD_REQUEST => "invalid",
+        StatusCode::NOT_IMPLEMENTED => "not_implemented",
+        _ => "error",
+    }
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with milliseconds, for
+/// example `1994-11-06T08:49:37.000Z`.
+pub(super) fn timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let secs_of_day = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(784_111_777_042);
+        assert_eq!("1994-11-06T08:49:37.042Z", timestamp(time));
+        assert_eq!("1970-01-01T00:00:00.000Z", timestamp(UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let path = std::env::temp_dir().join(format!(
+            "stellar-federation-audit-{}.jsonl",
+            std::process::id()
+        ));
+        let log = AuditLog::open(&path)
+            .unwrap()
+            .max_size(300)
+            .max_files(2)
+            .ip_salt("salt");
+        let outcome = Outcome {
+            kind: Some("name"),
+            q: Some("alice*example.org".to_string()),
+            client_ip: Some([127, 0, 0, 1].into()),
+            ..Outcome::default()
+        };
+        for _ in 0..6 {
+            log.record(&outcome, StatusCode::OK, false);
+        }
+        // Not a lookup.
+        log.record(&Outcome::default(), StatusCode::BAD_REQUEST, false);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let entry: serde_json::Value =
+            serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!("name", entry["query_type"]);
+        assert_eq!("alice*example.org", entry["q"]);
+        assert_eq!(200, entry["status"]);
+        assert_eq!("found", entry["result"]);
+        let hash = entry["client_ip_hash"].as_str().unwrap();
+        assert_eq!(32, hash.len());
+        assert_ne!(hash, AuditLog::open(&path).unwrap().hash("127.0.0.1"));
+        assert!(!contents.contains("127.0.0.1"));
+
+        // Each entry is about 200 bytes, so each file holds one entry.
+        let rotated = |index| log.rotated(index);
+        assert_eq!(1, contents.lines().count());
+        assert_eq!(1, fs::read_to_string(rotated(1)).unwrap().lines().count());
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+        for path in [path.clone(), rotated(1), rotated(2)] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+    #[test]
+    fn test_privacy_mode() {
+        let path = std::env::temp_dir().join(format!(
+            "stellar-federation-audit-private-{}.jsonl",
+            std::process::id()
+        ));
+        let log = AuditLog::open(&path).unwrap().ip_salt("salt");
+        let outcome = Outcome {
+            kind: Some("name"),
+            q: Some("alice*example.org".to_string()),
+            ..Outcome::default()
+        };
+        log.record(&outcome, StatusCode::NOT_FOUND, true);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("alice"));
+        let entry: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        assert_eq!(None, entry.get("q"));
+        assert_eq!(log.hash("alice*example.org"), entry["q_hash"]);
+        assert_eq!("not_found", entry["result"]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use super::{FederationHandler, FederationService};
+use ::axum::Router;
+
+impl<H: FederationHandler + 'static> FederationService<H> {
+    /// Returns an axum router serving federation requests on `path`.
+    ///
+    /// Merge the router into the application router to serve the
+    /// federation endpoint next to the other routes:
+    ///
+    /// ```rust,no_run
+    /// use axum::routing::get;
+    /// use axum::Router;
+    /// use stellar_federation::server::{FederationHandler, FederationService, HandlerFuture};
+    ///
+    /// #[derive(Debug)]
+    /// struct Users;
+    ///
+    /// impl FederationHandler for Users {
+    ///     fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+    ///         Box::pin(async move { Ok(None) })
+    ///     }
+    /// }
+    ///
+    /// let app: Router = Router::new()
+    ///     .route("/", get(|| async { "Hello" }))
+    ///     .merge(FederationService::new(Users).into_router("/federation"));
+    /// ```
+    ///
+    /// The service can also be mounted directly with
+    /// [`Router::route_service`].
+    pub fn into_router<S>(self, path: &str) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        Router::new().route_service(path, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::HandlerFuture;
+    use crate::FederationResponse;
+    use hyper::service::Service;
+    use hyper::{Body, Request, StatusCode};
+    use stellar_base::PublicKey;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    #[derive(Debug)]
+    struct Alice;
+
+    impl FederationHandler for Alice {
+        fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+            Box::pin(async move {
+                Ok(
+                    (address == "alice*example.org").then(|| FederationResponse {
+                        stellar_address: address.to_string(),
+                        account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+                        muxed_account: None,
+                        memo: None,
+                        extra: Default::default(),
+                    }),
+                )
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_into_router() {
+        let mut router: Router = FederationService::new(Alice).into_router("/federation");
+        let request = Request::get("/federation?type=name&q=alice*example.org")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.call(request).await.unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("application/json", response.headers()["content-type"]);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: FederationResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!("alice*example.org", body.stellar_address);
+        assert_eq!(ACCOUNT_ID, body.account_id.account_id());
+
+        let request = Request::get("/federation?type=name&q=bob*example.org")
+            .body(Body::empty())
+            .unwrap();
+        let response = router.call(request).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        let request = Request::get("/other").body(Body::empty()).unwrap();
+        let response = router.call(request).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+    }
+}
@@ -0,0 +1,76 @@
+use crate::Error;
+use hyper::header::HeaderValue;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// `Cache-Control` header of the federation responses of a
+/// [`FederationService`], telling clients and CDNs how long they can
+/// cache the records.
+///
+/// Set it for all the records with [`FederationService::cache_control`],
+/// or for each record with [`FederationHandler::cache_control`]. It's
+/// parsed from and formatted as the value of the header, for example
+/// `max-age=3600` or `no-store`.
+///
+/// [`FederationService`]: super::FederationService
+/// [`FederationService::cache_control`]: super::FederationService::cache_control
+/// [`FederationHandler::cache_control`]: super::FederationHandler::cache_control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheControl {
+    /// The record can be cached for the duration, rounded down to the
+    /// second.
+    MaxAge(Duration),
+    /// The record must not be cached, for example because its memo
+    /// changes frequently.
+    NoStore,
+}
+
+impl CacheControl {
+    /// Returns the value of the `Cache-Control` header.
+    pub(crate) fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_str(&self.to_string()).expect("invalid cache control")
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheControl::MaxAge(max_age) => write!(f, "max-age={}", max_age.as_secs()),
+            CacheControl::NoStore => f.write_str("no-store"),
+        }
+    }
+}
+
+impl FromStr for CacheControl {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<CacheControl, Error> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("no-store") {
+            return Ok(CacheControl::NoStore);
+        }
+        value
+            .strip_prefix("max-age=")
+            .and_then(|seconds| seconds.parse().ok())
+            .map(|seconds| CacheControl::MaxAge(Duration::from_secs(seconds)))
+            .ok_or_else(|| Error::InvalidConfig(format!("invalid cache control {:?}", value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let max_age = CacheControl::MaxAge(Duration::from_secs(3600));
+        assert_eq!(max_age, "max-age=3600".parse().unwrap());
+        assert_eq!(CacheControl::NoStore, " No-Store ".parse().unwrap());
+        assert_eq!("max-age=3600", max_age.header_value());
+        assert_eq!("no-store", CacheControl::NoStore.header_value());
+        for invalid in ["", "max-age=", "max-age=-1", "public"] {
+            assert!(invalid.parse::<CacheControl>().is_err());
+        }
+    }
+}
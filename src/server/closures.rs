@@ -0,0 +1,137 @@
+use super::{FederationHandler, FederationService, ForwardQuery, HandlerFuture};
+use crate::{Error, FederationResponse};
+use std::fmt;
+use std::future::Future;
+use stellar_base::PublicKey;
+
+type Lookup<T> = Box<dyn Fn(T) -> HandlerFuture<'static> + Send + Sync>;
+
+/// [`FederationHandler`] built from async closures, for ad-hoc
+/// backends.
+///
+/// Each closure receives the query and returns the matching record,
+/// with the same semantics as the [`FederationHandler`] methods. The
+/// query types without a closure are answered with
+/// `501 Not Implemented`.
+///
+/// ```rust,no_run
+/// use stellar_federation::server::FederationServer;
+///
+/// # async fn run() -> Result<(), stellar_federation::Error> {
+/// FederationServer::new()
+///     .on_name(|address| async move {
+///         // Look up `address` in the users database.
+///         Ok(None)
+///     })
+///     .on_id(|account_id| async move { Ok(None) })
+///     .into_service()
+///     .serve(([0, 0, 0, 0], 8000).into())
+///     .await
+/// # }
+/// ```
+#[derive(Default)]
+pub struct FederationServer {
+    name: Option<Lookup<String>>,
+    id: Option<Lookup<PublicKey>>,
+    txid: Option<Lookup<String>>,
+    forward: Option<Lookup<ForwardQuery>>,
+}
+
+impl FederationServer {
+    /// Creates a server without lookups.
+    pub fn new() -> FederationServer {
+        FederationServer::default()
+    }
+
+    /// Answers name lookups with `lookup`, called with the Stellar
+    /// address.
+    pub fn on_name<F, Fut>(mut self, lookup: F) -> FederationServer
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<FederationResponse>, Error>> + Send + 'static,
+    {
+        self.name = Some(boxed(lookup));
+        self
+    }
+
+    /// Answers id lookups with `lookup`, called with the account id.
+    pub fn on_id<F, Fut>(mut self, lookup: F) -> FederationServer
+    where
+        F: Fn(PublicKey) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<FederationResponse>, Error>> + Send + 'static,
+    {
+        self.id = Some(boxed(lookup));
+        self
+    }
+
+    /// Answers transaction id lookups with `lookup`, called with the
+    /// transaction id.
+    pub fn on_txid<F, Fut>(mut self, lookup: F) -> FederationServer
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<FederationResponse>, Error>> + Send + 'static,
+    {
+        self.txid = Some(boxed(lookup));
+        self
+    }
+
+    /// Answers forward lookups with `lookup`, called with the forward
+    /// query.
+    pub fn on_forward<F, Fut>(mut self, lookup: F) -> FederationServer
+    where
+        F: Fn(ForwardQuery) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Option<FederationResponse>, Error>> + Send + 'static,
+    {
+        self.forward = Some(boxed(lookup));
+        self
+    }
+
+    /// Returns a service answering requests with the closures.
+    pub fn into_service(self) -> FederationService<FederationServer> {
+        FederationService::new(self)
+    }
+}
+
+fn boxed<T, F, Fut>(lookup: F) -> Lookup<T>
+where
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Option<FederationResponse>, Error>> + Send + 'static,
+{
+    Box::new(move |query| Box::pin(lookup(query)))
+}
+
+fn call<T>(lookup: &Option<Lookup<T>>, query: T) -> HandlerFuture<'static> {
+    match lookup {
+        Some(lookup) => lookup(query),
+        None => Box::pin(async { Err(Error::NotImplemented) }),
+    }
+}
+
+impl fmt::Debug for FederationServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FederationServer")
+            .field("name", &self.name.is_some())
+            .field("id", &self.id.is_some())
+            .field("txid", &self.txid.is_some())
+            .field("forward", &self.forward.is_some())
+            .finish()
+    }
+}
+
+impl FederationHandler for FederationServer {
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        call(&self.name, address.to_string())
+    }
+
+    fn lookup_id<'a>(&'a self, account_id: &'a PublicKey) -> HandlerFuture<'a> {
+        call(&self.id, account_id.clone())
+    }
+
+    fn lookup_txid<'a>(&'a self, tx_id: &'a str) -> HandlerFuture<'a> {
+        call(&self.txid, tx_id.to_string())
+    }
+
+    fn lookup_forward<'a>(&'a self, query: &'a ForwardQuery) -> HandlerFuture<'a> {
+        call(&self.forward, query.clone())
+    }
+}
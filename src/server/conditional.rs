@@ -0,0 +1,72 @@
+use hyper::header::{HeaderValue, CONTENT_TYPE, ETAG};
+use hyper::{Body, Response, StatusCode};
+
+/// Returns the response with the JSON `body` of a record and its
+/// `ETag`, or `304 Not Modified` without body if the `ETag` matches the
+/// `If-None-Match` header of the request.
+pub(crate) fn record_response(
+    body: &serde_json::Value,
+    if_none_match: Option<&HeaderValue>,
+) -> Response<Body> {
+    let body = body.to_string();
+    let etag = etag(body.as_bytes());
+    let mut response = match if_none_match {
+        Some(if_none_match) if matches(if_none_match, &etag) => {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            response
+        }
+        _ => {
+            let mut response = Response::new(Body::from(body));
+            response
+                .headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            response
+        }
+    };
+    response.headers_mut().insert(ETAG, etag);
+    response
+}
+
+/// Returns the strong `ETag` of `body`, a hash of its contents.
+fn etag(body: &[u8]) -> HeaderValue {
+    let hash = stellar_base::crypto::hash(body);
+    let hex: String = hash[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    HeaderValue::from_str(&format!("\"{}\"", hex)).expect("invalid etag")
+}
+
+/// Returns whether the `If-None-Match` header matches `etag`, with the
+/// weak comparison required for `GET` requests.
+fn matches(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let if_none_match = match if_none_match.to_str() {
+        Ok(if_none_match) => if_none_match,
+        Err(_) => return false,
+    };
+    let etag = etag.to_str().unwrap_or_default();
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let etag = etag(b"{}");
+        let tag = etag.to_str().unwrap();
+        assert_eq!(34, tag.len());
+        let if_none_match = |value: &str| matches(&HeaderValue::from_str(value).unwrap(), &etag);
+        assert!(if_none_match(tag));
+        assert!(if_none_match(&format!("W/{}", tag)));
+        assert!(if_none_match(&format!("\"other\", {}", tag)));
+        assert!(if_none_match("*"));
+        assert!(!if_none_match("\"other\""));
+        assert!(!if_none_match(tag.trim_matches('"')));
+    }
+}
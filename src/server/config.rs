@@ -0,0 +1,636 @@
+use super::{
+    shutdown_signal, AuditLog, CacheControl, FederationHandler, FederationService,
+    StaticFileHandler, Webhook,
+};
+use crate::{Error, RateLimit};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Prefix of the environment variables overriding the configuration.
+const ENV_PREFIX: &str = "STELLAR_FEDERATION_";
+
+/// Environment variables overriding the configuration, with the path
+/// of the key they override and whether their value is a string or a
+/// TOML value, such as a number or a boolean.
+const ENV_OVERRIDES: &[(&str, &[&str], bool)] = &[
+    ("BIND", &["bind"], true),
+    ("TLS_CERTIFICATE", &["tls", "certificate"], true),
+    ("TLS_KEY", &["tls", "key"], true),
+    ("BACKEND_TYPE", &["backend", "type"], true),
+    ("BACKEND_PATH", &["backend", "path"], true),
+    ("BACKEND_URL", &["backend", "url"], true),
+    ("BACKEND_TABLE", &["backend", "table"], true),
+    (
+        "BACKEND_TRANSACTIONS_TABLE",
+        &["backend", "transactions_table"],
+        true,
+    ),
+    ("BACKEND_BASE_DN", &["backend", "base_dn"], true),
+    ("BACKEND_DOMAIN", &["backend", "domain"], true),
+    ("BACKEND_BIND_DN", &["backend", "bind_dn"], true),
+    ("BACKEND_PASSWORD", &["backend", "password"], true),
+    (
+        "RATE_LIMIT_REQUESTS_PER_SECOND",
+        &["rate_limit", "requests_per_second"],
+        false,
+    ),
+    ("RATE_LIMIT_BURST", &["rate_limit", "burst"], false),
+    ("CORS_ENABLED", &["cors", "enabled"], false),
+    ("CORS_ALLOW_ORIGIN", &["cors", "allow_origin"], true),
+    ("AUDIT_LOG_PATH", &["audit_log", "path"], true),
+    ("AUDIT_LOG_MAX_SIZE", &["audit_log", "max_size"], false),
+    ("AUDIT_LOG_MAX_FILES", &["audit_log", "max_files"], false),
+    ("AUDIT_LOG_IP_SALT", &["audit_log", "ip_salt"], true),
+    ("PRIVACY_MODE", &["privacy_mode"], false),
+    ("CACHE_CONTROL", &["cache_control"], true),
+    ("WEBHOOK_URL", &["webhook", "url"], true),
+    ("WEBHOOK_SECRET", &["webhook", "secret"], true),
+    ("OPENAPI", &["openapi"], false),
+];
+
+/// Configuration of the bundled federation server, to deploy it
+/// without writing Rust.
+///
+/// The `stellar-federation-server` binary, built with the `server`
+/// feature, serves the configuration file given as argument.
+///
+/// The configuration is read from a TOML file:
+///
+/// ```toml
+/// bind = "0.0.0.0:8000"
+///
+/// [backend]
+/// type = "static"
+/// path = "/etc/stellar-federation/records.toml"
+///
+/// [tls]
+/// certificate = "/etc/stellar-federation/cert.pem"
+/// key = "/etc/stellar-federation/key.pem"
+///
+/// [rate_limit]
+/// requests_per_second = 10.0
+/// burst = 20
+///
+/// [cors]
+/// allow_origin = "https://wallet.example.org"
+///
+/// [audit_log]
+/// path = "/var/log/stellar-federation/audit.jsonl"
+/// ```
+///
+/// Only the backend is required. The server listens on `0.0.0.0:8000`
+/// by default, without TLS, rate limit nor audit log, and allows
+/// cross-origin requests from any origin.
+///
+/// Each key can be overridden with an environment variable, for
+/// example `STELLAR_FEDERATION_BIND` for `bind`,
+/// `STELLAR_FEDERATION_TLS_CERTIFICATE` for `tls.certificate`, or
+/// `STELLAR_FEDERATION_BACKEND_PASSWORD` for `backend.password`, so
+/// that secrets don't have to be written in the file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the server listens on, unless it is socket activated by
+    /// systemd.
+    #[serde(default = "default_bind")]
+    pub bind: SocketAddr,
+    /// Certificate of the server, to serve requests over https.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Source of the records.
+    pub backend: BackendConfig,
+    /// Rate limit of each client.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimitConfig>,
+    /// Cross-origin requests settings.
+    #[serde(default)]
+    pub cors: CorsConfig,
+    /// Audit log of the lookups.
+    #[serde(default)]
+    pub audit_log: Option<AuditLogConfig>,
+    /// Whether to omit the queried addresses and the addresses of the
+    /// clients from the logs, see [`FederationService::privacy_mode`].
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// `Cache-Control` of the successful responses, for example
+    /// `max-age=3600` or `no-store`, see [`CacheControl`].
+    #[serde(default)]
+    pub cache_control: Option<String>,
+    /// Webhook called after each successful lookup.
+    #[serde(default)]
+    pub webhook: Option<WebhookConfig>,
+    /// Whether to serve the OpenAPI document of the server at
+    /// `/openapi.json`. Requires the `openapi` feature.
+    #[serde(default)]
+    pub openapi: bool,
+}
+
+/// PEM encoded certificate chain and private key of the server, see
+/// [`ServerConfig`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Path of the certificate chain, leaf certificate first.
+    pub certificate: PathBuf,
+    /// Path of the private key.
+    pub key: PathBuf,
+}
+
+/// Source of the records of the server, selected with its `type`, see
+/// [`ServerConfig`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum BackendConfig {
+    /// Records of a TOML, JSON or CSV file, served with a
+    /// [`StaticFileHandler`] reloaded on `SIGHUP`.
+    Static {
+        /// Path of the file.
+        path: PathBuf,
+    },
+    /// Records of a SQL database, served with `SqlHandler`. Requires the
+    /// `sqlx` feature.
+    Sql {
+        /// URL of the database, for example `postgres://localhost/users`.
+        url: String,
+        /// Table of the records.
+        #[serde(default)]
+        table: Option<String>,
+        /// Table of the transactions, for transaction id lookups.
+        #[serde(default)]
+        transactions_table: Option<String>,
+    },
+    /// Records of an LDAP directory, served with `LdapHandler`.
+    /// Requires the `ldap` feature.
+    Ldap {
+        /// URL of the directory, for example `ldaps://ldap.example.org`.
+        url: String,
+        /// Base DN of the users.
+        base_dn: String,
+        /// Domain of the Stellar addresses of the users.
+        domain: String,
+        /// DN to bind with before searching.
+        #[serde(default)]
+        bind_dn: Option<String>,
+        /// Password of `bind_dn`.
+        #[serde(default)]
+        password: Option<String>,
+    },
+}
+
+/// Rate limit of each client of the server, see [`ServerConfig`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Sustained number of requests per second.
+    pub requests_per_second: f64,
+    /// Number of requests that can be sent at once.
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+/// Cross-origin requests settings of the server, see [`ServerConfig`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Whether to add CORS headers to the responses.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Origin allowed to query the server, `*` for any origin.
+    #[serde(default = "default_allow_origin")]
+    pub allow_origin: String,
+}
+
+/// Audit log of the lookups of the server, see [`ServerConfig`] and
+/// [`AuditLog`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuditLogConfig {
+    /// Path of the log.
+    pub path: PathBuf,
+    /// Size in bytes above which the log is rotated.
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    /// Number of rotated logs kept.
+    #[serde(default)]
+    pub max_files: Option<usize>,
+    /// Salt hashed with the addresses of the clients.
+    #[serde(default)]
+    pub ip_salt: Option<String>,
+}
+
+/// Webhook called by the server after each successful lookup, see
+/// [`ServerConfig`] and [`Webhook`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// URL of the webhook.
+    pub url: String,
+    /// Secret signing the payloads.
+    pub secret: String,
+    /// Addresses to call the webhook for, all of them if empty.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> CorsConfig {
+        CorsConfig {
+            enabled: true,
+            allow_origin: default_allow_origin(),
+        }
+    }
+}
+
+fn default_bind() -> SocketAddr {
+    ([0, 0, 0, 0], 8000).into()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_allow_origin() -> String {
+    "*".to_string()
+}
+
+impl ServerConfig {
+    /// Loads the configuration file at `path`, applying the overrides
+    /// of the environment variables.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ServerConfig, Error> {
+        let contents = fs::read_to_string(path)?;
+        ServerConfig::parse(&contents, |name| std::env::var(name).ok())
+    }
+
+    /// Loads the configuration from the environment variables only.
+    pub fn from_env() -> Result<ServerConfig, Error> {
+        ServerConfig::parse("", |name| std::env::var(name).ok())
+    }
+
+    /// Parses a TOML configuration, without environment overrides.
+    pub fn from_toml(contents: &str) -> Result<ServerConfig, Error> {
+        ServerConfig::parse(contents, |_| None)
+    }
+
+    fn parse<F>(contents: &str, env: F) -> Result<ServerConfig, Error>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut config: toml::value::Table = toml::from_str(contents)?;
+        for (name, path, string) in ENV_OVERRIDES {
+            let value = match env(&format!("{}{}", ENV_PREFIX, name)) {
+                Some(value) => value,
+                None => continue,
+            };
+            let value = match string {
+                true => toml::Value::String(value),
+                false => parse_value(name, &value)?,
+            };
+            let (key, sections) = path.split_last().expect("empty override path");
+            let mut table = &mut config;
+            for section in sections {
+                table = match table
+                    .entry(section.to_string())
+                    .or_insert_with(|| toml::Value::Table(Default::default()))
+                {
+                    toml::Value::Table(table) => table,
+                    _ => return Err(invalid_config(&format!("{} is not a table", section))),
+                };
+            }
+            table.insert(key.to_string(), value);
+        }
+        Ok(toml::Value::Table(config).try_into()?)
+    }
+
+    /// Creates the handler of the backend, connecting to it if needed.
+    ///
+    /// The records of a static backend are reloaded when the process
+    /// receives `SIGHUP`.
+    pub async fn handler(&self) -> Result<Arc<dyn FederationHandler>, Error> {
+        match &self.backend {
+            BackendConfig::Static { path } => {
+                let handler = StaticFileHandler::open(path)?;
+                tokio::spawn(handler.clone().reload_on_sighup());
+                Ok(Arc::new(handler))
+            }
+            #[cfg(feature = "sqlx")]
+            BackendConfig::Sql {
+                url,
+                table,
+                transactions_table,
+            } => {
+                let mut handler = super::SqlHandler::connect(url).await?;
+                if let Some(table) = table {
+                    handler = handler.table(table);
+                }
+                if let Some(table) = transactions_table {
+                    handler = handler.transactions_table(table);
+                }
+                Ok(Arc::new(handler))
+            }
+            #[cfg(feature = "ldap")]
+            BackendConfig::Ldap {
+                url,
+                base_dn,
+                domain,
+                bind_dn,
+                password,
+            } => {
+                let mut handler = super::LdapHandler::new(url, base_dn, domain);
+                if let Some(bind_dn) = bind_dn {
+                    handler = handler.bind(bind_dn, password.as_deref().unwrap_or_default());
+                }
+                Ok(Arc::new(handler))
+            }
+            #[cfg(not(feature = "sqlx"))]
+            BackendConfig::Sql { .. } => {
+                Err(invalid_config("the sql backend requires the sqlx feature"))
+            }
+            #[cfg(not(feature = "ldap"))]
+            BackendConfig::Ldap { .. } => {
+                Err(invalid_config("the ldap backend requires the ldap feature"))
+            }
+        }
+    }
+
+    /// Creates the service answering federation requests, with the
+    /// handler of the backend.
+    pub async fn service(&self) -> Result<FederationService<Arc<dyn FederationHandler>>, Error> {
+        let mut service = FederationService::new(self.handler().await?);
+        if let Some(rate_limit) = &self.rate_limit {
+            let requests_per_second = rate_limit.requests_per_second;
+            if requests_per_second.is_nan() || requests_per_second <= 0.0 {
+                return Err(invalid_config("requests_per_second must be positive"));
+            }
+            let mut limit = RateLimit::new(rate_limit.requests_per_second);
+            if let Some(burst) = rate_limit.burst {
+                limit = limit.burst(burst);
+            }
+            service = service.rate_limit(limit);
+        }
+        if !self.cors.enabled {
+            service = service.disable_cors();
+        } else if hyper::header::HeaderValue::from_str(&self.cors.allow_origin).is_ok() {
+            service = service.allow_origin(&self.cors.allow_origin);
+        } else {
+            return Err(invalid_config("invalid cors allow_origin"));
+        }
+        if let Some(audit_log) = &self.audit_log {
+            let mut log = AuditLog::open(&audit_log.path)?;
+            if let Some(max_size) = audit_log.max_size {
+                log = log.max_size(max_size);
+            }
+            match audit_log.max_files {
+                Some(0) => return Err(invalid_config("max_files must be positive")),
+                Some(max_files) => log = log.max_files(max_files),
+                None => {}
+            }
+            if let Some(salt) = &audit_log.ip_salt {
+                log = log.ip_salt(salt);
+            }
+            service = service.audit_log(log);
+        }
+        if self.privacy_mode {
+            service = service.privacy_mode();
+        }
+        if let Some(cache_control) = &self.cache_control {
+            service = service.cache_control(cache_control.parse::<CacheControl>()?);
+        }
+        if let Some(webhook) = &self.webhook {
+            let mut hook = Webhook::new(&webhook.url, &webhook.secret)?;
+            if !webhook.addresses.is_empty() {
+                hook = hook.addresses(&webhook.addresses);
+            }
+            service = service.webhook(hook);
+        }
+        if self.openapi {
+            #[cfg(feature = "openapi")]
+            {
+                service = service.serve_openapi();
+            }
+            #[cfg(not(feature = "openapi"))]
+            return Err(invalid_config("openapi requires the openapi feature"));
+        }
+        Ok(service)
+    }
+
+    /// Serves federation requests until the process receives `SIGTERM`
+    /// or ctrl-c, over https if a certificate is configured.
+    ///
+    /// When the process is socket activated by systemd, the socket passed
+    /// by systemd is served instead of binding `bind`, see
+    /// [`systemd_listener`](super::systemd_listener).
+    ///
+    /// The records of a static backend, and the certificate, are
+    /// reloaded when the process receives `SIGHUP`.
+    pub async fn serve(self) -> Result<(), Error> {
+        let service = self.service().await?;
+        #[cfg(unix)]
+        let listener = super::systemd_listener()?;
+        #[cfg(not(unix))]
+        let listener: Option<std::net::TcpListener> = None;
+        match (&self.tls, listener) {
+            (None, None) => {
+                service
+                    .serve_with_shutdown(self.bind, shutdown_signal())
+                    .await
+            }
+            (None, Some(listener)) => {
+                service
+                    .serve_listener_with_shutdown(listener, shutdown_signal())
+                    .await
+            }
+            #[cfg(feature = "server-tls")]
+            (Some(tls), listener) => {
+                let tls = super::ServerTls::from_pem_files(&tls.certificate, &tls.key)?;
+                match listener {
+                    None => {
+                        service
+                            .serve_tls_with_shutdown(self.bind, tls, shutdown_signal())
+                            .await
+                    }
+                    Some(listener) => {
+                        service
+                            .serve_tls_listener_with_shutdown(listener, tls, shutdown_signal())
+                            .await
+                    }
+                }
+            }
+            #[cfg(not(feature = "server-tls"))]
+            (Some(_), _) => Err(invalid_config("tls requires the server-tls feature")),
+        }
+    }
+}
+
+/// Parses the value of the environment variable `name` as a TOML
+/// value.
+fn parse_value(name: &str, value: &str) -> Result<toml::Value, Error> {
+    let invalid = || invalid_config(&format!("invalid {}{}", ENV_PREFIX, name));
+    let mut table: toml::value::Table =
+        toml::from_str(&format!("value = {}", value)).map_err(|_| invalid())?;
+    table.remove("value").ok_or_else(invalid)
+}
+
+fn invalid_config(message: &str) -> Error {
+    Error::InvalidConfig(message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_from_toml() {
+        let config = ServerConfig::from_toml(
+            r#"
+            bind = "127.0.0.1:9000"
+
+            [backend]
+            type = "sql"
+            url = "postgres://localhost/users"
+
+            [rate_limit]
+            requests_per_second = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!("127.0.0.1:9000".parse::<SocketAddr>().unwrap(), config.bind);
+        assert_eq!(
+            BackendConfig::Sql {
+                url: "postgres://localhost/users".to_string(),
+                table: None,
+                transactions_table: None,
+            },
+            config.backend
+        );
+        assert_eq!(
+            Some(RateLimitConfig {
+                requests_per_second: 10.0,
+                burst: None,
+            }),
+            config.rate_limit
+        );
+        assert_eq!(None, config.tls);
+        assert_eq!(CorsConfig::default(), config.cors);
+        assert_eq!(None, config.audit_log);
+        assert!(!config.privacy_mode);
+        assert_eq!(None, config.cache_control);
+        assert_eq!(None, config.webhook);
+        assert!(!config.openapi);
+
+        for invalid in [
+            "",
+            "[backend]\ntype = \"redis\"",
+            "unknown = 1\n[backend]\ntype = \"static\"\npath = \"records.toml\"",
+        ] {
+            assert!(ServerConfig::from_toml(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let env: HashMap<_, _> = [
+            ("STELLAR_FEDERATION_BIND", "0.0.0.0:80"),
+            ("STELLAR_FEDERATION_BACKEND_TYPE", "ldap"),
+            ("STELLAR_FEDERATION_BACKEND_URL", "ldaps://ldap.example.org"),
+            ("STELLAR_FEDERATION_BACKEND_BASE_DN", "ou=users,dc=example"),
+            ("STELLAR_FEDERATION_BACKEND_DOMAIN", "example.org"),
+            ("STELLAR_FEDERATION_BACKEND_PASSWORD", "1234"),
+            ("STELLAR_FEDERATION_RATE_LIMIT_REQUESTS_PER_SECOND", "2.5"),
+            ("STELLAR_FEDERATION_RATE_LIMIT_BURST", "5"),
+            ("STELLAR_FEDERATION_CORS_ENABLED", "false"),
+            ("STELLAR_FEDERATION_PRIVACY_MODE", "true"),
+        ]
+        .iter()
+        .copied()
+        .collect();
+        let config = ServerConfig::parse(
+            "[backend]\ntype = \"static\"\npath = \"records.toml\"\n[cors]\nallow_origin = \"https://wallet.example.org\"",
+            |name| env.get(name).map(|value| value.to_string()),
+        );
+        // The path of the static backend is not a field of the ldap one.
+        assert!(config.is_err());
+
+        let config =
+            ServerConfig::parse("", |name| env.get(name).map(|value| value.to_string())).unwrap();
+        assert_eq!("0.0.0.0:80".parse::<SocketAddr>().unwrap(), config.bind);
+        assert_eq!(
+            BackendConfig::Ldap {
+                url: "ldaps://ldap.example.org".to_string(),
+                base_dn: "ou=users,dc=example".to_string(),
+                domain: "example.org".to_string(),
+                bind_dn: None,
+                password: Some("1234".to_string()),
+            },
+            config.backend
+        );
+        assert_eq!(
+            Some(RateLimitConfig {
+                requests_per_second: 2.5,
+                burst: Some(5),
+            }),
+            config.rate_limit
+        );
+        assert!(!config.cors.enabled);
+        assert!(config.privacy_mode);
+
+        let invalid = |name: &str| {
+            if name == "STELLAR_FEDERATION_RATE_LIMIT_BURST" {
+                Some("many".to_string())
+            } else {
+                None
+            }
+        };
+        assert!(matches!(
+            ServerConfig::parse("", invalid),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_service() {
+        let path = std::env::temp_dir().join(format!(
+            "stellar-federation-config-{}.toml",
+            std::process::id()
+        ));
+        fs::write(
+            &path,
+            "[[records]]\n\
+             name = \"alice*example.org\"\n\
+             account_id = \"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP\"\n",
+        )
+        .unwrap();
+        let config = ServerConfig::from_toml(&format!(
+            "[backend]\ntype = \"static\"\npath = {:?}\n[rate_limit]\nrequests_per_second = 0",
+            path
+        ))
+        .unwrap();
+        assert!(matches!(
+            config.service().await,
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let audit_path = path.with_extension("jsonl");
+        let config = ServerConfig {
+            rate_limit: None,
+            audit_log: Some(AuditLogConfig {
+                path: audit_path.clone(),
+                max_size: None,
+                max_files: None,
+                ip_salt: None,
+            }),
+            ..config
+        };
+        let service = config.service().await.unwrap();
+        let request = hyper::Request::get("/federation?type=name&q=alice*example.org")
+            .body(hyper::Body::empty())
+            .unwrap();
+        assert_eq!(200, service.handle(request).await.status());
+        let audit = fs::read_to_string(&audit_path).unwrap();
+        assert!(audit.contains("\"q\":\"alice*example.org\""));
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&audit_path).unwrap();
+    }
+}
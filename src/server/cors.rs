@@ -0,0 +1,47 @@
+use hyper::header::{
+    HeaderMap, HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, VARY,
+};
+use hyper::{Body, Response, StatusCode};
+
+/// How long browsers can cache the preflight responses, in seconds.
+const MAX_AGE: &str = "86400";
+
+/// Cross-origin resource sharing settings of the server.
+#[derive(Debug, Clone)]
+pub(crate) struct Cors {
+    allow_origin: HeaderValue,
+}
+
+impl Cors {
+    /// Allows requests from `origin`, `*` allowing any origin.
+    pub(crate) fn new(allow_origin: HeaderValue) -> Cors {
+        Cors { allow_origin }
+    }
+
+    /// Adds the CORS headers to `response`.
+    pub(crate) fn apply(&self, response: &mut Response<Body>) {
+        let headers = response.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, self.allow_origin.clone());
+        if self.allow_origin != "*" {
+            headers.append(VARY, HeaderValue::from_static("Origin"));
+        }
+    }
+
+    /// Answers a preflight request with the `request_headers` headers.
+    pub(crate) fn preflight(&self, request_headers: &HeaderMap) -> Response<Body> {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NO_CONTENT;
+        let headers = response.headers_mut();
+        headers.insert(
+            ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static("GET, OPTIONS"),
+        );
+        headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from_static(MAX_AGE));
+        if let Some(requested) = request_headers.get(ACCESS_CONTROL_REQUEST_HEADERS) {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+        }
+        self.apply(&mut response);
+        response
+    }
+}
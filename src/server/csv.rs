@@ -0,0 +1,192 @@
+use crate::{Error, FederationResponse};
+
+/// Columns of the CSV files loaded by [`StaticHandler`].
+///
+/// The first line of the files is a header naming the columns. By
+/// default records are read from the `name`, `account_id`, `memo_type`
+/// and `memo` columns, the memo columns being optional.
+///
+/// Files of users sharing a pooled account can leave out the account id
+/// column, see [`CsvColumns::default_account_id`].
+///
+/// [`StaticHandler`]: super::StaticHandler
+#[derive(Debug, Clone)]
+pub struct CsvColumns {
+    name: String,
+    account_id: String,
+    memo_type: String,
+    memo: String,
+    default_account_id: Option<String>,
+}
+
+impl Default for CsvColumns {
+    fn default() -> CsvColumns {
+        CsvColumns {
+            name: "name".to_string(),
+            account_id: "account_id".to_string(),
+            memo_type: "memo_type".to_string(),
+            memo: "memo".to_string(),
+            default_account_id: None,
+        }
+    }
+}
+
+impl CsvColumns {
+    /// Creates the default column mapping.
+    pub fn new() -> CsvColumns {
+        CsvColumns::default()
+    }
+
+    /// Reads the Stellar addresses from the `column` column.
+    pub fn name(mut self, column: &str) -> CsvColumns {
+        self.name = column.to_string();
+        self
+    }
+
+    /// Reads the account ids from the `column` column.
+    pub fn account_id(mut self, column: &str) -> CsvColumns {
+        self.account_id = column.to_string();
+        self
+    }
+
+    /// Reads the memo types from the `column` column.
+    pub fn memo_type(mut self, column: &str) -> CsvColumns {
+        self.memo_type = column.to_string();
+        self
+    }
+
+    /// Reads the memos from the `column` column.
+    pub fn memo(mut self, column: &str) -> CsvColumns {
+        self.memo = column.to_string();
+        self
+    }
+
+    /// Uses `account_id` for the records without an account id, the
+    /// account id column becoming optional.
+    pub fn default_account_id(mut self, account_id: &str) -> CsvColumns {
+        self.default_account_id = Some(account_id.to_string());
+        self
+    }
+}
+
+/// Parses the records of a CSV document, returning each with the line
+/// it starts on.
+pub(crate) fn parse_records(
+    contents: &str,
+    columns: &CsvColumns,
+) -> Result<Vec<(usize, FederationResponse)>, Error> {
+    let mut rows = parse_rows(contents)?.into_iter();
+    let (header_line, header) = rows.next().ok_or_else(|| csv_error(1, "missing header"))?;
+    let position = |column: &str| header.iter().position(|name| name.trim() == column);
+    let required = |column: &str| {
+        position(column)
+            .ok_or_else(|| csv_error(header_line, &format!("missing column {}", column)))
+    };
+    let name = required(&columns.name)?;
+    let account_id = match columns.default_account_id {
+        Some(_) => position(&columns.account_id),
+        None => Some(required(&columns.account_id)?),
+    };
+    let memo_type = position(&columns.memo_type);
+    let memo = position(&columns.memo);
+
+    let mut records = Vec::new();
+    for (line, row) in rows {
+        if row.len() != header.len() {
+            let message = format!("expected {} fields, found {}", header.len(), row.len());
+            return Err(csv_error(line, &message));
+        }
+        let cell = |index: Option<usize>| {
+            index
+                .map(|index| row[index].trim())
+                .filter(|value| !value.is_empty())
+        };
+        let record = serde_json::json!({
+            "stellar_address": row[name].trim(),
+            "account_id": cell(account_id).or(columns.default_account_id.as_deref()),
+            "memo_type": cell(memo_type),
+            "memo": cell(memo),
+        });
+        let record =
+            serde_json::from_value(record).map_err(|error| csv_error(line, &error.to_string()))?;
+        records.push((line, record));
+    }
+    Ok(records)
+}
+
+/// Splits a CSV document in rows of fields, as described in RFC 4180,
+/// skipping empty lines.
+fn parse_rows(contents: &str) -> Result<Vec<(usize, Vec<String>)>, Error> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            '\n' if quoted => {
+                field.push(c);
+                line += 1;
+            }
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if !row.is_empty() || !field.is_empty() {
+                    row.push(std::mem::take(&mut field));
+                    rows.push((row_line, std::mem::take(&mut row)));
+                }
+                line += 1;
+                row_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(csv_error(row_line, "unterminated quoted field"));
+    }
+    if !row.is_empty() || !field.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+fn csv_error(line: usize, message: &str) -> Error {
+    Error::CsvError {
+        line,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rows() {
+        let rows = parse_rows("a,b\r\n\n\"x, \"\"y\"\"\",\"multi\nline\"\nc,\n").unwrap();
+        assert_eq!(
+            vec![
+                (1, vec!["a".to_string(), "b".to_string()]),
+                (3, vec!["x, \"y\"".to_string(), "multi\nline".to_string()]),
+                (5, vec!["c".to_string(), "".to_string()]),
+            ],
+            rows
+        );
+        assert!(matches!(
+            parse_rows("a,b\n\"c,d\n"),
+            Err(Error::CsvError { line: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+use super::error_response;
+use crate::Error;
+use hyper::{Body, Response, StatusCode};
+
+/// Error answered to a federation request, as a JSON object with the
+/// `detail` of the error and the status code described in SEP-0002.
+///
+/// Handlers return them converted into [`Error`], for example with
+/// `Err(FederationError::BadRequest(detail).into())`. Other errors
+/// are answered with `500 Internal Server Error`, without exposing
+/// them to the client.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FederationError {
+    /// The query is invalid, answered with `400 Bad Request` and the
+    /// detail of the error.
+    #[error("{0}")]
+    BadRequest(String),
+    /// There is no record for the query, answered with
+    /// `404 Not Found`.
+    #[error("not found")]
+    NotFound,
+    /// The query type is not supported, answered with
+    /// `501 Not Implemented`.
+    #[error("query type not supported")]
+    NotImplemented,
+}
+
+impl FederationError {
+    /// Returns the status code of the error.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            FederationError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            FederationError::NotFound => StatusCode::NOT_FOUND,
+            FederationError::NotImplemented => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    /// Returns the response of the error, with a JSON body like
+    /// `{"detail": "not found"}`.
+    pub fn into_response(self) -> Response<Body> {
+        error_response(self.status(), &self.to_string())
+    }
+
+    /// Returns the error answered for `err`, or `None` if it's an
+    /// internal error.
+    pub(crate) fn from_error(err: &Error) -> Option<FederationError> {
+        match err {
+            Error::InvalidRequest(detail) => Some(FederationError::BadRequest(detail.clone())),
+            Error::NotFound => Some(FederationError::NotFound),
+            Error::NotImplemented => Some(FederationError::NotImplemented),
+            _ => None,
+        }
+    }
+}
+
+impl From<FederationError> for Error {
+    fn from(err: FederationError) -> Error {
+        match err {
+            FederationError::BadRequest(detail) => Error::InvalidRequest(detail),
+            FederationError::NotFound => Error::NotFound,
+            FederationError::NotImplemented => Error::NotImplemented,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_response() {
+        let errors = [
+            (FederationError::BadRequest("invalid q".to_string()), 400),
+            (FederationError::NotFound, 404),
+            (FederationError::NotImplemented, 501),
+        ];
+        for (err, status) in errors {
+            let converted = FederationError::from_error(&err.clone().into());
+            assert_eq!(Some(&err), converted.as_ref());
+            let detail = err.to_string();
+            let response = err.into_response();
+            assert_eq!(status, response.status().as_u16());
+            assert_eq!("application/json", response.headers()["content-type"]);
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(serde_json::json!({ "detail": detail }), body);
+        }
+        assert_eq!(None, FederationError::from_error(&Error::Timeout));
+    }
+}
@@ -0,0 +1,66 @@
+use super::{json_response, FederationHandler, ReadyFuture, Tenant};
+use futures_util::future;
+use hyper::{Body, Response, StatusCode};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Path of the liveness endpoint.
+pub(crate) const HEALTH_PATH: &str = "/healthz";
+
+/// Path of the readiness endpoint.
+pub(crate) const READY_PATH: &str = "/readyz";
+
+/// Named readiness check of a [`FederationService`].
+///
+/// [`FederationService`]: super::FederationService
+#[derive(Clone)]
+pub(crate) struct ReadinessCheck {
+    pub(crate) name: String,
+    pub(crate) check: Arc<dyn Fn() -> ReadyFuture<'static> + Send + Sync>,
+}
+
+impl fmt::Debug for ReadinessCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadinessCheck")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+/// Answers a liveness probe.
+pub(crate) fn health() -> Response<Body> {
+    json_response(StatusCode::OK, &serde_json::json!({ "status": "ok" }))
+}
+
+/// Answers a readiness probe, running the checks of `handler`, of the
+/// handlers of the `tenants`, and the `checks` concurrently.
+pub(crate) async fn readiness<H: FederationHandler>(
+    handler: &H,
+    tenants: &BTreeMap<String, Tenant>,
+    checks: &[ReadinessCheck],
+) -> Response<Body> {
+    let mut futures = vec![("handler", handler.check_ready())];
+    for (host, tenant) in tenants {
+        futures.push((host.as_str(), tenant.handler.check_ready()));
+    }
+    for check in checks {
+        futures.push((&check.name, (check.check)()));
+    }
+    let (names, futures): (Vec<_>, Vec<_>) = futures.into_iter().unzip();
+    let results = future::join_all(futures).await;
+    let failed: Vec<_> = names
+        .into_iter()
+        .zip(results)
+        .filter(|(_, result)| result.is_err())
+        .map(|(name, _)| name)
+        .collect();
+    if failed.is_empty() {
+        json_response(StatusCode::OK, &serde_json::json!({ "status": "ready" }))
+    } else {
+        json_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &serde_json::json!({ "status": "unavailable", "failed": failed }),
+        )
+    }
+}
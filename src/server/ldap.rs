@@ -0,0 +1,261 @@
+use super::{FederationHandler, HandlerFuture, ReadyFuture};
+use crate::{domain_to_ascii, split_stellar_address, Error, FederationResponse};
+use ldap3::{ldap_escape, Ldap, LdapConnAsync, Scope, SearchEntry};
+use std::collections::HashMap;
+
+/// [`FederationHandler`] answering name lookups from an LDAP directory.
+///
+/// The handler serves the addresses of a single domain: the name of
+/// the address is looked up in the `uid` attribute of the entries
+/// under the base DN, and the record is built from the
+/// `stellarAccountId`, `stellarMemoType` and `stellarMemo` attributes.
+/// All the attributes can be changed to match the schema of the
+/// directory:
+///
+/// ```rust,no_run
+/// use stellar_federation::server::{FederationService, LdapHandler};
+///
+/// # async fn run() -> Result<(), stellar_federation::Error> {
+/// let handler = LdapHandler::new(
+///     "ldaps://ldap.example.org",
+///     "ou=people,dc=example,dc=org",
+///     "example.org",
+/// )
+/// .bind("cn=federation,dc=example,dc=org", "secret")
+/// .filter("(objectClass=inetOrgPerson)")
+/// .name_attribute("mail")
+/// .account_id_attribute("description");
+/// FederationService::new(handler)
+///     .serve(([0, 0, 0, 0], 8000).into())
+///     .await
+/// # }
+/// ```
+///
+/// A connection is opened for each lookup. Names matching several
+/// entries are answered with `404 Not Found`, like names matching none.
+/// Directory errors, and entries with an invalid account id or memo, are
+/// answered with `500 Internal Server Error`.
+#[derive(Debug, Clone)]
+pub struct LdapHandler {
+    url: String,
+    base_dn: String,
+    domain: String,
+    bind: Option<(String, String)>,
+    filter: Option<String>,
+    name_attribute: String,
+    account_id_attribute: String,
+    memo_type_attribute: String,
+    memo_attribute: String,
+}
+
+impl LdapHandler {
+    /// Creates a handler serving the addresses of `domain` from the
+    /// entries under `base_dn` of the directory at `url`.
+    pub fn new(url: &str, base_dn: &str, domain: &str) -> LdapHandler {
+        LdapHandler {
+            url: url.to_string(),
+            base_dn: base_dn.to_string(),
+            domain: domain_to_ascii(domain).unwrap_or_else(|| domain.to_string()),
+            bind: None,
+            filter: None,
+            name_attribute: "uid".to_string(),
+            account_id_attribute: "stellarAccountId".to_string(),
+            memo_type_attribute: "stellarMemoType".to_string(),
+            memo_attribute: "stellarMemo".to_string(),
+        }
+    }
+
+    /// Binds as `dn` with `password` before searching, instead of
+    /// searching anonymously.
+    pub fn bind(mut self, dn: &str, password: &str) -> LdapHandler {
+        self.bind = Some((dn.to_string(), password.to_string()));
+        self
+    }
+
+    /// Only matches the entries matching the LDAP `filter`, for example
+    /// `(objectClass=inetOrgPerson)`.
+    pub fn filter(mut self, filter: &str) -> LdapHandler {
+        self.filter = Some(filter.to_string());
+        self
+    }
+
+    /// Matches the names of the addresses with the `attribute`
+    /// attribute.
+    pub fn name_attribute(mut self, attribute: &str) -> LdapHandler {
+        self.name_attribute = attribute.to_string();
+        self
+    }
+
+    /// Reads the account ids from the `attribute` attribute.
+    pub fn account_id_attribute(mut self, attribute: &str) -> LdapHandler {
+        self.account_id_attribute = attribute.to_string();
+        self
+    }
+
+    /// Reads the memo types from the `attribute` attribute.
+    pub fn memo_type_attribute(mut self, attribute: &str) -> LdapHandler {
+        self.memo_type_attribute = attribute.to_string();
+        self
+    }
+
+    /// Reads the memos from the `attribute` attribute.
+    pub fn memo_attribute(mut self, attribute: &str) -> LdapHandler {
+        self.memo_attribute = attribute.to_string();
+        self
+    }
+
+    fn search_filter(&self, name: &str) -> String {
+        let filter = format!("({}={})", self.name_attribute, ldap_escape(name));
+        match &self.filter {
+            Some(extra) => format!("(&{}{})", extra, filter),
+            None => filter,
+        }
+    }
+
+    fn record(
+        &self,
+        address: &str,
+        attrs: &HashMap<String, Vec<String>>,
+    ) -> Result<FederationResponse, Error> {
+        let attribute = |name: &str| attrs.get(name).and_then(|values| values.first());
+        let record = serde_json::json!({
+            "stellar_address": address,
+            "account_id": attribute(&self.account_id_attribute),
+            "memo_type": attribute(&self.memo_type_attribute),
+            "memo": attribute(&self.memo_attribute),
+        });
+        Ok(serde_json::from_value(record)?)
+    }
+
+    /// Returns the record of the entry in `entries`, unless there are
+    /// none or several.
+    fn single_record(
+        &self,
+        address: &str,
+        mut entries: Vec<HashMap<String, Vec<String>>>,
+    ) -> Result<Option<FederationResponse>, Error> {
+        match entries.pop() {
+            Some(attrs) if entries.is_empty() => self.record(address, &attrs).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Connects to the directory, binding if configured.
+    async fn connect(&self) -> Result<Ldap, Error> {
+        let (connection, mut ldap) = LdapConnAsync::new(&self.url).await.map_err(ldap_error)?;
+        ldap3::drive!(connection);
+        if let Some((dn, password)) = &self.bind {
+            ldap.simple_bind(dn, password)
+                .await
+                .and_then(|result| result.success())
+                .map_err(ldap_error)?;
+        }
+        Ok(ldap)
+    }
+
+    async fn search(&self, address: &str) -> Result<Option<FederationResponse>, Error> {
+        let name = match split_stellar_address(address) {
+            Some((name, domain)) if domain_to_ascii(domain).as_ref() == Some(&self.domain) => name,
+            _ => return Ok(None),
+        };
+        let mut ldap = self.connect().await?;
+        let attributes = vec![
+            self.account_id_attribute.as_str(),
+            self.memo_type_attribute.as_str(),
+            self.memo_attribute.as_str(),
+        ];
+        let (entries, _) = ldap
+            .search(
+                &self.base_dn,
+                Scope::Subtree,
+                &self.search_filter(name),
+                attributes,
+            )
+            .await
+            .and_then(|result| result.success())
+            .map_err(ldap_error)?;
+        let _ = ldap.unbind().await;
+        let entries = entries
+            .into_iter()
+            .map(|entry| SearchEntry::construct(entry).attrs)
+            .collect();
+        self.single_record(&format!("{}*{}", name, self.domain), entries)
+    }
+}
+
+impl FederationHandler for LdapHandler {
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        Box::pin(self.search(address))
+    }
+
+    fn check_ready(&self) -> ReadyFuture<'_> {
+        Box::pin(async move {
+            let mut ldap = self.connect().await?;
+            let _ = ldap.unbind().await;
+            Ok(())
+        })
+    }
+}
+
+fn ldap_error(err: ldap3::LdapError) -> Error {
+    Error::DatabaseError(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::Memo;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    #[test]
+    fn test_search_filter() {
+        let handler = LdapHandler::new("ldap://localhost", "dc=example,dc=org", "example.org");
+        assert_eq!("(uid=alice)", handler.search_filter("alice"));
+        assert_eq!("(uid=\\2a\\29)", handler.search_filter("*)"));
+        let handler = handler.filter("(objectClass=person)").name_attribute("cn");
+        assert_eq!(
+            "(&(objectClass=person)(cn=alice))",
+            handler.search_filter("alice")
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let handler = LdapHandler::new("ldap://localhost", "dc=example,dc=org", "example.org")
+            .account_id_attribute("description");
+        let mut attrs = HashMap::new();
+        attrs.insert("description".to_string(), vec![ACCOUNT_ID.to_string()]);
+        attrs.insert("stellarMemoType".to_string(), vec!["id".to_string()]);
+        attrs.insert("stellarMemo".to_string(), vec!["42".to_string()]);
+        let record = handler.record("alice*example.org", &attrs).unwrap();
+        assert_eq!(Some(Memo::new_id(42)), record.memo);
+
+        attrs.remove("description");
+        assert!(matches!(
+            handler.record("alice*example.org", &attrs),
+            Err(Error::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_single_record() {
+        let handler = LdapHandler::new("ldap://localhost", "dc=example,dc=org", "example.org");
+        let mut attrs = HashMap::new();
+        attrs.insert("stellarAccountId".to_string(), vec![ACCOUNT_ID.to_string()]);
+        let record = handler
+            .single_record("alice*example.org", vec![attrs.clone()])
+            .unwrap()
+            .unwrap();
+        assert_eq!(ACCOUNT_ID, record.account_id.account_id());
+
+        assert!(handler
+            .single_record("alice*example.org", Vec::new())
+            .unwrap()
+            .is_none());
+        assert!(handler
+            .single_record("alice*example.org", vec![attrs.clone(), attrs])
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,102 @@
+use super::Outcome;
+use hyper::StatusCode;
+use std::time::Duration;
+
+/// Format of the request logs of a [`FederationService`].
+///
+/// Both formats attach the fields of the request to the events, for
+/// subscribers recording structured data. The format only changes the
+/// message of the events.
+///
+/// [`FederationService`]: super::FederationService
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human readable message, for example `name 200 12ms`.
+    #[default]
+    Text,
+    /// The fields of the request as a JSON object, for log pipelines
+    /// reading the messages only.
+    Json,
+}
+
+/// Logs a request answered with `status` after `latency`, without its
+/// `q` parameter nor the address of the client in privacy mode.
+pub(crate) fn log(
+    format: LogFormat,
+    privacy_mode: bool,
+    outcome: &Outcome,
+    status: StatusCode,
+    latency: Duration,
+) {
+    let query_type = outcome.kind.unwrap_or("invalid");
+    let (q, client_ip) = match privacy_mode {
+        true => (None, None),
+        false => (
+            outcome.q.as_deref().map(anonymize),
+            outcome.client_ip.map(|ip| ip.to_string()),
+        ),
+    };
+    let tenant = outcome.tenant.as_deref();
+    let latency_ms = latency.as_millis() as u64;
+    let message = match format {
+        LogFormat::Text => format!("{} {} {}ms", query_type, status.as_u16(), latency_ms),
+        LogFormat::Json => serde_json::json!({
+            "query_type": query_type,
+            "q": q,
+            "status": status.as_u16(),
+            "latency_ms": latency_ms,
+            "client_ip": client_ip,
+            "tenant": tenant,
+        })
+        .to_string(),
+    };
+    if status.is_server_error() {
+        tracing::warn!(
+            target: "stellar_federation::server",
+            query_type,
+            q = q.as_deref(),
+            status = status.as_u16(),
+            latency_ms,
+            client_ip = client_ip.as_deref(),
+            tenant,
+            "{}",
+            message
+        );
+    } else {
+        tracing::info!(
+            target: "stellar_federation::server",
+            query_type,
+            q = q.as_deref(),
+            status = status.as_u16(),
+            latency_ms,
+            client_ip = client_ip.as_deref(),
+            tenant,
+            "{}",
+            message
+        );
+    }
+}
+
+/// Returns a stable pseudonym of the query `q`, so that the requests
+/// for the same address can be correlated without logging it.
+fn anonymize(q: &str) -> String {
+    let hash = stellar_base::crypto::hash(q.as_bytes());
+    hash[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anonymize() {
+        let q = anonymize("alice*example.org");
+        assert_eq!(16, q.len());
+        assert!(!q.contains("alice"));
+        assert_eq!(q, anonymize("alice*example.org"));
+        assert_ne!(q, anonymize("bob*example.org"));
+    }
+}
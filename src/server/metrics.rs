@@ -0,0 +1,200 @@
+use super::Outcome;
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::{Body, Response, StatusCode};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Path of the metrics endpoint.
+pub(crate) const METRICS_PATH: &str = "/metrics";
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Query type label of the requests that are not valid federation
+/// requests.
+const INVALID: &str = "invalid";
+
+/// Request metrics of a federation server, in the Prometheus text
+/// format.
+///
+/// The metrics of the requests of a tenant also have a `tenant` label.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    requests: BTreeMap<(Labels, u16), u64>,
+    latencies: BTreeMap<Labels, Histogram>,
+    backend_errors: BTreeMap<Labels, u64>,
+}
+
+/// Tenant and query type labels of a metric.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Labels {
+    tenant: Option<String>,
+    kind: &'static str,
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(tenant) = &self.tenant {
+            write!(f, "tenant=\"{}\",", tenant)?;
+        }
+        write!(f, "type=\"{}\"", self.kind)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Metrics {
+    /// Records a request answered with `status` after `latency`.
+    pub(crate) fn record(&self, outcome: &Outcome, status: StatusCode, latency: Duration) {
+        let labels = Labels {
+            tenant: outcome.tenant.clone(),
+            kind: outcome.kind.unwrap_or(INVALID),
+        };
+        let latency = latency.as_secs_f64();
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        *state
+            .requests
+            .entry((labels.clone(), status.as_u16()))
+            .or_default() += 1;
+        let histogram = state.latencies.entry(labels.clone()).or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(BUCKETS) {
+            if latency <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += latency;
+        histogram.count += 1;
+        if outcome.backend_error {
+            *state.backend_errors.entry(labels).or_default() += 1;
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub(crate) fn render(&self) -> String {
+        let state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let mut text = String::new();
+        // Writing to a string can't fail.
+        let _ = writeln!(
+            text,
+            "# HELP stellar_federation_requests_total Federation requests by query type and status.\n\
+             # TYPE stellar_federation_requests_total counter"
+        );
+        for ((labels, status), count) in &state.requests {
+            let _ = writeln!(
+                text,
+                "stellar_federation_requests_total{{{},status=\"{}\"}} {}",
+                labels, status, count
+            );
+        }
+        let _ = writeln!(
+            text,
+            "# HELP stellar_federation_request_duration_seconds Federation request latencies by query type.\n\
+             # TYPE stellar_federation_request_duration_seconds histogram"
+        );
+        for (labels, histogram) in &state.latencies {
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                let _ = writeln!(
+                    text,
+                    "stellar_federation_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                text,
+                "stellar_federation_request_duration_seconds_bucket{{{0},le=\"+Inf\"}} {1}\n\
+                 stellar_federation_request_duration_seconds_sum{{{0}}} {2}\n\
+                 stellar_federation_request_duration_seconds_count{{{0}}} {1}",
+                labels, histogram.count, histogram.sum
+            );
+        }
+        let _ = writeln!(
+            text,
+            "# HELP stellar_federation_backend_errors_total Federation handler errors by query type.\n\
+             # TYPE stellar_federation_backend_errors_total counter"
+        );
+        for (labels, count) in &state.backend_errors {
+            let _ = writeln!(
+                text,
+                "stellar_federation_backend_errors_total{{{}}} {}",
+                labels, count
+            );
+        }
+        text
+    }
+
+    /// Answers a request to the metrics endpoint.
+    pub(crate) fn response(&self) -> Response<Body> {
+        let mut response = Response::new(Body::from(self.render()));
+        *response.status_mut() = StatusCode::OK;
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4"),
+        );
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        let name = Outcome {
+            kind: Some("name"),
+            ..Outcome::default()
+        };
+        metrics.record(&name, StatusCode::OK, Duration::from_millis(20));
+        metrics.record(&name, StatusCode::OK, Duration::from_millis(200));
+        let failed = Outcome {
+            kind: Some("id"),
+            backend_error: true,
+            ..Outcome::default()
+        };
+        metrics.record(
+            &failed,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Duration::from_secs(20),
+        );
+        metrics.record(&Outcome::default(), StatusCode::BAD_REQUEST, Duration::ZERO);
+        let tenant = Outcome {
+            kind: Some("name"),
+            tenant: Some("example.org".to_string()),
+            ..Outcome::default()
+        };
+        metrics.record(&tenant, StatusCode::NOT_FOUND, Duration::from_millis(20));
+
+        let text = metrics.render();
+        let lines: Vec<_> = text.lines().collect();
+        for expected in [
+            "stellar_federation_requests_total{type=\"name\",status=\"200\"} 2",
+            "stellar_federation_requests_total{type=\"id\",status=\"500\"} 1",
+            "stellar_federation_requests_total{type=\"invalid\",status=\"400\"} 1",
+            "stellar_federation_request_duration_seconds_bucket{type=\"name\",le=\"0.025\"} 1",
+            "stellar_federation_request_duration_seconds_bucket{type=\"name\",le=\"0.25\"} 2",
+            "stellar_federation_request_duration_seconds_bucket{type=\"id\",le=\"10\"} 0",
+            "stellar_federation_request_duration_seconds_bucket{type=\"id\",le=\"+Inf\"} 1",
+            "stellar_federation_request_duration_seconds_count{type=\"name\"} 2",
+            "stellar_federation_backend_errors_total{type=\"id\"} 1",
+            "stellar_federation_requests_total{tenant=\"example.org\",type=\"name\",status=\"404\"} 1",
+            "stellar_federation_request_duration_seconds_count{tenant=\"example.org\",type=\"name\"} 1",
+        ] {
+            assert!(lines.contains(&expected), "missing {}", expected);
+        }
+    }
+}
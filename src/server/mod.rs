@@ -0,0 +1,848 @@
+//! Federation server.
+//!
+//! The server half of SEP-0002: implement [`FederationHandler`] to look
+//! up the records of your users, then serve it with
+//! [`FederationService`], a hyper service answering federation requests
+//! with spec-compliant JSON responses.
+//!
+//! ```rust,no_run
+//! use stellar_federation::server::{FederationHandler, FederationService, HandlerFuture};
+//! use stellar_federation::FederationResponse;
+//!
+//! #[derive(Debug)]
+//! struct Users;
+//!
+//! impl FederationHandler for Users {
+//!     fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+//!         Box::pin(async move {
+//!             // Look up `address` in the users database.
+//!             Ok(None)
+//!         })
+//!     }
+//! }
+//!
+//! # async fn run() -> Result<(), stellar_federation::Error> {
+//! FederationService::new(Users)
+//!     .serve(([0, 0, 0, 0], 8000).into())
+//!     .await
+//! # }
+//! ```
+//!
+//! For quick integrations, [`FederationServer`] builds a handler from
+//! async closures instead.
+//!
+//! Small servers can use [`StaticHandler`] to serve a fixed table of
+//! records loaded from a TOML, JSON or CSV file. Enable the `sqlx`
+//! feature to serve records stored in a Postgres, MySQL or SQLite
+//! database with `SqlHandler`, or the `ldap` feature to serve them
+//! from an LDAP directory with `LdapHandler`. The records of a
+//! [`RecordStore`] can be managed over HTTP with [`AdminService`].
+//!
+//! [`FederationService`] is a `tower::Service`, so it can also be
+//! embedded in other tower compatible servers and composed with tower
+//! middlewares.
+//!
+//! The module requires the `server` feature. Enable the `axum` feature
+//! to mount the service in an axum application with
+//! `FederationService::into_router`, or the `server-tls` feature to
+//! serve requests over https with `FederationService::serve_tls`. The
+//! `acme` feature also obtains the certificates automatically, for
+//! example from Let's Encrypt, see `FederationService::serve_acme`.
+//! The `openapi` feature describes the service with an OpenAPI
+//! document, see `FederationService::openapi`.
+use crate::{domain_to_ascii, response_json, Error, FederationResponse, RateLimit};
+use hyper::header::{HeaderValue, ALLOW, CACHE_CONTROL, CONTENT_TYPE, IF_NONE_MATCH, RETRY_AFTER};
+use hyper::server::conn::{AddrIncoming, AddrStream};
+use hyper::service::make_service_fn;
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use stellar_base::PublicKey;
+use tower_service::Service;
+
+mod access;
+#[cfg(feature = "acme")]
+mod acme;
+mod admin;
+mod audit;
+#[cfg(feature = "axum")]
+mod axum;
+mod cache_control;
+mod closures;
+mod conditional;
+mod config;
+mod cors;
+mod csv;
+mod error;
+mod health;
+#[cfg(feature = "ldap")]
+mod ldap;
+#[cfg(feature = "tracing")]
+mod log;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "openapi")]
+mod openapi;
+mod shutdown;
+#[cfg(feature = "sqlx")]
+mod sql;
+mod static_handler;
+#[cfg(unix)]
+mod systemd;
+mod tenant;
+#[cfg(feature = "server-tls")]
+mod tls;
+mod webhook;
+
+pub use self::access::IpNetwork;
+use self::access::{Access, AccessControl};
+#[cfg(feature = "acme")]
+pub use self::acme::AcmeTls;
+pub use self::admin::{AdminService, RecordStore, StoreFuture};
+pub use self::audit::AuditLog;
+pub use self::cache_control::CacheControl;
+pub use self::closures::FederationServer;
+pub use self::config::{
+    AuditLogConfig, BackendConfig, CorsConfig, RateLimitConfig, ServerConfig, TlsConfig,
+    WebhookConfig,
+};
+use self::cors::Cors;
+pub use self::csv::CsvColumns;
+pub use self::error::FederationError;
+use self::health::{ReadinessCheck, HEALTH_PATH, READY_PATH};
+#[cfg(feature = "ldap")]
+pub use self::ldap::LdapHandler;
+#[cfg(feature = "tracing")]
+pub use self::log::LogFormat;
+#[cfg(feature = "metrics")]
+use self::metrics::{Metrics, METRICS_PATH};
+#[cfg(feature = "openapi")]
+use self::openapi::OPENAPI_PATH;
+pub use self::shutdown::shutdown_signal;
+#[cfg(feature = "sqlx")]
+pub use self::sql::SqlHandler;
+pub use self::static_handler::{StaticFileHandler, StaticHandler};
+#[cfg(unix)]
+pub use self::systemd::systemd_listener;
+use self::tenant::request_host;
+pub use self::tenant::Tenant;
+#[cfg(feature = "server-tls")]
+pub use self::tls::ServerTls;
+pub use self::webhook::Webhook;
+use crate::FederationRequest;
+pub use crate::ForwardQuery;
+
+/// Future returned by the [`FederationHandler`] methods.
+pub type HandlerFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<FederationResponse>, Error>> + Send + 'a>>;
+
+/// Future returned by the readiness checks of a [`FederationService`].
+pub type ReadyFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Error>> + Send + 'a>>;
+
+/// Source of the records served by a [`FederationService`].
+///
+/// Each method returns the record matching the query, or `None` if
+/// there is none, which the server answers with `404 Not Found`. Errors
+/// are answered with `500 Internal Server Error`, without exposing
+/// them to the client.
+///
+/// Only name lookups are required: the other lookups are answered with
+/// `501 Not Implemented` unless the handler implements them.
+///
+/// Handlers can reject a query, for example a forward query missing a
+/// parameter, with a [`FederationError`], answered with its status code
+/// and detail.
+pub trait FederationHandler: fmt::Debug + Send + Sync {
+    /// Looks up the record of the Stellar `address`.
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a>;
+
+    /// Looks up the record of `account_id`, for reverse lookups.
+    fn lookup_id<'a>(&'a self, account_id: &'a PublicKey) -> HandlerFuture<'a> {
+        let _ = account_id;
+        Box::pin(async { Err(Error::NotImplemented) })
+    }
+
+    /// Looks up the record of the sender of the transaction `tx_id`.
+    fn lookup_txid<'a>(&'a self, tx_id: &'a str) -> HandlerFuture<'a> {
+        let _ = tx_id;
+        Box::pin(async { Err(Error::NotImplemented) })
+    }
+
+    /// Looks up the record to forward a payment to, from the
+    /// parameters of the forward `query`.
+    fn lookup_forward<'a>(&'a self, query: &'a ForwardQuery) -> HandlerFuture<'a> {
+        let _ = query;
+        Box::pin(async { Err(Error::NotImplemented) })
+    }
+
+    /// Checks that the handler can answer lookups, for example that its
+    /// database is reachable, for the readiness endpoint of the server.
+    fn check_ready(&self) -> ReadyFuture<'_> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Returns the `Cache-Control` of the responses with `record`,
+    /// overriding the one set with [`FederationService::cache_control`].
+    ///
+    /// The default implementation returns `None`, to use the one of the
+    /// service.
+    fn cache_control(&self, record: &FederationResponse) -> Option<CacheControl> {
+        let _ = record;
+        None
+    }
+}
+
+/// Shared handlers, for example `Arc<dyn FederationHandler>` to choose
+/// the handler at runtime.
+impl<H: FederationHandler + ?Sized> FederationHandler for Arc<H> {
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        (**self).lookup_name(address)
+    }
+
+    fn lookup_id<'a>(&'a self, account_id: &'a PublicKey) -> HandlerFuture<'a> {
+        (**self).lookup_id(account_id)
+    }
+
+    fn lookup_txid<'a>(&'a self, tx_id: &'a str) -> HandlerFuture<'a> {
+        (**self).lookup_txid(tx_id)
+    }
+
+    fn lookup_forward<'a>(&'a self, query: &'a ForwardQuery) -> HandlerFuture<'a> {
+        (**self).lookup_forward(query)
+    }
+
+    fn check_ready(&self) -> ReadyFuture<'_> {
+        (**self).check_ready()
+    }
+
+    fn cache_control(&self, record: &FederationResponse) -> Option<CacheControl> {
+        (**self).cache_control(record)
+    }
+}
+
+/// Service answering federation requests with a [`FederationHandler`].
+///
+/// The service answers `GET` requests on any path, it can be mounted
+/// behind a router to serve a specific path only.
+///
+/// It implements [`tower_service::Service`] for requests with any body,
+/// the trait used by hyper, so it can be embedded in any tower
+/// compatible stack and wrapped with tower middlewares, for example
+/// for authentication or tracing. The responses have a complete
+/// [`Body`], which can also be built from the output of
+/// [`FederationService::handle`] for other http stacks.
+///
+/// Browser wallets can query the service from any origin: the
+/// responses have the `Access-Control-Allow-Origin: *` header, and
+/// `OPTIONS` preflight requests are answered, as required by SEP-0002.
+/// See [`FederationService::allow_origin`] to restrict the origins.
+///
+/// The successful responses have an `ETag` header, a hash of their
+/// body. Requests with a matching `If-None-Match` header are answered
+/// with `304 Not Modified` and no body, so that clients and CDNs can
+/// revalidate their cached records cheaply.
+///
+/// The requests of each client can be rate limited, and clients can be
+/// denied access by IP address. The address of the client is read from
+/// the [`RemoteAddr`] extension of the requests, set by
+/// [`FederationService::serve`]. Requests without the extension are not
+/// limited.
+///
+/// The `/healthz` and `/readyz` paths are reserved for liveness and
+/// readiness probes, for example in Kubernetes deployments. The service
+/// is ready when [`FederationHandler::check_ready`] and the checks
+/// added with [`FederationService::readiness_check`] succeed, otherwise
+/// `/readyz` is answered with `503 Service Unavailable` and the names
+/// of the failed checks.
+///
+/// With the `metrics` feature, the `/metrics` path is also reserved for
+/// Prometheus metrics: the number of requests by query type and
+/// status, their latencies, and the number of handler errors.
+///
+/// Denied clients are answered with `403 Forbidden` on the reserved
+/// paths too, but the reserved paths are not rate limited unless
+/// [`FederationService::rate_limit_reserved_paths`] is enabled.
+///
+/// With the `tracing` feature, the requests are logged with
+/// [tracing](https://crates.io/crates/tracing), in the
+/// `stellar_federation::server` target: the query type, a pseudonym of
+/// the `q` parameter, the status, the latency and the address of the
+/// client. See [`FederationService::log_format`] for JSON messages, and
+/// [`FederationService::privacy_mode`] to omit the queries and the
+/// clients.
+#[derive(Debug)]
+pub struct FederationService<H> {
+    handler: Arc<H>,
+    settings: Arc<Settings>,
+    remote_addr: Option<SocketAddr>,
+}
+
+/// Settings of a [`FederationService`], shared by its clones.
+#[derive(Debug, Clone)]
+struct Settings {
+    cors: Option<Cors>,
+    access: AccessControl,
+    rate_limit_reserved_paths: bool,
+    readiness_checks: Vec<ReadinessCheck>,
+    id_lookups: bool,
+    txid_lookups: bool,
+    tenants: BTreeMap<String, Tenant>,
+    #[cfg(feature = "metrics")]
+    metrics: Arc<Metrics>,
+    #[cfg(feature = "tracing")]
+    log_format: LogFormat,
+    audit_log: Option<Arc<AuditLog>>,
+    privacy_mode: bool,
+    cache_control: Option<CacheControl>,
+    webhook: Option<Arc<Webhook>>,
+    #[cfg(feature = "openapi")]
+    serve_openapi: bool,
+    grace_period: Duration,
+}
+
+/// What happened to a request, for metrics and logs.
+#[derive(Debug, Default)]
+struct Outcome {
+    /// The query type, if the request is valid.
+    kind: Option<&'static str>,
+    /// The `q` parameter, if the request is valid.
+    q: Option<String>,
+    /// The host of the tenant answering the request, if any.
+    tenant: Option<String>,
+    /// The address of the client, if known.
+    client_ip: Option<IpAddr>,
+    /// Whether the handler failed.
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    backend_error: bool,
+    /// The record answered, if the service has a webhook.
+    record: Option<serde_json::Value>,
+}
+
+/// Request extension with the address of the client.
+///
+/// Insert it in the requests to rate limit or deny clients when
+/// embedding [`FederationService`] in another server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoteAddr(pub SocketAddr);
+
+impl<H> Clone for FederationService<H> {
+    fn clone(&self) -> FederationService<H> {
+        FederationService {
+            handler: self.handler.clone(),
+            settings: self.settings.clone(),
+            remote_addr: self.remote_addr,
+        }
+    }
+}
+
+impl<H: FederationHandler + 'static> FederationService<H> {
+    /// Creates a service answering requests with `handler`.
+    pub fn new(handler: H) -> FederationService<H> {
+        FederationService {
+            handler: Arc::new(handler),
+            settings: Arc::new(Settings {
+                cors: Some(Cors::new(HeaderValue::from_static("*"))),
+                access: AccessControl::default(),
+                rate_limit_reserved_paths: false,
+                readiness_checks: Vec::new(),
+                id_lookups: true,
+                txid_lookups: true,
+                tenants: BTreeMap::new(),
+                #[cfg(feature = "metrics")]
+                metrics: Arc::new(Metrics::default()),
+                #[cfg(feature = "tracing")]
+                log_format: LogFormat::default(),
+                audit_log: None,
+                privacy_mode: false,
+                cache_control: None,
+                webhook: None,
+                #[cfg(feature = "openapi")]
+                serve_openapi: false,
+                grace_period: shutdown::DEFAULT_GRACE_PERIOD,
+            }),
+            remote_addr: None,
+        }
+    }
+
+    /// Only allows cross-origin requests from `origin`, for example
+    /// `https://wallet.example.org`, instead of any origin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `origin` is not a valid header value.
+    pub fn allow_origin(mut self, origin: &str) -> FederationService<H> {
+        let origin = HeaderValue::from_str(origin).expect("invalid origin");
+        Arc::make_mut(&mut self.settings).cors = Some(Cors::new(origin));
+        self
+    }
+
+    /// Doesn't add CORS headers to the responses, nor answer preflight
+    /// requests, for example when they are handled by a proxy.
+    pub fn disable_cors(mut self) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).cors = None;
+        self
+    }
+
+    /// Limits the requests of each client to `limit`, answering the
+    /// requests over the limit with `429 Too Many Requests` and a
+    /// `Retry-After` header.
+    pub fn rate_limit(mut self, limit: RateLimit) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).access.rate_limit(limit);
+        self
+    }
+
+    /// Doesn't rate limit the clients in `network`, for example the
+    /// servers of trusted partners.
+    pub fn allow_ip(mut self, network: IpNetwork) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).access.allow(network);
+        self
+    }
+
+    /// Answers the requests of the clients in `network` with
+    /// `403 Forbidden`, even if they are also allowed with
+    /// [`FederationService::allow_ip`].
+    pub fn deny_ip(mut self, network: IpNetwork) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).access.deny(network);
+        self
+    }
+
+    /// Also rate limits the requests of the `/healthz`, `/readyz`,
+    /// `/metrics` and `/openapi.json` paths, which are exempt by default
+    /// so that probes and scrapers are never throttled. Allow their
+    /// networks with [`FederationService::allow_ip`] instead of
+    /// exempting every client.
+    pub fn rate_limit_reserved_paths(mut self, enable: bool) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).rate_limit_reserved_paths = enable;
+        self
+    }
+
+    /// Adds the `check` readiness check, for example to check that a
+    /// service used by the handler is reachable.
+    pub fn readiness_check<F, Fut>(mut self, name: &str, check: F) -> FederationService<H>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send + 'static,
+    {
+        Arc::make_mut(&mut self.settings)
+            .readiness_checks
+            .push(ReadinessCheck {
+                name: name.to_string(),
+                check: Arc::new(move || Box::pin(check())),
+            });
+        self
+    }
+
+    /// Answers `type=id` queries with `501 Not Implemented`, without
+    /// calling the handler, so that the names owning an account can't
+    /// be discovered.
+    pub fn disable_id_lookups(mut self) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).id_lookups = false;
+        self
+    }
+
+    /// Answers `type=txid` queries with `501 Not Implemented`, without
+    /// calling the handler.
+    pub fn disable_txid_lookups(mut self) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).txid_lookups = false;
+        self
+    }
+
+    /// Answers the requests for `host`, for example `example.org`, with
+    /// the handler and settings of `tenant` instead of the handler of
+    /// the service.
+    ///
+    /// The host of the requests is read from their `Host` header,
+    /// without the port. Requests for other hosts are answered with the
+    /// handler of the service. The metrics and logs of the requests of a
+    /// tenant are labeled with its host.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `host` is not a valid domain.
+    pub fn tenant(mut self, host: &str, tenant: Tenant) -> FederationService<H> {
+        let host = domain_to_ascii(host).expect("invalid tenant host");
+        Arc::make_mut(&mut self.settings)
+            .tenants
+            .insert(host, tenant);
+        self
+    }
+
+    /// Sets the format of the messages of the request logs.
+    #[cfg(feature = "tracing")]
+    pub fn log_format(mut self, format: LogFormat) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).log_format = format;
+        self
+    }
+
+    /// Records the lookups in the append-only `log`, see [`AuditLog`].
+    pub fn audit_log(mut self, log: AuditLog) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).audit_log = Some(Arc::new(log));
+        self
+    }
+
+    /// Doesn't record the queried addresses, nor the addresses of the
+    /// clients: the request logs omit them, and the audit log records a
+    /// hash of the `q` parameter instead of its value. The metrics never
+    /// record them.
+    pub fn privacy_mode(mut self) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).privacy_mode = true;
+        self
+    }
+
+    /// Adds the `cache_control` header to the successful responses, so
+    /// that clients and CDNs can cache the records. Handlers can override
+    /// it for each record with [`FederationHandler::cache_control`].
+    ///
+    /// By default, the responses don't have a `Cache-Control` header.
+    pub fn cache_control(mut self, cache_control: CacheControl) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).cache_control = Some(cache_control);
+        self
+    }
+
+    /// Calls `webhook` after each successful lookup, see [`Webhook`].
+    pub fn webhook(mut self, webhook: Webhook) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).webhook = Some(Arc::new(webhook));
+        self
+    }
+
+    /// Answers `GET /openapi.json` with the OpenAPI document of the
+    /// service, see [`FederationService::openapi`].
+    #[cfg(feature = "openapi")]
+    pub fn serve_openapi(mut self) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).serve_openapi = true;
+        self
+    }
+
+    /// Sets how long in-flight requests are given to complete after
+    /// the shutdown signal, see
+    /// [`FederationService::serve_with_shutdown`]. Defaults to 30
+    /// seconds.
+    pub fn shutdown_grace_period(mut self, grace_period: Duration) -> FederationService<H> {
+        Arc::make_mut(&mut self.settings).grace_period = grace_period;
+        self
+    }
+
+    /// Returns the handler of the service.
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /// Returns the OpenAPI 3 document describing the federation
+    /// requests answered by the service, at the conventional
+    /// `/federation` path, and its probes.
+    ///
+    /// The document reflects the settings of the service, for example
+    /// the disabled query types, but not the settings of its tenants.
+    #[cfg(feature = "openapi")]
+    pub fn openapi(&self) -> serde_json::Value {
+        openapi::document(&self.settings)
+    }
+
+    /// Answers the federation `request`.
+    pub async fn handle<B>(&self, request: Request<B>) -> Response<Body> {
+        let remote_addr = request.extensions().get::<RemoteAddr>();
+        // Probes are not cross-origin, and only rate limited if enabled.
+        if self.is_reserved_path(request.uri().path()) {
+            let access = match remote_addr {
+                Some(RemoteAddr(addr)) if self.settings.rate_limit_reserved_paths => {
+                    self.settings.access.check(addr.ip(), Instant::now())
+                }
+                Some(RemoteAddr(addr)) if self.settings.access.is_denied(addr.ip()) => {
+                    Access::Denied
+                }
+                _ => Access::Allowed,
+            };
+            if let Some(response) = access_response(access) {
+                return response;
+            }
+        }
+        match request.uri().path() {
+            HEALTH_PATH => return health::health(),
+            READY_PATH => {
+                return health::readiness(
+                    &*self.handler,
+                    &self.settings.tenants,
+                    &self.settings.readiness_checks,
+                )
+                .await
+            }
+            #[cfg(feature = "metrics")]
+            METRICS_PATH => return self.settings.metrics.response(),
+            #[cfg(feature = "openapi")]
+            OPENAPI_PATH if self.settings.serve_openapi => {
+                return json_response(StatusCode::OK, &self.openapi())
+            }
+            _ => {}
+        }
+        let started = Instant::now();
+        let cors = self.settings.cors.as_ref();
+        let tenant = match self.settings.tenants.is_empty() {
+            true => None,
+            false => {
+                request_host(&request).and_then(|host| self.settings.tenants.get_key_value(&host))
+            }
+        };
+        let mut outcome = Outcome {
+            tenant: tenant.map(|(host, _)| host.clone()),
+            client_ip: remote_addr.map(|RemoteAddr(addr)| addr.ip()),
+            ..Outcome::default()
+        };
+        let access = match remote_addr {
+            Some(RemoteAddr(addr)) => self.settings.access.check(addr.ip(), Instant::now()),
+            None => Access::Allowed,
+        };
+        let mut response = match (access_response(access), cors, request.method()) {
+            (Some(response), _, _) => response,
+            (None, Some(cors), &Method::OPTIONS) => return cors.preflight(request.headers()),
+            (None, _, _) => {
+                let tenant = tenant.map(|(_, tenant)| tenant);
+                self.answer(request, tenant, &mut outcome).await
+            }
+        };
+        if let Some(cors) = cors {
+            cors.apply(&mut response);
+        }
+        self.observe(&outcome, &response, started);
+        response
+    }
+
+    /// Returns whether `path` is reserved for the probes, the metrics or
+    /// the OpenAPI document.
+    fn is_reserved_path(&self, path: &str) -> bool {
+        match path {
+            HEALTH_PATH | READY_PATH => true,
+            #[cfg(feature = "metrics")]
+            METRICS_PATH => true,
+            #[cfg(feature = "openapi")]
+            OPENAPI_PATH => self.settings.serve_openapi,
+            _ => false,
+        }
+    }
+
+    /// Records the metrics and logs of a request.
+    fn observe(&self, outcome: &Outcome, response: &Response<Body>, started: Instant) {
+        #[cfg(feature = "metrics")]
+        self.settings
+            .metrics
+            .record(outcome, response.status(), started.elapsed());
+        #[cfg(feature = "tracing")]
+        log::log(
+            self.settings.log_format,
+            self.settings.privacy_mode,
+            outcome,
+            response.status(),
+            started.elapsed(),
+        );
+        #[cfg(not(any(feature = "metrics", feature = "tracing")))]
+        let _ = started;
+        if let Some(audit_log) = &self.settings.audit_log {
+            audit_log.record(outcome, response.status(), self.settings.privacy_mode);
+        }
+        if let Some(webhook) = &self.settings.webhook {
+            webhook.notify(outcome);
+        }
+    }
+
+    async fn answer<B>(
+        &self,
+        request: Request<B>,
+        tenant: Option<&Tenant>,
+        outcome: &mut Outcome,
+    ) -> Response<Body> {
+        if request.method() != Method::GET {
+            let mut response = error_response(StatusCode::METHOD_NOT_ALLOWED, "method not allowed");
+            response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static("GET"));
+            return response;
+        }
+        let if_none_match = request.headers().get(IF_NONE_MATCH);
+        let query = request.uri().query().unwrap_or_default();
+        let request = match FederationRequest::parse(query) {
+            Ok(request) => request,
+            Err(detail) => return FederationError::BadRequest(detail.to_string()).into_response(),
+        };
+        outcome.kind = Some(request.kind());
+        outcome.q = request.q();
+        let (handler, id_lookups, txid_lookups): (&dyn FederationHandler, _, _) = match tenant {
+            Some(tenant) => (&*tenant.handler, tenant.id_lookups, tenant.txid_lookups),
+            None => (
+                &*self.handler,
+                self.settings.id_lookups,
+                self.settings.txid_lookups,
+            ),
+        };
+        let result = match &request {
+            FederationRequest::Name(address) => handler.lookup_name(address).await,
+            FederationRequest::Id(_) if !id_lookups => Err(FederationError::NotImplemented.into()),
+            FederationRequest::Id(account_id) => handler.lookup_id(account_id).await,
+            FederationRequest::TxId(_) if !txid_lookups => {
+                Err(FederationError::NotImplemented.into())
+            }
+            FederationRequest::TxId(tx_id) => handler.lookup_txid(tx_id).await,
+            FederationRequest::Forward(query) => handler.lookup_forward(query).await,
+        };
+        match result {
+            Ok(Some(record)) => {
+                let body = response_json(&record);
+                let mut response = conditional::record_response(&body, if_none_match);
+                if self.settings.webhook.is_some() {
+                    outcome.record = Some(body);
+                }
+                let cache_control = handler
+                    .cache_control(&record)
+                    .or(self.settings.cache_control);
+                if let Some(cache_control) = cache_control {
+                    response
+                        .headers_mut()
+                        .insert(CACHE_CONTROL, cache_control.header_value());
+                }
+                response
+            }
+            Ok(None) => FederationError::NotFound.into_response(),
+            Err(err) => match FederationError::from_error(&err) {
+                Some(err) => err.into_response(),
+                None => {
+                    outcome.backend_error = true;
+                    error_response(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+                }
+            },
+        }
+    }
+
+    /// Serves federation requests on `addr` until the server fails.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), Error> {
+        self.serve_with_shutdown(addr, std::future::pending()).await
+    }
+
+    /// Serves federation requests on `addr` until `signal` completes,
+    /// for example [`shutdown_signal`].
+    ///
+    /// After the signal, the server stops accepting connections and
+    /// waits for the in-flight requests to complete, for up to the
+    /// [grace period](FederationService::shutdown_grace_period). The
+    /// connections still open after the grace period are closed when
+    /// the runtime shuts down.
+    ///
+    /// ```rust,no_run
+    /// # use stellar_federation::server::{FederationHandler, FederationService};
+    /// use stellar_federation::server::shutdown_signal;
+    ///
+    /// # async fn run<H: FederationHandler + 'static>(handler: H) -> Result<(), stellar_federation::Error> {
+    /// FederationService::new(handler)
+    ///     .serve_with_shutdown(([0, 0, 0, 0], 8000).into(), shutdown_signal())
+    ///     .await
+    /// # }
+    /// ```
+    pub async fn serve_with_shutdown<S>(self, addr: SocketAddr, signal: S) -> Result<(), Error>
+    where
+        S: Future<Output = ()> + Send + 'static,
+    {
+        self.serve_incoming(Server::try_bind(&addr)?, signal).await
+    }
+
+    /// Serves federation requests on the bound `listener` until the
+    /// server fails, for example a socket passed by systemd, see
+    /// [`systemd_listener`].
+    pub async fn serve_listener(self, listener: std::net::TcpListener) -> Result<(), Error> {
+        self.serve_listener_with_shutdown(listener, std::future::pending())
+            .await
+    }
+
+    /// Serves federation requests on the bound `listener` until `signal`
+    /// completes, see [`FederationService::serve_with_shutdown`].
+    pub async fn serve_listener_with_shutdown<S>(
+        self,
+        listener: std::net::TcpListener,
+        signal: S,
+    ) -> Result<(), Error>
+    where
+        S: Future<Output = ()> + Send + 'static,
+    {
+        self.serve_incoming(Server::from_tcp(listener)?, signal)
+            .await
+    }
+
+    async fn serve_incoming<S>(
+        self,
+        builder: hyper::server::Builder<AddrIncoming>,
+        signal: S,
+    ) -> Result<(), Error>
+    where
+        S: Future<Output = ()> + Send + 'static,
+    {
+        let (signal, deadline) = shutdown::grace_period(signal, self.settings.grace_period);
+        let make_service = make_service_fn(move |connection: &AddrStream| {
+            let mut service = self.clone();
+            service.remote_addr = Some(connection.remote_addr());
+            async move { Ok::<_, Infallible>(service) }
+        });
+        let server = builder.serve(make_service).with_graceful_shutdown(signal);
+        if let Some(result) = shutdown::run_until(server, deadline).await {
+            result?;
+        }
+        Ok(())
+    }
+}
+
+impl<H, B> Service<Request<B>> for FederationService<H>
+where
+    H: FederationHandler + 'static,
+    B: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        if let Some(addr) = self.remote_addr {
+            request.extensions_mut().insert(RemoteAddr(addr));
+        }
+        let service = self.clone();
+        Box::pin(async move { Ok(service.handle(request).await) })
+    }
+}
+
+fn json_response(status: StatusCode, body: &serde_json::Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    response
+}
+
+/// Returns an error response, with the `detail` of the error in the
+/// JSON body.
+fn error_response(status: StatusCode, detail: &str) -> Response<Body> {
+    json_response(status, &serde_json::json!({ "detail": detail }))
+}
+
+/// Returns the response to a request the client is denied, or `None` if
+/// the request is allowed.
+fn access_response(access: Access) -> Option<Response<Body>> {
+    match access {
+        Access::Allowed => None,
+        Access::Denied => Some(error_response(StatusCode::FORBIDDEN, "forbidden")),
+        Access::Limited(retry_after) => {
+            let mut response = error_response(StatusCode::TOO_MANY_REQUESTS, "too many requests");
+            // Round up, so that clients don't retry too early.
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+            Some(response)
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use super::health::{HEALTH_PATH, READY_PATH};
+#[cfg(feature = "metrics")]
+use super::metrics::METRICS_PATH;
+use super::Settings;
+use serde_json::{json, Map, Value};
+
+/// Path of the OpenAPI document.
+pub(crate) const OPENAPI_PATH: &str = "/openapi.json";
+
+/// Path of the federation endpoint in the OpenAPI document.
+const FEDERATION_PATH: &str = "/federation";
+
+/// Returns the OpenAPI document of a service with `settings`.
+pub(crate) fn document(settings: &Settings) -> Value {
+    let mut query_types = vec!["name"];
+    if settings.id_lookups {
+        query_types.push("id");
+    }
+    if settings.txid_lookups {
+        query_types.push("txid");
+    }
+    query_types.push("forward");
+
+    let mut responses = Map::new();
+    let mut ok = json!({
+        "description": "The record matching the query.",
+        "headers": {
+            "ETag": {
+                "description": "Hash of the body, for conditional requests.",
+                "schema": { "type": "string" }
+            }
+        },
+        "content": {
+            "application/json": {
+                "schema": { "$ref": "#/components/schemas/FederationResponse" }
+            }
+        }
+    });
+    if settings.cache_control.is_some() {
+        ok["headers"]["Cache-Control"] = json!({
+            "description": "How long the record can be cached.",
+            "schema": { "type": "string" }
+        });
+    }
+    responses.insert("200".to_string(), ok);
+    responses.insert(
+        "304".to_string(),
+        json!({ "description": "The record matches the `If-None-Match` header." }),
+    );
+    let errors = [
+        (
+            "400",
+            "The query has missing, duplicate, unknown or invalid parameters.",
+        ),
+        ("403", "The client is denied access."),
+        ("404", "There is no record matching the query."),
+        ("405", "The method is not `GET`."),
+        ("429", "The client sent too many requests."),
+        ("500", "The server failed to look up the record."),
+        ("501", "The query type is not supported."),
+    ];
+    for (status, description) in errors.iter() {
+        responses.insert(status.to_string(), error_response(description));
+    }
+
+    let mut paths = Map::new();
+    paths.insert(
+        FEDERATION_PATH.to_string(),
+        json!({
+            "get": {
+                "operationId": "federation",
+                "summary": "Looks up a federation record, as described in SEP-0002.",
+                "parameters": [
+                    {
+                        "name": "type",
+                        "in": "query",
+                        "required": true,
+                        "description": "Type of the query.",
+                        "schema": { "type": "string", "enum": query_types }
+                    },
+                    {
+                        "name": "q",
+                        "in": "query",
+                        "required": true,
+                        "description": "Stellar address, account id or transaction id to look up. Forward queries have the parameters of the forward request instead.",
+                        "schema": { "type": "string" },
+                        "example": "alice*example.org"
+                    },
+                    {
+                        "name": "If-None-Match",
+                        "in": "header",
+                        "required": false,
+                        "description": "ETag of a cached record.",
+                        "schema": { "type": "string" }
+                    }
+                ],
+                "responses": responses
+            }
+        }),
+    );
+    paths.insert(
+        HEALTH_PATH.to_string(),
+        probe("Liveness probe.", "The server is running."),
+    );
+    paths.insert(
+        READY_PATH.to_string(),
+        probe(
+            "Readiness probe, answered with `503 Service Unavailable` and the failed checks when the server is not ready.",
+            "The server is ready.",
+        ),
+    );
+    #[cfg(feature = "metrics")]
+    paths.insert(
+        METRICS_PATH.to_string(),
+        json!({
+            "get": {
+                "operationId": "metrics",
+                "summary": "Prometheus metrics.",
+                "responses": {
+                    "200": {
+                        "description": "The metrics, in the Prometheus text format.",
+                        "content": { "text/plain": { "schema": { "type": "string" } } }
+                    }
+                }
+            }
+        }),
+    );
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Stellar federation server",
+            "description": "Resolves Stellar addresses to account ids, as described in SEP-0002.",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "FederationResponse": {
+                    "type": "object",
+                    "required": ["stellar_address", "account_id"],
+                    "properties": {
+                        "stellar_address": { "type": "string", "example": "alice*example.org" },
+                        "account_id": {
+                            "type": "string",
+                            "example": "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+                        },
+                        "memo_type": { "type": "string", "enum": ["text", "id", "hash", "return"] },
+                        "memo": { "type": "string" }
+                    }
+                },
+                "Error": {
+                    "type": "object",
+                    "required": ["detail"],
+                    "properties": { "detail": { "type": "string" } }
+                }
+            }
+        }
+    })
+}
+
+fn error_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {
+            "application/json": {
+                "schema": { "$ref": "#/components/schemas/Error" }
+            }
+        }
+    })
+}
+
+fn probe(summary: &str, description: &str) -> Value {
+    json!({
+        "get": {
+            "summary": summary,
+            "responses": {
+                "200": {
+                    "description": description,
+                    "content": {
+                        "application/json": {
+                            "schema": {
+                                "type": "object",
+                                "properties": { "status": { "type": "string" } }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::{FederationService, StaticHandler};
+    use hyper::{Body, Request, StatusCode};
+
+    #[tokio::test]
+    async fn test_serve_openapi() {
+        let service = FederationService::new(StaticHandler::default()).disable_txid_lookups();
+        let request = || Request::get("/openapi.json").body(Body::empty()).unwrap();
+        // The request is answered as a federation request.
+        let response = service.handle(request()).await;
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let service = service.serve_openapi();
+        let response = service.handle(request()).await;
+        assert_eq!(StatusCode::OK, response.status());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let document: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(service.openapi(), document);
+        assert_eq!("3.0.3", document["openapi"]);
+        let federation = &document["paths"]["/federation"]["get"];
+        assert_eq!(
+            serde_json::json!(["name", "id", "forward"]),
+            federation["parameters"][0]["schema"]["enum"]
+        );
+        assert!(federation["responses"]["404"].is_object());
+        assert!(document["paths"]["/readyz"].is_object());
+        assert!(document["components"]["schemas"]["FederationResponse"].is_object());
+    }
+}
@@ -0,0 +1,74 @@
+use futures_util::future::{self, Either};
+use std::future::{pending, Future};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Default time given to in-flight requests to complete after the
+/// shutdown signal.
+pub(crate) const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Completes when the process receives `SIGTERM` or ctrl-c.
+///
+/// Use it as the shutdown signal of
+/// [`FederationService::serve_with_shutdown`] to stop the server
+/// gracefully, for example during rolling deploys.
+///
+/// [`FederationService::serve_with_shutdown`]: super::FederationService::serve_with_shutdown
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        // Never complete if the handler can't be installed, rather than
+        // shutting down immediately.
+        if tokio::signal::ctrl_c().await.is_err() {
+            pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            let terminate = async move {
+                terminate.recv().await;
+            };
+            future::select(Box::pin(ctrl_c), Box::pin(terminate)).await;
+            return;
+        }
+    }
+    ctrl_c.await
+}
+
+/// Splits the shutdown `signal` in a signal for the server, and a
+/// future completing `grace_period` after the signal.
+pub(crate) fn grace_period<S>(
+    signal: S,
+    grace_period: Duration,
+) -> (impl Future<Output = ()>, impl Future<Output = ()>)
+where
+    S: Future<Output = ()>,
+{
+    let (sender, receiver) = oneshot::channel();
+    let signal = async move {
+        signal.await;
+        let _ = sender.send(());
+    };
+    let deadline = async move {
+        match receiver.await {
+            Ok(()) => tokio::time::sleep(grace_period).await,
+            // The server stopped before the signal.
+            Err(_) => pending().await,
+        }
+    };
+    (signal, deadline)
+}
+
+/// Runs `server` until it completes, or until `deadline`.
+pub(crate) async fn run_until<F, D>(server: F, deadline: D) -> Option<F::Output>
+where
+    F: Future,
+    D: Future<Output = ()>,
+{
+    match future::select(Box::pin(server), Box::pin(deadline)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
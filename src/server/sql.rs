@@ -0,0 +1,477 @@
+use super::{FederationHandler, HandlerFuture, ReadyFuture, RecordStore, StoreFuture};
+use crate::{normalize_stellar_address, response_json, Error, FederationResponse};
+use sqlx::any::{AnyPool, AnyPoolOptions};
+use stellar_base::PublicKey;
+
+/// Default maximum number of connections of [`SqlHandler::connect`].
+const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+/// [`FederationHandler`] answering name, id and transaction id lookups
+/// from a SQL database, using sqlx.
+///
+/// Postgres, MySQL and SQLite databases are supported. The records are
+/// read from the `stellar_federation_records` table, or the table set
+/// with [`SqlHandler::table`], with the following schema:
+///
+/// ```sql
+/// CREATE TABLE stellar_federation_records (
+///     name VARCHAR(255) PRIMARY KEY NOT NULL,
+///     account_id VARCHAR(56) NOT NULL,
+///     memo_type VARCHAR(6),
+///     memo VARCHAR(64)
+/// );
+/// CREATE INDEX stellar_federation_records_account_id
+///     ON stellar_federation_records (account_id);
+/// ```
+///
+/// `name` is the full Stellar address, with its domain in ASCII form,
+/// and `memo_type` and `memo` are either both set or both `NULL`.
+/// Records with an invalid account id or memo are answered with
+/// `500 Internal Server Error`.
+///
+/// Tables created with a `memo_type VARCHAR(4)` column can't store the
+/// `return` memo type, widen the column before adding such records, for
+/// example with `ALTER TABLE stellar_federation_records ALTER COLUMN
+/// memo_type TYPE VARCHAR(6)` on Postgres or `ALTER TABLE
+/// stellar_federation_records MODIFY memo_type VARCHAR(6)` on MySQL.
+///
+/// Transaction id lookups are answered from a second table, set with
+/// [`SqlHandler::transactions_table`], mapping the id of each
+/// transaction to the name of the user who sent it:
+///
+/// ```sql
+/// CREATE TABLE stellar_federation_transactions (
+///     tx_id VARCHAR(64) PRIMARY KEY NOT NULL,
+///     name VARCHAR(255) NOT NULL
+/// );
+/// ```
+///
+/// `tx_id` is the hex encoded hash of the transaction, in lowercase.
+/// Without this table, transaction id lookups are answered with
+/// `501 Not Implemented`.
+///
+/// Users sharing a pooled account are told apart with their memo, for
+/// example an id memo per user. Id lookups of an account shared by
+/// several records are answered with `404 Not Found`.
+///
+/// The handler is also a [`RecordStore`], so the records can be managed
+/// with an [`AdminService`].
+///
+/// Queries are prepared once per connection and the connections are
+/// pooled, [`SqlHandler::from_pool`] accepts a pool configured by the
+/// application.
+///
+/// [`AdminService`]: super::AdminService
+#[derive(Debug, Clone)]
+pub struct SqlHandler {
+    pool: AnyPool,
+    table: String,
+    transactions_table: Option<String>,
+    name_query: String,
+    id_query: String,
+    txid_query: Option<String>,
+    list_query: String,
+    insert_query: String,
+    update_query: String,
+    delete_query: String,
+}
+
+impl SqlHandler {
+    /// Connects to the database at `url`, for example
+    /// `postgres://localhost/users`, with a pool of up to 10
+    /// connections.
+    pub async fn connect(url: &str) -> Result<SqlHandler, Error> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(DEFAULT_MAX_CONNECTIONS)
+            .connect(url)
+            .await
+            .map_err(database_error)?;
+        Ok(SqlHandler::from_pool(pool))
+    }
+
+    /// Creates a handler querying the databases of `pool`.
+    ///
+    /// The sqlx drivers must be installed before creating the pool,
+    /// with `sqlx::any::install_default_drivers`.
+    pub fn from_pool(pool: AnyPool) -> SqlHandler {
+        SqlHandler {
+            pool,
+            table: String::new(),
+            transactions_table: None,
+            name_query: String::new(),
+            id_query: String::new(),
+            txid_query: None,
+            list_query: String::new(),
+            insert_query: String::new(),
+            update_query: String::new(),
+            delete_query: String::new(),
+        }
+        .table("stellar_federation_records")
+    }
+
+    /// Reads the records from `table` instead of
+    /// `stellar_federation_records`.
+    ///
+    /// The table name is not escaped, it must not come from user input.
+    pub fn table(mut self, table: &str) -> SqlHandler {
+        self.table = table.to_string();
+        self.prepare_queries()
+    }
+
+    /// Answers transaction id lookups from `table`, for example
+    /// `stellar_federation_transactions`.
+    ///
+    /// The table name is not escaped, it must not come from user input.
+    pub fn transactions_table(mut self, table: &str) -> SqlHandler {
+        self.transactions_table = Some(table.to_string());
+        self.prepare_queries()
+    }
+
+    fn prepare_queries(mut self) -> SqlHandler {
+        // Postgres only accepts numbered parameters, MySQL only accepts
+        // positional ones.
+        let postgres = matches!(
+            self.pool.connect_options().database_url.scheme(),
+            "postgres" | "postgresql"
+        );
+        let parameter = |index: usize| match postgres {
+            true => format!("${}", index),
+            false => "?".to_string(),
+        };
+        let columns = "name, account_id, memo_type, memo";
+        let table = &self.table;
+        self.name_query = format!(
+            "SELECT {} FROM {} WHERE name = {}",
+            columns,
+            table,
+            parameter(1)
+        );
+        self.id_query = format!(
+            "SELECT {} FROM {} WHERE account_id = {} LIMIT 2",
+            columns,
+            table,
+            parameter(1)
+        );
+        self.txid_query = self.transactions_table.as_ref().map(|transactions| {
+            format!(
+                "SELECT r.name, r.account_id, r.memo_type, r.memo FROM {} r \
+                 JOIN {} t ON t.name = r.name WHERE t.tx_id = {}",
+                table,
+                transactions,
+                parameter(1)
+            )
+        });
+        self.list_query = format!("SELECT {} FROM {} ORDER BY name", columns, table);
+        self.insert_query = format!(
+            "INSERT INTO {} ({}) VALUES ({}, {}, {}, {})",
+            table,
+            columns,
+            parameter(1),
+            parameter(2),
+            parameter(3),
+            parameter(4)
+        );
+        self.update_query = format!(
+            "UPDATE {} SET name = {}, account_id = {}, memo_type = {}, memo = {} \
+             WHERE name = {}",
+            table,
+            parameter(1),
+            parameter(2),
+            parameter(3),
+            parameter(4),
+            parameter(5)
+        );
+        self.delete_query = format!("DELETE FROM {} WHERE name = {}", table, parameter(1));
+        self
+    }
+
+    /// Returns the connection pool of the handler.
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    /// Returns the record matched by `query`, unless it matches none
+    /// or several.
+    async fn fetch(&self, query: &str, value: String) -> Result<Option<FederationResponse>, Error> {
+        let mut rows: Vec<Row> = sqlx::query_as(query)
+            .bind(value)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(database_error)?;
+        match rows.pop() {
+            Some(row) if rows.is_empty() => Ok(Some(record(row)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Runs the `query` modifying a record, binding the columns of
+    /// `record` and then `address`, failing with [`Error::NotFound`] if
+    /// no row is modified.
+    async fn modify(
+        &self,
+        query: &str,
+        record: Option<&FederationResponse>,
+        address: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut query = sqlx::query(query);
+        if let Some(record) = record {
+            let body = response_json(record);
+            let column = |name: &str| body.get(name).and_then(|value| value.as_str());
+            query = query
+                .bind(record.stellar_address.clone())
+                .bind(record.account_id.account_id())
+                .bind(column("memo_type").map(str::to_string))
+                .bind(column("memo").map(str::to_string));
+        }
+        if let Some(address) = address {
+            query = query.bind(address.to_string());
+        }
+        let result = query.execute(&self.pool).await.map_err(|err| {
+            let exists = err
+                .as_database_error()
+                .map_or(false, |err| err.is_unique_violation());
+            match exists {
+                true => Error::RecordExists,
+                false => database_error(err),
+            }
+        })?;
+        match result.rows_affected() {
+            0 => Err(Error::NotFound),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Row of the records table: the name, account id, memo type and memo.
+type Row = (String, String, Option<String>, Option<String>);
+
+fn record((name, account_id, memo_type, memo): Row) -> Result<FederationResponse, Error> {
+    let record = serde_json::json!({
+        "stellar_address": name,
+        "account_id": account_id,
+        "memo_type": memo_type,
+        "memo": memo,
+    });
+    Ok(serde_json::from_value(record)?)
+}
+
+impl FederationHandler for SqlHandler {
+    fn check_ready(&self) -> ReadyFuture<'_> {
+        Box::pin(async move {
+            sqlx::query("SELECT 1")
+                .execute(&self.pool)
+                .await
+                .map_err(database_error)?;
+            Ok(())
+        })
+    }
+
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        Box::pin(async move {
+            match normalize_stellar_address(address) {
+                Some(address) => self.fetch(&self.name_query, address).await,
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn lookup_id<'a>(&'a self, account_id: &'a PublicKey) -> HandlerFuture<'a> {
+        Box::pin(async move { self.fetch(&self.id_query, account_id.account_id()).await })
+    }
+
+    fn lookup_txid<'a>(&'a self, tx_id: &'a str) -> HandlerFuture<'a> {
+        Box::pin(async move {
+            match &self.txid_query {
+                Some(query) => self.fetch(query, tx_id.to_ascii_lowercase()).await,
+                None => Err(Error::NotImplemented),
+            }
+        })
+    }
+}
+
+impl RecordStore for SqlHandler {
+    fn list_records(&self) -> StoreFuture<'_, Vec<FederationResponse>> {
+        Box::pin(async move {
+            let rows: Vec<Row> = sqlx::query_as(&self.list_query)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(database_error)?;
+            rows.into_iter().map(record).collect()
+        })
+    }
+
+    fn create_record(&self, record: FederationResponse) -> StoreFuture<'_, ()> {
+        Box::pin(async move { self.modify(&self.insert_query, Some(&record), None).await })
+    }
+
+    fn update_record<'a>(
+        &'a self,
+        address: &'a str,
+        record: FederationResponse,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            self.modify(&self.update_query, Some(&record), Some(address))
+                .await
+        })
+    }
+
+    fn delete_record<'a>(&'a self, address: &'a str) -> StoreFuture<'a, ()> {
+        Box::pin(async move { self.modify(&self.delete_query, None, Some(address)).await })
+    }
+}
+
+fn database_error(err: sqlx::Error) -> Error {
+    Error::DatabaseError(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::Memo;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    #[tokio::test]
+    async fn test_sqlite() {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE users (
+                name VARCHAR(255) PRIMARY KEY NOT NULL,
+                account_id VARCHAR(56) NOT NULL,
+                memo_type VARCHAR(6),
+                memo VARCHAR(64)
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO users VALUES (?, ?, 'id', '42')")
+            .bind("alice*xn--bcher-kva.example")
+            .bind(ACCOUNT_ID)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let handler = SqlHandler::from_pool(pool).table("users");
+        let record = handler
+            .lookup_name("alice*bücher.example")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("alice*xn--bcher-kva.example", record.stellar_address);
+        assert_eq!(Some(Memo::new_id(42)), record.memo);
+
+        let account_id = PublicKey::from_account_id(ACCOUNT_ID).unwrap();
+        let record = handler.lookup_id(&account_id).await.unwrap().unwrap();
+        assert_eq!("alice*xn--bcher-kva.example", record.stellar_address);
+        assert!(handler
+            .lookup_name("bob*example.org")
+            .await
+            .unwrap()
+            .is_none());
+
+        // Pooled accounts can't be resolved by id.
+        sqlx::query("INSERT INTO users VALUES (?, ?, 'id', '43')")
+            .bind("bob*example.org")
+            .bind(ACCOUNT_ID)
+            .execute(handler.pool())
+            .await
+            .unwrap();
+        assert!(handler.lookup_id(&account_id).await.unwrap().is_none());
+        let record = handler
+            .lookup_name("bob*example.org")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(Memo::new_id(43)), record.memo);
+
+        // Transaction id lookups need a transactions table.
+        assert!(matches!(
+            handler.lookup_txid("abc").await,
+            Err(Error::NotImplemented)
+        ));
+        sqlx::query(
+            "CREATE TABLE transactions (
+                tx_id VARCHAR(64) PRIMARY KEY NOT NULL,
+                name VARCHAR(255) NOT NULL
+            )",
+        )
+        .execute(handler.pool())
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO transactions VALUES ('abc', 'bob*example.org')")
+            .execute(handler.pool())
+            .await
+            .unwrap();
+        let handler = handler.transactions_table("transactions");
+        let record = handler.lookup_txid("ABC").await.unwrap().unwrap();
+        assert_eq!("bob*example.org", record.stellar_address);
+        assert!(handler.lookup_txid("def").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_record_store() {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE stellar_federation_records (
+                name VARCHAR(255) PRIMARY KEY NOT NULL,
+                account_id VARCHAR(56) NOT NULL,
+                memo_type VARCHAR(6),
+                memo VARCHAR(64)
+            )",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let handler = SqlHandler::from_pool(pool);
+        let record = |name: &str, memo| FederationResponse {
+            stellar_address: name.to_string(),
+            account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+            muxed_account: None,
+            memo: Some(Memo::new_id(memo)),
+            extra: Default::default(),
+        };
+        handler
+            .create_record(record("alice*example.org", 1))
+            .await
+            .unwrap();
+        handler
+            .create_record(record("bob*example.org", 2))
+            .await
+            .unwrap();
+        assert!(matches!(
+            handler.create_record(record("bob*example.org", 2)).await,
+            Err(Error::RecordExists)
+        ));
+        assert!(matches!(
+            handler
+                .update_record("alice*example.org", record("bob*example.org", 1))
+                .await,
+            Err(Error::RecordExists)
+        ));
+        handler
+            .update_record("alice*example.org", record("carol*example.org", 3))
+            .await
+            .unwrap();
+        handler.delete_record("bob*example.org").await.unwrap();
+        assert!(matches!(
+            handler.delete_record("bob*example.org").await,
+            Err(Error::NotFound)
+        ));
+        let records = handler.list_records().await.unwrap();
+        assert_eq!(1, records.len());
+        assert_eq!("carol*example.org", records[0].stellar_address);
+        assert_eq!(Some(Memo::new_id(3)), records[0].memo);
+    }
+}
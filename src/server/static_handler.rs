@@ -0,0 +1,658 @@
+use super::csv::{self, CsvColumns};
+use super::{CacheControl, FederationHandler, HandlerFuture};
+use crate::{normalize_stellar_address, Error, FederationResponse, StaticResolver};
+use serde::de::Error as SerdeError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use stellar_base::PublicKey;
+
+/// [`FederationHandler`] answering name and id lookups from a fixed
+/// table of records, kept in memory.
+///
+/// The records are loaded from a TOML or JSON file with a `records`
+/// list, each record having a `name`, an `account_id`, and an optional
+/// `memo_type` and `memo`:
+///
+/// ```toml
+/// [[records]]
+/// name = "alice*example.org"
+/// account_id = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+/// memo_type = "id"
+/// memo = "42"
+/// ```
+///
+/// Users sharing a pooled account are told apart with their memo. The
+/// account can be set once at the top of the file, for the records
+/// without an `account_id`:
+///
+/// ```toml
+/// account_id = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+///
+/// [[records]]
+/// name = "alice*example.org"
+/// memo_type = "id"
+/// memo = 42
+///
+/// [[records]]
+/// name = "bob*example.org"
+/// memo_type = "hash"
+/// memo = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="
+/// ```
+///
+/// Id memos can be written as strings or integers, they are always
+/// answered as strings, and hash memos are written in base64, as
+/// described in SEP-0002. An id lookup of a pooled account is answered
+/// with `404 Not Found`, since it matches several records.
+///
+/// Records can have a `cache_control`, for example `max-age=3600` or
+/// `no-store`, overriding the [`CacheControl`] of the service for their
+/// responses.
+///
+/// Records can also be loaded from CSV files, see
+/// [`StaticHandler::from_csv`].
+///
+/// The domains of the names are matched case-insensitively, and
+/// internationalized domains match their ASCII form. Files with several
+/// records for the same address, in any format, fail to load with
+/// [`Error::RecordExists`].
+///
+/// The records are fixed, [`StaticFileHandler`] reloads them when their
+/// file changes.
+#[derive(Debug, Clone, Default)]
+pub struct StaticHandler {
+    records: StaticResolver,
+    /// `Cache-Control` of the records, by normalized address.
+    cache_controls: BTreeMap<String, CacheControl>,
+}
+
+#[derive(Deserialize)]
+struct RecordsFile<T> {
+    #[serde(default)]
+    account_id: Option<String>,
+    records: Vec<T>,
+}
+
+impl StaticHandler {
+    /// Creates a handler serving the records of `records`.
+    pub fn new(records: StaticResolver) -> StaticHandler {
+        StaticHandler {
+            records,
+            cache_controls: BTreeMap::new(),
+        }
+    }
+
+    /// Loads the records of the file at `path`.
+    ///
+    /// Files with the `json` extension are parsed as JSON, files with
+    /// the `csv` extension as CSV with the default columns, and other
+    /// files as TOML.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<StaticHandler, Error> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        if path.extension() == Some("json".as_ref()) {
+            StaticHandler::from_json(&contents)
+        } else if path.extension() == Some("csv".as_ref()) {
+            StaticHandler::from_csv(&contents, &CsvColumns::new())
+        } else {
+            StaticHandler::from_toml(&contents)
+        }
+    }
+
+    /// Loads the records of the CSV file at `path`, see
+    /// [`StaticHandler::from_csv`].
+    pub fn open_csv<P: AsRef<Path>>(path: P, columns: &CsvColumns) -> Result<StaticHandler, Error> {
+        let contents = fs::read_to_string(path)?;
+        StaticHandler::from_csv(&contents, columns)
+    }
+
+    /// Parses the records of a TOML document.
+    pub fn from_toml(contents: &str) -> Result<StaticHandler, Error> {
+        let file: RecordsFile<toml::value::Table> = toml::from_str(contents)?;
+        let mut handler = StaticHandler::default();
+        for mut table in file.records {
+            let name = table
+                .remove("name")
+                .ok_or_else(|| toml::de::Error::missing_field("name"))?;
+            table.insert("stellar_address".to_string(), name);
+            if let Some(account_id) = &file.account_id {
+                table
+                    .entry("account_id".to_string())
+                    .or_insert_with(|| account_id.clone().into());
+            }
+            if let Some(toml::Value::Integer(memo)) = table.get("memo") {
+                let memo = memo.to_string();
+                table.insert("memo".to_string(), memo.into());
+            }
+            let cache_control = match table.remove("cache_control") {
+                Some(toml::Value::String(value)) => Some(value.parse()?),
+                Some(_) => return Err(toml::de::Error::custom("invalid cache_control").into()),
+                None => None,
+            };
+            handler.insert(toml::Value::Table(table).try_into()?, cache_control)?;
+        }
+        Ok(handler)
+    }
+
+    /// Parses the records of a JSON document.
+    pub fn from_json(contents: &str) -> Result<StaticHandler, Error> {
+        let file: RecordsFile<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(contents)?;
+        let mut handler = StaticHandler::default();
+        for mut object in file.records {
+            let name = object
+                .remove("name")
+                .ok_or_else(|| serde_json::Error::missing_field("name"))?;
+            object.insert("stellar_address".to_string(), name);
+            if let Some(account_id) = &file.account_id {
+                object
+                    .entry("account_id")
+                    .or_insert_with(|| account_id.clone().into());
+            }
+            if let Some(serde_json::Value::Number(memo)) = object.get("memo") {
+                let memo = memo.to_string();
+                object.insert("memo".to_string(), memo.into());
+            }
+            let cache_control = match object.remove("cache_control") {
+                Some(serde_json::Value::String(value)) => Some(value.parse()?),
+                Some(_) => return Err(serde_json::Error::custom("invalid cache_control").into()),
+                None => None,
+            };
+            handler.insert(serde_json::from_value(object.into())?, cache_control)?;
+        }
+        Ok(handler)
+    }
+
+    /// Parses the records of a CSV document, reading their fields from
+    /// `columns`.
+    ///
+    /// The whole document is validated: invalid addresses, account ids
+    /// and memos fail with [`Error::CsvError`] and the line of the
+    /// record, and duplicate addresses with [`Error::RecordExists`].
+    pub fn from_csv(contents: &str, columns: &CsvColumns) -> Result<StaticHandler, Error> {
+        let mut handler = StaticHandler::default();
+        for (line, record) in csv::parse_records(contents, columns)? {
+            match handler.insert(record, None) {
+                Err(Error::RecordExists) => return Err(Error::RecordExists),
+                Err(_) => {
+                    return Err(Error::CsvError {
+                        line,
+                        message: "invalid stellar address".to_string(),
+                    })
+                }
+                Ok(()) => {}
+            }
+        }
+        Ok(handler)
+    }
+
+    /// Returns the records served by the handler.
+    pub fn records(&self) -> &StaticResolver {
+        &self.records
+    }
+
+    /// Adds `record`, failing with [`Error::RecordExists`] if there is
+    /// already a record with its address.
+    fn insert(
+        &mut self,
+        record: FederationResponse,
+        cache_control: Option<CacheControl>,
+    ) -> Result<(), Error> {
+        if self.records.get(&record.stellar_address).is_some() {
+            return Err(Error::RecordExists);
+        }
+        let address = normalize_stellar_address(&record.stellar_address);
+        self.records.insert(record)?;
+        if let (Some(address), Some(cache_control)) = (address, cache_control) {
+            self.cache_controls.insert(address, cache_control);
+        }
+        Ok(())
+    }
+}
+
+impl FederationHandler for StaticHandler {
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        let record = self.records.get(address).cloned();
+        Box::pin(async move { Ok(record) })
+    }
+
+    fn lookup_id<'a>(&'a self, account_id: &'a PublicKey) -> HandlerFuture<'a> {
+        // Accounts shared by several records can't be resolved.
+        let mut records = self.records.account_records(account_id);
+        let record = match (records.next(), records.next()) {
+            (Some(record), None) => Some(record.clone()),
+            _ => None,
+        };
+        Box::pin(async move { Ok(record) })
+    }
+
+    fn cache_control(&self, record: &FederationResponse) -> Option<CacheControl> {
+        let address = normalize_stellar_address(&record.stellar_address)?;
+        self.cache_controls.get(&address).copied()
+    }
+}
+
+/// [`FederationHandler`] serving the records of a file with a
+/// [`StaticHandler`], reloading them when the file changes.
+///
+/// The records are reloaded with [`StaticFileHandler::reload`], when the
+/// process receives `SIGHUP` with [`StaticFileHandler::reload_on_sighup`],
+/// or when the modification time of the file changes with
+/// [`StaticFileHandler::watch`]:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use stellar_federation::server::{FederationService, StaticFileHandler};
+///
+/// # async fn run() -> Result<(), stellar_federation::Error> {
+/// let handler = StaticFileHandler::open("records.toml")?;
+/// tokio::spawn(handler.clone().watch(Duration::from_secs(5)));
+/// FederationService::new(handler)
+///     .serve(([0, 0, 0, 0], 8000).into())
+///     .await
+/// # }
+/// ```
+///
+/// The whole file is validated before replacing the records, lookups
+/// are answered from either the previous or the new records, never from
+/// a mix of both. If the file can't be loaded, the previous records are
+/// kept.
+#[derive(Debug, Clone)]
+pub struct StaticFileHandler {
+    path: PathBuf,
+    columns: Option<CsvColumns>,
+    loaded: Arc<RwLock<Loaded>>,
+}
+
+/// Records loaded by a [`StaticFileHandler`].
+#[derive(Debug)]
+struct Loaded {
+    handler: Arc<StaticHandler>,
+    /// Modification time of the file when it was loaded.
+    modified: Option<SystemTime>,
+}
+
+impl StaticFileHandler {
+    /// Loads the records of the file at `path`, like
+    /// [`StaticHandler::open`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<StaticFileHandler, Error> {
+        StaticFileHandler::load(path.as_ref().to_path_buf(), None)
+    }
+
+    /// Loads the records of the CSV file at `path`, like
+    /// [`StaticHandler::open_csv`].
+    pub fn open_csv<P: AsRef<Path>>(
+        path: P,
+        columns: &CsvColumns,
+    ) -> Result<StaticFileHandler, Error> {
+        StaticFileHandler::load(path.as_ref().to_path_buf(), Some(columns.clone()))
+    }
+
+    fn load(path: PathBuf, columns: Option<CsvColumns>) -> Result<StaticFileHandler, Error> {
+        let loaded = load_file(&path, columns.as_ref())?;
+        Ok(StaticFileHandler {
+            path,
+            columns,
+            loaded: Arc::new(RwLock::new(loaded)),
+        })
+    }
+
+    /// Reads the file again, keeping the current records if it can't be
+    /// loaded.
+    pub fn reload(&self) -> Result<(), Error> {
+        let loaded = load_file(&self.path, self.columns.as_ref())?;
+        *self.loaded.write().unwrap_or_else(|err| err.into_inner()) = loaded;
+        Ok(())
+    }
+
+    /// Returns the records currently served.
+    pub fn current(&self) -> Arc<StaticHandler> {
+        let loaded = self.loaded.read().unwrap_or_else(|err| err.into_inner());
+        loaded.handler.clone()
+    }
+
+    /// Reloads the records whenever the process receives `SIGHUP`.
+    ///
+    /// The returned future must be spawned, it completes only if the
+    /// signal can't be received.
+    pub async fn reload_on_sighup(self) {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(_) => return,
+            };
+            while hangup.recv().await.is_some() {
+                let _ = self.reload();
+            }
+        }
+    }
+
+    /// Checks the modification time of the file every `interval`,
+    /// reloading the records when it changes.
+    ///
+    /// The returned future must be spawned, it never completes.
+    pub async fn watch(self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            let modified = fs::metadata(&self.path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            let loaded = self.loaded.read().unwrap_or_else(|err| err.into_inner());
+            let changed = modified != loaded.modified;
+            drop(loaded);
+            if changed {
+                let _ = self.reload();
+            }
+        }
+    }
+}
+
+fn load_file(path: &Path, columns: Option<&CsvColumns>) -> Result<Loaded, Error> {
+    // Read before the contents, so that changes made while loading
+    // them are loaded again.
+    let modified = fs::metadata(path)?.modified().ok();
+    let handler = match columns {
+        Some(columns) => StaticHandler::open_csv(path, columns)?,
+        None => StaticHandler::open(path)?,
+    };
+    Ok(Loaded {
+        handler: Arc::new(handler),
+        modified,
+    })
+}
+
+impl FederationHandler for StaticFileHandler {
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        let handler = self.current();
+        Box::pin(async move { handler.lookup_name(address).await })
+    }
+
+    fn lookup_id<'a>(&'a self, account_id: &'a PublicKey) -> HandlerFuture<'a> {
+        let handler = self.current();
+        Box::pin(async move { handler.lookup_id(account_id).await })
+    }
+
+    fn cache_control(&self, record: &FederationResponse) -> Option<CacheControl> {
+        self.current().cache_control(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::Memo;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    #[tokio::test]
+    async fn test_from_toml() {
+        let handler = StaticHandler::from_toml(&format!(
+            r#"
+            [[records]]
+            name = "alice*example.org"
+            account_id = "{}"
+            memo_type = "id"
+            memo = "42"
+            "#,
+            ACCOUNT_ID
+        ))
+        .unwrap();
+        let record = handler
+            .lookup_name("alice*Example.ORG")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("alice*example.org", record.stellar_address);
+        assert_eq!(Some(Memo::new_id(42)), record.memo);
+
+        let account_id = PublicKey::from_account_id(ACCOUNT_ID).unwrap();
+        let record = handler.lookup_id(&account_id).await.unwrap().unwrap();
+        assert_eq!("alice*example.org", record.stellar_address);
+        assert!(handler
+            .lookup_name("bob*example.org")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let handler = StaticHandler::from_json(&format!(
+            r#"{{"account_id": "{}", "records": [
+                {{"name": "alice*example.org", "cache_control": "max-age=60"}},
+                {{"name": "bob*example.org"}}
+            ]}}"#,
+            ACCOUNT_ID
+        ))
+        .unwrap();
+        let cache_control = |address| {
+            let record = handler.records().get(address).unwrap();
+            handler.cache_control(record)
+        };
+        assert_eq!(
+            Some(CacheControl::MaxAge(Duration::from_secs(60))),
+            cache_control("alice*Example.org")
+        );
+        assert_eq!(None, cache_control("bob*example.org"));
+
+        for invalid in [r#""cache_control": "forever""#, r#""cache_control": 60"#] {
+            let contents = format!(
+                r#"{{"records": [{{"name": "alice*example.org", "account_id": "{}", {}}}]}}"#,
+                ACCOUNT_ID, invalid
+            );
+            assert!(StaticHandler::from_json(&contents).is_err());
+        }
+    }
+
+    #[test]
+    fn test_from_json() {
+        let handler = StaticHandler::from_json(&format!(
+            r#"{{"records": [{{"name": "alice*example.org", "account_id": "{}"}}]}}"#,
+            ACCOUNT_ID
+        ))
+        .unwrap();
+        assert_eq!(1, handler.records().len());
+
+        let missing_name = format!(r#"{{"records": [{{"account_id": "{}"}}]}}"#, ACCOUNT_ID);
+        assert!(matches!(
+            StaticHandler::from_json(&missing_name),
+            Err(Error::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_records() {
+        let toml = format!(
+            r#"
+            account_id = "{}"
+            [[records]]
+            name = "alice*example.org"
+            [[records]]
+            name = "alice*Example.ORG"
+            "#,
+            ACCOUNT_ID
+        );
+        let json = format!(
+            r#"{{"account_id": "{}", "records": [
+                {{"name": "alice*example.org"}},
+                {{"name": "alice*Example.ORG"}}
+            ]}}"#,
+            ACCOUNT_ID
+        );
+        let csv = format!(
+            "name,account_id\nalice*example.org,{0}\nalice*Example.ORG,{0}\n",
+            ACCOUNT_ID
+        );
+        let columns = CsvColumns::new();
+        for result in [
+            StaticHandler::from_toml(&toml),
+            StaticHandler::from_json(&json),
+            StaticHandler::from_csv(&csv, &columns),
+        ] {
+            assert!(matches!(result, Err(Error::RecordExists)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pooled_account() {
+        let handler = StaticHandler::from_toml(&format!(
+            r#"
+            account_id = "{}"
+
+            [[records]]
+            name = "alice*example.org"
+            memo_type = "id"
+            memo = 42
+
+            [[records]]
+            name = "bob*example.org"
+            memo_type = "id"
+            memo = "43"
+            "#,
+            ACCOUNT_ID
+        ))
+        .unwrap();
+        let record = handler
+            .lookup_name("alice*example.org")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ACCOUNT_ID, record.account_id.account_id());
+        assert_eq!(Some(Memo::new_id(42)), record.memo);
+        let record = handler
+            .lookup_name("bob*example.org")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(Memo::new_id(43)), record.memo);
+
+        let account_id = PublicKey::from_account_id(ACCOUNT_ID).unwrap();
+        assert!(handler.lookup_id(&account_id).await.unwrap().is_none());
+
+        let handler = StaticHandler::from_json(&format!(
+            r#"{{"account_id": "{}", "records": [{{"name": "alice*example.org", "memo_type": "id", "memo": 42}}]}}"#,
+            ACCOUNT_ID
+        ))
+        .unwrap();
+        let record = handler.records().get("alice*example.org").unwrap();
+        assert_eq!(Some(Memo::new_id(42)), record.memo);
+
+        let columns = CsvColumns::new().default_account_id(ACCOUNT_ID);
+        let handler = StaticHandler::from_csv(
+            "name,memo_type,memo\n\
+             alice*example.org,id,42\n\
+             bob*example.org,id,43\n",
+            &columns,
+        )
+        .unwrap();
+        let record = handler.records().get("bob*example.org").unwrap();
+        assert_eq!(ACCOUNT_ID, record.account_id.account_id());
+        assert_eq!(Some(Memo::new_id(43)), record.memo);
+    }
+
+    #[tokio::test]
+    async fn test_from_csv() {
+        let columns = CsvColumns::new().name("address").account_id("account");
+        let handler = StaticHandler::from_csv(
+            &format!(
+                "address,account,memo_type,memo\n\
+                 alice*example.org,{0},id,42\n\
+                 bob*example.org,{0},,\n",
+                ACCOUNT_ID
+            ),
+            &columns,
+        )
+        .unwrap();
+        let record = handler
+            .lookup_name("alice*example.org")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(Memo::new_id(42)), record.memo);
+        let record = handler
+            .lookup_name("bob*example.org")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, record.memo);
+
+        let errors = [
+            ("name,account_id\n", "missing column address", 1),
+            (
+                "address,account\nalice*example.org,invalid\n",
+                "Malformed account_id",
+                2,
+            ),
+            ("address,account\nalice,{0}\n", "invalid stellar address", 2),
+            (
+                "address,account,memo_type,memo\n\nalice*example.org,{0},id,abc\n",
+                "Malformed id memo",
+                3,
+            ),
+            (
+                "address,account\nalice*example.org\n",
+                "expected 2 fields, found 1",
+                2,
+            ),
+        ];
+        for (contents, expected_message, expected_line) in errors {
+            match StaticHandler::from_csv(&contents.replace("{0}", ACCOUNT_ID), &columns) {
+                Err(Error::CsvError { line, message }) => {
+                    assert_eq!(expected_line, line);
+                    assert_eq!(expected_message, message);
+                }
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let path = std::env::temp_dir().join(format!(
+            "stellar-federation-records-{}.csv",
+            std::process::id()
+        ));
+        let columns = CsvColumns::new().default_account_id(ACCOUNT_ID);
+        fs::write(&path, "name\nalice*example.org\n").unwrap();
+        let handler = StaticFileHandler::open_csv(&path, &columns).unwrap();
+        let watch = tokio::spawn(handler.clone().watch(Duration::from_millis(10)));
+        assert!(handler
+            .current()
+            .records()
+            .get("alice*example.org")
+            .is_some());
+
+        // Invalid files are not applied.
+        fs::write(&path, "name\nalice*example.org\nalice*example.org\n").unwrap();
+        assert!(matches!(handler.reload(), Err(Error::RecordExists)));
+        assert_eq!(1, handler.current().records().len());
+
+        fs::write(&path, "name\nbob*example.org\n").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        for _ in 0..100 {
+            if handler.current().records().get("bob*example.org").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(handler
+            .lookup_name("bob*example.org")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(handler
+            .lookup_name("alice*example.org")
+            .await
+            .unwrap()
+            .is_none());
+        watch.abort();
+        fs::remove_file(&path).unwrap();
+    }
+}
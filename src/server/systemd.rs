@@ -0,0 +1,84 @@
+use crate::Error;
+use std::net::TcpListener;
+use std::os::unix::io::FromRawFd;
+
+/// First file descriptor passed by systemd, after stdin, stdout and
+/// stderr.
+const LISTEN_FDS_START: i32 = 3;
+
+/// Returns the listening socket passed by systemd with socket
+/// activation, or `None` if the process was not socket activated.
+///
+/// Serve it with [`FederationService::serve_listener`] instead of
+/// binding a socket, so that the socket stays open while the server
+/// restarts, and connections are queued by the kernel until the new
+/// process accepts them:
+///
+/// ```ini
+/// # stellar-federation.socket
+/// [Socket]
+/// ListenStream=8000
+///
+/// [Install]
+/// WantedBy=sockets.target
+///
+/// # stellar-federation.service
+/// [Service]
+/// ExecStart=/usr/local/bin/stellar-federation-server /etc/stellar-federation.toml
+/// ```
+///
+/// Like `sd_listen_fds`, the `LISTEN_PID`, `LISTEN_FDS` and
+/// `LISTEN_FDNAMES` environment variables are removed, so that they are
+/// not inherited by child processes. Only the first socket is used if
+/// systemd passes several.
+///
+/// Fails if the first socket is not a TCP listener. Must only be called
+/// once, the socket is owned by the returned listener.
+///
+/// [`FederationService::serve_listener`]: super::FederationService::serve_listener
+pub fn systemd_listener() -> Result<Option<TcpListener>, Error> {
+    let fds = listen_fds(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    );
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"].iter() {
+        std::env::remove_var(name);
+    }
+    if fds == 0 {
+        return Ok(None);
+    }
+    // Safety: systemd passed the file descriptor to this process, and
+    // the environment variables were removed so it's only taken once.
+    let listener = unsafe { TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    // Fails if the file descriptor is not a socket.
+    listener.local_addr()?;
+    Ok(Some(listener))
+}
+
+/// Returns the number of file descriptors passed to the process `pid`,
+/// from the values of `LISTEN_PID` and `LISTEN_FDS`.
+fn listen_fds(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> usize {
+    match listen_pid.and_then(|listen_pid| listen_pid.parse::<u32>().ok()) {
+        Some(listen_pid) if listen_pid == pid => listen_fds
+            .and_then(|listen_fds| listen_fds.parse().ok())
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_fds() {
+        assert_eq!(1, listen_fds(Some("42"), Some("1"), 42));
+        assert_eq!(2, listen_fds(Some("42"), Some("2"), 42));
+        // The variables were set for another process.
+        assert_eq!(0, listen_fds(Some("41"), Some("1"), 42));
+        assert_eq!(0, listen_fds(None, Some("1"), 42));
+        assert_eq!(0, listen_fds(Some("42"), None, 42));
+        assert_eq!(0, listen_fds(Some("42"), Some("many"), 42));
+    }
+}
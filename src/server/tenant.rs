@@ -0,0 +1,94 @@
+use super::FederationHandler;
+use crate::domain_to_ascii;
+use hyper::header::HOST;
+use hyper::Request;
+use std::fmt;
+use std::sync::Arc;
+
+/// Tenant of a multi-tenant [`FederationService`], answering the
+/// requests for a host with its own handler and settings.
+///
+/// [`FederationService`]: super::FederationService
+#[derive(Clone)]
+pub struct Tenant {
+    pub(crate) handler: Arc<dyn FederationHandler>,
+    pub(crate) id_lookups: bool,
+    pub(crate) txid_lookups: bool,
+}
+
+impl Tenant {
+    /// Creates a tenant answering lookups with `handler`.
+    pub fn new<H: FederationHandler + 'static>(handler: H) -> Tenant {
+        Tenant {
+            handler: Arc::new(handler),
+            id_lookups: true,
+            txid_lookups: true,
+        }
+    }
+
+    /// Answers the `type=id` queries of the tenant with
+    /// `501 Not Implemented`, like
+    /// [`FederationService::disable_id_lookups`].
+    ///
+    /// [`FederationService::disable_id_lookups`]: super::FederationService::disable_id_lookups
+    pub fn disable_id_lookups(mut self) -> Tenant {
+        self.id_lookups = false;
+        self
+    }
+
+    /// Answers the `type=txid` queries of the tenant with
+    /// `501 Not Implemented`.
+    pub fn disable_txid_lookups(mut self) -> Tenant {
+        self.txid_lookups = false;
+        self
+    }
+}
+
+impl fmt::Debug for Tenant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenant")
+            .field("handler", &self.handler)
+            .field("id_lookups", &self.id_lookups)
+            .field("txid_lookups", &self.txid_lookups)
+            .finish()
+    }
+}
+
+/// Returns the host of `request` in ASCII form, without its port, from
+/// the `Host` header or the request URI.
+pub(crate) fn request_host<B>(request: &Request<B>) -> Option<String> {
+    let authority = match request.headers().get(HOST) {
+        Some(host) => host.to_str().ok()?,
+        None => request.uri().authority()?.as_str(),
+    };
+    let host = match authority.rfind(':') {
+        // Not the colon of an IPv6 address.
+        Some(colon) if !authority[colon..].contains(']') => &authority[..colon],
+        _ => authority,
+    };
+    domain_to_ascii(host.trim_end_matches('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_host() {
+        let host = |host: &str| {
+            let request = Request::get("/federation")
+                .header(HOST, host)
+                .body(())
+                .unwrap();
+            request_host(&request)
+        };
+        assert_eq!(Some("example.org".to_string()), host("Example.ORG"));
+        assert_eq!(Some("example.org".to_string()), host("example.org.:8000"));
+        assert_eq!(Some("[::1]".to_string()), host("[::1]:8000"));
+
+        let request = Request::get("https://example.org:8443/federation")
+            .body(())
+            .unwrap();
+        assert_eq!(Some("example.org".to_string()), request_host(&request));
+    }
+}
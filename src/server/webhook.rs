@@ -0,0 +1,369 @@
+use super::admin::constant_time_eq;
+use super::audit::timestamp;
+use super::Outcome;
+use crate::connector::{new_https_connector, HttpsConnector};
+use crate::dns::Dns;
+use crate::proxy::ProxyConnector;
+use crate::tls::TlsConfig;
+use crate::{normalize_stellar_address, Error};
+use hmac::{Hmac, Mac};
+use hyper::client::HttpConnector;
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Client, Request, Uri};
+use sha2::Sha256;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{mpsc, Semaphore};
+use url::Url;
+
+/// Header of the signature of the webhook payloads.
+const SIGNATURE_HEADER: &str = "x-stellar-federation-signature";
+
+/// Default number of payloads waiting to be delivered.
+const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+/// Default number of payloads delivered at the same time.
+const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+/// Default number of retries of a failed delivery.
+const DEFAULT_MAX_RETRIES: u32 = 5;
+
+/// Default delay before the first retry, doubled after each retry.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Default timeout of each delivery.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Webhook called by a [`FederationService`] after each successful
+/// lookup, for example to alert a fraud team when some addresses are
+/// resolved.
+///
+/// The webhook receives a `POST` request with a JSON payload:
+///
+/// ```json
+/// {
+///   "timestamp": "2024-05-01T12:00:00.000Z",
+///   "query_type": "name",
+///   "q": "alice*example.org",
+///   "tenant": null,
+///   "record": {
+///     "stellar_address": "alice*example.org",
+///     "account_id": "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+///   }
+/// }
+/// ```
+///
+/// The payload is signed with HMAC-SHA256 and the secret of the
+/// webhook, in the `X-Stellar-Federation-Signature` header, for example
+/// `sha256=5bdcc146…`, which receivers check with
+/// [`Webhook::verify_signature`].
+///
+/// The payloads are queued and delivered concurrently in the background,
+/// without delaying the responses. Failed deliveries, with an error or a
+/// status other than `2xx`, are retried with an exponential backoff,
+/// without holding back the delivery of the other payloads. Payloads are
+/// dropped when the queue is full, and when all the retries fail.
+///
+/// The payloads include the queried address, even in privacy mode.
+///
+/// [`FederationService`]: super::FederationService
+pub struct Webhook {
+    delivery: Delivery,
+    addresses: Option<BTreeSet<String>>,
+    queue_size: usize,
+    /// Queue of the payloads, created with the delivery task on the
+    /// first lookup.
+    queue: Mutex<Option<mpsc::Sender<String>>>,
+}
+
+/// Settings to deliver the payloads of a [`Webhook`].
+#[derive(Clone)]
+struct Delivery {
+    client: Client<HttpsConnector>,
+    url: Uri,
+    secret: Vec<u8>,
+    max_concurrency: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+    timeout: Duration,
+}
+
+impl Webhook {
+    /// Creates a webhook sending the payloads to `url`, signed with
+    /// `secret`.
+    ///
+    /// Fails if `url` is not an http or https URL, or if `secret` is
+    /// empty.
+    pub fn new(url: &str, secret: &str) -> Result<Webhook, Error> {
+        let parsed: Url = url.parse()?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(Error::InvalidConfig(format!("invalid webhook url {}", url)));
+        }
+        if secret.is_empty() {
+            return Err(Error::InvalidConfig("empty webhook secret".to_string()));
+        }
+        let url = parsed
+            .as_str()
+            .parse()
+            .map_err(|_| Error::InvalidConfig(format!("invalid webhook url {}", url)))?;
+        let mut http = HttpConnector::new_with_resolver(Dns::new(None));
+        http.enforce_http(false);
+        let https = new_https_connector(
+            ProxyConnector::new(http, Vec::new()),
+            &TlsConfig::default(),
+            Default::default(),
+        )?;
+        Ok(Webhook {
+            delivery: Delivery {
+                client: Client::builder().build(https),
+                url,
+                secret: secret.as_bytes().to_vec(),
+                max_concurrency: DEFAULT_MAX_CONCURRENCY,
+                max_retries: DEFAULT_MAX_RETRIES,
+                retry_delay: DEFAULT_RETRY_DELAY,
+                timeout: DEFAULT_TIMEOUT,
+            },
+            addresses: None,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            queue: Mutex::new(None),
+        })
+    }
+
+    /// Only calls the webhook for the lookups of the records of
+    /// `addresses`, instead of all the lookups.
+    pub fn addresses<I, S>(mut self, addresses: I) -> Webhook
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let addresses = addresses
+            .into_iter()
+            .filter_map(|address| normalize_stellar_address(address.as_ref()));
+        self.addresses = Some(addresses.collect());
+        self
+    }
+
+    /// Sets the number of payloads waiting to be delivered, above which
+    /// the payloads are dropped. Defaults to 1024.
+    pub fn queue_size(mut self, queue_size: usize) -> Webhook {
+        self.queue_size = queue_size.max(1);
+        self
+    }
+
+    /// Sets the number of payloads delivered at the same time, including
+    /// the ones waiting to be retried. Defaults to 8.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Webhook {
+        self.delivery.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// Sets the number of retries of a failed delivery. Defaults to 5.
+    pub fn max_retries(mut self, max_retries: u32) -> Webhook {
+        self.delivery.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry of a failed delivery,
+    /// doubled after each retry. Defaults to 1 second.
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Webhook {
+        self.delivery.retry_delay = retry_delay;
+        self
+    }
+
+    /// Sets the timeout of each delivery. Defaults to 10 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Webhook {
+        self.delivery.timeout = timeout;
+        self
+    }
+
+    /// Returns whether `signature`, the value of the
+    /// `X-Stellar-Federation-Signature` header of a webhook request, is
+    /// the signature of `body` with `secret`.
+    pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+        let expected = sign(secret.as_bytes(), body);
+        constant_time_eq(expected.as_bytes(), signature.trim().as_bytes())
+    }
+
+    /// Queues the payload of a lookup, if it succeeded.
+    pub(super) fn notify(&self, outcome: &Outcome) {
+        let record = match &outcome.record {
+            Some(record) => record,
+            None => return,
+        };
+        if let Some(addresses) = &self.addresses {
+            let address = record["stellar_address"]
+                .as_str()
+                .and_then(normalize_stellar_address);
+            if !address.is_some_and(|address| addresses.contains(&address)) {
+                return;
+            }
+        }
+        let payload = serde_json::json!({
+            "timestamp": timestamp(SystemTime::now()),
+            "query_type": outcome.kind,
+            "q": outcome.q,
+            "tenant": outcome.tenant,
+            "record": record,
+        });
+        let mut queue = self.queue.lock().unwrap_or_else(|err| err.into_inner());
+        let queue = queue.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel(self.queue_size);
+            tokio::spawn(self.delivery.clone().run(receiver));
+            sender
+        });
+        // The payload is dropped if the queue is full.
+        let _ = queue.try_send(payload.to_string());
+    }
+}
+
+impl Delivery {
+    /// Delivers the queued payloads, until the webhook is dropped.
+    async fn run(self, mut queue: mpsc::Receiver<String>) {
+        let deliveries = Arc::new(Semaphore::new(self.max_concurrency));
+        while let Some(payload) = queue.recv().await {
+            let permit = match deliveries.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => return,
+            };
+            let delivery = self.clone();
+            tokio::spawn(async move {
+                delivery.deliver(&payload).await;
+                drop(permit);
+            });
+        }
+    }
+
+    /// Delivers `payload`, retrying failed deliveries.
+    async fn deliver(&self, payload: &str) {
+        let signature = sign(&self.secret, payload.as_bytes());
+        let mut delay = self.retry_delay;
+        for retry in 0..=self.max_retries {
+            if retry > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            if self.send(payload, &signature).await {
+                break;
+            }
+        }
+    }
+
+    /// Sends `payload`, returning whether it was delivered.
+    async fn send(&self, payload: &str, signature: &str) -> bool {
+        let request = Request::post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, signature)
+            .body(Body::from(payload.to_string()));
+        let request = match request {
+            Ok(request) => request,
+            Err(_) => return false,
+        };
+        match tokio::time::timeout(self.timeout, self.client.request(request)).await {
+            Ok(Ok(response)) => response.status().is_success(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("url", &self.delivery.url)
+            .field("addresses", &self.addresses)
+            .field("queue_size", &self.queue_size)
+            .field("max_concurrency", &self.delivery.max_concurrency)
+            .field("max_retries", &self.delivery.max_retries)
+            .field("retry_delay", &self.delivery.retry_delay)
+            .field("timeout", &self.delivery.timeout)
+            .finish()
+    }
+}
+
+/// Returns the HMAC-SHA256 signature of `body` with `secret`, for
+/// example `sha256=5bdcc146…`.
+fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // Test cases 2 and 6 of RFC 4231.
+        let signature = sign(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            signature
+        );
+        assert!(Webhook::verify_signature(
+            "Jefe",
+            b"what do ya want for nothing?",
+            &signature
+        ));
+        assert!(!Webhook::verify_signature(
+            "Jefe",
+            b"what do ya want?",
+            &signature
+        ));
+        assert_eq!(
+            "sha256=60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            sign(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+            )
+        );
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Webhook::new("https://hooks.example.org/federation", "secret").is_ok());
+        assert!(Webhook::new("ftp://hooks.example.org", "secret").is_err());
+        assert!(Webhook::new("https://hooks.example.org", "").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_deliveries() {
+        // The endpoint never answers, so each delivery holds its
+        // connection until it times out.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (accepted, mut connections) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+                let _ = accepted.send(());
+            }
+        });
+
+        let webhook = Webhook::new(&format!("http://{}/hook", addr), "secret")
+            .unwrap()
+            .timeout(Duration::from_secs(60));
+        for q in ["alice*example.org", "bob*example.org"] {
+            webhook.notify(&Outcome {
+                kind: Some("name"),
+                q: Some(q.to_string()),
+                record: Some(serde_json::json!({ "stellar_address": q })),
+                ..Outcome::default()
+            });
+        }
+        for _ in 0..2 {
+            tokio::time::timeout(Duration::from_secs(5), connections.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    }
+}
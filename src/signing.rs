@@ -0,0 +1,140 @@
+use crate::Error;
+use hyper::header::HeaderMap;
+use hyper::{Method, Request};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The parts of a request covered by a signature.
+#[derive(Debug, Clone)]
+pub struct SigningRequest<'a> {
+    /// The request method.
+    pub method: &'a Method,
+    /// The request host.
+    pub host: &'a str,
+    /// The request path.
+    pub path: &'a str,
+    /// The request query, if any.
+    pub query: Option<&'a str>,
+    /// The value of the `Date` header sent with the request.
+    pub date: &'a str,
+}
+
+/// Signs the requests sent by [`FederationClient`](crate::FederationClient),
+/// for example following the HTTP Signatures draft.
+///
+/// The signer runs after the middlewares, just before the request is
+/// sent, including retries and redirects. The client sets the `Date`
+/// header of the request before calling the signer.
+///
+/// ```rust
+/// use hyper::header::{HeaderMap, HeaderValue};
+/// use stellar_federation::{Error, RequestSigner, SigningRequest};
+///
+/// #[derive(Debug)]
+/// struct KeyIdSigner;
+///
+/// impl RequestSigner for KeyIdSigner {
+///     fn sign(&self, request: &SigningRequest<'_>, headers: &mut HeaderMap) -> Result<(), Error> {
+///         let signing_string = format!(
+///             "(request-target): {} {}?{}\ndate: {}",
+///             request.method.as_str().to_lowercase(),
+///             request.path,
+///             request.query.unwrap_or_default(),
+///             request.date,
+///         );
+///         let signature = sign_with_my_key(signing_string.as_bytes());
+///         let value = format!(
+///             "keyId=\"my-key\",headers=\"(request-target) date\",signature=\"{}\"",
+///             signature
+///         );
+///         headers.insert(
+///             "signature",
+///             HeaderValue::from_str(&value).map_err(|err| Error::SigningError(err.into()))?,
+///         );
+///         Ok(())
+///     }
+/// }
+/// # fn sign_with_my_key(_: &[u8]) -> String { String::new() }
+/// ```
+pub trait RequestSigner: fmt::Debug + Send + Sync {
+    /// Signs `request`, adding the signature to `headers`.
+    fn sign(&self, request: &SigningRequest<'_>, headers: &mut HeaderMap) -> Result<(), Error>;
+}
+
+/// Sets the `Date` header of `request` and signs it with `signer`.
+pub(crate) fn sign_request(
+    signer: &dyn RequestSigner,
+    request: &mut Request<()>,
+    now: SystemTime,
+) -> Result<(), Error> {
+    let date = http_date(now);
+    request.headers_mut().insert(
+        hyper::header::DATE,
+        date.parse().map_err(http::Error::from)?,
+    );
+    let uri = request.uri().clone();
+    let method = request.method().clone();
+    let signing_request = SigningRequest {
+        method: &method,
+        host: uri.host().unwrap_or_default(),
+        path: uri.path(),
+        query: uri.query(),
+        date: &date,
+    };
+    signer.sign(&signing_request, request.headers_mut())
+}
+
+/// Formats `time` as an http date, for example `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Returns the year, month and day of the date `days` after the epoch.
+pub(crate) fn civil_from_days(days: u64) -> (i64, i64, i64) {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_http_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!("Sun, 06 Nov 1994 08:49:37 GMT", http_date(time));
+        let time = UNIX_EPOCH + Duration::from_secs(951_782_400);
+        assert_eq!("Tue, 29 Feb 2000 00:00:00 GMT", http_date(time));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{Error, StellarTomlError};
+use hyper::body::Bytes;
+use hyper::Response;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// Result of a request shared by concurrent calls.
+pub(crate) type SharedResult = Result<Arc<Response<Bytes>>, Arc<Error>>;
+
+/// Deduplicates concurrent calls with the same key.
+#[derive(Debug)]
+pub(crate) struct Singleflight<T> {
+    calls: Mutex<HashMap<String, Vec<oneshot::Sender<T>>>>,
+}
+
+impl<T: Clone> Singleflight<T> {
+    pub(crate) fn new() -> Singleflight<T> {
+        Singleflight {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Runs the future returned by `f`, unless a call with `key` is
+    /// already in flight, in which case its result is returned instead.
+    pub(crate) async fn run<F, Fut>(&self, key: &str, f: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        loop {
+            let receiver = {
+                let mut calls = self.calls.lock().unwrap_or_else(|err| err.into_inner());
+                match calls.get_mut(key) {
+                    Some(waiters) => {
+                        let (sender, receiver) = oneshot::channel();
+                        waiters.push(sender);
+                        receiver
+                    }
+                    None => {
+                        calls.insert(key.to_string(), Vec::new());
+                        break;
+                    }
+                }
+            };
+            match receiver.await {
+                Ok(value) => return value,
+                // The call in flight was cancelled, try running it again.
+                Err(_) => continue,
+            }
+        }
+
+        let call = Call {
+            calls: &self.calls,
+            key,
+            finished: false,
+        };
+        let value = f().await;
+        for waiter in call.finish() {
+            let _ = waiter.send(value.clone());
+        }
+        value
+    }
+}
+
+/// Call in flight, removed from the calls when dropped.
+struct Call<'a, T> {
+    calls: &'a Mutex<HashMap<String, Vec<oneshot::Sender<T>>>>,
+    key: &'a str,
+    finished: bool,
+}
+
+impl<'a, T> Call<'a, T> {
+    /// Removes the call, returning the senders of the waiting calls.
+    fn finish(mut self) -> Vec<oneshot::Sender<T>> {
+        self.finished = true;
+        let mut calls = self.calls.lock().unwrap_or_else(|err| err.into_inner());
+        calls.remove(self.key).unwrap_or_default()
+    }
+}
+
+impl<'a, T> Drop for Call<'a, T> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let mut calls = self.calls.lock().unwrap_or_else(|err| err.into_inner());
+            calls.remove(self.key);
+        }
+    }
+}
+
+/// Returns `result` in a form that can be shared.
+pub(crate) fn share(result: Result<Response<Bytes>, Error>) -> SharedResult {
+    result.map(Arc::new).map_err(Arc::new)
+}
+
+/// Returns the shared `result`, cloning the response.
+///
+/// Errors that can be cloned are returned as is, the others are wrapped
+/// in [`Error::Coalesced`], so that all the calls sharing the result get
+/// the same variant whatever the order they finish in.
+pub(crate) fn unshare(result: SharedResult) -> Result<Response<Bytes>, Error> {
+    match result {
+        Ok(response) => {
+            Ok(Arc::try_unwrap(response).unwrap_or_else(|response| clone_response(&response)))
+        }
+        Err(err) => Err(clone_error(&err).unwrap_or(Error::Coalesced(err))),
+    }
+}
+
+/// Returns the error wrapped by coalesced errors, or `err` itself.
+pub(crate) fn shared_source(err: &Error) -> &Error {
+    match err {
+        Error::Coalesced(err) => shared_source(err),
+        err => err,
+    }
+}
+
+/// Returns a copy of `err`, if it doesn't wrap an error that can't be
+/// cloned.
+fn clone_error(err: &Error) -> Option<Error> {
+    let clone = match err {
+        Error::InvalidStellarAddress => Error::InvalidStellarAddress,
+        Error::MissingFederationServer => Error::MissingFederationServer,
+        Error::ErrorResponse { status, detail } => Error::ErrorResponse {
+            status: *status,
+            detail: detail.clone(),
+        },
+        Error::TomlResolveError(StellarTomlError::ClientError(status)) => {
+            StellarTomlError::ClientError(*status).into()
+        }
+        Error::TomlResolveError(StellarTomlError::ServerError(status)) => {
+            StellarTomlError::ServerError(*status).into()
+        }
+        Error::Timeout => Error::Timeout,
+        Error::InvalidProxy => Error::InvalidProxy,
+        Error::TooManyRedirects => Error::TooManyRedirects,
+        Error::RedirectNotAllowed(url) => Error::RedirectNotAllowed(url.clone()),
+        Error::InvalidCertificate => Error::InvalidCertificate,
+        Error::CachedFailure(message) => Error::CachedFailure(message.clone()),
+        Error::Coalesced(err) => Error::Coalesced(err.clone()),
+        Error::Aborted => Error::Aborted,
+        Error::NotFound => Error::NotFound,
+        Error::Offline => Error::Offline,
+        Error::NotImplemented => Error::NotImplemented,
+        Error::CertificatePinMismatch(domain) => Error::CertificatePinMismatch(domain.clone()),
+        Error::InvalidRequest(reason) => Error::InvalidRequest(reason.clone()),
+        Error::ResponseTooLarge(limit) => Error::ResponseTooLarge(*limit),
+        Error::WebAuthError(message) => Error::WebAuthError(message.clone()),
+        Error::MissingHomeDomain => Error::MissingHomeDomain,
+        Error::ResponseMismatch { expected, actual } => Error::ResponseMismatch {
+            expected: expected.clone(),
+            actual: actual.clone(),
+        },
+        _ => return None,
+    };
+    Some(clone)
+}
+
+fn clone_response(response: &Response<Bytes>) -> Response<Bytes> {
+    let mut clone = Response::new(response.body().clone());
+    *clone.status_mut() = response.status();
+    *clone.version_mut() = response.version();
+    *clone.headers_mut() = response.headers().clone();
+    clone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_calls_are_deduplicated() {
+        let group = Singleflight::new();
+        let calls = AtomicUsize::new(0);
+        let call = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            42
+        };
+        let (a, b) = tokio::join!(group.run("key", call), group.run("key", call));
+        assert_eq!((42, 42), (a, b));
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+
+        group.run("key", call).await;
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_unshare() {
+        let result = share(Err(Error::Timeout));
+        let shared = result.clone();
+        assert!(matches!(unshare(shared), Err(Error::Timeout)));
+        assert!(matches!(unshare(result), Err(Error::Timeout)));
+
+        let io_error = std::io::Error::other("reset");
+        let result = share(Err(Error::IoError(io_error)));
+        let shared = result.clone();
+        for result in [shared, result] {
+            match unshare(result) {
+                Err(Error::Coalesced(err)) => assert!(matches!(*err, Error::IoError(_))),
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::proxy::{destination, BoxError};
+use hyper::Uri;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const VERSION: u8 = 0x05;
+const NO_AUTHENTICATION: u8 = 0x00;
+const USERNAME_PASSWORD: u8 = 0x02;
+const NO_ACCEPTABLE_METHODS: u8 = 0xff;
+const CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Connects to `dst` through the SOCKS5 proxy at the other end of `stream`.
+///
+/// The destination host name is sent to the proxy, which is then
+/// responsible for resolving it.
+pub(crate) async fn connect(
+    stream: &mut TcpStream,
+    dst: &Uri,
+    credentials: &Option<(String, String)>,
+) -> Result<(), BoxError> {
+    let (host, port) = destination(dst)?;
+
+    let method = if credentials.is_some() {
+        USERNAME_PASSWORD
+    } else {
+        NO_AUTHENTICATION
+    };
+    stream.write_all(&[VERSION, 1, method]).await?;
+    let mut reply = [0; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err("invalid socks5 proxy response".into());
+    }
+    if reply[1] == NO_ACCEPTABLE_METHODS || reply[1] != method {
+        return Err("socks5 proxy rejected authentication method".into());
+    }
+
+    if let Some((username, password)) = credentials {
+        if username.len() > 255 || password.len() > 255 {
+            return Err("socks5 credentials too long".into());
+        }
+        let mut request = vec![0x01, username.len() as u8];
+        request.extend_from_slice(username.as_bytes());
+        request.push(password.len() as u8);
+        request.extend_from_slice(password.as_bytes());
+        stream.write_all(&request).await?;
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err("socks5 proxy authentication failed".into());
+        }
+    }
+
+    if host.len() > 255 {
+        return Err("socks5 destination host too long".into());
+    }
+    let mut request = vec![VERSION, CONNECT, 0x00, ATYP_DOMAIN, host.len() as u8];
+    request.extend_from_slice(host.as_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != VERSION {
+        return Err("invalid socks5 proxy response".into());
+    }
+    if reply[1] != 0x00 {
+        return Err(format!("socks5 proxy connect failed with code {}", reply[1]).into());
+    }
+    let address_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => stream.read_u8().await? as usize,
+        _ => return Err("invalid socks5 proxy response".into()),
+    };
+    // Bound address and port, not needed.
+    let mut bound = vec![0; address_len + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
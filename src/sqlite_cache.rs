@@ -0,0 +1,211 @@
+use crate::cache::{CacheEntry, CacheFuture, FederationCache};
+use crate::Error;
+use hyper::body::Bytes;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Schema migrations, applied in order. Never change a released
+/// migration, add a new one instead.
+const MIGRATIONS: &[&str] = &["CREATE TABLE stellar_federation_cache (
+        key TEXT PRIMARY KEY NOT NULL,
+        value BLOB NOT NULL,
+        expires_at INTEGER NOT NULL
+    );
+    CREATE INDEX stellar_federation_cache_expires_at
+        ON stellar_federation_cache (expires_at);"];
+
+/// [`FederationCache`] storing entries in a SQLite database.
+///
+/// The cache uses its own tables, prefixed with `stellar_federation_`,
+/// so it can share the database of the application. The schema is
+/// created and migrated when the cache is opened.
+///
+/// Queries are executed with blocking calls.
+#[derive(Debug)]
+pub struct SqliteCache {
+    connection: Mutex<Connection>,
+    evictions: AtomicU64,
+}
+
+impl SqliteCache {
+    /// Opens the SQLite database at `path`, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteCache, Error> {
+        let connection = Connection::open(path).map_err(cache_error)?;
+        SqliteCache::from_connection(connection)
+    }
+
+    /// Creates a cache using `connection`.
+    pub fn from_connection(mut connection: Connection) -> Result<SqliteCache, Error> {
+        migrate(&mut connection).map_err(cache_error)?;
+        Ok(SqliteCache {
+            connection: Mutex::new(connection),
+            evictions: AtomicU64::new(0),
+        })
+    }
+
+    fn read(&self, key: &str) -> rusqlite::Result<Option<Bytes>> {
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let value: Option<Vec<u8>> = connection
+            .query_row(
+                "SELECT value FROM stellar_federation_cache WHERE key = ?1 AND expires_at > ?2",
+                params![key, unix_time(SystemTime::now())],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(value.map(Bytes::from))
+    }
+
+    fn write(&self, key: &str, value: &[u8], ttl: Duration) -> rusqlite::Result<()> {
+        let now = SystemTime::now();
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let expired = connection.execute(
+            "DELETE FROM stellar_federation_cache WHERE expires_at <= ?1",
+            params![unix_time(now)],
+        )?;
+        self.evictions.fetch_add(expired as u64, Ordering::Relaxed);
+        connection.execute(
+            "INSERT OR REPLACE INTO stellar_federation_cache (key, value, expires_at)
+                VALUES (?1, ?2, ?3)",
+            params![key, value, unix_time(now + ttl)],
+        )?;
+        Ok(())
+    }
+
+    fn read_entries(&self) -> rusqlite::Result<Vec<CacheEntry>> {
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let mut statement = connection.prepare(
+            "SELECT key, value, expires_at FROM stellar_federation_cache WHERE expires_at > ?1",
+        )?;
+        let entries = statement.query_map(params![unix_time(SystemTime::now())], |row| {
+            let value: Vec<u8> = row.get(1)?;
+            let expires_at: i64 = row.get(2)?;
+            Ok(CacheEntry {
+                key: row.get(0)?,
+                value: Bytes::from(value),
+                expires_at: UNIX_EPOCH + Duration::from_secs(expires_at.max(0) as u64),
+            })
+        })?;
+        entries.collect()
+    }
+
+    fn remove_prefix(&self, prefix: &str) -> rusqlite::Result<()> {
+        let connection = self
+            .connection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        connection.execute(
+            "DELETE FROM stellar_federation_cache WHERE substr(key, 1, length(?1)) = ?1",
+            params![prefix],
+        )?;
+        Ok(())
+    }
+}
+
+impl FederationCache for SqliteCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Bytes>> {
+        let value = self.read(key).ok().flatten();
+        Box::pin(async move { value })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Duration) -> CacheFuture<'a, ()> {
+        // Failing to cache an entry is not an error.
+        let _ = self.write(key, &value, ttl);
+        Box::pin(async {})
+    }
+
+    fn invalidate<'a>(&'a self, prefix: &'a str) -> CacheFuture<'a, ()> {
+        let _ = self.remove_prefix(prefix);
+        Box::pin(async {})
+    }
+
+    fn entries(&self) -> CacheFuture<'_, Vec<CacheEntry>> {
+        let entries = self.read_entries().unwrap_or_default();
+        Box::pin(async move { entries })
+    }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
+
+/// Applies the migrations that have not been applied yet.
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(
+        "CREATE TABLE IF NOT EXISTS stellar_federation_schema (version INTEGER NOT NULL)",
+    )?;
+    let version: i64 = transaction.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM stellar_federation_schema",
+        [],
+        |row| row.get(0),
+    )?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        transaction.execute_batch(migration)?;
+        transaction.execute(
+            "INSERT INTO stellar_federation_schema (version) VALUES (?1)",
+            params![index as i64 + 1],
+        )?;
+    }
+    transaction.commit()
+}
+
+fn unix_time(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn cache_error(err: rusqlite::Error) -> Error {
+    Error::CacheError(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sqlite_cache() {
+        let cache = SqliteCache::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let ttl = Duration::from_secs(60);
+        cache.set("federation/a", Bytes::from("a"), ttl).await;
+        cache.set("stellar.toml/b", Bytes::from("b"), ttl).await;
+        assert_eq!(Some(Bytes::from("a")), cache.get("federation/a").await);
+        assert_eq!(2, cache.entries().await.len());
+
+        cache.invalidate("federation/").await;
+        assert_eq!(None, cache.get("federation/a").await);
+        assert_eq!(Some(Bytes::from("b")), cache.get("stellar.toml/b").await);
+
+        cache
+            .set("federation/c", Bytes::from("c"), Duration::from_secs(0))
+            .await;
+        assert_eq!(None, cache.get("federation/c").await);
+    }
+
+    #[test]
+    fn test_migrations_are_applied_once() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        migrate(&mut connection).unwrap();
+        let version: i64 = connection
+            .query_row(
+                "SELECT MAX(version) FROM stellar_federation_schema",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(MIGRATIONS.len() as i64, version);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::resolver::{ResolveFuture, Resolver};
+use crate::{normalize_stellar_address, Error, FederationResponse};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use stellar_base::PublicKey;
+use url::Url;
+
+/// [`Resolver`] serving a fixed table of federation records, without
+/// any network request.
+///
+/// The records can be loaded from a TOML or JSON file mapping each
+/// Stellar address to its account id and optional memo:
+///
+/// ```rust
+/// use stellar_federation::StaticResolver;
+///
+/// # fn run() -> Result<(), stellar_federation::Error> {
+/// let resolver = StaticResolver::from_toml(
+///     r#"
+///     ["payouts*example.org"]
+///     account_id = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+///     memo_type = "id"
+///     memo = "42"
+///     "#,
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Transaction id and forward lookups are not supported and, like
+/// missing records, fail with [`Error::NotFound`].
+#[derive(Debug, Clone, Default)]
+pub struct StaticResolver {
+    records: BTreeMap<String, FederationResponse>,
+}
+
+impl StaticResolver {
+    /// Creates a resolver without records.
+    pub fn new() -> StaticResolver {
+        StaticResolver::default()
+    }
+
+    /// Loads the records of the file at `path`.
+    ///
+    /// Files with the `json` extension are parsed as JSON, other files
+    /// as TOML.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<StaticResolver, Error> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        if path.extension() == Some("json".as_ref()) {
+            StaticResolver::from_json(&contents)
+        } else {
+            StaticResolver::from_toml(&contents)
+        }
+    }
+
+    /// Parses the records of a TOML document, with a table per address.
+    pub fn from_toml(contents: &str) -> Result<StaticResolver, Error> {
+        let tables: BTreeMap<String, toml::value::Table> = toml::from_str(contents)?;
+        let mut resolver = StaticResolver::new();
+        for (address, mut table) in tables {
+            table.insert("stellar_address".to_string(), address.into());
+            resolver.insert(toml::Value::Table(table).try_into()?)?;
+        }
+        Ok(resolver)
+    }
+
+    /// Parses the records of a JSON document, with an object per address.
+    pub fn from_json(contents: &str) -> Result<StaticResolver, Error> {
+        let objects: BTreeMap<String, serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(contents)?;
+        let mut resolver = StaticResolver::new();
+        for (address, mut object) in objects {
+            object.insert("stellar_address".to_string(), address.into());
+            resolver.insert(serde_json::from_value(object.into())?)?;
+        }
+        Ok(resolver)
+    }
+
+    /// Adds the `record`, replacing the record of the same address.
+    pub fn insert(&mut self, record: FederationResponse) -> Result<(), Error> {
+        let address = normalize_stellar_address(&record.stellar_address)
+            .ok_or(Error::InvalidStellarAddress)?;
+        self.records.insert(address, record);
+        Ok(())
+    }
+
+    /// Returns the record of `address`, if any.
+    pub fn get(&self, address: &str) -> Option<&FederationResponse> {
+        self.records.get(&normalize_stellar_address(address)?)
+    }
+
+    /// Returns the record of `account_id`, if any.
+    pub fn get_account_id(&self, account_id: &PublicKey) -> Option<&FederationResponse> {
+        self.records
+            .values()
+            .find(|record| record.account_id == *account_id)
+    }
+
+    /// Returns the records of `account_id`, several records sharing a
+    /// pooled account and telling their users apart with their memo.
+    #[cfg(feature = "server")]
+    pub(crate) fn account_records<'a>(
+        &'a self,
+        account_id: &'a PublicKey,
+    ) -> impl Iterator<Item = &'a FederationResponse> + 'a {
+        self.records
+            .values()
+            .filter(move |record| record.account_id == *account_id)
+    }
+
+    /// Returns the number of records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if the resolver has no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn find_address(&self, address: &str) -> Result<FederationResponse, Error> {
+        normalize_stellar_address(address).ok_or(Error::InvalidStellarAddress)?;
+        self.get(address).cloned().ok_or(Error::NotFound)
+    }
+
+    fn find_account_id(&self, account_id: &PublicKey) -> Result<FederationResponse, Error> {
+        self.get_account_id(account_id)
+            .cloned()
+            .ok_or(Error::NotFound)
+    }
+}
+
+impl Resolver for StaticResolver {
+    fn resolve_address<'a>(&'a self, address: &'a str) -> ResolveFuture<'a> {
+        let result = self.find_address(address);
+        Box::pin(async move { result })
+    }
+
+    fn resolve_account_id<'a>(
+        &'a self,
+        account_id: &'a PublicKey,
+        _server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        let result = self.find_account_id(account_id);
+        Box::pin(async move { result })
+    }
+
+    fn resolve_transaction_id<'a>(
+        &'a self,
+        _tx_id: &'a str,
+        _server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        Box::pin(async { Err(Error::NotFound) })
+    }
+
+    fn resolve_forward<'a>(
+        &'a self,
+        _forward_parameters: &'a [(&'a str, &'a str)],
+        _server: &'a Url,
+    ) -> ResolveFuture<'a> {
+        Box::pin(async { Err(Error::NotFound) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use stellar_base::Memo;
+
+    const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+    #[tokio::test]
+    async fn test_from_toml() {
+        let resolver = StaticResolver::from_toml(&format!(
+            r#"
+            ["payouts*bücher.example"]
+            account_id = "{}"
+            memo_type = "id"
+            memo = "42"
+            "#,
+            ACCOUNT_ID
+        ))
+        .unwrap();
+        let response = resolver
+            .resolve_address("payouts*xn--bcher-kva.example")
+            .await
+            .unwrap();
+        assert_eq!("payouts*bücher.example", response.stellar_address);
+        assert_eq!(Some(Memo::new_id(42)), response.memo);
+
+        let account_id = PublicKey::from_account_id(ACCOUNT_ID).unwrap();
+        let server: Url = "https://example.org/federation".parse().unwrap();
+        let response = resolver
+            .resolve_account_id(&account_id, &server)
+            .await
+            .unwrap();
+        assert_eq!("payouts*bücher.example", response.stellar_address);
+
+        assert!(matches!(
+            resolver.resolve_address("other*example.org").await,
+            Err(Error::NotFound)
+        ));
+        assert!(matches!(
+            resolver.resolve_address("invalid").await,
+            Err(Error::InvalidStellarAddress)
+        ));
+    }
+
+    #[test]
+    fn test_from_json() {
+        let resolver = StaticResolver::from_json(&format!(
+            r#"{{"payouts*example.org": {{"account_id": "{}"}}}}"#,
+            ACCOUNT_ID
+        ))
+        .unwrap();
+        assert_eq!(1, resolver.len());
+        assert_eq!(None, resolver.get("payouts*example.org").unwrap().memo);
+
+        let invalid = r#"{"payouts*example.org": {"account_id": "invalid"}}"#;
+        assert!(matches!(
+            StaticResolver::from_json(invalid),
+            Err(Error::JsonError(_))
+        ));
+    }
+}
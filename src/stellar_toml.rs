@@ -0,0 +1,171 @@
+use hyper::StatusCode;
+use stellar_base::PublicKey;
+
+/// Path of the `stellar.toml` file of a domain.
+pub const STELLAR_TOML_PATH: &str = ".well-known/stellar.toml";
+
+/// The `stellar.toml` file of a domain, as described in SEP-0001.
+///
+/// Only the fields describing the Stellar integration of the domain are
+/// parsed, the organization, currencies and validators sections are
+/// ignored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StellarToml {
+    /// The version of SEP-0001 the file adheres to, `VERSION`.
+    #[serde(alias = "VERSION")]
+    pub version: Option<String>,
+    /// The passphrase of the network of the domain,
+    /// `NETWORK_PASSPHRASE`.
+    #[serde(alias = "NETWORK_PASSPHRASE")]
+    pub network_passphrase: Option<String>,
+    /// The SEP-0002 federation server, `FEDERATION_SERVER`.
+    #[serde(alias = "FEDERATION_SERVER")]
+    #[serde(default, with = "option_display_fromstr")]
+    pub federation_server: Option<hyper::Uri>,
+    /// The SEP-0003 compliance server, `AUTH_SERVER`.
+    #[serde(alias = "AUTH_SERVER")]
+    #[serde(default, with = "option_display_fromstr")]
+    pub auth_server: Option<hyper::Uri>,
+    /// The SEP-0006 transfer server, `TRANSFER_SERVER`.
+    #[serde(alias = "TRANSFER_SERVER")]
+    #[serde(default, with = "option_display_fromstr")]
+    pub transfer_server: Option<hyper::Uri>,
+    /// The SEP-0024 transfer server, `TRANSFER_SERVER_SEP0024`.
+    #[serde(alias = "TRANSFER_SERVER_SEP0024")]
+    #[serde(default, with = "option_display_fromstr")]
+    pub transfer_server_sep0024: Option<hyper::Uri>,
+    /// The SEP-0012 KYC server, `KYC_SERVER`.
+    #[serde(alias = "KYC_SERVER")]
+    #[serde(default, with = "option_display_fromstr")]
+    pub kyc_server: Option<hyper::Uri>,
+    /// The SEP-0010 web authentication endpoint, `WEB_AUTH_ENDPOINT`.
+    #[serde(alias = "WEB_AUTH_ENDPOINT")]
+    #[serde(default, with = "option_display_fromstr")]
+    pub web_auth_endpoint: Option<hyper::Uri>,
+    /// The key signing SEP-0010 challenges, `SIGNING_KEY`.
+    #[serde(alias = "SIGNING_KEY")]
+    #[serde(default, with = "option_display_fromstr")]
+    pub signing_key: Option<PublicKey>,
+    /// The public Horizon server of the domain, `HORIZON_URL`.
+    #[serde(alias = "HORIZON_URL")]
+    pub horizon_url: Option<String>,
+    /// The Stellar accounts controlled by the domain, `ACCOUNTS`.
+    #[serde(alias = "ACCOUNTS")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<String>,
+    /// The key signing SEP-0007 URIs, `URI_REQUEST_SIGNING_KEY`.
+    #[serde(alias = "URI_REQUEST_SIGNING_KEY")]
+    pub uri_request_signing_key: Option<String>,
+}
+
+/// Error fetching or parsing a `stellar.toml` file.
+#[derive(thiserror::Error, Debug)]
+pub enum StellarTomlError {
+    /// The server answered with a client error status.
+    #[error("client response error: {0}")]
+    ClientError(StatusCode),
+    /// The server answered with a server error status.
+    #[error("server response error: {0}")]
+    ServerError(StatusCode),
+    /// The file is not a valid `stellar.toml` file.
+    #[error("toml parse error")]
+    TomlParseError(#[from] toml::de::Error),
+    /// Http error.
+    #[error("hyper error")]
+    HyperError(#[from] hyper::Error),
+    /// Invalid url format.
+    #[error("invalid uri")]
+    InvalidUri(#[from] http::uri::InvalidUri),
+}
+
+/// Returns the uri of the `stellar.toml` file of `domain`, using http
+/// instead of https if `insecure`.
+pub(crate) fn stellar_toml_uri(
+    domain: &str,
+    insecure: bool,
+) -> Result<hyper::Uri, StellarTomlError> {
+    let scheme = if insecure { "http" } else { "https" };
+    Ok(format!("{}://{}/{}", scheme, domain, STELLAR_TOML_PATH).parse()?)
+}
+
+mod option_display_fromstr {
+    use serde::de::{Deserialize, Deserializer, Error};
+    use serde::ser::Serializer;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => value.parse().map(Some).map_err(D::Error::custom),
+            None => Ok(None),
+        }
+    }
+
+    pub fn serialize<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Display,
+    {
+        match value {
+            Some(value) => serializer.serialize_str(&value.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stellar_toml_uri() {
+        assert_eq!(
+            "https://example.org/.well-known/stellar.toml",
+            stellar_toml_uri("example.org", false).unwrap().to_string()
+        );
+        assert_eq!(
+            "http://127.0.0.1:8000/.well-known/stellar.toml",
+            stellar_toml_uri("127.0.0.1:8000", true)
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let toml: StellarToml = toml::from_str(
+            r#"
+            VERSION="2.0.0"
+            FEDERATION_SERVER="https://example.org/federation"
+            SIGNING_KEY="GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"
+            ACCOUNTS=["GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"]
+
+            [DOCUMENTATION]
+            ORG_NAME="Example"
+
+            [[CURRENCIES]]
+            code="USDC"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(Some("2.0.0"), toml.version.as_deref());
+        assert_eq!(
+            "https://example.org/federation",
+            toml.federation_server.unwrap().to_string()
+        );
+        assert_eq!(
+            "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP",
+            toml.signing_key.unwrap().account_id()
+        );
+        assert_eq!(1, toml.accounts.len());
+        assert!(toml.web_auth_endpoint.is_none());
+
+        let result: Result<StellarToml, _> = toml::from_str(r#"FEDERATION_SERVER="not a url""#);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,51 @@
+use crate::Error;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+/// Future returned by [`Timer::sleep`].
+pub type SleepFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Timer used by [`FederationClient`](crate::FederationClient) to wait
+/// between retries and to enforce per-call timeouts.
+///
+/// The default timer uses tokio. Implement this trait, together with
+/// [`Transport`](crate::Transport), to run the client on a different
+/// async runtime.
+pub trait Timer: fmt::Debug + Send + Sync {
+    /// Returns a future that completes after `duration`.
+    fn sleep(&self, duration: Duration) -> SleepFuture;
+}
+
+/// Timer based on tokio.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TokioTimer;
+
+impl Timer for TokioTimer {
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Runs `future`, failing with [`Error::Timeout`] if it doesn't complete
+/// within `duration`.
+pub(crate) async fn timeout<F, T>(
+    timer: &dyn Timer,
+    duration: Duration,
+    future: F,
+) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    let mut future = Box::pin(future);
+    let mut sleep = timer.sleep(duration);
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(output);
+        }
+        sleep.as_mut().poll(cx).map(|()| Err(Error::Timeout))
+    })
+    .await
+}
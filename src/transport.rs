@@ -0,0 +1,128 @@
+use crate::connector::HttpsConnector;
+use crate::pinning::CertificatePinMismatch;
+use crate::Error;
+use hyper::body::{Bytes, HttpBody};
+use hyper::{Body, Client, Request, Response};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Future returned by [`Transport::send`].
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Response<Bytes>, Error>> + Send + 'a>>;
+
+/// Transport used by [`FederationClient`](crate::FederationClient) to
+/// send http requests.
+///
+/// The default transport is based on hyper. Implement this trait to
+/// send requests using a different http stack, then configure the
+/// client with
+/// [`FederationClientBuilder::transport`](crate::FederationClientBuilder::transport).
+///
+/// Retries and redirects are handled by the client, so a transport
+/// only needs to send a single request.
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Sends the `request` and returns the response together with its
+    /// whole body.
+    ///
+    /// Implementations must fail with [`Error::ResponseTooLarge`] if the
+    /// body is larger than `limit` bytes.
+    fn send(&self, request: Request<()>, limit: usize) -> TransportFuture<'_>;
+
+    /// Sends the `request` with its body, like [`send`](Transport::send).
+    ///
+    /// Used to post SEP-0010 challenges. The default implementation
+    /// fails with [`Error::WebAuthError`], implement it to use web
+    /// authentication with a custom transport.
+    #[cfg(feature = "sep10")]
+    fn send_with_body(&self, _request: Request<Bytes>, _limit: usize) -> TransportFuture<'_> {
+        Box::pin(async {
+            Err(Error::WebAuthError(
+                "transport can't send request bodies".to_string(),
+            ))
+        })
+    }
+}
+
+/// Transport based on a hyper client.
+#[derive(Debug, Clone)]
+pub(crate) struct HyperTransport {
+    inner: Client<HttpsConnector>,
+    timeout: Option<Duration>,
+}
+
+impl HyperTransport {
+    pub(crate) fn new(inner: Client<HttpsConnector>, timeout: Option<Duration>) -> HyperTransport {
+        HyperTransport { inner, timeout }
+    }
+
+    async fn send_with_timeout(
+        &self,
+        request: Request<Body>,
+        limit: usize,
+    ) -> Result<Response<Bytes>, Error> {
+        let request = async {
+            let response = self.inner.request(request).await.map_err(map_hyper_error)?;
+            let (parts, body) = response.into_parts();
+            let bytes = read_body(body, limit).await?;
+            Ok(Response::from_parts(parts, bytes))
+        };
+
+        match self.timeout {
+            None => request.await,
+            Some(timeout) => tokio::time::timeout(timeout, request)
+                .await
+                .map_err(|_| Error::Timeout)?,
+        }
+    }
+}
+
+impl Transport for HyperTransport {
+    fn send(&self, request: Request<()>, limit: usize) -> TransportFuture<'_> {
+        Box::pin(self.send_with_timeout(request.map(|_| Body::empty()), limit))
+    }
+
+    #[cfg(feature = "sep10")]
+    fn send_with_body(&self, request: Request<Bytes>, limit: usize) -> TransportFuture<'_> {
+        Box::pin(self.send_with_timeout(request.map(Body::from), limit))
+    }
+}
+
+/// Reads the whole `body`, failing if it's larger than `limit` bytes.
+async fn read_body(mut body: Body, limit: usize) -> Result<Bytes, Error> {
+    let too_large = body
+        .size_hint()
+        .exact()
+        .map(|size| size > limit as u64)
+        .unwrap_or(false);
+    if too_large {
+        return Err(Error::ResponseTooLarge(limit));
+    }
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(map_hyper_error)?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(Error::ResponseTooLarge(limit));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes.into())
+}
+
+fn map_hyper_error(err: hyper::Error) -> Error {
+    let mut source = std::error::Error::source(&err);
+    while let Some(cause) = source {
+        if let Some(io_err) = cause.downcast_ref::<std::io::Error>() {
+            if io_err.kind() == std::io::ErrorKind::TimedOut {
+                return Error::Timeout;
+            }
+        }
+        if let Some(mismatch) = cause.downcast_ref::<CertificatePinMismatch>() {
+            return Error::CertificatePinMismatch(mismatch.0.clone());
+        }
+        source = cause.source();
+    }
+    Error::HyperError(err)
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "blocking")]
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use stellar_federation::blocking::FederationClient;
+use url::Url;
+
+#[test]
+fn test_blocking_resolve_address_from_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut head = Vec::new();
+        let mut buf = [0; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            socket.read_exact(&mut buf).unwrap();
+            head.push(buf[0]);
+        }
+        let body = r#"{"stellar_address":"test*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP","memo_type":"id","memo":"42"}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).unwrap();
+    });
+
+    let client = FederationClient::new().unwrap();
+    let server_url: Url = format!("http://{}/federation", addr).parse().unwrap();
+    let response = client
+        .resolve_address_from_server("test*example.org", &server_url)
+        .unwrap();
+    assert_eq!("test*example.org", response.stellar_address);
+    assert!(response.memo.unwrap().is_id());
+    server.join().unwrap();
+}
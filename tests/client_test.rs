@@ -0,0 +1,1882 @@
+use futures_util::stream::{self, StreamExt};
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, HeaderValue, DATE};
+use hyper::{Request, Response, StatusCode};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use stellar_base::PublicKey;
+use stellar_federation::{
+    BatchOptions, BatchProgress, CacheFuture, CachePolicy, CachedLookup, DnsFuture, DnsResolver,
+    Error, FederationCache, FederationClient, Identity, ManualClock, Middleware, Proxy, RateLimit,
+    RequestSigner, ResolveOptions, Resolver, RetryPolicy, SigningRequest, SleepFuture,
+    StellarTomlError, Timer, TlsVersion, Transport, TransportFuture,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use url::Url;
+
+#[tokio::test]
+async fn test_request_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (_socket, _) = listener.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    });
+
+    let client = FederationClient::builder()
+        .timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let server: Url = format!("http://{}/federation", addr).parse().unwrap();
+    let result = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await;
+
+    assert!(matches!(result, Err(Error::Timeout)));
+}
+
+#[tokio::test]
+async fn test_per_call_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (_socket, _) = listener.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    });
+
+    let client = FederationClient::new();
+    let server: Url = format!("http://{}/federation", addr).parse().unwrap();
+    let options = ResolveOptions::new().timeout(Duration::from_millis(100));
+    let result = client
+        .resolve_address_from_server_with_options("test*example.org", &server, &options)
+        .await;
+
+    assert!(matches!(result, Err(Error::Timeout)));
+}
+
+#[tokio::test]
+async fn test_proxy_tunnel() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let proxy = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let request = read_head(&mut socket).await;
+        socket
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+        read_head(&mut socket).await;
+        let body = r#"{"stellar_address":"test*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        request
+    });
+
+    let client = FederationClient::builder()
+        .proxy(
+            Proxy::all(&format!("http://user:secret@{}", addr))
+                .unwrap()
+                .no_proxy(vec!["other.org"]),
+        )
+        .build()
+        .unwrap();
+    let server: Url = "http://federation.example.org/federation".parse().unwrap();
+    let response = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+    assert_eq!("test*example.org", response.stellar_address);
+
+    let request = proxy.await.unwrap();
+    assert!(request.starts_with("CONNECT federation.example.org:80 HTTP/1.1\r\n"));
+    assert!(request.contains("Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+}
+
+#[derive(Debug)]
+struct LocalhostResolver;
+
+impl DnsResolver for LocalhostResolver {
+    fn lookup(&self, host: &str) -> DnsFuture {
+        assert_eq!("federation.example.org", host);
+        Box::pin(async { Ok(vec![IpAddr::from([127, 0, 0, 1])]) })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_dns_resolver() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let request = read_head(&mut socket).await;
+        let body = r#"{"stellar_address":"test*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        request
+    });
+
+    let client = FederationClient::builder()
+        .dns_resolver(LocalhostResolver)
+        .build()
+        .unwrap();
+    let url: Url = format!("http://federation.example.org:{}/federation", addr.port())
+        .parse()
+        .unwrap();
+    let response = client
+        .resolve_address_from_server("test*example.org", &url)
+        .await
+        .unwrap();
+    assert_eq!("test*example.org", response.stellar_address);
+
+    let request = server.await.unwrap();
+    assert!(request.contains(&format!("host: federation.example.org:{}\r\n", addr.port())));
+}
+
+#[tokio::test]
+async fn test_allow_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        loop {
+            let request = read_head(&mut socket).await;
+            let body = if request.starts_with("GET /.well-known/stellar.toml ") {
+                format!("FEDERATION_SERVER=\"http://{}/federation\"", addr)
+            } else {
+                format!(
+                    r#"{{"stellar_address":"test*{}","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}}"#,
+                    addr
+                )
+            };
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    let address = format!("test*127.0.0.1:{}", addr.port());
+    let client = FederationClient::new();
+    assert!(client.resolve_address(&address).await.is_err());
+
+    let client = FederationClient::builder()
+        .allow_http(true)
+        .build()
+        .unwrap();
+    let response = client.resolve_address(&address).await.unwrap();
+    assert_eq!(address, response.stellar_address);
+}
+
+async fn read_head(socket: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    let mut buf = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        socket.read_exact(&mut buf).await.unwrap();
+        head.push(buf[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn test_socks5_proxy() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let proxy = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut greeting = [0; 3];
+        socket.read_exact(&mut greeting).await.unwrap();
+        assert_eq!([5, 1, 2], greeting);
+        socket.write_all(&[5, 2]).await.unwrap();
+
+        let mut auth = [0; 13];
+        socket.read_exact(&mut auth).await.unwrap();
+        assert_eq!(b"\x01\x04user\x06secret", &auth);
+        socket.write_all(&[1, 0]).await.unwrap();
+
+        let host = b"federation.example.org";
+        let mut connect = vec![0; 5 + host.len() + 2];
+        socket.read_exact(&mut connect).await.unwrap();
+        assert_eq!(&[5, 1, 0, 3, host.len() as u8], &connect[..5]);
+        assert_eq!(&host[..], &connect[5..5 + host.len()]);
+        assert_eq!(&[0, 80], &connect[5 + host.len()..]);
+        socket
+            .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 80])
+            .await
+            .unwrap();
+
+        read_head(&mut socket).await;
+        let body = r#"{"stellar_address":"test*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    let client = FederationClient::builder()
+        .proxy(Proxy::all(&format!("socks5://user:secret@{}", addr)).unwrap())
+        .build()
+        .unwrap();
+    let server: Url = "http://federation.example.org/federation".parse().unwrap();
+    let response = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+    assert_eq!("test*example.org", response.stellar_address);
+    proxy.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_response_too_large() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        read_head(&mut socket).await;
+        let body = vec![b' '; 2048];
+        let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+        socket.write_all(head.as_bytes()).await.unwrap();
+        socket.write_all(&body).await.unwrap();
+    });
+
+    let client = FederationClient::builder()
+        .max_response_size(1024)
+        .build()
+        .unwrap();
+    let server: Url = format!("http://{}/federation", addr).parse().unwrap();
+    let result = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await;
+
+    assert!(matches!(result, Err(Error::ResponseTooLarge(1024))));
+}
+
+/// Answers name lookups with the queried address, and id lookups with
+/// the queried account id, muxed or not, after checking the request.
+#[derive(Debug)]
+struct EchoTransport {
+    check: fn(&Request<()>),
+    unknown_account_ids: &'static [&'static str],
+}
+
+impl EchoTransport {
+    fn new() -> EchoTransport {
+        EchoTransport {
+            check: |_| {},
+            unknown_account_ids: &[],
+        }
+    }
+
+    /// Checks every request with `check`.
+    fn check(mut self, check: fn(&Request<()>)) -> EchoTransport {
+        self.check = check;
+        self
+    }
+
+    /// Answers the lookups of `account_ids` with `404 Not Found`.
+    fn unknown_account_ids(mut self, account_ids: &'static [&'static str]) -> EchoTransport {
+        self.unknown_account_ids = account_ids;
+        self
+    }
+}
+
+impl Transport for EchoTransport {
+    fn send(&self, request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        (self.check)(&request);
+        let query = request.uri().query().unwrap_or_default();
+        let response = match query.strip_prefix("type=id&q=") {
+            Some(account_id) if self.unknown_account_ids.contains(&account_id) => {
+                let mut response = Response::new(Bytes::new());
+                *response.status_mut() = StatusCode::NOT_FOUND;
+                response
+            }
+            Some(account_id) => Response::new(Bytes::from(format!(
+                r#"{{"stellar_address":"test*example.org","account_id":"{}"}}"#,
+                account_id
+            ))),
+            None => Response::new(echo_address(&request)),
+        };
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_transport() {
+    let client = FederationClient::builder()
+        .transport(EchoTransport::new().check(|request| {
+            assert_eq!(Some("type=name&q=test*example.org"), request.uri().query());
+        }))
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    let response = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+    assert_eq!("test*example.org", response.stellar_address);
+}
+
+/// Answers every lookup with another address and account.
+#[derive(Debug)]
+struct MismatchTransport;
+
+impl Transport for MismatchTransport {
+    fn send(&self, _request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        Box::pin(async {
+            Ok(Response::new(Bytes::from_static(
+                br#"{"stellar_address":"mallory*example.org","account_id":"GDKIJJIKXLOM2NRMPNQZUUYK24ZPVFC6426GZAEP3KUK6KEJLACCWNMX"}"#,
+            )))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_verify_responses() {
+    let client = FederationClient::builder()
+        .transport(MismatchTransport)
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    let result = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await;
+    assert!(matches!(
+        result,
+        Err(Error::ResponseMismatch { expected, actual })
+            if expected == "test*example.org" && actual == "mallory*example.org"
+    ));
+    let account_id =
+        PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+            .unwrap();
+    let result = client.resolve_account_id(&account_id, &server).await;
+    assert!(matches!(
+        result,
+        Err(Error::ResponseMismatch { expected, actual })
+            if expected == account_id.account_id()
+                && actual == "GDKIJJIKXLOM2NRMPNQZUUYK24ZPVFC6426GZAEP3KUK6KEJLACCWNMX"
+    ));
+
+    let client = FederationClient::builder()
+        .transport(MismatchTransport)
+        .verify_responses(false)
+        .build()
+        .unwrap();
+    let response = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+    assert_eq!("mallory*example.org", response.stellar_address);
+    assert!(client
+        .resolve_account_id(&account_id, &server)
+        .await
+        .is_ok());
+}
+
+#[derive(Debug, Default)]
+struct FlakyTransport {
+    attempts: Mutex<u32>,
+}
+
+impl Transport for FlakyTransport {
+    fn send(&self, _request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        let mut attempts = self.attempts.lock().unwrap();
+        *attempts += 1;
+        let response = if *attempts < 3 {
+            let mut response = Response::new(Bytes::new());
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            response
+        } else {
+            Response::new(Bytes::from_static(
+                br#"{"stellar_address":"test*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#,
+            ))
+        };
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct RecordingTimer {
+    sleeps: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl Timer for RecordingTimer {
+    fn sleep(&self, duration: Duration) -> SleepFuture {
+        self.sleeps.lock().unwrap().push(duration);
+        Box::pin(async {})
+    }
+}
+
+#[tokio::test]
+async fn test_retry_with_custom_timer() {
+    let timer = RecordingTimer::default();
+    let client = FederationClient::builder()
+        .transport(FlakyTransport::default())
+        .timer(timer.clone())
+        .retry_policy(
+            RetryPolicy::new(3)
+                .base_delay(Duration::from_millis(10))
+                .jitter(false),
+        )
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    let response = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+    assert_eq!("test*example.org", response.stellar_address);
+    assert_eq!(
+        vec![Duration::from_millis(10), Duration::from_millis(20)],
+        *timer.sleeps.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_per_call_no_retry() {
+    let client = FederationClient::builder()
+        .transport(FlakyTransport::default())
+        .timer(RecordingTimer::default())
+        .retry_policy(RetryPolicy::new(3))
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    let options = ResolveOptions::new().no_retry();
+    let result = client
+        .resolve_address_from_server_with_options("test*example.org", &server, &options)
+        .await;
+    assert!(matches!(result, Err(Error::ErrorResponse { status, .. }) if status.is_server_error()));
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let timer = RecordingTimer::default();
+    let client = FederationClient::builder()
+        .transport(EchoTransport::new())
+        .timer(timer.clone())
+        .rate_limit(RateLimit::new(1.0).burst(2))
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    for _ in 0..3 {
+        client
+            .resolve_address_from_server("test*example.org", &server)
+            .await
+            .unwrap();
+    }
+    let sleeps = timer.sleeps.lock().unwrap();
+    assert_eq!(1, sleeps.len());
+    assert!(sleeps[0] > Duration::from_millis(900));
+}
+
+#[tokio::test]
+async fn test_pinned_domain_requires_matching_certificate() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = listener.accept().await;
+    });
+
+    let client = FederationClient::builder()
+        .pin_certificate("127.0.0.1", [0; 32])
+        .build()
+        .unwrap();
+    let server: Url = format!("http://{}/federation", addr).parse().unwrap();
+    let result = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await;
+
+    assert!(matches!(result, Err(Error::CertificatePinMismatch(domain)) if domain == "127.0.0.1"));
+}
+
+#[tokio::test]
+async fn test_pinned_public_key_domain_is_normalized() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = listener.accept().await;
+    });
+
+    let client = FederationClient::builder()
+        .pin_public_key("LocalHost", [0; 32])
+        .build()
+        .unwrap();
+    let server: Url = format!("http://localhost:{}/federation", addr.port())
+        .parse()
+        .unwrap();
+    let result = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await;
+
+    assert!(matches!(result, Err(Error::CertificatePinMismatch(domain)) if domain == "localhost"));
+}
+
+#[derive(Debug, Default)]
+struct DiscoveryTransport {
+    uris: Mutex<Vec<String>>,
+}
+
+impl Transport for DiscoveryTransport {
+    fn send(&self, request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        self.uris.lock().unwrap().push(request.uri().to_string());
+        let body = if request.uri().path() == "/.well-known/stellar.toml" {
+            Bytes::from_static(br#"FEDERATION_SERVER="https://federation.example.org/federation""#)
+        } else {
+            echo_address(&request)
+        };
+        Box::pin(async move { Ok(Response::new(body)) })
+    }
+}
+
+/// Returns a federation response with the address queried by `request`.
+fn echo_address(request: &Request<()>) -> Bytes {
+    let query = request.uri().query().unwrap_or_default();
+    let address = url::form_urlencoded::parse(query.as_bytes())
+        .find(|(name, _)| name == "q")
+        .map(|(_, q)| q.into_owned())
+        .unwrap_or_default();
+    Bytes::from(format!(
+        r#"{{"stellar_address":"{}","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}}"#,
+        address
+    ))
+}
+
+#[tokio::test]
+async fn test_resolve_internationalized_domain() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .build()
+        .unwrap();
+    let response = client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    assert_eq!("maria*xn--bcher-kva.example", response.stellar_address);
+    assert_eq!(
+        vec![
+            "https://xn--bcher-kva.example/.well-known/stellar.toml".to_string(),
+            "https://federation.example.org/federation?type=name&q=maria*xn--bcher-kva.example"
+                .to_string(),
+        ],
+        *transport.uris.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_client_resolver() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .build()
+        .unwrap();
+    let resolver: &dyn Resolver = &client;
+    let response = resolver
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    assert_eq!("maria*xn--bcher-kva.example", response.stellar_address);
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    resolver
+        .resolve_forward(&[("account", "123")], &server)
+        .await
+        .unwrap();
+    assert_eq!(
+        "https://federation.example.org/federation?type=forward&account=123",
+        transport.uris.lock().unwrap()[2]
+    );
+}
+
+#[tokio::test]
+async fn test_offline_mode() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .response_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    client.set_offline(true);
+    assert!(client.is_offline());
+    client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    assert!(matches!(
+        client.resolve_address("jose*example.org").await,
+        Err(Error::Offline)
+    ));
+    assert_eq!(2, transport.uris.lock().unwrap().len());
+
+    client.set_offline(false);
+    client.resolve_address("jose*example.org").await.unwrap();
+    assert_eq!(4, transport.uris.lock().unwrap().len());
+}
+
+#[tokio::test]
+async fn test_resolve_addresses() {
+    let client = FederationClient::builder()
+        .transport(DiscoveryTransport::default())
+        .build()
+        .unwrap();
+    let results = client
+        .resolve_addresses(&["maria*bücher.example", "invalid", "jose*example.org"], 2)
+        .await;
+    assert_eq!(3, results.len());
+    assert!(results[0].is_ok());
+    assert!(matches!(results[1], Err(Error::InvalidStellarAddress)));
+    assert!(results[2].is_ok());
+}
+
+#[tokio::test]
+async fn test_resolve_addresses_fetches_stellar_toml_once_per_domain() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .build()
+        .unwrap();
+    let addresses = [
+        "maria*bücher.example",
+        "jose*example.org",
+        "ana*bücher.example",
+        "luis*example.org",
+    ];
+    let results = client.resolve_addresses(&addresses, 4).await;
+    assert!(results.iter().all(Result::is_ok));
+    let uris = transport.uris.lock().unwrap();
+    let toml_fetches = uris
+        .iter()
+        .filter(|uri| uri.ends_with("/.well-known/stellar.toml"))
+        .count();
+    assert_eq!(2, toml_fetches);
+    assert_eq!(6, uris.len());
+}
+
+#[tokio::test]
+async fn test_resolve_addresses_with_progress() {
+    let client = FederationClient::builder()
+        .transport(DiscoveryTransport::default())
+        .build()
+        .unwrap();
+    let mut events = Vec::new();
+    let results = client
+        .resolve_addresses_with_progress(
+            &["maria*bücher.example", "invalid"],
+            &BatchOptions::new().max_concurrency(2),
+            |event| events.push((event.index, event.result.is_ok(), event.progress)),
+        )
+        .await;
+    assert_eq!(2, results.len());
+    events.sort_by_key(|event| event.0);
+    assert_eq!(2, events.len());
+    assert!(events[0].1);
+    assert!(!events[1].1);
+    let last = events
+        .iter()
+        .map(|event| event.2)
+        .max_by_key(|p| p.completed);
+    assert_eq!(
+        Some(BatchProgress {
+            completed: 2,
+            failed: 1,
+            remaining: 0
+        }),
+        last
+    );
+}
+
+#[tokio::test]
+async fn test_resolve_many() {
+    let client = FederationClient::builder()
+        .transport(DiscoveryTransport::default())
+        .build()
+        .unwrap();
+    let addresses = ["maria*bücher.example", "invalid", "jose*example.org"];
+    let results = client.resolve_many(&addresses, &BatchOptions::new()).await;
+    assert_eq!(3, results.len());
+    assert!(results["maria*bücher.example"].is_ok());
+    assert!(matches!(
+        results["invalid"],
+        Err(Error::InvalidStellarAddress)
+    ));
+    assert!(results["jose*example.org"].is_ok());
+
+    let options = BatchOptions::new().max_concurrency(1).fail_fast(true);
+    let results = client.resolve_many(&addresses, &options).await;
+    assert!(matches!(
+        results["invalid"],
+        Err(Error::InvalidStellarAddress)
+    ));
+    assert!(results["maria*bücher.example"].is_ok());
+    assert!(matches!(results["jose*example.org"], Err(Error::Aborted)));
+}
+
+#[tokio::test]
+async fn test_batch_resolve_options() {
+    let client = FederationClient::builder()
+        .transport(SlowTransport::default())
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap();
+    let options = BatchOptions::new()
+        .resolve_options(ResolveOptions::new().timeout(Duration::from_millis(10)));
+    let results = client
+        .resolve_many(&["maria*bücher.example"], &options)
+        .await;
+    assert!(matches!(
+        results["maria*bücher.example"],
+        Err(Error::Timeout)
+    ));
+
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    let account_ids = vec![PublicKey::from_account_id(
+        "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP",
+    )
+    .unwrap()];
+    let results = client
+        .resolve_account_ids_with_progress(&account_ids, &server, &options, |_| {})
+        .await;
+    assert!(matches!(results[0], Err(Error::Timeout)));
+    let results = client.resolve_account_ids(&account_ids, &server, 1).await;
+    assert!(results[0].is_ok());
+}
+
+#[derive(Debug, Default)]
+struct ConcurrencyTransport {
+    running: Mutex<HashMap<String, (usize, usize)>>,
+}
+
+impl Transport for ConcurrencyTransport {
+    fn send(&self, request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        if request.uri().path() == "/.well-known/stellar.toml" {
+            let body = br#"FEDERATION_SERVER="https://federation.example.org/federation""#;
+            return Box::pin(async move { Ok(Response::new(Bytes::from_static(body))) });
+        }
+        let query = request.uri().query().unwrap_or_default();
+        let domain = query.rsplit('*').next().unwrap().to_string();
+        let body = echo_address(&request);
+        Box::pin(async move {
+            {
+                let mut running = self.running.lock().unwrap();
+                let (now, max) = running.entry(domain.clone()).or_default();
+                *now += 1;
+                *max = (*max).max(*now);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.lock().unwrap().get_mut(&domain).unwrap().0 -= 1;
+            Ok(Response::new(body))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_resolve_addresses_with_domain_concurrency_limits() {
+    let transport = Arc::new(ConcurrencyTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .max_concurrency_per_domain(2)
+        .domain_max_concurrency("small.example", 1)
+        .build()
+        .unwrap();
+    let addresses: Vec<String> = (0..6)
+        .flat_map(|i| {
+            vec![
+                format!("a{}*small.example", i),
+                format!("b{}*example.org", i),
+            ]
+        })
+        .collect();
+    let addresses: Vec<&str> = addresses.iter().map(String::as_str).collect();
+    let results = client.resolve_addresses(&addresses, 10).await;
+    assert!(results.iter().all(Result::is_ok));
+    let running = transport.running.lock().unwrap();
+    assert_eq!(1, running["small.example"].1);
+    assert_eq!(2, running["example.org"].1);
+}
+
+#[tokio::test]
+async fn test_resolve_account_ids() {
+    let client = FederationClient::builder()
+        .transport(
+            EchoTransport::new()
+                .unknown_account_ids(&["GDKIJJIKXLOM2NRMPNQZUUYK24ZPVFC6426GZAEP3KUK6KEJLACCWNMX"]),
+        )
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    let account_ids = vec![
+        PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+            .unwrap(),
+        PublicKey::from_account_id("GDKIJJIKXLOM2NRMPNQZUUYK24ZPVFC6426GZAEP3KUK6KEJLACCWNMX")
+            .unwrap(),
+    ];
+    let results = client.resolve_account_ids(&account_ids, &server, 2).await;
+    assert_eq!(2, results.len());
+    assert_eq!(account_ids[0], results[0].as_ref().unwrap().account_id);
+    assert!(results[1].is_err());
+}
+
+#[tokio::test]
+async fn test_resolve_muxed_account() {
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    let public_key =
+        PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+            .unwrap();
+    let account = public_key.to_muxed_account(42);
+
+    let client = FederationClient::builder()
+        .transport(EchoTransport::new())
+        .build()
+        .unwrap();
+    let response = client
+        .resolve_muxed_account(&account, &server)
+        .await
+        .unwrap();
+    assert_eq!(public_key, response.account_id);
+    assert!(response.muxed_account.is_none());
+
+    let client = FederationClient::builder()
+        .transport(EchoTransport::new())
+        .muxed_id_lookups(true)
+        .build()
+        .unwrap();
+    let response = client
+        .resolve_muxed_account(&account, &server)
+        .await
+        .unwrap();
+    assert_eq!(public_key, response.account_id);
+    assert_eq!(account, response.destination());
+}
+
+#[tokio::test]
+async fn test_resolve_address_stream() {
+    let client = FederationClient::builder()
+        .transport(DiscoveryTransport::default())
+        .build()
+        .unwrap();
+    let addresses = stream::iter(vec!["maria*bücher.example", "invalid"]);
+    let mut results: Vec<_> = client.resolve_address_stream(addresses, 2).collect().await;
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!("invalid", results[0].0);
+    assert!(matches!(results[0].1, Err(Error::InvalidStellarAddress)));
+    assert_eq!("maria*bücher.example", results[1].0);
+    assert!(results[1].1.is_ok());
+}
+
+#[tokio::test]
+async fn test_stellar_toml_cache() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    client.resolve_address("jose*bücher.example").await.unwrap();
+    let uris = transport.uris.lock().unwrap();
+    let toml_requests = uris
+        .iter()
+        .filter(|uri| uri.ends_with("/.well-known/stellar.toml"))
+        .count();
+    assert_eq!(3, uris.len());
+    assert_eq!(1, toml_requests);
+}
+
+#[tokio::test]
+async fn test_prefetch_domains() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let servers = client
+        .prefetch_domains(&["bücher.example", "example.org"])
+        .await;
+    assert_eq!(2, servers.len());
+    for server in servers {
+        assert_eq!(
+            "https://federation.example.org/federation",
+            server.unwrap().as_str()
+        );
+    }
+    client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    assert_eq!(3, transport.uris.lock().unwrap().len());
+}
+
+#[tokio::test]
+async fn test_stellar_toml_refresh() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let toml_requests = || {
+        transport
+            .uris
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|uri| uri.ends_with("/.well-known/stellar.toml"))
+            .count()
+    };
+    client.resolve_stellar_toml("example.org").await.unwrap();
+    assert_eq!(1, toml_requests());
+
+    let handle = client.spawn_stellar_toml_refresh(Duration::from_millis(20));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    handle.stop();
+    let refreshed = toml_requests();
+    assert!(refreshed >= 2);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(refreshed, toml_requests());
+}
+
+#[tokio::test]
+async fn test_response_cache() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .response_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    let address = "maria*xn--bcher-kva.example";
+    let requests = || transport.uris.lock().unwrap().len();
+
+    client
+        .resolve_address_from_server(address, &server)
+        .await
+        .unwrap();
+    client
+        .resolve_address_from_server(address, &server)
+        .await
+        .unwrap();
+    assert_eq!(1, requests());
+
+    let options = ResolveOptions::new().cache_policy(CachePolicy::Refresh);
+    client
+        .resolve_address_from_server_with_options(address, &server, &options)
+        .await
+        .unwrap();
+    assert_eq!(2, requests());
+
+    client.invalidate("maria*bücher.example").await;
+    client
+        .resolve_address_from_server(address, &server)
+        .await
+        .unwrap();
+    assert_eq!(3, requests());
+}
+
+#[tokio::test]
+async fn test_stellar_toml_cache_policy() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let toml_requests = || {
+        transport
+            .uris
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|uri| uri.ends_with("/.well-known/stellar.toml"))
+            .count()
+    };
+    let address = "maria*example.org";
+
+    client.resolve_address(address).await.unwrap();
+    client.resolve_address(address).await.unwrap();
+    assert_eq!(1, toml_requests());
+
+    let options = ResolveOptions::new().cache_policy(CachePolicy::NoCache);
+    client
+        .resolve_address_with_options(address, &options)
+        .await
+        .unwrap();
+    assert_eq!(2, toml_requests());
+
+    let options = ResolveOptions::new().cache_policy(CachePolicy::Refresh);
+    client
+        .resolve_address_with_options(address, &options)
+        .await
+        .unwrap();
+    assert_eq!(3, toml_requests());
+
+    client.resolve_address(address).await.unwrap();
+    assert_eq!(3, toml_requests());
+}
+
+#[tokio::test]
+async fn test_stale_while_revalidate() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let clock = ManualClock::new();
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .clock(clock.clone())
+        .response_cache_ttl(Duration::from_secs(60))
+        .stale_while_revalidate(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    let address = "maria*xn--bcher-kva.example";
+    let requests = || transport.uris.lock().unwrap().len();
+
+    client
+        .resolve_address_from_server(address, &server)
+        .await
+        .unwrap();
+    clock.advance(Duration::from_secs(90));
+    let response = client
+        .resolve_address_from_server(address, &server)
+        .await
+        .unwrap();
+    assert_eq!(address, response.stellar_address);
+    assert_eq!(1, requests());
+
+    // The stale response is refreshed in the background.
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(2, requests());
+    client
+        .resolve_address_from_server(address, &server)
+        .await
+        .unwrap();
+    assert_eq!(2, requests());
+}
+
+#[tokio::test]
+async fn test_cache_stats_and_entries() {
+    let client = FederationClient::builder()
+        .transport(DiscoveryTransport::default())
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .response_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    let stats = client.cache_stats();
+    assert_eq!((2, 2, 0), (stats.hits, stats.misses, stats.evictions));
+
+    let mut entries = client.cache_entries().await;
+    assert_eq!(2, entries.len());
+    entries.sort_by_key(|entry| matches!(entry, CachedLookup::Response { .. }));
+    match &entries[..] {
+        [CachedLookup::FederationServer { domain, server, .. }, CachedLookup::Response { q, response, .. }] =>
+        {
+            assert_eq!("xn--bcher-kva.example", domain);
+            assert_eq!(
+                "https://federation.example.org/federation",
+                server.as_ref().unwrap().as_str()
+            );
+            assert_eq!("maria*xn--bcher-kva.example", q);
+            assert_eq!("maria*xn--bcher-kva.example", response.stellar_address);
+        }
+        entries => panic!("unexpected entries {:?}", entries),
+    }
+}
+
+#[derive(Debug, Default)]
+struct RecordingCache {
+    keys: Mutex<Vec<String>>,
+}
+
+impl FederationCache for RecordingCache {
+    fn get<'a>(&'a self, _key: &'a str) -> CacheFuture<'a, Option<Bytes>> {
+        Box::pin(async { None })
+    }
+
+    fn set<'a>(&'a self, key: &'a str, _value: Bytes, _ttl: Duration) -> CacheFuture<'a, ()> {
+        self.keys.lock().unwrap().push(key.to_string());
+        Box::pin(async {})
+    }
+
+    fn invalidate<'a>(&'a self, _prefix: &'a str) -> CacheFuture<'a, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[tokio::test]
+async fn test_custom_cache() {
+    let cache = Arc::new(RecordingCache::default());
+    let client = FederationClient::builder()
+        .transport(DiscoveryTransport::default())
+        .cache(SharedCache(cache.clone()))
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .response_cache_ttl(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    client
+        .resolve_address("maria*bücher.example")
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![
+            "stellar.toml/xn--bcher-kva.example".to_string(),
+            "federation/maria*xn--bcher-kva.example/name/https://federation.example.org/federation"
+                .to_string(),
+        ],
+        *cache.keys.lock().unwrap()
+    );
+}
+
+#[derive(Debug)]
+struct SharedCache(Arc<RecordingCache>);
+
+impl FederationCache for SharedCache {
+    fn get<'a>(&'a self, key: &'a str) -> CacheFuture<'a, Option<Bytes>> {
+        self.0.get(key)
+    }
+
+    fn set<'a>(&'a self, key: &'a str, value: Bytes, ttl: Duration) -> CacheFuture<'a, ()> {
+        self.0.set(key, value, ttl)
+    }
+
+    fn invalidate<'a>(&'a self, prefix: &'a str) -> CacheFuture<'a, ()> {
+        self.0.invalidate(prefix)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ETagTransport {
+    conditional_requests: Mutex<Vec<Option<String>>>,
+}
+
+impl Transport for ETagTransport {
+    fn send(&self, request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        let if_none_match = request
+            .headers()
+            .get("if-none-match")
+            .map(|value| value.to_str().unwrap().to_string());
+        let not_modified = if_none_match.as_deref() == Some("\"v1\"");
+        self.conditional_requests
+            .lock()
+            .unwrap()
+            .push(if_none_match);
+        let response = if not_modified {
+            Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header("cache-control", "no-cache")
+                .body(Bytes::new())
+        } else {
+            Response::builder()
+                .header("etag", "\"v1\"")
+                .header("cache-control", "no-cache")
+                .body(Bytes::from_static(
+                    br#"{"stellar_address":"maria*xn--bcher-kva.example","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#,
+                ))
+        };
+        Box::pin(async move { Ok(response.unwrap()) })
+    }
+}
+
+#[tokio::test]
+async fn test_http_caching_revalidates_responses() {
+    let transport = Arc::new(ETagTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .http_caching(true)
+        .build()
+        .unwrap();
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    for _ in 0..2 {
+        let response = client
+            .resolve_address_from_server("maria*xn--bcher-kva.example", &server)
+            .await
+            .unwrap();
+        assert_eq!("maria*xn--bcher-kva.example", response.stellar_address);
+    }
+    assert_eq!(
+        vec![None, Some("\"v1\"".to_string())],
+        *transport.conditional_requests.lock().unwrap()
+    );
+}
+
+#[derive(Debug, Default)]
+struct NotFoundTransport {
+    requests: Mutex<usize>,
+}
+
+impl Transport for NotFoundTransport {
+    fn send(&self, _request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        *self.requests.lock().unwrap() += 1;
+        let response = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Bytes::from_static(b"not found"))
+            .unwrap();
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+#[tokio::test]
+async fn test_negative_cache() {
+    let transport = Arc::new(NotFoundTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .negative_cache_ttl(Duration::from_secs(10))
+        .build()
+        .unwrap();
+    for _ in 0..2 {
+        let result = client.resolve_address("alice*gmail.com").await;
+        assert!(matches!(
+            result,
+            Err(Error::TomlResolveError(StellarTomlError::ClientError(
+                StatusCode::NOT_FOUND
+            )))
+        ));
+    }
+    assert_eq!(1, *transport.requests.lock().unwrap());
+
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    for _ in 0..2 {
+        let result = client
+            .resolve_address_from_server("alice*example.org", &server)
+            .await;
+        assert!(matches!(result, Err(Error::NotFound)));
+    }
+    assert_eq!(2, *transport.requests.lock().unwrap());
+
+    client.invalidate("alice*example.org").await;
+    let _ = client
+        .resolve_address_from_server("alice*example.org", &server)
+        .await;
+    assert_eq!(3, *transport.requests.lock().unwrap());
+}
+
+#[derive(Debug, Default)]
+struct SlowTransport {
+    requests: Mutex<usize>,
+}
+
+impl Transport for SlowTransport {
+    fn send(&self, _request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        *self.requests.lock().unwrap() += 1;
+        Box::pin(async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(Response::new(Bytes::from_static(
+                br#"{"stellar_address":"maria*xn--bcher-kva.example","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#,
+            )))
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_coalesce_requests() {
+    let transport = Arc::new(SlowTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .coalesce_requests(true)
+        .build()
+        .unwrap();
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    let resolve = || client.resolve_address_from_server("maria*bücher.example", &server);
+    let (a, b, c) = tokio::join!(resolve(), resolve(), resolve());
+    for response in &[a, b, c] {
+        assert_eq!(
+            "maria*xn--bcher-kva.example",
+            response.as_ref().unwrap().stellar_address
+        );
+    }
+    assert_eq!(1, *transport.requests.lock().unwrap());
+}
+
+/// Fails every request with the error returned by `error`, after a
+/// delay.
+#[derive(Debug)]
+struct SlowFailingTransport {
+    requests: Mutex<usize>,
+    error: fn() -> Error,
+}
+
+impl Transport for SlowFailingTransport {
+    fn send(&self, _request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        *self.requests.lock().unwrap() += 1;
+        Box::pin(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Err((self.error)())
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_coalesce_failed_requests() {
+    let transport = Arc::new(SlowFailingTransport {
+        requests: Mutex::new(0),
+        error: || Error::Timeout,
+    });
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .coalesce_requests(true)
+        .build()
+        .unwrap();
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    let resolve = || client.resolve_address_from_server("maria*example.org", &server);
+    let (a, b, c) = tokio::join!(resolve(), resolve(), resolve());
+    for result in &[a, b, c] {
+        assert!(matches!(result, Err(Error::Timeout)));
+    }
+    assert_eq!(1, *transport.requests.lock().unwrap());
+
+    // Connection errors can't be cloned, and are still cached as
+    // failures of the `stellar.toml` file.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let connections = Arc::new(Mutex::new(0));
+    let accepted = connections.clone();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            *accepted.lock().unwrap() += 1;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(socket);
+        }
+    });
+    let client = FederationClient::builder()
+        .allow_http(true)
+        .coalesce_requests(true)
+        .negative_cache_ttl(Duration::from_secs(10))
+        .build()
+        .unwrap();
+    let address = format!("maria*127.0.0.1:{}", addr.port());
+    let resolve = || client.resolve_address(&address);
+    let (a, b, c) = tokio::join!(resolve(), resolve(), resolve());
+    for result in &[a, b, c] {
+        match result {
+            Err(Error::Coalesced(err)) => assert!(matches!(**err, Error::HyperError(_))),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+    assert!(matches!(
+        client.resolve_address(&address).await,
+        Err(Error::CachedFailure(_))
+    ));
+    assert_eq!(1, *connections.lock().unwrap());
+}
+
+#[derive(Debug)]
+struct SharedTransport<T>(Arc<T>);
+
+impl<T: Transport> Transport for SharedTransport<T> {
+    fn send(&self, request: Request<()>, limit: usize) -> TransportFuture<'_> {
+        self.0.send(request, limit)
+    }
+    #[cfg(feature = "sep10")]
+    fn send_with_body(&self, request: Request<Bytes>, limit: usize) -> TransportFuture<'_> {
+        self.0.send_with_body(request, limit)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ApiKeyMiddleware {
+    responses: Mutex<u32>,
+}
+
+impl Middleware for ApiKeyMiddleware {
+    fn on_request(&self, mut request: Request<()>) -> Request<()> {
+        request
+            .headers_mut()
+            .insert("x-api-key", HeaderValue::from_static("secret"));
+        request
+    }
+
+    fn on_response(&self, response: Response<Bytes>) -> TransportFuture<'_> {
+        *self.responses.lock().unwrap() += 1;
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+#[tokio::test]
+async fn test_middleware() {
+    let middleware = Arc::new(ApiKeyMiddleware::default());
+    let client = FederationClient::builder()
+        .transport(EchoTransport::new().check(|request| {
+            assert_eq!("secret", request.headers()["x-api-key"]);
+        }))
+        .middleware(SharedMiddleware(middleware.clone()))
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+    assert_eq!(1, *middleware.responses.lock().unwrap());
+}
+
+#[derive(Debug)]
+struct SharedMiddleware(Arc<ApiKeyMiddleware>);
+
+impl Middleware for SharedMiddleware {
+    fn on_request(&self, request: Request<()>) -> Request<()> {
+        self.0.on_request(request)
+    }
+
+    fn on_response(&self, response: Response<Bytes>) -> TransportFuture<'_> {
+        self.0.on_response(response)
+    }
+}
+
+#[test]
+fn test_tls_settings() {
+    let client = FederationClient::builder()
+        .min_tls_version(TlsVersion::Tls12)
+        .build();
+    assert!(client.is_ok());
+
+    let client = FederationClient::builder()
+        .tls_cipher_suites(vec!["TLS_NOT_A_CIPHER_SUITE"])
+        .build();
+    assert!(matches!(client, Err(Error::TlsError(_))));
+}
+
+#[cfg(feature = "native-tls")]
+#[test]
+fn test_invalid_identity() {
+    let client = FederationClient::builder()
+        .identity(Identity::from_pkcs12_der(b"not an archive", "secret"))
+        .build();
+    assert!(matches!(client, Err(Error::InvalidIdentity)));
+}
+
+#[derive(Debug)]
+struct PathSigner;
+
+impl RequestSigner for PathSigner {
+    fn sign(&self, request: &SigningRequest<'_>, headers: &mut HeaderMap) -> Result<(), Error> {
+        let value = format!(
+            "{} {} {}?{}",
+            request.method,
+            request.host,
+            request.path,
+            request.query.unwrap_or_default()
+        );
+        headers.insert("signature", HeaderValue::from_str(&value).unwrap());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_request_signer() {
+    let client = FederationClient::builder()
+        .transport(EchoTransport::new().check(|request| {
+            assert_eq!(
+                "GET example.org /federation?type=name&q=test*example.org",
+                request.headers()["signature"]
+            );
+            assert!(request.headers()[DATE].to_str().unwrap().ends_with(" GMT"));
+        }))
+        .request_signer(PathSigner)
+        .build()
+        .unwrap();
+    let server: Url = "https://example.org/federation".parse().unwrap();
+    client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+}
+
+#[cfg(feature = "sep10")]
+#[derive(Debug)]
+struct WebAuthTransport {
+    server: stellar_base::KeyPair,
+    tokens: Vec<String>,
+    issued: Mutex<usize>,
+    uris: Mutex<Vec<String>>,
+}
+
+#[cfg(feature = "sep10")]
+impl WebAuthTransport {
+    /// Issues the `tokens` in order, and accepts the last issued one.
+    fn new(tokens: &[&str]) -> WebAuthTransport {
+        WebAuthTransport {
+            server: stellar_base::KeyPair::random().unwrap(),
+            tokens: tokens.iter().map(|token| token.to_string()).collect(),
+            issued: Mutex::new(0),
+            uris: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn challenge(&self, account: &str) -> String {
+        use stellar_base::account::DataValue;
+        use stellar_base::amount::Stroops;
+        use stellar_base::time_bounds::TimeBounds;
+        use stellar_base::xdr::{self, XDRSerialize};
+        use stellar_base::{Network, Operation, Transaction};
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let time_bounds = TimeBounds::from_xdr(&xdr::TimeBounds {
+            min_time: xdr::TimePoint {
+                value: xdr::Uint64 { value: now - 10 },
+            },
+            max_time: xdr::TimePoint {
+                value: xdr::Uint64 { value: now + 900 },
+            },
+        })
+        .unwrap();
+        let operation = Operation::new_manage_data()
+            .with_source_account(PublicKey::from_account_id(account).unwrap())
+            .with_data_name("example.org auth".to_string())
+            .with_data_value(Some(DataValue::from_slice(&[b'n'; 64]).unwrap()))
+            .build()
+            .unwrap();
+        let mut tx = Transaction::builder(self.server.public_key().clone(), 0, Stroops::new(100))
+            .with_time_bounds(time_bounds)
+            .add_operation(operation)
+            .into_transaction()
+            .unwrap();
+        tx.sign(&self.server, &Network::new_test()).unwrap();
+        tx.into_envelope().xdr_base64().unwrap()
+    }
+}
+
+#[cfg(feature = "sep10")]
+impl Transport for WebAuthTransport {
+    fn send(&self, request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        self.uris.lock().unwrap().push(request.uri().to_string());
+        let body = match request.uri().path() {
+            "/.well-known/stellar.toml" => format!(
+                "FEDERATION_SERVER=\"https://federation.example.org/federation\"\n\
+                 WEB_AUTH_ENDPOINT=\"https://auth.example.org/auth\"\n\
+                 SIGNING_KEY=\"{}\"\n\
+                 NETWORK_PASSPHRASE=\"Test SDF Network ; September 2015\"\n",
+                self.server.public_key().account_id()
+            ),
+            "/auth" => {
+                let query: HashMap<_, _> =
+                    url::form_urlencoded::parse(request.uri().query().unwrap().as_bytes())
+                        .collect();
+                assert_eq!("example.org", query["home_domain"]);
+                serde_json::json!({ "transaction": self.challenge(&query["account"]) }).to_string()
+            }
+            _ => {
+                let issued = *self.issued.lock().unwrap();
+                let last = issued.clamp(1, self.tokens.len()) - 1;
+                let authorization = format!("Bearer {}", self.tokens[last]);
+                if request
+                    .headers()
+                    .get("authorization")
+                    .map(|value| value.as_bytes())
+                    != Some(authorization.as_bytes())
+                {
+                    let response = Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(Bytes::new())
+                        .unwrap();
+                    return Box::pin(async move { Ok(response) });
+                }
+                r#"{"stellar_address":"test*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#.to_string()
+            }
+        };
+        Box::pin(async move { Ok(Response::new(Bytes::from(body))) })
+    }
+
+    fn send_with_body(&self, request: Request<Bytes>, _limit: usize) -> TransportFuture<'_> {
+        use stellar_base::transaction::TransactionEnvelope;
+        use stellar_base::xdr::XDRDeserialize;
+
+        self.uris
+            .lock()
+            .unwrap()
+            .push(format!("POST {}", request.uri()));
+        assert_eq!("application/json", request.headers()["content-type"]);
+        let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+        let challenge =
+            TransactionEnvelope::from_xdr_base64(body["transaction"].as_str().unwrap()).unwrap();
+        assert_eq!(2, challenge.as_transaction().unwrap().signatures().len());
+        let mut issued = self.issued.lock().unwrap();
+        let token = &self.tokens[(*issued).min(self.tokens.len() - 1)];
+        *issued += 1;
+        let body = serde_json::json!({ "token": token }).to_string();
+        Box::pin(async move { Ok(Response::new(Bytes::from(body))) })
+    }
+}
+
+#[cfg(feature = "sep10")]
+#[tokio::test]
+async fn test_web_auth() {
+    let transport = Arc::new(WebAuthTransport::new(&["jwt"]));
+    let key = stellar_base::KeyPair::random().unwrap();
+
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .build()
+        .unwrap();
+    let token = client
+        .web_auth_token("example.org", key.public_key(), &key)
+        .await
+        .unwrap();
+    assert_eq!("jwt", token.token());
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    let result = client
+        .resolve_address_from_server("test*example.org", &server)
+        .await;
+    assert!(matches!(
+        result,
+        Err(Error::ErrorResponse { status, .. }) if status == StatusCode::UNAUTHORIZED
+    ));
+
+    transport.uris.lock().unwrap().clear();
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .stellar_toml_cache_ttl(Duration::from_secs(60))
+        .web_auth("example.org", key.public_key().clone(), key)
+        .build()
+        .unwrap();
+    client.resolve_address("test*example.org").await.unwrap();
+    client.resolve_address("test*example.org").await.unwrap();
+    let uris = transport.uris.lock().unwrap();
+    assert_eq!("https://example.org/.well-known/stellar.toml", uris[0]);
+    assert!(uris[1].starts_with("https://auth.example.org/auth?account=G"));
+    assert_eq!("POST https://auth.example.org/auth", uris[2]);
+    assert_eq!(
+        "https://federation.example.org/federation?type=name&q=test*example.org",
+        uris[3]
+    );
+    assert_eq!(5, uris.len());
+}
+
+#[cfg(feature = "sep10")]
+#[tokio::test]
+async fn test_web_auth_token_expiration() {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let jwt = |exp: u64| {
+        let claims = format!(r#"{{"exp":{}}}"#, exp);
+        format!(
+            "e30.{}.sig",
+            base64::encode_config(claims, base64::URL_SAFE_NO_PAD)
+        )
+    };
+    let transport = Arc::new(WebAuthTransport::new(&[&jwt(now + 600), &jwt(now + 1800)]));
+    let key = stellar_base::KeyPair::random().unwrap();
+    let clock = ManualClock::new();
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .clock(clock.clone())
+        .web_auth("example.org", key.public_key().clone(), key)
+        .build()
+        .unwrap();
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    for _ in 0..2 {
+        client
+            .resolve_address_from_server("test*example.org", &server)
+            .await
+            .unwrap();
+    }
+    assert_eq!(5, transport.uris.lock().unwrap().len());
+
+    clock.advance(Duration::from_secs(600));
+    client
+        .resolve_address_from_server("test*example.org", &server)
+        .await
+        .unwrap();
+    let uris = transport.uris.lock().unwrap();
+    assert_eq!(8, uris.len());
+    assert!(uris[5].starts_with("https://auth.example.org/auth?account=G"));
+    assert_eq!("POST https://auth.example.org/auth", uris[6]);
+    let toml_fetches = uris
+        .iter()
+        .filter(|uri| uri.ends_with("/.well-known/stellar.toml"))
+        .count();
+    assert_eq!(1, toml_fetches);
+}
+
+#[cfg(feature = "sep10")]
+#[tokio::test]
+async fn test_web_auth_errors() {
+    let key = stellar_base::KeyPair::random().unwrap();
+    let client = FederationClient::builder()
+        .transport(NotFoundTransport::default())
+        .web_auth("gmail.com", key.public_key().clone(), key)
+        .build()
+        .unwrap();
+    let server: Url = "https://federation.example.org/federation".parse().unwrap();
+    let result = client
+        .resolve_address_from_server("alice*example.org", &server)
+        .await;
+    assert!(matches!(
+        result,
+        Err(Error::TomlResolveError(StellarTomlError::ClientError(
+            StatusCode::NOT_FOUND
+        )))
+    ));
+}
+
+#[tokio::test]
+async fn test_discover() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .build()
+        .unwrap();
+    let endpoints = client.discover("bücher.example").await.unwrap();
+    assert_eq!("xn--bcher-kva.example", endpoints.domain);
+    assert_eq!(
+        "https://federation.example.org/federation",
+        endpoints.federation_server().unwrap().as_str()
+    );
+    assert_eq!(None, endpoints.web_auth_endpoint);
+    assert_eq!(
+        vec!["https://xn--bcher-kva.example/.well-known/stellar.toml".to_string()],
+        *transport.uris.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_resolve_address_detailed() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .build()
+        .unwrap();
+    let detailed = client
+        .resolve_address_detailed("maria*bücher.example")
+        .await
+        .unwrap();
+    assert_eq!(
+        "maria*xn--bcher-kva.example",
+        detailed.response.stellar_address
+    );
+    assert_eq!(
+        "https://federation.example.org/federation",
+        detailed.federation_server.as_str()
+    );
+    assert!(detailed.stellar_toml.federation_server.is_some());
+    assert_eq!(2, transport.uris.lock().unwrap().len());
+}
+
+#[tokio::test]
+async fn test_resolve_address_with_toml() {
+    let transport = Arc::new(DiscoveryTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .build()
+        .unwrap();
+    let stellar_toml =
+        toml::from_str(r#"FEDERATION_SERVER="https://federation.example.org/federation""#).unwrap();
+    let response = client
+        .resolve_address_with_toml("maria*bücher.example", &stellar_toml)
+        .await
+        .unwrap();
+    assert_eq!("maria*xn--bcher-kva.example", response.stellar_address);
+    assert_eq!(
+        vec![
+            "https://federation.example.org/federation?type=name&q=maria*xn--bcher-kva.example"
+                .to_string()
+        ],
+        *transport.uris.lock().unwrap()
+    );
+
+    let stellar_toml = toml::from_str("").unwrap();
+    let result = client
+        .resolve_address_with_toml("maria*bücher.example", &stellar_toml)
+        .await;
+    assert!(matches!(result, Err(Error::MissingFederationServer)));
+}
+
+#[cfg(feature = "horizon")]
+#[derive(Debug, Default)]
+struct HorizonTransport {
+    uris: Mutex<Vec<String>>,
+}
+
+#[cfg(feature = "horizon")]
+impl Transport for HorizonTransport {
+    fn send(&self, request: Request<()>, _limit: usize) -> TransportFuture<'_> {
+        self.uris.lock().unwrap().push(request.uri().to_string());
+        let (status, body): (_, &'static [u8]) = match request.uri().path() {
+            "/accounts/GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP" => (
+                StatusCode::OK,
+                br#"{"account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP","home_domain":"example.org"}"#,
+            ),
+            "/.well-known/stellar.toml" => (
+                StatusCode::OK,
+                br#"FEDERATION_SERVER="https://federation.example.org/federation""#,
+            ),
+            "/federation" => (
+                StatusCode::OK,
+                br#"{"stellar_address":"alice*example.org","account_id":"GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP"}"#,
+            ),
+            _ => (StatusCode::NOT_FOUND, br#"{"status":404}"#),
+        };
+        let response = Response::builder()
+            .status(status)
+            .body(Bytes::from_static(body))
+            .unwrap();
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+#[cfg(feature = "horizon")]
+#[tokio::test]
+async fn test_reverse_lookup() {
+    let transport = Arc::new(HorizonTransport::default());
+    let client = FederationClient::builder()
+        .transport(SharedTransport(transport.clone()))
+        .horizon_url("https://horizon.example.org".parse().unwrap())
+        .build()
+        .unwrap();
+    let account_id =
+        PublicKey::from_account_id("GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP")
+            .unwrap();
+    let lookup = client.reverse_lookup(&account_id).await.unwrap();
+    assert_eq!("https://horizon.example.org/", lookup.horizon_url.as_str());
+    assert_eq!("example.org", lookup.home_domain);
+    assert_eq!(
+        "https://federation.example.org/federation",
+        lookup.federation_server.as_str()
+    );
+    assert_eq!("alice*example.org", lookup.response.stellar_address);
+    assert_eq!(
+        vec![
+            "https://horizon.example.org/accounts/GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP".to_string(),
+            "https://example.org/.well-known/stellar.toml".to_string(),
+            "https://federation.example.org/federation?type=id&q=GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP".to_string(),
+        ],
+        *transport.uris.lock().unwrap()
+    );
+
+    let unknown =
+        PublicKey::from_account_id("GDKIJJIKXLOM2NRMPNQZUUYK24ZPVFC6426GZAEP3KUK6KEJLACCWNMX")
+            .unwrap();
+    let result = client.reverse_lookup(&unknown).await;
+    assert!(matches!(result, Err(Error::NotFound)));
+}
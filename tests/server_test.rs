@@ -0,0 +1,765 @@
+#![cfg(feature = "server")]
+
+use hyper::body::to_bytes;
+use hyper::service::Service;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use std::convert::Infallible;
+use std::future::poll_fn;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use stellar_base::{Memo, PublicKey};
+use stellar_federation::server::{
+    CacheControl, FederationError, FederationHandler, FederationServer, FederationService,
+    HandlerFuture, RemoteAddr, StaticHandler, Tenant, Webhook,
+};
+use stellar_federation::{Error, FederationClient, FederationResponse, RateLimit};
+
+const ACCOUNT_ID: &str = "GBUFHFEIMKTBQQFDSCAZFOC6MAUE3EHBVE4S4RYKMX62PMWDIDSD44CP";
+
+#[derive(Debug)]
+struct Users;
+
+impl FederationHandler for Users {
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        Box::pin(async move {
+            match address {
+                "alice*example.org" => Ok(Some(FederationResponse {
+                    stellar_address: address.to_string(),
+                    account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+                    muxed_account: None,
+                    memo: Some(Memo::new_id(42)),
+                    extra: Default::default(),
+                })),
+                "broken*example.org" => Err(Error::Timeout),
+                _ => Ok(None),
+            }
+        })
+    }
+}
+
+async fn get(query: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::get(format!("https://example.org/federation?{}", query))
+        .body(Body::empty())
+        .unwrap();
+    let response = FederationService::new(Users).handle(request).await;
+    json(response).await
+}
+
+async fn json(response: Response<Body>) -> (StatusCode, serde_json::Value) {
+    let status = response.status();
+    assert_eq!("application/json", response.headers()["content-type"]);
+    let body = to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[tokio::test]
+async fn test_name_lookup() {
+    let (status, body) = get("type=name&q=alice*example.org").await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!(
+        serde_json::json!({
+            "stellar_address": "alice*example.org",
+            "account_id": ACCOUNT_ID,
+            "memo_type": "id",
+            "memo": "42",
+        }),
+        body
+    );
+
+    let (status, body) = get("type=name&q=bob*example.org").await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+    assert_eq!(serde_json::json!({ "detail": "not found" }), body);
+}
+
+#[tokio::test]
+async fn test_memo_serialization() {
+    let handler = StaticHandler::from_toml(&format!(
+        r#"
+        account_id = "{}"
+
+        [[records]]
+        name = "alice*example.org"
+        memo_type = "id"
+        memo = "18446744073709551615"
+
+        [[records]]
+        name = "bob*example.org"
+        memo_type = "hash"
+        memo = "AQIDBAUGBwgJCgsMDQ4PEBESExQVFhcYGRobHB0eHyA="
+
+        [[records]]
+        name = "carol*example.org"
+        memo_type = "text"
+        memo = "carol"
+        "#,
+        ACCOUNT_ID
+    ))
+    .unwrap();
+    let service = FederationService::new(handler);
+    let expected = [
+        ("alice", "id", "18446744073709551615"),
+        (
+            "bob",
+            "hash",
+            "AQIDBAUGBwgJCgsMDQ4PEBESExQVFhcYGRobHB0eHyA=",
+        ),
+        ("carol", "text", "carol"),
+    ];
+    for (name, memo_type, memo) in expected {
+        let request = Request::get(format!(
+            "https://example.org/federation?type=name&q={}*example.org",
+            name
+        ))
+        .body(Body::empty())
+        .unwrap();
+        let (status, body) = json(service.handle(request).await).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(
+            serde_json::json!({
+                "stellar_address": format!("{}*example.org", name),
+                "account_id": ACCOUNT_ID,
+                "memo_type": memo_type,
+                "memo": memo,
+            }),
+            body
+        );
+    }
+
+    // The pooled account is shared by all the records.
+    let request = Request::get(format!(
+        "https://example.org/federation?type=id&q={}",
+        ACCOUNT_ID
+    ))
+    .body(Body::empty())
+    .unwrap();
+    let (status, _) = json(service.handle(request).await).await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+}
+
+#[tokio::test]
+async fn test_errors() {
+    let (status, _) = get("type=name&q=bob").await;
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    let (status, _) = get(&format!("type=id&q={}", ACCOUNT_ID)).await;
+    assert_eq!(StatusCode::NOT_IMPLEMENTED, status);
+    let (status, body) = get("type=name&q=broken*example.org").await;
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, status);
+    assert_eq!(
+        serde_json::json!({ "detail": "internal server error" }),
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_handler_errors() {
+    let service = FederationServer::new()
+        .on_name(|address| async move {
+            match address.as_str() {
+                "banned*example.org" => {
+                    Err(FederationError::BadRequest("account closed".to_string()).into())
+                }
+                "moved*example.org" => Err(FederationError::NotFound.into()),
+                _ => Err(FederationError::NotImplemented.into()),
+            }
+        })
+        .into_service();
+    let expected = [
+        ("banned", StatusCode::BAD_REQUEST, "account closed"),
+        ("moved", StatusCode::NOT_FOUND, "not found"),
+        (
+            "alice",
+            StatusCode::NOT_IMPLEMENTED,
+            "query type not supported",
+        ),
+    ];
+    for (name, expected_status, detail) in expected {
+        let request = Request::get(format!("/federation?type=name&q={}*example.org", name))
+            .body(Body::empty())
+            .unwrap();
+        let (status, body) = json(service.handle(request).await).await;
+        assert_eq!(expected_status, status);
+        assert_eq!(serde_json::json!({ "detail": detail }), body);
+    }
+}
+
+#[tokio::test]
+async fn test_disable_lookups() {
+    fn record() -> FederationResponse {
+        FederationResponse {
+            stellar_address: "alice*example.org".to_string(),
+            account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+            muxed_account: None,
+            memo: None,
+            extra: Default::default(),
+        }
+    }
+    fn server() -> FederationServer {
+        FederationServer::new()
+            .on_name(|_| async { Ok(Some(record())) })
+            .on_id(|_| async { Ok(Some(record())) })
+            .on_txid(|_| async { Ok(Some(record())) })
+    }
+    let queries = [
+        "type=name&q=alice*example.org".to_string(),
+        format!("type=id&q={}", ACCOUNT_ID),
+        "type=txid&q=abc".to_string(),
+    ];
+    let service = server().into_service();
+    for query in &queries {
+        let request = Request::get(format!("/federation?{}", query))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(StatusCode::OK, service.handle(request).await.status());
+    }
+
+    let service = server()
+        .into_service()
+        .disable_id_lookups()
+        .disable_txid_lookups();
+    let expected = [
+        StatusCode::OK,
+        StatusCode::NOT_IMPLEMENTED,
+        StatusCode::NOT_IMPLEMENTED,
+    ];
+    for (query, expected) in queries.iter().zip(expected) {
+        let request = Request::get(format!("/federation?{}", query))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(expected, service.handle(request).await.status());
+    }
+}
+
+#[tokio::test]
+async fn test_closures() {
+    let service = FederationServer::new()
+        .on_name(|address| async move {
+            Ok(Some(FederationResponse {
+                stellar_address: address,
+                account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+                muxed_account: None,
+                memo: None,
+                extra: Default::default(),
+            }))
+        })
+        .into_service();
+    let request = Request::get("/federation?type=name&q=alice*example.org")
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = json(service.handle(request).await).await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("alice*example.org", body["stellar_address"]);
+
+    let request = Request::get(format!("/federation?type=id&q={}", ACCOUNT_ID))
+        .body(Body::empty())
+        .unwrap();
+    let (status, _) = json(service.handle(request).await).await;
+    assert_eq!(StatusCode::NOT_IMPLEMENTED, status);
+}
+
+#[tokio::test]
+async fn test_forward() {
+    let service = FederationServer::new()
+        .on_forward(|query| async move {
+            if query.forward_type() != "bank_account" {
+                return Ok(None);
+            }
+            match query.get("acct") {
+                Some(acct) => Ok(Some(FederationResponse {
+                    stellar_address: format!("{}*example.org", acct),
+                    account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+                    muxed_account: None,
+                    memo: None,
+                    extra: Default::default(),
+                })),
+                None => Err(Error::InvalidRequest("missing acct parameter".to_string())),
+            }
+        })
+        .into_service();
+    let get = |query: &str| {
+        let request = Request::get(format!("/federation?{}", query))
+            .body(Body::empty())
+            .unwrap();
+        let service = service.clone();
+        async move { json(service.handle(request).await).await }
+    };
+
+    let (status, body) = get("type=forward&forward_type=bank_account&acct=123").await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("123*example.org", body["stellar_address"]);
+    let (status, body) = get("type=forward&forward_type=bank_account").await;
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    assert_eq!(
+        serde_json::json!({ "detail": "missing acct parameter" }),
+        body
+    );
+    let (status, _) = get("type=forward&forward_type=card&acct=123").await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+    let (status, body) = get("type=forward&acct=123").await;
+    assert_eq!(StatusCode::BAD_REQUEST, status);
+    assert_eq!(
+        serde_json::json!({ "detail": "missing forward_type parameter" }),
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_cors() {
+    let service = FederationService::new(Users);
+    let request = Request::get("/federation?type=name&q=bob*example.org")
+        .body(Body::empty())
+        .unwrap();
+    let response = service.handle(request).await;
+    assert_eq!("*", response.headers()["access-control-allow-origin"]);
+
+    let request = Request::options("/federation")
+        .header("access-control-request-method", "GET")
+        .header("access-control-request-headers", "x-wallet")
+        .body(Body::empty())
+        .unwrap();
+    let response = service.handle(request).await;
+    assert_eq!(StatusCode::NO_CONTENT, response.status());
+    let headers = response.headers();
+    assert_eq!("*", headers["access-control-allow-origin"]);
+    assert_eq!("GET, OPTIONS", headers["access-control-allow-methods"]);
+    assert_eq!("x-wallet", headers["access-control-allow-headers"]);
+
+    let service = FederationService::new(Users).allow_origin("https://wallet.example.org");
+    let request = Request::get("/federation?type=name&q=bob*example.org")
+        .body(Body::empty())
+        .unwrap();
+    let response = service.handle(request).await;
+    let headers = response.headers();
+    assert_eq!(
+        "https://wallet.example.org",
+        headers["access-control-allow-origin"]
+    );
+    assert_eq!("Origin", headers["vary"]);
+
+    let service = FederationService::new(Users).disable_cors();
+    let request = Request::options("/federation").body(Body::empty()).unwrap();
+    let response = service.handle(request).await;
+    assert_eq!(StatusCode::METHOD_NOT_ALLOWED, response.status());
+    assert!(!response
+        .headers()
+        .contains_key("access-control-allow-origin"));
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let service = FederationService::new(Users)
+        .rate_limit(RateLimit::new(1.0))
+        .deny_ip("192.0.2.0/24".parse().unwrap());
+    let get = |addr: &str| {
+        let mut request = Request::get("/federation?type=name&q=alice*example.org")
+            .body(Body::empty())
+            .unwrap();
+        let addr = format!("{}:4000", addr).parse().unwrap();
+        request.extensions_mut().insert(RemoteAddr(addr));
+        service.handle(request)
+    };
+
+    assert_eq!(StatusCode::OK, get("198.51.100.1").await.status());
+    let response = get("198.51.100.1").await;
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+    assert_eq!("1", response.headers()["retry-after"]);
+    assert_eq!("*", response.headers()["access-control-allow-origin"]);
+    assert_eq!(StatusCode::OK, get("198.51.100.2").await.status());
+    assert_eq!(StatusCode::FORBIDDEN, get("192.0.2.1").await.status());
+}
+
+#[tokio::test]
+async fn test_health() {
+    let ready = Arc::new(AtomicBool::new(true));
+    let service = FederationService::new(Users)
+        .rate_limit(RateLimit::new(1.0))
+        .readiness_check("cache", {
+            let ready = ready.clone();
+            move || {
+                let ready = ready.load(Ordering::SeqCst);
+                async move {
+                    if ready {
+                        Ok(())
+                    } else {
+                        Err(Error::Timeout)
+                    }
+                }
+            }
+        });
+    let get = |path: &str| {
+        let mut request = Request::get(path).body(Body::empty()).unwrap();
+        let addr = "198.51.100.1:4000".parse().unwrap();
+        request.extensions_mut().insert(RemoteAddr(addr));
+        let service = service.clone();
+        async move { json(service.handle(request).await).await }
+    };
+
+    for _ in 0..2 {
+        let (status, body) = get("/healthz").await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(serde_json::json!({ "status": "ok" }), body);
+    }
+    let (status, body) = get("/readyz").await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!(serde_json::json!({ "status": "ready" }), body);
+    ready.store(false, Ordering::SeqCst);
+    let (status, body) = get("/readyz").await;
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status);
+    assert_eq!(
+        serde_json::json!({ "status": "unavailable", "failed": ["cache"] }),
+        body
+    );
+}
+
+#[tokio::test]
+async fn test_reserved_paths_access() {
+    let service = FederationService::new(Users)
+        .rate_limit(RateLimit::new(1.0))
+        .deny_ip("192.0.2.0/24".parse().unwrap());
+    let get = |service: &FederationService<Users>, addr: &str| {
+        let mut request = Request::get("/healthz").body(Body::empty()).unwrap();
+        let addr = format!("{}:4000", addr).parse().unwrap();
+        request.extensions_mut().insert(RemoteAddr(addr));
+        let service = service.clone();
+        async move { service.handle(request).await.status() }
+    };
+
+    assert_eq!(StatusCode::FORBIDDEN, get(&service, "192.0.2.1").await);
+    for _ in 0..2 {
+        assert_eq!(StatusCode::OK, get(&service, "198.51.100.1").await);
+    }
+
+    let service = service.rate_limit_reserved_paths(true);
+    assert_eq!(StatusCode::OK, get(&service, "198.51.100.2").await);
+    assert_eq!(
+        StatusCode::TOO_MANY_REQUESTS,
+        get(&service, "198.51.100.2").await
+    );
+}
+
+#[tokio::test]
+async fn test_tenants() {
+    let tenant = FederationServer::new()
+        .on_name(|address| async move {
+            Ok(Some(FederationResponse {
+                stellar_address: address,
+                account_id: PublicKey::from_account_id(ACCOUNT_ID).unwrap(),
+                muxed_account: None,
+                memo: Some(Memo::new_id(7)),
+                extra: Default::default(),
+            }))
+        })
+        .on_id(|_| async { Ok(None) });
+    let service = FederationService::new(Users)
+        .tenant("Wallet.example", Tenant::new(tenant).disable_id_lookups());
+    let get = |host: &str, query: &str| {
+        let request = Request::get(format!("/federation?{}", query))
+            .header("host", host)
+            .body(Body::empty())
+            .unwrap();
+        let service = service.clone();
+        async move { json(service.handle(request).await).await }
+    };
+
+    let (status, body) = get("wallet.example:8000", "type=name&q=bob*wallet.example").await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("7", body["memo"]);
+    let (status, _) = get("wallet.example", &format!("type=id&q={}", ACCOUNT_ID)).await;
+    assert_eq!(StatusCode::NOT_IMPLEMENTED, status);
+
+    // Other hosts are answered by the handler of the service.
+    let (status, body) = get("example.org", "type=name&q=alice*example.org").await;
+    assert_eq!(StatusCode::OK, status);
+    assert_eq!("42", body["memo"]);
+    let (status, _) = get("example.org", "type=name&q=bob*wallet.example").await;
+    assert_eq!(StatusCode::NOT_FOUND, status);
+}
+
+#[tokio::test]
+async fn test_cache_control() {
+    let handler = StaticHandler::from_toml(&format!(
+        r#"
+        account_id = "{}"
+
+        [[records]]
+        name = "alice*example.org"
+
+        [[records]]
+        name = "bob*example.org"
+        memo_type = "id"
+        memo = 7
+        cache_control = "no-store"
+        "#,
+        ACCOUNT_ID
+    ))
+    .unwrap();
+    let service = FederationService::new(handler)
+        .cache_control(CacheControl::MaxAge(Duration::from_secs(3600)));
+    let cache_control = |query: &str| {
+        let request = Request::get(format!("/federation?{}", query))
+            .body(Body::empty())
+            .unwrap();
+        let service = service.clone();
+        async move {
+            let response = service.handle(request).await;
+            response
+                .headers()
+                .get("cache-control")
+                .map(|value| value.to_str().unwrap().to_string())
+        }
+    };
+
+    assert_eq!(
+        Some("max-age=3600".to_string()),
+        cache_control("type=name&q=alice*example.org").await
+    );
+    assert_eq!(
+        Some("no-store".to_string()),
+        cache_control("type=name&q=bob*example.org").await
+    );
+    // Errors are not cached.
+    assert_eq!(None, cache_control("type=name&q=carol*example.org").await);
+}
+
+#[tokio::test]
+async fn test_conditional_get() {
+    let service =
+        FederationService::new(Users).cache_control(CacheControl::MaxAge(Duration::from_secs(60)));
+    let get = |if_none_match: Option<&str>| {
+        let mut request = Request::get("/federation?type=name&q=alice*example.org");
+        if let Some(if_none_match) = if_none_match {
+            request = request.header("if-none-match", if_none_match);
+        }
+        service.handle(request.body(Body::empty()).unwrap())
+    };
+
+    let response = get(None).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+    assert_eq!(etag, get(None).await.headers()["etag"]);
+
+    let response = get(Some(&etag)).await;
+    assert_eq!(StatusCode::NOT_MODIFIED, response.status());
+    assert_eq!(etag, response.headers()["etag"]);
+    assert_eq!("max-age=60", response.headers()["cache-control"]);
+    assert!(to_bytes(response.into_body()).await.unwrap().is_empty());
+
+    let response = get(Some("\"outdated\"")).await;
+    assert_eq!(StatusCode::OK, response.status());
+    let (_, body) = json(response).await;
+    assert_eq!("42", body["memo"]);
+}
+
+#[tokio::test]
+async fn test_webhook() {
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let make_service = hyper::service::make_service_fn(move |_| {
+        let sender = sender.clone();
+        let attempts = attempts.clone();
+        async move {
+            Ok::<_, Infallible>(hyper::service::service_fn(move |request: Request<Body>| {
+                let sender = sender.clone();
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let signature = request.headers()["x-stellar-federation-signature"]
+                        .to_str()
+                        .unwrap()
+                        .to_string();
+                    let body = to_bytes(request.into_body()).await.unwrap();
+                    // The first delivery fails, to check that it's retried.
+                    let status = match attempt {
+                        0 => StatusCode::INTERNAL_SERVER_ERROR,
+                        _ => {
+                            sender.send((signature, body)).unwrap();
+                            StatusCode::NO_CONTENT
+                        }
+                    };
+                    let response = Response::builder().status(status).body(Body::empty());
+                    Ok::<_, Infallible>(response.unwrap())
+                }
+            }))
+        }
+    });
+    let addr = unused_addr();
+    tokio::spawn(hyper::Server::bind(&addr).serve(make_service));
+
+    let webhook = Webhook::new(&format!("http://{}/hooks", addr), "secret")
+        .unwrap()
+        .addresses(["alice*Example.org"])
+        .retry_delay(Duration::from_millis(10));
+    let service = FederationService::new(Users).webhook(webhook);
+    for query in [
+        "type=name&q=bob*example.org",
+        "type=name&q=alice*example.org",
+    ] {
+        let request = Request::get(format!("/federation?{}", query))
+            .body(Body::empty())
+            .unwrap();
+        service.handle(request).await;
+    }
+
+    let (signature, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(Webhook::verify_signature("secret", &body, &signature));
+    assert!(!Webhook::verify_signature("other", &body, &signature));
+    let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!("name", payload["query_type"]);
+    assert_eq!("alice*example.org", payload["q"]);
+    assert_eq!(ACCOUNT_ID, payload["record"]["account_id"]);
+    assert!(received.try_recv().is_err());
+}
+
+#[derive(Debug)]
+struct Slow(Duration);
+
+impl FederationHandler for Slow {
+    fn lookup_name<'a>(&'a self, address: &'a str) -> HandlerFuture<'a> {
+        Box::pin(async move {
+            tokio::time::sleep(self.0).await;
+            Users.lookup_name(address).await
+        })
+    }
+}
+
+fn unused_addr() -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap()
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown() {
+    let addr = unused_addr();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(
+        FederationService::new(Slow(Duration::from_millis(300))).serve_with_shutdown(
+            addr,
+            async move {
+                let _ = stopped.await;
+            },
+        ),
+    );
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let url = format!("http://{}/federation?type=name&q=alice*example.org", addr);
+    let request = tokio::spawn(hyper::Client::new().get(url.parse().unwrap()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    stop.send(()).unwrap();
+
+    // The in-flight request completes before the server stops.
+    let response = request.await.unwrap().unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_serve_listener() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(FederationService::new(Users).serve_listener_with_shutdown(
+        listener,
+        async move {
+            let _ = stopped.await;
+        },
+    ));
+
+    let url = format!("http://{}/federation?type=name&q=alice*example.org", addr);
+    let response = hyper::Client::new()
+        .get(url.parse().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, response.status());
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_muxed_id_lookup() {
+    let handler = StaticHandler::from_toml(&format!(
+        r#"
+        [[records]]
+        name = "alice*example.org"
+        account_id = "{}"
+        "#,
+        ACCOUNT_ID
+    ))
+    .unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(
+        FederationService::new(handler).serve_listener_with_shutdown(listener, async move {
+            let _ = stopped.await;
+        }),
+    );
+
+    let client = FederationClient::builder()
+        .allow_http(true)
+        .muxed_id_lookups(true)
+        .build()
+        .unwrap();
+    let public_key = PublicKey::from_account_id(ACCOUNT_ID).unwrap();
+    let response = client
+        .resolve_muxed_account(
+            &public_key.to_muxed_account(42),
+            &format!("http://{}/federation", addr).parse().unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!("alice*example.org", response.stellar_address);
+    assert_eq!(public_key, response.account_id);
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_grace_period() {
+    let addr = unused_addr();
+    let service = FederationService::new(Slow(Duration::from_secs(60)))
+        .shutdown_grace_period(Duration::from_millis(100));
+    let server = tokio::spawn(service.serve_with_shutdown(addr, async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let url = format!("http://{}/federation?type=name&q=alice*example.org", addr);
+    tokio::spawn(hyper::Client::new().get(url.parse().unwrap()));
+
+    let result = tokio::time::timeout(Duration::from_secs(5), server).await;
+    assert!(result.unwrap().unwrap().is_ok());
+}
+
+async fn call_tower_service<S>(mut service: S, request: Request<String>) -> StatusCode
+where
+    S: tower_service::Service<Request<String>, Response = Response<Body>>,
+    S::Error: std::fmt::Debug,
+{
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    service.call(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_tower_service() {
+    let request = Request::get("/federation?type=name&q=alice*example.org")
+        .body(String::new())
+        .unwrap();
+    let status = call_tower_service(FederationService::new(Users), request).await;
+    assert_eq!(StatusCode::OK, status);
+}
+
+#[tokio::test]
+async fn test_service() {
+    let mut service = FederationService::new(Users);
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/federation?type=name&q=alice*example.org")
+        .body(Body::empty())
+        .unwrap();
+    let response = service.call(request).await.unwrap();
+    assert_eq!(StatusCode::METHOD_NOT_ALLOWED, response.status());
+    assert_eq!("GET", response.headers()["allow"]);
+}